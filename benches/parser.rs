@@ -0,0 +1,81 @@
+use codecrafters_shell::lexer::split_command_lines;
+use codecrafters_shell::parser::parse_input;
+use criterion::black_box;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+
+/// A command line of `words` arguments mixing plain, quoted, and escaped words, split into a
+/// pipeline every 50 words and ending with redirections.
+fn long_command_line(words: usize) -> String {
+    let mut line = String::from("echo");
+    for index in 0..words {
+        match index % 5 {
+            0 => line.push_str(&format!(" plain{index}")),
+            1 => line.push_str(&format!(" 'single quoted {index}'")),
+            2 => line.push_str(&format!(" \"double \\\"quoted\\\" {index}\"")),
+            3 => line.push_str(&format!(" escaped\\ word{index}")),
+            _ => line.push_str(&format!(" mixed\"{index}\"'x'")),
+        }
+        if index % 50 == 49 {
+            line.push_str(" | cat");
+        }
+    }
+    line.push_str(" > out.txt 2>> err.txt");
+    line
+}
+
+fn bench_parse_input(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_input");
+    for words in [10, 100, 1_000, 10_000] {
+        let line = long_command_line(words);
+        group.bench_with_input(BenchmarkId::from_parameter(words), &line, |b, line| {
+            b.iter(|| parse_input(black_box(line)));
+        });
+    }
+    group.finish();
+}
+
+/// A pasted script of `lines` lines, every fifth one opening a quote closed on the next line.
+fn pasted_script(lines: usize) -> String {
+    let mut script = String::new();
+    for index in 0..lines {
+        if index % 5 == 0 {
+            script.push_str(&format!("echo 'quoted block {index}\n"));
+        } else if index % 5 == 1 {
+            script.push_str(&format!("continues here {index}' | cat\n"));
+        } else {
+            script.push_str(&format!("ls -l dir{index} > out{index}.txt\n"));
+        }
+    }
+    script
+}
+
+/// A quoted string spanning `lines` lines, as when pasting a long heredoc-like block.
+fn long_quoted_block(lines: usize) -> String {
+    let mut block = String::from("echo '");
+    for index in 0..lines {
+        block.push_str(&format!("line {index} of a long quoted block\n"));
+    }
+    block.push('\'');
+    block
+}
+
+fn bench_split_command_lines(c: &mut Criterion) {
+    let mut group = c.benchmark_group("split_command_lines");
+    for lines in [100, 1_000, 10_000] {
+        let script = pasted_script(lines);
+        group.bench_with_input(BenchmarkId::new("script", lines), &script, |b, script| {
+            b.iter(|| split_command_lines(black_box(script)));
+        });
+        let block = long_quoted_block(lines);
+        group.bench_with_input(BenchmarkId::new("quoted_block", lines), &block, |b, block| {
+            b.iter(|| split_command_lines(black_box(block)));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse_input, bench_split_command_lines);
+criterion_main!(benches);
@@ -0,0 +1,65 @@
+use codecrafters_shell::command_cache::CommandCache;
+use codecrafters_shell::commands::search_executable;
+use codecrafters_shell::frecency::DirectoryDatabase;
+use codecrafters_shell::parser::ENVIRONMENT_VARIABLE_PATH;
+use codecrafters_shell::parser::ENVIRONMENT_VARIABLE_PATH_DELIMITER;
+use codecrafters_shell::shell_helper::ShellCompleter;
+use criterion::black_box;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+const PATH_DIRECTORIES: usize = 50;
+const EXECUTABLES_PER_DIRECTORY: usize = 200;
+
+/// Populate a scratch `$PATH` of `PATH_DIRECTORIES` directories holding `EXECUTABLES_PER_DIRECTORY`
+/// executables each, and return its root.
+fn large_path() -> PathBuf {
+    let root = std::env::temp_dir().join(format!("ccsh-bench-path-{}", std::process::id()));
+    let mut directories = Vec::new();
+    for directory_index in 0..PATH_DIRECTORIES {
+        let directory = root.join(format!("bin{directory_index}"));
+        fs::create_dir_all(&directory).expect("create bench PATH directory");
+        for executable_index in 0..EXECUTABLES_PER_DIRECTORY {
+            let executable = directory.join(format!("tool{directory_index}_{executable_index}"));
+            fs::write(&executable, "#!/bin/sh\n").expect("create bench executable");
+            fs::set_permissions(&executable, fs::Permissions::from_mode(0o755)).expect("chmod bench executable");
+        }
+        directories.push(directory.display().to_string());
+    }
+    std::env::set_var(
+        ENVIRONMENT_VARIABLE_PATH,
+        directories.join(&ENVIRONMENT_VARIABLE_PATH_DELIMITER.to_string()),
+    );
+    root
+}
+
+fn bench_path_scan(c: &mut Criterion) {
+    let root = large_path();
+    let last_tool = format!("tool{}_{}", PATH_DIRECTORIES - 1, EXECUTABLES_PER_DIRECTORY - 1);
+
+    c.bench_function("ShellCompleter::new", |b| {
+        b.iter(|| {
+            ShellCompleter::new(
+                Arc::new(Mutex::new(DirectoryDatabase::default())),
+                CommandCache::load(None),
+            )
+        });
+    });
+    c.bench_function("search_executable/last_directory", |b| {
+        b.iter(|| search_executable(black_box(&last_tool)));
+    });
+    c.bench_function("search_executable/missing", |b| {
+        b.iter(|| search_executable(black_box("no-such-tool")));
+    });
+
+    let _ = fs::remove_dir_all(root);
+}
+
+criterion_group!(benches, bench_path_scan);
+criterion_main!(benches);
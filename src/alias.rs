@@ -0,0 +1,126 @@
+use crate::lexer::lex;
+use crate::lexer::TokenKind;
+use crate::parser::is_variable_name;
+use crate::parser::CHAR_BACKSLASH;
+use crate::parser::CHAR_EQUALS_SIGN;
+use crate::parser::CHAR_PAREN_OPEN;
+use crate::parser::CHAR_TAB;
+use std::collections::BTreeMap;
+
+/// Replace the aliases in `line` by their values.
+///
+/// An unquoted, unescaped word in command position (after any `NAME=value` prefixes) that names an
+/// alias is replaced, and the value is expanded in turn, except for the aliases already being
+/// expanded, so `alias ls='ls -F'` ends. When a value ends in a blank, the word after it is
+/// checked as well, as in bash (`alias sudo='sudo '`).
+#[must_use]
+pub fn expand_aliases(line: &str, aliases: &BTreeMap<String, String>) -> String {
+    if aliases.is_empty() {
+        return line.to_string();
+    }
+    expand(line, aliases, &mut Vec::new()).0
+}
+
+/// Expand the aliases of `input` other than those in `expanding`. Also returns whether the word
+/// after `input` is in command position.
+fn expand<'a>(input: &str, aliases: &'a BTreeMap<String, String>, expanding: &mut Vec<&'a str>) -> (String, bool) {
+    let lexed = lex(input);
+    let mut expanded = String::with_capacity(input.len());
+    let mut copied = 0;
+    let mut expect_command = true;
+    let mut skip_target = false;
+    for token in &lexed.tokens {
+        match token.kind {
+            TokenKind::Operator => {
+                expect_command = true;
+                skip_target = false;
+            }
+            TokenKind::Redirection => skip_target = true,
+            TokenKind::Word if skip_target => skip_target = false,
+            TokenKind::Word if expect_command => {
+                let word = &input[token.span.clone()];
+                if is_assignment(word) {
+                    continue;
+                }
+                expect_command = false;
+                // A `( ... )` group starts with the command inside it.
+                let name = word.trim_start_matches(CHAR_PAREN_OPEN);
+                if !token.quoted.is_empty() || name.contains(CHAR_BACKSLASH) {
+                    continue;
+                }
+                let Some((name, value)) = aliases
+                    .get_key_value(name)
+                    .filter(|(name, _)| !expanding.contains(&name.as_str()))
+                else {
+                    continue;
+                };
+                expanded.push_str(&input[copied..token.span.end - name.len()]);
+                expanding.push(name);
+                let (replacement, ends_in_command_position) = expand(value, aliases, expanding);
+                expanding.pop();
+                expanded.push_str(&replacement);
+                copied = token.span.end;
+                expect_command = ends_in_command_position || value.ends_with([' ', CHAR_TAB]);
+            }
+            TokenKind::Word | TokenKind::Comment => {}
+        }
+    }
+    expanded.push_str(&input[copied..]);
+    (expanded, expect_command)
+}
+
+/// Whether `word` is a `NAME=value` assignment, which can precede the command name.
+fn is_assignment(word: &str) -> bool {
+    word.split_once(CHAR_EQUALS_SIGN)
+        .is_some_and(|(name, _)| is_variable_name(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases(definitions: &[(&str, &str)]) -> BTreeMap<String, String> {
+        definitions
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn expansion() {
+        let aliases = aliases(&[
+            ("ls", "ls -F"),
+            ("ll", "ls -l"),
+            ("a", "b x"),
+            ("b", "a y"),
+            ("sudo", "sudo "),
+            ("nohup", "nohup\t"),
+            ("g", "git"),
+            ("quiet", "echo ok; g"),
+        ]);
+        let cases = [
+            ("ls", "ls -F"),
+            ("ll /tmp", "ls -F -l /tmp"),
+            ("a", "a y x"),
+            ("b", "b x y"),
+            ("sudo ll", "sudo  ls -F -l"),
+            ("sudo sudo g", "sudo  sudo  git"),
+            ("nohup g", "nohup\t git"),
+            ("g g", "git g"),
+            ("echo ll; ll | ll && g", "echo ll; ls -F -l | ls -F -l && git"),
+            ("quiet status", "echo ok; git status"),
+            ("X=1 Y=2 g log", "X=1 Y=2 git log"),
+            ("g >out ll", "git >out ll"),
+            (">out g", ">out git"),
+            ("(g status)", "(git status)"),
+            ("'g' \"g\" \\g", "'g' \"g\" \\g"),
+            ("'g'; \"g\"; \\g; g''", "'g'; \"g\"; \\g; g''"),
+            ("echo # g", "echo # g"),
+            ("unknown ll", "unknown ll"),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(expand_aliases(line, &aliases), expected, "{line:?}");
+        }
+        assert_eq!(expand_aliases("ll", &BTreeMap::new()), "ll");
+    }
+}
@@ -0,0 +1,458 @@
+use crate::parser::expand_operand;
+use crate::parser::Expander;
+use std::fmt;
+
+/// Operators, longest first so that `<<=` is not read as `<<` and `=`.
+const ARITHMETIC_OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "*=", "/=", "%=", "+=", "-=", "&=",
+    "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~", "?", ":", "=", ",", "(", ")",
+];
+const ASSIGNMENT_OPERATORS: &[&str] = &["=", "*=", "/=", "%=", "+=", "-=", "<<=", ">>=", "&=", "^=", "|="];
+/// The binary operators from the loosest binding to the tightest, `**` aside.
+const BINARY_OPERATOR_LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<=", ">=", "<", ">"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+/// How deep variables holding expressions may refer to further variables.
+const MAX_VARIABLE_DEPTH: usize = 32;
+
+/// An arithmetic expression that could not be evaluated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArithmeticError {
+    pub message: &'static str,
+    /// The token where evaluation stopped, if any.
+    pub token: Option<String>,
+}
+
+impl fmt::Display for ArithmeticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.token {
+            Some(token) => write!(f, "{} (error token is \"{token}\")", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ArithmeticToken {
+    Number(String),
+    Name(String),
+    Operator(&'static str),
+}
+
+impl ArithmeticToken {
+    fn text(&self) -> &str {
+        match self {
+            Self::Number(text) | Self::Name(text) => text,
+            Self::Operator(operator) => operator,
+        }
+    }
+}
+
+/// Evaluate the arithmetic expression of `$(( ))` or `(( ))` with 64-bit integers that wrap on
+/// overflow, as bash does.
+///
+/// The parameters and command substitutions of `expression` are expanded and its quotes removed
+/// first. Numbers are decimal, hexadecimal after `0x`, octal after `0`, or `base#digits` for
+/// bases 2 to 36. A name stands for the value of that variable, `0` when it is unset or empty, and
+/// a value that is not a number is evaluated as an expression itself. The operators are C's, from
+/// the tightest binding: `id++ id--`, `++id --id`, unary `+ - ! ~`, `**`, `* / %`, `+ -`,
+/// `<< >>`, `< <= > >=`, `== !=`, `&`, `^`, `|`, `&&`, `||`, `?:`, the assignments `= *= /= %=
+/// += -= <<= >>= &= ^= |=`, and `,`; comparisons and logical operators yield `1` or `0`. Operands
+/// that do not decide the result of `&&`, `||`, or `?:` are not evaluated, so their assignments
+/// do not happen.
+pub fn evaluate_arithmetic(expression: &str, expander: &mut dyn Expander) -> Result<i64, ArithmeticError> {
+    let expanded = expand_operand(expression, false, expander);
+    evaluate_expanded(&expanded, expander, 0)
+}
+
+fn evaluate_expanded(expression: &str, expander: &mut dyn Expander, depth: usize) -> Result<i64, ArithmeticError> {
+    let tokens = tokenize(expression)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut evaluator = Evaluator {
+        tokens,
+        position: 0,
+        expander,
+        depth,
+    };
+    let value = evaluator.comma(true)?;
+    match evaluator.tokens.get(evaluator.position) {
+        Some(token) => Err(error("syntax error in expression", Some(token))),
+        None => Ok(value),
+    }
+}
+
+fn error(message: &'static str, token: Option<&ArithmeticToken>) -> ArithmeticError {
+    ArithmeticError {
+        message,
+        token: token.map(|token| token.text().to_string()),
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<ArithmeticToken>, ArithmeticError> {
+    let mut tokens = Vec::new();
+    let mut rest = expression.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let length = if c.is_ascii_alphanumeric() || c == '_' {
+            // A number can hold the `#` of `base#digits`.
+            let number = c.is_ascii_digit();
+            let length = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || (number && c == '#')))
+                .unwrap_or(rest.len());
+            let text = rest[..length].to_string();
+            tokens.push(if number {
+                ArithmeticToken::Number(text)
+            } else {
+                ArithmeticToken::Name(text)
+            });
+            length
+        } else if let Some(operator) = ARITHMETIC_OPERATORS
+            .iter()
+            .find(|operator| rest.starts_with(**operator))
+        {
+            tokens.push(ArithmeticToken::Operator(operator));
+            operator.len()
+        } else {
+            return Err(ArithmeticError {
+                message: "syntax error: invalid arithmetic operator",
+                token: Some(rest.to_string()),
+            });
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// The value of the number `text`: decimal, `0x` hexadecimal, `0` octal, or `base#digits`.
+fn parse_number(text: &str) -> Option<i64> {
+    let (base, digits) = match text.split_once('#') {
+        Some((base, digits)) => (base.parse().ok().filter(|base| (2..=36).contains(base))?, digits),
+        None => match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(digits) => (16, digits),
+            None if text.len() > 1 && text.starts_with('0') => (8, &text[1..]),
+            None => (10, text),
+        },
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    digits.chars().try_fold(0_i64, |value, c| {
+        let digit = c.to_digit(base)?;
+        Some(value.wrapping_mul(i64::from(base)).wrapping_add(i64::from(digit)))
+    })
+}
+
+/// Evaluates while parsing, by recursive descent over the levels of binding. With `evaluate`
+/// false an operand is only parsed, for the operands `&&`, `||`, and `?:` skip.
+struct Evaluator<'a> {
+    tokens: Vec<ArithmeticToken>,
+    position: usize,
+    expander: &'a mut dyn Expander,
+    /// How many variable values deep this expression is.
+    depth: usize,
+}
+
+impl Evaluator<'_> {
+    fn peek(&self) -> Option<&ArithmeticToken> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_operator(&self, offset: usize) -> Option<&'static str> {
+        match self.tokens.get(self.position + offset) {
+            Some(ArithmeticToken::Operator(operator)) => Some(operator),
+            _ => None,
+        }
+    }
+
+    /// Consume the operator `operator` if it is next.
+    fn take_operator(&mut self, operator: &str) -> bool {
+        let matches = self.peek_operator(0) == Some(operator);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn expect_operator(&mut self, operator: &'static str, message: &'static str) -> Result<(), ArithmeticError> {
+        if self.take_operator(operator) {
+            Ok(())
+        } else {
+            Err(error(message, self.peek()))
+        }
+    }
+
+    fn variable(&mut self, name: &str) -> Result<i64, ArithmeticError> {
+        self.expander.check_set(name);
+        let value = self.expander.parameter(name).unwrap_or_default();
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(0);
+        }
+        if let Some(number) = parse_number(value) {
+            return Ok(number);
+        }
+        if self.depth >= MAX_VARIABLE_DEPTH {
+            return Err(ArithmeticError {
+                message: "expression recursion level exceeded",
+                token: Some(name.to_string()),
+            });
+        }
+        evaluate_expanded(value, self.expander, self.depth + 1)
+    }
+
+    fn assign(&mut self, name: &str, value: i64) {
+        self.expander.assign(name, &value.to_string());
+    }
+
+    fn comma(&mut self, evaluate: bool) -> Result<i64, ArithmeticError> {
+        let mut value = self.assignment(evaluate)?;
+        while self.take_operator(",") {
+            value = self.assignment(evaluate)?;
+        }
+        Ok(value)
+    }
+
+    fn assignment(&mut self, evaluate: bool) -> Result<i64, ArithmeticError> {
+        let operator = self
+            .peek_operator(1)
+            .filter(|operator| ASSIGNMENT_OPERATORS.contains(operator));
+        let (Some(ArithmeticToken::Name(name)), Some(operator)) = (self.peek().cloned(), operator) else {
+            return self.conditional(evaluate);
+        };
+        self.position += 2;
+        let right = self.assignment(evaluate)?;
+        if !evaluate {
+            return Ok(0);
+        }
+        let value = match operator.strip_suffix('=').filter(|operator| !operator.is_empty()) {
+            Some(operator) => {
+                let left = self.variable(&name)?;
+                apply(operator, left, right)?
+            }
+            None => right,
+        };
+        self.assign(&name, value);
+        Ok(value)
+    }
+
+    fn conditional(&mut self, evaluate: bool) -> Result<i64, ArithmeticError> {
+        let condition = self.binary(0, evaluate)?;
+        if !self.take_operator("?") {
+            return Ok(condition);
+        }
+        let when_true = self.comma(evaluate && condition != 0)?;
+        self.expect_operator(":", "`:' expected for conditional expression")?;
+        let when_false = self.conditional(evaluate && condition == 0)?;
+        Ok(if condition != 0 { when_true } else { when_false })
+    }
+
+    /// The binary operators of `BINARY_OPERATOR_LEVELS[level]` and tighter, left to right.
+    fn binary(&mut self, level: usize, evaluate: bool) -> Result<i64, ArithmeticError> {
+        let Some(operators) = BINARY_OPERATOR_LEVELS.get(level) else {
+            return self.power(evaluate);
+        };
+        let mut left = self.binary(level + 1, evaluate)?;
+        while let Some(operator) = self.peek_operator(0).filter(|operator| operators.contains(operator)) {
+            self.position += 1;
+            let right_evaluated = match operator {
+                "&&" => evaluate && left != 0,
+                "||" => evaluate && left == 0,
+                _ => evaluate,
+            };
+            let right = self.binary(level + 1, right_evaluated)?;
+            left = if evaluate { apply(operator, left, right)? } else { 0 };
+        }
+        Ok(left)
+    }
+
+    fn power(&mut self, evaluate: bool) -> Result<i64, ArithmeticError> {
+        let base = self.unary(evaluate)?;
+        if !self.take_operator("**") {
+            return Ok(base);
+        }
+        let exponent = self.power(evaluate)?;
+        if evaluate {
+            apply("**", base, exponent)
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn unary(&mut self, evaluate: bool) -> Result<i64, ArithmeticError> {
+        let Some(operator) = self.peek_operator(0) else {
+            return self.postfix(evaluate);
+        };
+        match operator {
+            "++" | "--" => {
+                self.position += 1;
+                let Some(ArithmeticToken::Name(name)) = self.peek().cloned() else {
+                    return Err(error("syntax error: operand expected", self.peek()));
+                };
+                self.position += 1;
+                if !evaluate {
+                    return Ok(0);
+                }
+                let value = self
+                    .variable(&name)?
+                    .wrapping_add(if operator == "++" { 1 } else { -1 });
+                self.assign(&name, value);
+                Ok(value)
+            }
+            "+" | "-" | "!" | "~" => {
+                self.position += 1;
+                let value = self.unary(evaluate)?;
+                Ok(match operator {
+                    "-" => value.wrapping_neg(),
+                    "!" => i64::from(value == 0),
+                    "~" => !value,
+                    _ => value,
+                })
+            }
+            _ => self.postfix(evaluate),
+        }
+    }
+
+    fn postfix(&mut self, evaluate: bool) -> Result<i64, ArithmeticError> {
+        if let (Some(ArithmeticToken::Name(name)), Some(operator @ ("++" | "--"))) =
+            (self.peek().cloned(), self.peek_operator(1))
+        {
+            self.position += 2;
+            if !evaluate {
+                return Ok(0);
+            }
+            let value = self.variable(&name)?;
+            self.assign(&name, value.wrapping_add(if operator == "++" { 1 } else { -1 }));
+            return Ok(value);
+        }
+        self.primary(evaluate)
+    }
+
+    fn primary(&mut self, evaluate: bool) -> Result<i64, ArithmeticError> {
+        let token = self.peek().cloned();
+        match token {
+            Some(ArithmeticToken::Number(text)) => {
+                self.position += 1;
+                parse_number(&text).ok_or(ArithmeticError {
+                    message: "value too great for base",
+                    token: Some(text),
+                })
+            }
+            Some(ArithmeticToken::Name(name)) => {
+                self.position += 1;
+                if evaluate {
+                    self.variable(&name)
+                } else {
+                    Ok(0)
+                }
+            }
+            Some(ArithmeticToken::Operator("(")) => {
+                self.position += 1;
+                let value = self.comma(evaluate)?;
+                self.expect_operator(")", "missing `)'")?;
+                Ok(value)
+            }
+            token => Err(error("syntax error: operand expected", token.as_ref())),
+        }
+    }
+}
+
+/// Apply the binary operator `operator` to `left` and `right`.
+fn apply(operator: &str, left: i64, right: i64) -> Result<i64, ArithmeticError> {
+    let division_by_zero = || ArithmeticError {
+        message: "division by 0",
+        token: Some(right.to_string()),
+    };
+    Ok(match operator {
+        "||" => i64::from(left != 0 || right != 0),
+        "&&" => i64::from(left != 0 && right != 0),
+        "|" => left | right,
+        "^" => left ^ right,
+        "&" => left & right,
+        "==" => i64::from(left == right),
+        "!=" => i64::from(left != right),
+        "<=" => i64::from(left <= right),
+        ">=" => i64::from(left >= right),
+        "<" => i64::from(left < right),
+        ">" => i64::from(left > right),
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" if right == 0 => return Err(division_by_zero()),
+        "/" => left.wrapping_div(right),
+        "%" if right == 0 => return Err(division_by_zero()),
+        "%" => left.wrapping_rem(right),
+        "**" if right < 0 => {
+            return Err(ArithmeticError {
+                message: "exponent less than 0",
+                token: Some(right.to_string()),
+            })
+        }
+        "**" => left.wrapping_pow(u32::try_from(right).unwrap_or(u32::MAX)),
+        _ => {
+            return Err(ArithmeticError {
+                message: "syntax error: invalid arithmetic operator",
+                token: Some(operator.to_string()),
+            })
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::tests::TestExpander;
+
+    #[test]
+    fn evaluation() {
+        let cases = [
+            ("1 + 2 * 3", 7),
+            ("(1 + 2) * 3", 9),
+            ("2 ** 10", 1024),
+            ("7 / 2", 3),
+            ("-7 % 3", -1),
+            ("0x1f + 010 + 2#101", 44),
+            ("1 << 4 | 256 >> 2", 80),
+            ("1 << 2 <= 4", 1),
+            ("10 > 3 && _empty == 0", 1),
+            ("_empty ? 1 : 2", 2),
+            ("(1, ~4)", -5),
+            ("$# * 3", 6),
+        ];
+        for (expression, expected) in cases {
+            let value = evaluate_arithmetic(expression, &mut TestExpander::default());
+            assert_eq!(value, Ok(expected), "expression: {expression:?}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let errors = [
+            ("1 +", "syntax error: operand expected"),
+            ("(1", "missing `)'"),
+            ("2 ** -1", "exponent less than 0 (error token is \"-1\")"),
+            ("1 2", "syntax error in expression (error token is \"2\")"),
+            (
+                "1 @ 2",
+                "syntax error: invalid arithmetic operator (error token is \"@ 2\")",
+            ),
+            ("09", "value too great for base (error token is \"09\")"),
+        ];
+        for (expression, expected) in errors {
+            let error =
+                evaluate_arithmetic(expression, &mut TestExpander::default()).map_err(|error| error.to_string());
+            assert_eq!(error, Err(expected.to_string()), "expression: {expression:?}");
+        }
+    }
+}
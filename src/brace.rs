@@ -0,0 +1,210 @@
+use crate::pattern::PATTERN_ESCAPE;
+
+pub const BRACE_CLOSE: char = '}';
+pub const BRACE_OPEN: char = '{';
+pub const BRACE_SEPARATOR: char = ',';
+const SEQUENCE_SEPARATOR: &str = "..";
+/// Characters with a meaning in brace expansion, escaped in a word's pattern form when quoted.
+pub const BRACE_SPECIAL_CHARS: &[char] = &[BRACE_CLOSE, BRACE_OPEN, BRACE_SEPARATOR];
+const VARIABLE_PREFIX: char = '$';
+
+/// The words `word`, in pattern form, brace-expands to, left to right: `pre{a,b}post` becomes
+/// `preapost` and `prebpost`. Alternatives may hold further braces, which expand in turn.
+///
+/// A sequence expression, `{x..y}` or `{x..y..step}`, stands for the integers or single letters
+/// from `x` to `y`, counting up or down by `step`; integers written with a leading zero are padded
+/// to the same width. A brace without a matching `}`, or holding neither a top-level `,` nor a
+/// sequence, is literal, as is `${`.
+#[must_use]
+pub fn expand_braces(word: &str) -> Vec<String> {
+    let mut search_from = 0;
+    while let Some(open) = find_unescaped(word, BRACE_OPEN, search_from) {
+        let after_dollar = word[..open].ends_with(VARIABLE_PREFIX) && !is_escaped(word, open - 1);
+        if let Some((close, separators)) = brace_body(word, open).filter(|_| !after_dollar) {
+            let (prefix, suffix) = (&word[..open], &word[close + 1..]);
+            if separators.is_empty() {
+                if let Some(sequence) = expand_sequence(&word[open + 1..close]) {
+                    return sequence
+                        .iter()
+                        .flat_map(|item| expand_braces(&format!("{prefix}{item}{suffix}")))
+                        .collect();
+                }
+            } else {
+                let bounds: Vec<usize> = [open].into_iter().chain(separators).chain([close]).collect();
+                return bounds
+                    .windows(2)
+                    .flat_map(|bound| expand_braces(&format!("{prefix}{}{suffix}", &word[bound[0] + 1..bound[1]])))
+                    .collect();
+            }
+        }
+        search_from = open + 1;
+    }
+    vec![word.to_string()]
+}
+
+/// The byte index of the first unescaped `target` at or after `from`.
+fn find_unescaped(word: &str, target: char, from: usize) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in word.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == PATTERN_ESCAPE {
+            escaped = true;
+        } else if c == target && index >= from {
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// Whether the character at byte `index` follows an unescaped `\`.
+fn is_escaped(word: &str, index: usize) -> bool {
+    word[..index].chars().rev().take_while(|&c| c == PATTERN_ESCAPE).count() % 2 == 1
+}
+
+/// The index of the `}` matching the `{` at `open`, with the indices of the `,`s directly inside
+/// it, or `None` when it is not closed.
+fn brace_body(word: &str, open: usize) -> Option<(usize, Vec<usize>)> {
+    let mut depth = 0_usize;
+    let mut separators = Vec::new();
+    let mut escaped = false;
+    for (index, c) in word[open + 1..].char_indices() {
+        let index = open + 1 + index;
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            PATTERN_ESCAPE => escaped = true,
+            BRACE_OPEN => depth += 1,
+            BRACE_CLOSE if depth == 0 => return Some((index, separators)),
+            BRACE_CLOSE => depth -= 1,
+            BRACE_SEPARATOR if depth == 0 => separators.push(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The items of the sequence expression `body`, the text between the braces, or `None` when it is
+/// not one.
+fn expand_sequence(body: &str) -> Option<Vec<String>> {
+    let mut parts = body.split(SEQUENCE_SEPARATOR);
+    let (start, end) = (parts.next()?, parts.next()?);
+    let step = match parts.next() {
+        Some(step) => step.parse::<i64>().ok()?.unsigned_abs().max(1),
+        None => 1,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+
+    if let (Ok(first), Ok(last)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let padded = [start, end]
+            .iter()
+            .any(|bound| bound.trim_start_matches('-').len() > 1 && bound.trim_start_matches('-').starts_with('0'));
+        let width = if padded { start.len().max(end.len()) } else { 0 };
+        return Some(
+            count(first, last, step)
+                .into_iter()
+                .map(|n| format!("{n:0width$}"))
+                .collect(),
+        );
+    }
+
+    let (mut start_chars, mut end_chars) = (start.chars(), end.chars());
+    match (
+        start_chars.next(),
+        start_chars.next(),
+        end_chars.next(),
+        end_chars.next(),
+    ) {
+        (Some(first), None, Some(last), None) if first.is_ascii_alphabetic() && last.is_ascii_alphabetic() => Some(
+            count(i64::from(u32::from(first)), i64::from(u32::from(last)), step)
+                .into_iter()
+                .filter_map(|n| char::from_u32(u32::try_from(n).ok()?))
+                .map(String::from)
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// The numbers from `first` to `last`, up or down by `step`, stopping before passing `last`.
+fn count(first: i64, last: i64, step: u64) -> Vec<i64> {
+    let step = i64::try_from(step).unwrap_or(i64::MAX);
+    let mut numbers = Vec::new();
+    let mut n = first;
+    loop {
+        numbers.push(n);
+        let next = if first <= last {
+            n.checked_add(step)
+        } else {
+            n.checked_sub(step)
+        };
+        match next {
+            Some(next) if (first <= last && next <= last) || (first > last && next >= last) => n = next,
+            _ => break numbers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_expansions(cases: &[(&str, &[&str])]) {
+        for (word, expected) in cases {
+            assert_eq!(expand_braces(word), *expected, "word: {word:?}");
+        }
+    }
+
+    #[test]
+    fn alternatives() {
+        assert_expansions(&[
+            (
+                "src/{parser,exec,builtins}",
+                &["src/parser", "src/exec", "src/builtins"],
+            ),
+            ("x{1,2}{3,4}", &["x13", "x14", "x23", "x24"]),
+            ("{,x}y", &["y", "xy"]),
+            ("{a,}", &["a", ""]),
+            ("a{b,c{d,e}}f", &["abf", "acdf", "acef"]),
+            ("{a,{b,{c,d}}}", &["a", "b", "c", "d"]),
+            ("{{a,b}}", &["{a}", "{b}"]),
+            ("{a}", &["{a}"]),
+            ("{}", &["{}"]),
+            ("{a,b", &["{a,b"]),
+            ("a,b}", &["a,b}"]),
+            ("${a,b}", &["${a,b}"]),
+            ("\\${a,b}", &["\\$a", "\\$b"]),
+            ("\\{a,b}", &["\\{a,b}"]),
+            ("{a\\,b}", &["{a\\,b}"]),
+            ("{a,b\\}", &["{a,b\\}"]),
+            ("{a\\}b,c}", &["a\\}b", "c"]),
+        ]);
+    }
+
+    #[test]
+    fn sequences() {
+        assert_expansions(&[
+            ("{1..5}", &["1", "2", "3", "4", "5"]),
+            ("{3..1}", &["3", "2", "1"]),
+            ("{08..11}", &["08", "09", "10", "11"]),
+            ("{-1..1}", &["-1", "0", "1"]),
+            ("{a..e}", &["a", "b", "c", "d", "e"]),
+            ("{a..e..2}", &["a", "c", "e"]),
+            ("{C..A}", &["C", "B", "A"]),
+            ("{0..100..25}", &["0", "25", "50", "75", "100"]),
+            ("{10..1..-4}", &["10", "6", "2"]),
+            ("{1..1}", &["1"]),
+            ("f{1..2}{a,b}", &["f1a", "f1b", "f2a", "f2b"]),
+            ("{a,{1..3}}", &["a", "1", "2", "3"]),
+            ("{1..}", &["{1..}"]),
+            ("{a..3}", &["{a..3}"]),
+            ("{1..2..3..4}", &["{1..2..3..4}"]),
+            ("{ab..c}", &["{ab..c}"]),
+            ("{1..2..x}", &["{1..2..x}"]),
+        ]);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::parser::ENVIRONMENT_VARIABLE_CLICOLOR;
+use crate::parser::ENVIRONMENT_VARIABLE_CLICOLOR_FORCE;
+use crate::parser::ENVIRONMENT_VARIABLE_NO_COLOR;
+use crate::parser::ENVIRONMENT_VARIABLE_TERM;
+use std::env::var_os;
+use std::io::IsTerminal;
+
+const ANSI_ESCAPE: char = '\x1b';
+const CSI_START: char = '[';
+const SGR_END: char = 'm';
+const TERM_DUMB: &str = "dumb";
+
+/// Whether color may be written to `stream`. The single policy behind every colored output:
+///
+/// 1. `NO_COLOR` set to anything non-empty turns color off (<https://no-color.org>).
+/// 2. `CLICOLOR_FORCE` set to anything but `0` turns it on, even when `stream` is not a terminal.
+/// 3. `CLICOLOR=0` or `TERM=dumb` turns it off.
+/// 4. Otherwise color is on only when `stream` is a terminal.
+#[must_use]
+pub fn color_enabled(stream: &impl IsTerminal) -> bool {
+    let set = |name: &str| var_os(name).filter(|value| !value.is_empty());
+    if set(ENVIRONMENT_VARIABLE_NO_COLOR).is_some() {
+        return false;
+    }
+    if set(ENVIRONMENT_VARIABLE_CLICOLOR_FORCE).is_some_and(|value| value != "0") {
+        return true;
+    }
+    if set(ENVIRONMENT_VARIABLE_CLICOLOR).is_some_and(|value| value == "0")
+        || set(ENVIRONMENT_VARIABLE_TERM).is_some_and(|value| value == TERM_DUMB)
+    {
+        return false;
+    }
+    stream.is_terminal()
+}
+
+/// `text` without its SGR (`ESC [ ... m`) sequences, for output where color is disabled.
+/// Other escape sequences, such as cursor movement, are kept.
+#[must_use]
+pub fn strip_colors(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(ANSI_ESCAPE) {
+        stripped.push_str(&rest[..start]);
+        let sequence = &rest[start + ANSI_ESCAPE.len_utf8()..];
+        let parameters = sequence
+            .strip_prefix(CSI_START)
+            .map(|after| after.trim_start_matches(|c: char| c.is_ascii_digit() || c == ';'));
+        match parameters.and_then(|after| after.strip_prefix(SGR_END)) {
+            Some(after) => rest = after,
+            None => {
+                stripped.push(ANSI_ESCAPE);
+                rest = sequence;
+            }
+        }
+    }
+    stripped.push_str(rest);
+    stripped
+}
@@ -0,0 +1,163 @@
+use crate::commands::ExecIgnore;
+use crate::parser::ENVIRONMENT_VARIABLE_PATH;
+use crate::parser::ENVIRONMENT_VARIABLE_PATH_DELIMITER;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env::var;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+pub const COMMAND_CACHE_FILE: &str = "commands";
+const DIRECTORY_HEADER: char = '@';
+const DIRECTORY_FIELD_SEPARATOR: char = '\t';
+
+/// Executable names found in one `$PATH` directory when it had modification time `modified`.
+#[derive(Clone, Debug)]
+struct CachedDirectory {
+    modified: u128,
+    names: Vec<String>,
+}
+
+/// The `$PATH` executables seen at the last scan, persisted so startup only rescans directories
+/// whose modification time changed.
+///
+/// The file holds `@<mtime-nanos>\t<directory>` headers, each followed by one executable name per line.
+#[derive(Debug, Default)]
+pub struct CommandCache {
+    path: Option<PathBuf>,
+    directories: HashMap<PathBuf, CachedDirectory>,
+}
+
+impl CommandCache {
+    /// Load `commands` in `cache_directory`, starting empty when it is missing or unreadable.
+    #[must_use]
+    pub fn load(cache_directory: Option<&Path>) -> Self {
+        let path = cache_directory.map(|directory| directory.join(COMMAND_CACHE_FILE));
+        let mut cache = Self {
+            path,
+            directories: HashMap::new(),
+        };
+
+        let Some(content) = cache.path.as_ref().and_then(|path| std::fs::read_to_string(path).ok()) else {
+            return cache;
+        };
+        let mut current: Option<&mut CachedDirectory> = None;
+        for line in content.lines() {
+            if let Some(header) = line.strip_prefix(DIRECTORY_HEADER) {
+                current = header
+                    .split_once(DIRECTORY_FIELD_SEPARATOR)
+                    .and_then(|(modified, directory)| Some((modified.parse().ok()?, directory)))
+                    .map(|(modified, directory)| {
+                        cache
+                            .directories
+                            .entry(PathBuf::from(directory))
+                            .or_insert(CachedDirectory {
+                                modified,
+                                names: Vec::new(),
+                            })
+                    });
+            } else if let Some(directory) = current.as_mut() {
+                directory.names.push(line.to_string());
+            }
+        }
+        cache
+    }
+
+    /// Executable names in every `$PATH` directory, minus those hidden by `$EXECIGNORE`, rescanning
+    /// only directories that changed since the cache was written. Directories no longer on `$PATH`
+    /// are dropped, and the cache is saved when anything changed.
+    pub fn executables(&mut self) -> Vec<String> {
+        let Ok(path_var) = var(ENVIRONMENT_VARIABLE_PATH) else {
+            return Vec::new();
+        };
+
+        let ignore = ExecIgnore::from_env();
+        let mut changed = false;
+        let mut executables = Vec::new();
+        let mut seen = HashSet::new();
+        for path_dir in path_var.split(ENVIRONMENT_VARIABLE_PATH_DELIMITER) {
+            let directory = PathBuf::from(path_dir);
+            let Some(modified) = modified_nanos(&directory) else {
+                continue;
+            };
+            seen.insert(directory.clone());
+            let up_to_date = self
+                .directories
+                .get(&directory)
+                .is_some_and(|cached| cached.modified == modified);
+            if !up_to_date {
+                let names = scan_directory(&directory);
+                self.directories
+                    .insert(directory.clone(), CachedDirectory { modified, names });
+                changed = true;
+            }
+            // Applied on the way out rather than when scanning, so the cache outlives `$EXECIGNORE` changes.
+            if let Some(cached) = self.directories.get(&directory) {
+                executables.extend(
+                    cached
+                        .names
+                        .iter()
+                        .filter(|name| !ignore.ignores(&directory.join(name)))
+                        .cloned(),
+                );
+            }
+        }
+
+        let cached_directories = self.directories.len();
+        self.directories.retain(|directory, _| seen.contains(directory));
+        if changed || self.directories.len() != cached_directories {
+            self.save();
+        }
+        executables
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(mut file) = std::fs::File::create(path) {
+            for (directory, cached) in &self.directories {
+                let _ = writeln!(
+                    file,
+                    "{DIRECTORY_HEADER}{}{DIRECTORY_FIELD_SEPARATOR}{}",
+                    cached.modified,
+                    directory.display()
+                );
+                for name in &cached.names {
+                    let _ = writeln!(file, "{name}");
+                }
+            }
+        }
+    }
+}
+
+fn modified_nanos(directory: &Path) -> Option<u128> {
+    let modified = std::fs::metadata(directory).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+/// Names of the executable files in `directory`. Names that would break the line-based cache
+/// file (newlines, or a leading `@`) are skipped.
+fn scan_directory(directory: &Path) -> Vec<String> {
+    let mut names = Vec::new();
+    if let Ok(dir_entries) = std::fs::read_dir(directory) {
+        for dir_entry in dir_entries.flatten() {
+            if let Ok(entry_metadata) = dir_entry.metadata() {
+                if entry_metadata.is_file() && (entry_metadata.permissions().mode() & 0o111 != 0) {
+                    if let Ok(file_name) = dir_entry.file_name().into_string() {
+                        if !file_name.contains('\n') && !file_name.starts_with(DIRECTORY_HEADER) {
+                            names.push(file_name);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    names
+}
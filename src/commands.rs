@@ -1,28 +1,76 @@
-use crate::jobs::JobManager;
+use crate::conditional::parse_test_arguments;
+use crate::error::io_error_message;
+use crate::error::report_error;
+use crate::error::ShellError;
+use crate::executor::ExecutionContext;
+use crate::fuzzy::closest;
+use crate::history::CommandHistory;
+use crate::history::HistoryFormat;
+use crate::home::home_directory;
+use crate::jobs::ResumeError;
+use crate::options::list_options;
+use crate::parse_dump::dump_parse;
 use crate::parser::expand_escape_sequences;
+use crate::parser::is_variable_name;
+use crate::parser::quote_word;
+use crate::parser::single_quote;
 use crate::parser::OutputRedirection;
+use crate::parser::BUILTIN_COMMANDS;
+use crate::parser::COMMAND_ALIAS;
+use crate::parser::COMMAND_BG;
 use crate::parser::COMMAND_CD;
+use crate::parser::COMMAND_COLON;
+use crate::parser::COMMAND_DEBUG;
 use crate::parser::COMMAND_ECHO;
 use crate::parser::COMMAND_ECHO_FLAG_EXPAND_ESCAPE;
 use crate::parser::COMMAND_EXIT;
+use crate::parser::COMMAND_HASH;
 use crate::parser::COMMAND_HISTORY;
 use crate::parser::COMMAND_JOBS;
+use crate::parser::COMMAND_JUMP;
+use crate::parser::COMMAND_KILL;
+use crate::parser::COMMAND_PRINTF;
 use crate::parser::COMMAND_PWD;
+use crate::parser::COMMAND_RETURN;
+use crate::parser::COMMAND_SESSION;
+use crate::parser::COMMAND_SET;
+use crate::parser::COMMAND_SHOPT;
+use crate::parser::COMMAND_TEST;
+use crate::parser::COMMAND_TEST_BRACKET;
 use crate::parser::COMMAND_TYPE;
-use crate::parser::ENVIRONMENT_VARIABLE_HOME;
+use crate::parser::COMMAND_UNALIAS;
+use crate::parser::COMMAND_Z;
+use crate::parser::ENVIRONMENT_VARIABLE_EXECIGNORE;
+use crate::parser::ENVIRONMENT_VARIABLE_OLDPWD;
 use crate::parser::ENVIRONMENT_VARIABLE_PATH;
 use crate::parser::ENVIRONMENT_VARIABLE_PATH_DELIMITER;
+use crate::parser::ENVIRONMENT_VARIABLE_PWD;
 use crate::parser::HOME_DIRECTORY;
-use rustyline::history::SearchDirection;
-use rustyline::Editor;
+use crate::pattern::pattern_matches;
+use crate::printf::format_printf;
+use crate::session::Session;
+use crate::signals::parse_signal;
+use crate::signals::reset_child_signals;
+use crate::signals::signal_name;
+use crate::signals::SIGNALS;
+use crate::state::ShellState;
+use std::collections::BTreeMap;
 use std::env::current_dir;
 use std::env::set_current_dir;
+use std::env::set_var;
+use std::env::split_paths;
 use std::env::var;
+use std::env::var_os;
+use std::env::vars_os;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fs::File;
 use std::fs::OpenOptions;
 use std::io;
 use std::io::Read;
 use std::io::Write;
-use std::iter::Enumerate;
+use std::os::fd::AsRawFd;
+use std::os::fd::RawFd;
 use std::os::unix::fs::PermissionsExt;
 use std::os::unix::process::CommandExt;
 use std::path::Path;
@@ -30,280 +78,637 @@ use std::path::PathBuf;
 use std::process::Child;
 use std::process::Command;
 use std::process::Stdio;
-use std::vec::IntoIter;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
+const MAX_COMMAND_SUGGESTIONS: usize = 3;
+/// Characters an alias name cannot hold, besides whitespace.
+const ALIAS_NAME_FORBIDDEN: &[char] = &['/', '$', '`', '=', '\'', '"', '\\', '|', '&', ';', '(', ')', '<', '>'];
+const DEBUG_PARSE: &str = "parse";
+const SESSION_RESTORE: &str = "restore";
+const SESSION_SAVE: &str = "save";
+/// The last argument of `[`.
+const TEST_BRACKET_CLOSE: &str = "]";
+const SET_USAGE: &str = "[-eux] [-o option] [--] [arg ...]";
+const Z_FLAG_LIST: &str = "-l";
+
+/// What the REPL does after a command: carry on with its status, or unwind and exit with a code.
 #[derive(Debug, PartialEq)]
 pub enum BuiltinAction {
-    Continue,
+    Continue(i32),
     Exit(i32),
+    /// `return` ends the running function call with this status.
+    Return(i32),
+    /// Returned in the forked child of a `( ... )` group, which runs this body and exits with its
+    /// status.
+    Subshell(String),
 }
 
 #[must_use]
-#[allow(clippy::too_many_arguments)]
-pub fn dispatch_builtin<H: rustyline::Helper, I: rustyline::history::History>(
+pub fn dispatch_builtin(
     command: &str,
-    arguments: Enumerate<IntoIter<String>>,
+    arguments: &[String],
     stdin: Box<dyn Read>,
     stdout: Box<dyn Write>,
     stderr: Box<dyn Write>,
-    editor: &mut Editor<H, I>,
-    last_appended_index: &mut usize,
-    job_mgr: &mut JobManager,
+    ctx: &mut ExecutionContext<'_>,
 ) -> Option<BuiltinAction> {
-    match command {
-        COMMAND_CD => {
-            command_cd(arguments, stdin, stdout, stderr);
-            Some(BuiltinAction::Continue)
-        }
-        COMMAND_ECHO => {
-            command_echo(arguments, stdin, stdout, stderr);
-            Some(BuiltinAction::Continue)
-        }
-        COMMAND_EXIT => {
-            let mut arguments = arguments;
-            let exit_code = match arguments.next() {
-                Some((_, code)) => code.parse::<i32>().unwrap_or(0),
-                None => 0,
-            };
-            Some(BuiltinAction::Exit(exit_code))
-        }
-        COMMAND_PWD => {
-            command_pwd(arguments, stdin, stdout, stderr);
-            Some(BuiltinAction::Continue)
-        }
-        COMMAND_TYPE => {
-            command_type(arguments, stdin, stdout, stderr);
-            Some(BuiltinAction::Continue)
-        }
-        COMMAND_HISTORY => {
-            command_history(editor, last_appended_index, arguments, stdin, stdout, stderr);
-            Some(BuiltinAction::Continue)
-        }
+    let state = &mut *ctx.state;
+    let status = match command {
+        COMMAND_ALIAS => command_alias(arguments, stdin, stdout, stderr, state),
+        COMMAND_BG => command_bg(arguments, stdin, stdout, stderr, state),
+        COMMAND_CD => command_cd(arguments, stdin, stdout, stderr, state),
+        COMMAND_COLON => 0,
+        COMMAND_DEBUG => command_debug(arguments, stdin, stdout, stderr, state),
+        COMMAND_SET => command_set(arguments, stdin, stdout, stderr, state),
+        COMMAND_SHOPT => command_shopt(arguments, stdin, stdout, stderr, state),
+        COMMAND_TEST => command_test(arguments, stdin, stdout, stderr, state),
+        COMMAND_TEST_BRACKET => command_test_bracket(arguments, stdin, stdout, stderr, state),
+        COMMAND_JUMP => command_jump(arguments, stdin, stdout, stderr, state),
+        COMMAND_ECHO => command_echo(arguments, stdin, stdout, stderr, state),
+        COMMAND_EXIT => return Some(command_exit(arguments, stdin, stdout, stderr, state)),
+        COMMAND_PRINTF => command_printf(arguments, stdin, stdout, stderr, state),
+        COMMAND_PWD => command_pwd(arguments, stdin, stdout, stderr, state),
+        COMMAND_RETURN => return Some(command_return(arguments, stdin, stdout, stderr, state)),
+        COMMAND_TYPE => command_type(arguments, stdin, stdout, stderr, state),
+        COMMAND_HISTORY => command_history(ctx.history, arguments, stdin, stdout, stderr, state),
+        COMMAND_HASH => command_hash(arguments, stdin, stdout, stderr, state),
         COMMAND_JOBS => {
             let mut stdout = stdout;
-            job_mgr.list_jobs(&mut stdout);
-            Some(BuiltinAction::Continue)
+            state.job_mgr.list_jobs(&mut stdout);
+            0
         }
-        _ => None,
-    }
+        COMMAND_KILL => command_kill(arguments, stdin, stdout, stderr, state),
+        COMMAND_SESSION => command_session(arguments, stdin, stdout, stderr, state),
+        COMMAND_UNALIAS => command_unalias(arguments, stdin, stdout, stderr, state),
+        COMMAND_Z => command_z(arguments, stdin, stdout, stderr, state),
+        _ => return None,
+    };
+    Some(BuiltinAction::Continue(status))
 }
 
 pub fn is_executable(full_path_to_executable: &Path) -> io::Result<bool> {
     Ok(full_path_to_executable.is_file() && (full_path_to_executable.metadata()?.permissions().mode() & 0o111 != 0))
 }
 
-pub fn search_executable(command: &str) -> Option<String> {
+/// The `$EXECIGNORE` patterns: full paths matching any of them are not commands for `$PATH`
+/// search or completion, e.g. `*.dll` for Windows libraries on a WSL-mounted `$PATH` entry.
+#[derive(Debug, Default)]
+pub struct ExecIgnore {
+    patterns: Vec<String>,
+}
+
+impl ExecIgnore {
+    /// The colon-separated patterns in `$EXECIGNORE`, read when called so changes apply at once.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let patterns = var(ENVIRONMENT_VARIABLE_EXECIGNORE)
+            .unwrap_or_default()
+            .split(ENVIRONMENT_VARIABLE_PATH_DELIMITER)
+            .filter(|pattern| !pattern.is_empty())
+            .map(String::from)
+            .collect();
+        Self { patterns }
+    }
+
+    #[must_use]
+    pub fn ignores(&self, path: &Path) -> bool {
+        !self.patterns.is_empty()
+            && path
+                .to_str()
+                .is_some_and(|path| self.patterns.iter().any(|pattern| pattern_matches(pattern, path)))
+    }
+}
+
+/// Names of all executable files in the `$PATH` directories not hidden by `$EXECIGNORE`,
+/// possibly with duplicates.
+#[must_use]
+pub fn path_executables() -> Vec<String> {
+    let ignore = ExecIgnore::from_env();
+    let mut executables = Vec::new();
     if let Ok(path_var) = var(ENVIRONMENT_VARIABLE_PATH) {
         for path_dir in path_var.split(ENVIRONMENT_VARIABLE_PATH_DELIMITER) {
-            let full_path = PathBuf::from(path_dir).join(command);
-            if is_executable(&full_path).unwrap_or(false) {
-                return Some(full_path.to_string_lossy().to_string());
+            if let Ok(dir_entries) = std::fs::read_dir(path_dir) {
+                for dir_entry in dir_entries.flatten() {
+                    if ignore.ignores(&dir_entry.path()) {
+                        continue;
+                    }
+                    if let Ok(entry_metadata) = dir_entry.metadata() {
+                        if entry_metadata.is_file() && (entry_metadata.permissions().mode() & 0o111 != 0) {
+                            if let Ok(file_name) = dir_entry.file_name().into_string() {
+                                executables.push(file_name);
+                            }
+                        }
+                    }
+                }
             }
         }
     }
-    None
+    executables
 }
 
-pub fn get_redirection(output: OutputRedirection) -> Option<Box<dyn Write>> {
-    if let Some(file_name) = output.file_name {
-        let mut options = OpenOptions::new();
-        options.create(true).write(true);
-        if output.append_to {
-            options.append(true);
-        } else {
-            options.truncate(true);
-        }
-        let file = options.open(&file_name);
-        match file {
-            Ok(file) => Some(Box::new(file) as Box<dyn Write>),
-            Err(e) => {
-                eprintln!("Error opening file {file_name}: {e}");
-                None
-            }
-        }
+/// The error for an unknown `command`, naming the closest builtins and `$PATH` commands if any.
+#[must_use]
+pub fn command_not_found(command: &str) -> ShellError {
+    let executables = path_executables();
+    let candidates = BUILTIN_COMMANDS
+        .iter()
+        .copied()
+        .chain(executables.iter().map(String::as_str));
+    let suggestions = closest(candidates, command, MAX_COMMAND_SUGGESTIONS)
+        .into_iter()
+        .map(ToString::to_string)
+        .collect();
+    ShellError::CommandNotFound {
+        command: command.to_string(),
+        suggestions,
+    }
+}
+
+/// The first executable named `command` in the `$PATH` directories, skipping paths that match
+/// `$EXECIGNORE`.
+pub fn search_executable(command: impl AsRef<OsStr>) -> Option<PathBuf> {
+    let path_var = var_os(ENVIRONMENT_VARIABLE_PATH)?;
+    let ignore = ExecIgnore::from_env();
+    split_paths(&path_var)
+        .map(|path_dir| path_dir.join(command.as_ref()))
+        .find(|full_path| !ignore.ignores(full_path) && is_executable(full_path).unwrap_or(false))
+}
+
+/// Open the redirection target in truncate or append mode.
+pub fn get_redirection(output: &OutputRedirection) -> Result<File, ShellError> {
+    let mut options = OpenOptions::new();
+    options.create(true).write(true);
+    if output.append_to {
+        options.append(true);
     } else {
-        None
+        options.truncate(true);
     }
+    options
+        .open(&output.file_name)
+        .map_err(|source| ShellError::RedirectionFailed {
+            path: output.file_name.clone(),
+            source,
+        })
+}
+
+/// Open `file_name` for a `< file` redirection.
+pub fn get_input_redirection(file_name: &str) -> Result<File, ShellError> {
+    File::open(file_name).map_err(|source| ShellError::RedirectionFailed {
+        path: file_name.to_string(),
+        source,
+    })
 }
 
+/// Spawn `executable_path` with `original_command` as its `argv[0]`, the `NAME=value` pairs of
+/// `environment` added to its environment, and each file in `descriptors` open as its descriptor
+/// number beyond the standard streams, or that descriptor closed for `None`.
 #[allow(clippy::too_many_arguments)]
 pub fn run_executable(
-    executable_path: &str,
-    original_command: &str,
-    command_arguments: Enumerate<IntoIter<String>>,
+    executable_path: &Path,
+    original_command: &OsStr,
+    command_arguments: &[OsString],
+    environment: &[(String, String)],
     stdin: Stdio,
-    stdout: &mut Box<dyn Write>,
-    stderr: &mut Box<dyn Write>,
-    inherit_stdout: bool,
-    inherit_stderr: bool,
-    previous_child: Option<Child>,
+    stdout: Stdio,
+    stderr: Stdio,
+    descriptors: &[(RawFd, Option<File>)],
 ) -> Result<Child, io::Error> {
     let mut command = Command::new(executable_path);
-    command.arg0(original_command);
-    command.stdin(stdin);
-
-    if inherit_stdout {
-        command.stdout(Stdio::inherit());
-    } else {
-        command.stdout(Stdio::piped());
-    }
-
-    if inherit_stderr {
-        command.stderr(Stdio::inherit());
-    } else {
-        command.stderr(Stdio::piped());
-    }
-
-    for (_, argument) in command_arguments {
-        command.arg(argument);
+    reset_child_signals(&mut command)
+        .arg0(original_command)
+        .args(command_arguments)
+        .envs(environment.iter().map(|(name, value)| (name, value)))
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(stderr);
+    if !descriptors.is_empty() {
+        inherit_descriptors(&mut command, descriptors);
     }
+    command.spawn()
+}
 
-    let mut child = command.spawn()?;
-
-    if let Some(mut previous) = previous_child {
-        previous.wait()?;
+/// Make the child open each file in `descriptors` as its descriptor number, and close those without one.
+fn inherit_descriptors(command: &mut Command, descriptors: &[(RawFd, Option<File>)]) {
+    let mut mapping: Vec<(RawFd, RawFd)> = descriptors
+        .iter()
+        .filter_map(|(descriptor, file)| Some((*descriptor, file.as_ref()?.as_raw_fd())))
+        .collect();
+    let closed: Vec<RawFd> = descriptors
+        .iter()
+        .filter(|(_, file)| file.is_none())
+        .map(|(descriptor, _)| *descriptor)
+        .collect();
+    let above_targets = mapping
+        .iter()
+        .map(|(descriptor, _)| descriptor + 1)
+        .max()
+        .unwrap_or_default();
+    // SAFETY: `fcntl`, `dup2`, and `close` are async-signal-safe, and the closure does not allocate.
+    unsafe {
+        command.pre_exec(move || {
+            // Move every file above all the targets first, so no `dup2` closes a file still to be placed.
+            for (_, file) in &mut mapping {
+                *file = libc::fcntl(*file, libc::F_DUPFD_CLOEXEC, above_targets);
+                if *file < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            // The duplicate made by `dup2` is not close-on-exec, so it survives into the program.
+            for &(descriptor, file) in &mapping {
+                if libc::dup2(file, descriptor) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            for &descriptor in &closed {
+                libc::close(descriptor);
+            }
+            Ok(())
+        });
     }
+}
 
-    if !inherit_stdout {
-        if let Some(mut child_stdout) = child.stdout.take() {
-            io::copy(&mut child_stdout, stdout)?;
+/// `debug parse line...`: show how the line, the arguments joined by spaces, parses, without
+/// running it.
+pub fn command_debug(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    mut stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> i32 {
+    let status = match arguments.split_first() {
+        Some((action, line)) if action == DEBUG_PARSE && !line.is_empty() => {
+            let dumped = dump_parse(&line.join(" "), &state.aliases, &mut stdout, &mut stderr);
+            dumped.unwrap_or_else(|error| ShellError::from(error).report(&mut stderr))
         }
-    }
-
-    if !inherit_stderr {
-        if let Some(mut child_stderr) = child.stderr.take() {
-            io::copy(&mut child_stderr, stderr)?;
+        _ => {
+            report_error(
+                &mut stderr,
+                COMMAND_DEBUG,
+                format_args!("usage: {COMMAND_DEBUG} parse line"),
+            );
+            2
         }
-    }
-
-    Ok(child)
+    };
+    stdout.flush().unwrap_or_default();
+    status
 }
 
 pub fn command_echo(
-    arguments: Enumerate<IntoIter<String>>,
+    arguments: &[String],
     _stdin: Box<dyn Read>,
     mut stdout: Box<dyn Write>,
-    _stderr: Box<dyn Write>,
-) {
+    mut stderr: Box<dyn Write>,
+    _state: &mut ShellState,
+) -> i32 {
+    match write_echo(arguments, &mut stdout).and_then(|()| stdout.flush()) {
+        Ok(()) => 0,
+        Err(error) => {
+            report_error(
+                &mut stderr,
+                COMMAND_ECHO,
+                format_args!("write error: {}", io_error_message(&error)),
+            );
+            1
+        }
+    }
+}
+
+fn write_echo(arguments: &[String], stdout: &mut dyn Write) -> io::Result<()> {
     let mut expand_escape_sequences_flag = false;
     let mut first_argument = true;
 
-    for (index, argument) in arguments {
+    for (index, argument) in arguments.iter().enumerate() {
         if first_argument && index == 0 && argument == COMMAND_ECHO_FLAG_EXPAND_ESCAPE {
             expand_escape_sequences_flag = true;
             continue;
         }
 
         if !first_argument {
-            write!(stdout, " ").unwrap_or_default();
+            write!(stdout, " ")?;
         }
 
         if expand_escape_sequences_flag {
-            write!(stdout, "{}", expand_escape_sequences(&argument)).unwrap_or_default();
+            let (bytes, stopped) = expand_escape_sequences(argument);
+            stdout.write_all(&bytes)?;
+            if stopped {
+                return Ok(());
+            }
         } else {
-            write!(stdout, "{argument}").unwrap_or_default();
+            write!(stdout, "{argument}")?;
         }
 
         first_argument = false;
     }
-    writeln!(stdout).unwrap_or_default();
-    stdout.flush().unwrap_or_default();
+    writeln!(stdout)
+}
+
+/// Ask the REPL to shut down with the given code, or the last status without one. The exit itself
+/// happens in the caller so history is saved and output flushed on the way out.
+pub fn command_exit(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    _stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> BuiltinAction {
+    let action = match arguments {
+        [] => BuiltinAction::Exit(state.last_status),
+        [code] => match code.parse::<i64>() {
+            // Like a process exit status, the code is taken modulo 256.
+            Ok(code) => BuiltinAction::Exit(i32::try_from(code.rem_euclid(256)).unwrap_or_default()),
+            Err(_) => {
+                report_error(
+                    &mut stderr,
+                    COMMAND_EXIT,
+                    format_args!("{code}: numeric argument required"),
+                );
+                BuiltinAction::Exit(2)
+            }
+        },
+        _ => {
+            report_error(&mut stderr, COMMAND_EXIT, "too many arguments");
+            BuiltinAction::Continue(1)
+        }
+    };
+    stderr.flush().unwrap_or_default();
+    action
+}
+
+/// `return [n]`: end the running function with status `n` (modulo 256), or the last command's.
+pub fn command_return(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    _stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> BuiltinAction {
+    let action = match arguments {
+        [] => BuiltinAction::Return(state.last_status),
+        [code] => match code.parse::<i64>() {
+            Ok(code) => BuiltinAction::Return(i32::try_from(code.rem_euclid(256)).unwrap_or_default()),
+            Err(_) => {
+                report_error(
+                    &mut stderr,
+                    COMMAND_RETURN,
+                    format_args!("{code}: numeric argument required"),
+                );
+                BuiltinAction::Return(2)
+            }
+        },
+        _ => {
+            report_error(&mut stderr, COMMAND_RETURN, "too many arguments");
+            BuiltinAction::Continue(1)
+        }
+    };
+    stderr.flush().unwrap_or_default();
+    action
 }
 
 pub fn command_type(
-    mut arguments: Enumerate<IntoIter<String>>,
+    arguments: &[String],
     _stdin: Box<dyn Read>,
     mut stdout: Box<dyn Write>,
     mut stderr: Box<dyn Write>,
-) {
-    if let Some((_, command)) = arguments.next() {
+    state: &mut ShellState,
+) -> i32 {
+    let mut status = 0;
+    if let Some(command) = arguments.first() {
         match command.as_str() {
-            COMMAND_CD | COMMAND_ECHO | COMMAND_EXIT | COMMAND_PWD | COMMAND_TYPE | COMMAND_HISTORY | COMMAND_JOBS => {
+            name if state.aliases.contains_key(name) => {
+                let value = &state.aliases[name];
+                writeln!(stdout, "{command} is aliased to `{value}'").unwrap_or_default();
+            }
+            name if state.functions.contains_key(name) => {
+                writeln!(stdout, "{command} is a function").unwrap_or_default();
+            }
+            name if BUILTIN_COMMANDS.contains(&name) || state.plugins.contains(name) => {
                 writeln!(stdout, "{command} is a shell builtin").unwrap_or_default();
             }
             _ => {
-                if let Some(path) = search_executable(&command) {
-                    writeln!(stdout, "{command} is {path}").unwrap_or_default();
+                if let Some(path) = search_executable(command) {
+                    writeln!(stdout, "{command} is {}", path.display()).unwrap_or_default();
                 } else {
-                    writeln!(stderr, "{command}: not found").unwrap_or_default();
+                    report_error(&mut stderr, COMMAND_TYPE, format_args!("{command}: not found"));
+                    status = 1;
                 }
             }
         }
     }
     stdout.flush().unwrap_or_default();
     stderr.flush().unwrap_or_default();
+    status
 }
 
-pub fn command_pwd(
-    _arguments: Enumerate<IntoIter<String>>,
+/// `test expression` exits with `0` when the expression holds, `1` when it does not, and `2` when
+/// it is not a valid expression or a test fails, as `-eq` does with a word that is not an integer.
+pub fn command_test(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    _stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    _state: &mut ShellState,
+) -> i32 {
+    evaluate_test(COMMAND_TEST, arguments, &mut stderr)
+}
+
+/// `[ expression ]` is `test expression`, and must end with `]`.
+pub fn command_test_bracket(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    _stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    _state: &mut ShellState,
+) -> i32 {
+    match arguments.split_last() {
+        Some((last, expression)) if last == TEST_BRACKET_CLOSE => {
+            evaluate_test(COMMAND_TEST_BRACKET, expression, &mut stderr)
+        }
+        _ => {
+            report_error(&mut stderr, COMMAND_TEST_BRACKET, "missing `]'");
+            stderr.flush().unwrap_or_default();
+            2
+        }
+    }
+}
+
+/// The status of `test` with `arguments`, reporting errors as `command`.
+fn evaluate_test(command: &str, arguments: &[String], stderr: &mut dyn Write) -> i32 {
+    let result = parse_test_arguments(arguments).and_then(|expression| expression.evaluate_literal());
+    match result {
+        Ok(holds) => i32::from(!holds),
+        Err(error) => {
+            report_error(stderr, command, error);
+            stderr.flush().unwrap_or_default();
+            2
+        }
+    }
+}
+
+/// `printf format [argument ...]` prints the arguments as the format says, with the conversions of
+/// [`format_printf`]. An argument that is not a valid number is reported and the status is `1`.
+pub fn command_printf(
+    arguments: &[String],
     _stdin: Box<dyn Read>,
     mut stdout: Box<dyn Write>,
     mut stderr: Box<dyn Write>,
-) {
-    if let Ok(current_dir) = current_dir() {
-        writeln!(stdout, "{}", current_dir.display()).unwrap_or_default();
-    } else {
-        writeln!(stderr, "pwd: error retrieving current directory").unwrap_or_default();
+    _state: &mut ShellState,
+) -> i32 {
+    let arguments = match arguments.split_first() {
+        Some((separator, rest)) if separator == "--" => rest,
+        _ => arguments,
+    };
+    let Some((format, arguments)) = arguments.split_first() else {
+        report_error(
+            &mut stderr,
+            COMMAND_PRINTF,
+            format_args!("usage: {COMMAND_PRINTF} format [arguments]"),
+        );
+        stderr.flush().unwrap_or_default();
+        return 2;
+    };
+    let (output, errors) = format_printf(format, arguments);
+    let mut status = 0;
+    if let Err(error) = stdout.write_all(&output).and_then(|()| stdout.flush()) {
+        report_error(
+            &mut stderr,
+            COMMAND_PRINTF,
+            format_args!("write error: {}", io_error_message(&error)),
+        );
+        status = 1;
+    }
+    for error in &errors {
+        report_error(&mut stderr, COMMAND_PRINTF, error);
+        status = 1;
     }
-    stdout.flush().unwrap_or_default();
     stderr.flush().unwrap_or_default();
+    status
 }
 
-pub fn command_history<H: rustyline::Helper, I: rustyline::history::History>(
-    readline: &mut Editor<H, I>,
-    last_appended_index: &mut usize,
-    arguments: Enumerate<IntoIter<String>>,
+pub fn command_pwd(
+    _arguments: &[String],
     _stdin: Box<dyn Read>,
     mut stdout: Box<dyn Write>,
     mut stderr: Box<dyn Write>,
-) {
-    let args: Vec<String> = arguments.map(|(_, a)| a).collect();
+    _state: &mut ShellState,
+) -> i32 {
+    let status = match current_dir() {
+        Ok(current_dir) => {
+            writeln!(stdout, "{}", current_dir.display()).unwrap_or_default();
+            0
+        }
+        Err(error) => {
+            report_error(
+                &mut stderr,
+                COMMAND_PWD,
+                format_args!("error retrieving current directory: {}", io_error_message(&error)),
+            );
+            1
+        }
+    };
+    stdout.flush().unwrap_or_default();
+    stderr.flush().unwrap_or_default();
+    status
+}
 
-    if args.first().map(std::string::String::as_str) == Some("-r") {
-        if let Some(path) = args.get(1) {
+pub fn command_history(
+    history: &mut dyn CommandHistory,
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    mut stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> i32 {
+    if arguments.first().map(std::string::String::as_str) == Some("-r") {
+        if let Some(path) = arguments.get(1) {
             if let Ok(content) = std::fs::read_to_string(path) {
                 for line in content.lines() {
                     if !line.is_empty() {
-                        let _ = readline.add_history_entry(line);
+                        history.add(line);
                     }
                 }
             }
         }
-        return;
+        return 0;
     }
 
-    if args.first().map(std::string::String::as_str) == Some("-a") {
-        if let Some(path) = args.get(1) {
+    if arguments.first().map(std::string::String::as_str) == Some("-a") {
+        if let Some(path) = arguments.get(1) {
             if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
-                let history = readline.history();
                 let len = history.len();
-                for i in *last_appended_index..len {
-                    if let Ok(Some(entry)) = history.get(i, SearchDirection::Forward) {
-                        let _ = writeln!(file, "{}", entry.entry);
+                for i in state.last_appended_index..len {
+                    if let Some(entry) = history.entry(i) {
+                        let _ = writeln!(file, "{entry}");
                     }
                 }
-                *last_appended_index = len;
+                state.last_appended_index = len;
             }
         }
-        return;
+        return 0;
     }
 
-    if args.first().map(std::string::String::as_str) == Some("-w") {
-        if let Some(path) = args.get(1) {
+    if arguments.first().map(std::string::String::as_str) == Some("-w") {
+        if let Some(path) = arguments.get(1) {
             if let Ok(mut file) = std::fs::File::create(path) {
-                let history = readline.history();
                 for i in 0..history.len() {
-                    if let Ok(Some(entry)) = history.get(i, SearchDirection::Forward) {
-                        let _ = writeln!(file, "{}", entry.entry);
+                    if let Some(entry) = history.entry(i) {
+                        let _ = writeln!(file, "{entry}");
                     }
                 }
             }
         }
-        return;
+        return 0;
+    }
+
+    if let Some(flag @ ("-R" | "-W")) = arguments.first().map(std::string::String::as_str) {
+        let (Some(format), Some(path), None) = (arguments.get(1), arguments.get(2), arguments.get(3)) else {
+            report_error(
+                &mut stderr,
+                COMMAND_HISTORY,
+                format_args!("usage: history {flag} bash|zsh FILE"),
+            );
+            stderr.flush().unwrap_or_default();
+            return 2;
+        };
+        let Some(format) = HistoryFormat::from_name(format) else {
+            report_error(
+                &mut stderr,
+                COMMAND_HISTORY,
+                format_args!("{format}: unknown history format"),
+            );
+            stderr.flush().unwrap_or_default();
+            return 2;
+        };
+        let result = if flag == "-R" {
+            std::fs::read(path).map(|content| {
+                for entry in format.import(&content) {
+                    history.add(&entry);
+                }
+            })
+        } else {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default();
+            std::fs::write(path, format.export(&history.entries(), timestamp))
+        };
+        let status = match result {
+            Ok(()) => 0,
+            Err(error) => {
+                report_error(
+                    &mut stderr,
+                    COMMAND_HISTORY,
+                    format_args!("{path}: {}", io_error_message(&error)),
+                );
+                1
+            }
+        };
+        stderr.flush().unwrap_or_default();
+        return status;
     }
 
-    let history = readline.history();
-    let count = if let Some(arg) = args.first() {
+    let count = if let Some(arg) = arguments.first() {
         arg.parse::<usize>().unwrap_or(0)
     } else {
         0
@@ -313,37 +718,656 @@ pub fn command_history<H: rustyline::Helper, I: rustyline::history::History>(
     let start_index = if count > 0 { len.saturating_sub(count) } else { 0 };
 
     for i in start_index..len {
-        if let Ok(Some(entry)) = history.get(i, SearchDirection::Forward) {
-            writeln!(stdout, "{:>5}  {}", i + 1, entry.entry).unwrap_or_default();
+        if let Some(entry) = history.entry(i) {
+            writeln!(stdout, "{:>5}  {entry}", i + 1).unwrap_or_default();
         }
     }
     stdout.flush().unwrap_or_default();
     stderr.flush().unwrap_or_default();
+    0
 }
 
 pub fn command_cd(
-    mut arguments: Enumerate<IntoIter<String>>,
+    arguments: &[String],
     _stdin: Box<dyn Read>,
     mut stdout: Box<dyn Write>,
     mut stderr: Box<dyn Write>,
-) {
-    let directory = match arguments.next() {
-        Some((_, dir)) => {
-            if dir == HOME_DIRECTORY {
-                var(ENVIRONMENT_VARIABLE_HOME).unwrap_or_default()
-            } else {
-                dir
+    state: &mut ShellState,
+) -> i32 {
+    let directory = match arguments.first() {
+        Some(dir) if dir != HOME_DIRECTORY => PathBuf::from(dir),
+        _ => {
+            let Some(home) = home_directory() else {
+                report_error(&mut stderr, COMMAND_CD, "HOME not set");
+                stderr.flush().unwrap_or_default();
+                return 1;
+            };
+            home
+        }
+    };
+
+    let previous_dir = current_dir().ok();
+    let status = match set_current_dir(&directory) {
+        Ok(()) => {
+            record_directory_change(previous_dir.as_deref());
+            if let Ok(new_dir) = current_dir() {
+                if previous_dir.as_ref() != Some(&new_dir) {
+                    if let Ok(mut directories) = state.directories.lock() {
+                        directories.visit(&new_dir);
+                    }
+                }
             }
+            0
+        }
+        Err(error) => {
+            report_error(
+                &mut stderr,
+                COMMAND_CD,
+                format_args!("{}: {}", directory.display(), io_error_message(&error)),
+            );
+            1
+        }
+    };
+    stdout.flush().unwrap_or_default();
+    stderr.flush().unwrap_or_default();
+    status
+}
+
+pub fn command_jump(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    mut stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> i32 {
+    let fragment = arguments.join(" ");
+    if fragment.is_empty() {
+        report_error(
+            &mut stderr,
+            COMMAND_JUMP,
+            format_args!("usage: {COMMAND_JUMP} <fragment>"),
+        );
+        stderr.flush().unwrap_or_default();
+        return 2;
+    }
+
+    let status = jump(COMMAND_JUMP, &fragment, &mut stderr, state);
+    stdout.flush().unwrap_or_default();
+    stderr.flush().unwrap_or_default();
+    status
+}
+
+/// `z [-l] [fragment...]`: jump to the best frecency match for the fragment, like `j`, or with
+/// `-l` (or no fragment) list the matching directories and their scores, best last.
+pub fn command_z(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    mut stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> i32 {
+    let (list, keywords) = match arguments.split_first() {
+        Some((flag, rest)) if flag == Z_FLAG_LIST => (true, rest),
+        Some((flag, _)) if flag.starts_with('-') && flag.len() > 1 => {
+            report_error(&mut stderr, COMMAND_Z, format_args!("{flag}: invalid option"));
+            report_error(
+                &mut stderr,
+                COMMAND_Z,
+                format_args!("usage: {COMMAND_Z} [-l] [fragment...]"),
+            );
+            stderr.flush().unwrap_or_default();
+            return 2;
         }
-        None => var(ENVIRONMENT_VARIABLE_HOME).unwrap_or_default(),
+        _ => (arguments.is_empty(), arguments),
+    };
+    let fragment = keywords.join(" ");
+
+    let status = if list {
+        let Ok(directories) = state.directories.lock() else {
+            return 1;
+        };
+        let scores = directories.scores(&fragment);
+        for (score, directory) in scores.iter().rev() {
+            writeln!(stdout, "{score:<10.1} {}", directory.display()).unwrap_or_default();
+        }
+        i32::from(scores.is_empty())
+    } else {
+        jump(COMMAND_Z, &fragment, &mut stderr, state)
+    };
+    stdout.flush().unwrap_or_default();
+    stderr.flush().unwrap_or_default();
+    status
+}
+
+/// Change to the best frecency match for `fragment` and record the visit, reporting failures as
+/// `command`.
+fn jump(command: &str, fragment: &str, stderr: &mut Box<dyn Write>, state: &mut ShellState) -> i32 {
+    let Ok(mut directories) = state.directories.lock() else {
+        return 1;
+    };
+    let Some(target) = directories.matches(fragment).into_iter().next() else {
+        report_error(stderr, command, format_args!("no match for {fragment}"));
+        return 1;
     };
 
-    match set_current_dir(&directory) {
-        Ok(()) => {}
-        Err(_) => {
-            writeln!(stderr, "cd: {directory}: No such file or directory").unwrap_or_default();
+    let previous_dir = current_dir().ok();
+    match set_current_dir(&target) {
+        Ok(()) => {
+            record_directory_change(previous_dir.as_deref());
+            directories.visit(&target);
+            0
+        }
+        Err(error) => {
+            report_error(
+                stderr,
+                command,
+                format_args!("{}: {}", target.display(), io_error_message(&error)),
+            );
+            1
+        }
+    }
+}
+
+/// Export `OLDPWD` as `previous_dir` and `PWD` as the new working directory, as `~-` and `~+`
+/// read them.
+fn record_directory_change(previous_dir: Option<&Path>) {
+    if let Some(previous_dir) = previous_dir {
+        set_var(ENVIRONMENT_VARIABLE_OLDPWD, previous_dir);
+    }
+    if let Ok(new_dir) = current_dir() {
+        set_var(ENVIRONMENT_VARIABLE_PWD, new_dir);
+    }
+}
+
+/// `set` lists the variables; otherwise `-e`, `-u`, `-x`, and `-o name` turn options on, and the
+/// same with `+` off, with `-o` alone listing them. The first argument that is not an option, or
+/// every argument after `--`, replaces the positional parameters.
+pub fn command_set(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    mut stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> i32 {
+    if arguments.is_empty() {
+        list_variables(state, &mut stdout);
+        stdout.flush().unwrap_or_default();
+        return 0;
+    }
+
+    let mut status = 0;
+    let mut args = arguments.iter();
+
+    while let Some(argument) = args.next() {
+        if argument == "--" {
+            state.positional = args.cloned().collect();
+            break;
+        }
+        let (enabled, flags) = if let Some(flags) = argument.strip_prefix('-').filter(|flags| !flags.is_empty()) {
+            (true, flags)
+        } else if let Some(flags) = argument.strip_prefix('+').filter(|flags| !flags.is_empty()) {
+            (false, flags)
+        } else {
+            state.positional = std::iter::once(argument).chain(args).cloned().collect();
+            break;
+        };
+        for flag in flags.chars() {
+            if flag != 'o' {
+                if let Err(message) = state.options.set_flag(flag, enabled) {
+                    report_error(&mut stderr, COMMAND_SET, message);
+                    report_error(
+                        &mut stderr,
+                        COMMAND_SET,
+                        format_args!("usage: {COMMAND_SET} {SET_USAGE}"),
+                    );
+                    stderr.flush().unwrap_or_default();
+                    return 2;
+                }
+                continue;
+            }
+            let Some(name) = args.next() else {
+                list_options(&state.options.named(), &mut stdout);
+                continue;
+            };
+            if let Err(message) = state.options.set_named(name, enabled) {
+                report_error(&mut stderr, COMMAND_SET, message);
+                status = 1;
+            }
+        }
+    }
+    stdout.flush().unwrap_or_default();
+    stderr.flush().unwrap_or_default();
+    status
+}
+
+/// Print every shell and environment variable with a valid name as `name=value`, sorted by name,
+/// with the value quoted so that it reads back the same.
+fn list_variables(state: &ShellState, out: &mut dyn Write) {
+    let mut variables: BTreeMap<String, String> = vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.to_string_lossy().into_owned())))
+        .collect();
+    variables.extend(state.variables.clone());
+    for (name, value) in variables.iter().filter(|(name, _)| is_variable_name(name)) {
+        let _ = writeln!(out, "{name}={}", quote_word(value));
+    }
+}
+
+/// `alias name=value ...` defines aliases, which apply from the next command line on, and
+/// `alias name ...` prints them; `alias` alone, or `alias -p`, prints them all, as
+/// `alias name='value'`.
+pub fn command_alias(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    mut stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> i32 {
+    let print = |out: &mut dyn Write, name: &str, value: &str| {
+        writeln!(out, "{COMMAND_ALIAS} {name}={}", single_quote(value)).unwrap_or_default();
+    };
+    let (list_all, arguments) = match arguments.split_first() {
+        Some((flag, rest)) if flag == "-p" => (true, rest),
+        _ => (arguments.is_empty(), arguments),
+    };
+    if list_all {
+        for (name, value) in &state.aliases {
+            print(&mut stdout, name, value);
+        }
+    }
+    if let Some(flag) = arguments.first().filter(|flag| flag.len() > 1 && flag.starts_with('-')) {
+        report_error(&mut stderr, COMMAND_ALIAS, format_args!("{flag}: invalid option"));
+        report_error(
+            &mut stderr,
+            COMMAND_ALIAS,
+            format_args!("usage: {COMMAND_ALIAS} [-p] [name[=value] ...]"),
+        );
+        stderr.flush().unwrap_or_default();
+        return 2;
+    }
+
+    let mut status = 0;
+    for argument in arguments {
+        match argument.split_once('=') {
+            Some((name, _)) if !is_alias_name(name) => {
+                report_error(&mut stderr, COMMAND_ALIAS, format_args!("`{name}': invalid alias name"));
+                status = 1;
+            }
+            Some((name, value)) => {
+                state.aliases.insert(name.to_string(), value.to_string());
+            }
+            None => match state.aliases.get(argument) {
+                Some(value) => print(&mut stdout, argument, value),
+                None => {
+                    report_error(&mut stderr, COMMAND_ALIAS, format_args!("{argument}: not found"));
+                    status = 1;
+                }
+            },
+        }
+    }
+    stdout.flush().unwrap_or_default();
+    stderr.flush().unwrap_or_default();
+    status
+}
+
+/// Whether `name` can name an alias: not empty, without whitespace, quotes, `$`, `/`, `=`, or the
+/// characters that end a word.
+fn is_alias_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || ALIAS_NAME_FORBIDDEN.contains(&c))
+}
+
+/// `unalias name ...` removes aliases, and `unalias -a` all of them.
+pub fn command_unalias(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    _stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> i32 {
+    let mut status = 0;
+    match arguments.split_first() {
+        Some((flag, _)) if flag == "-a" => state.aliases.clear(),
+        Some((flag, _)) if flag.len() > 1 && flag.starts_with('-') => {
+            report_error(&mut stderr, COMMAND_UNALIAS, format_args!("{flag}: invalid option"));
+            status = 2;
+        }
+        Some(_) => {
+            for name in arguments {
+                if state.aliases.remove(name).is_none() {
+                    report_error(&mut stderr, COMMAND_UNALIAS, format_args!("{name}: not found"));
+                    status = 1;
+                }
+            }
+        }
+        None => status = 2,
+    }
+    if status == 2 {
+        report_error(
+            &mut stderr,
+            COMMAND_UNALIAS,
+            format_args!("usage: {COMMAND_UNALIAS} [-a] name [name ...]"),
+        );
+    }
+    stderr.flush().unwrap_or_default();
+    status
+}
+
+pub fn command_shopt(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    mut stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> i32 {
+    let (enabled, names) = match arguments.split_first() {
+        Some((flag, names)) if flag == "-s" => (Some(true), names),
+        Some((flag, names)) if flag == "-u" => (Some(false), names),
+        Some((flag, _)) if flag.starts_with('-') => {
+            report_error(&mut stderr, COMMAND_SHOPT, format_args!("{flag}: invalid option"));
+            report_error(
+                &mut stderr,
+                COMMAND_SHOPT,
+                format_args!("usage: {COMMAND_SHOPT} [-s|-u] [optname ...]"),
+            );
+            return 2;
+        }
+        _ => (None, arguments),
+    };
+
+    let mut status = 0;
+
+    let all = state.options.shopt_named();
+    match enabled {
+        Some(enabled) if !names.is_empty() => {
+            for name in names {
+                if let Err(message) = state.options.set_shopt(name, enabled) {
+                    report_error(&mut stderr, COMMAND_SHOPT, message);
+                    status = 1;
+                }
+            }
+        }
+        Some(enabled) => {
+            let selected: Vec<_> = all.into_iter().filter(|(_, state)| *state == enabled).collect();
+            list_options(&selected, &mut stdout);
+        }
+        None if names.is_empty() => list_options(&all, &mut stdout),
+        None => {
+            for name in names {
+                match all.iter().find(|(option, _)| option == name) {
+                    Some(option) => list_options(std::slice::from_ref(option), &mut stdout),
+                    None => {
+                        report_error(
+                            &mut stderr,
+                            COMMAND_SHOPT,
+                            format_args!("{name}: invalid shell option name"),
+                        );
+                        status = 1;
+                    }
+                }
+            }
+        }
+    }
+    stdout.flush().unwrap_or_default();
+    stderr.flush().unwrap_or_default();
+    status
+}
+
+/// `bg [%job ...]` continues stopped jobs in the background, by default the most recent one.
+pub fn command_bg(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    mut stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> i32 {
+    let specs: Vec<Option<&str>> = if arguments.is_empty() {
+        vec![None]
+    } else {
+        arguments.iter().map(|spec| Some(spec.as_str())).collect()
+    };
+    let mut status = 0;
+    for spec in specs {
+        match state.job_mgr.resume(spec, &mut stdout) {
+            Ok(_) => {}
+            Err(ResumeError::NoSuchJob) => {
+                let spec = spec.unwrap_or("current");
+                report_error(&mut stderr, COMMAND_BG, format_args!("{spec}: no such job"));
+                status = 1;
+            }
+            Err(ResumeError::AlreadyRunning(id)) => {
+                report_error(&mut stderr, COMMAND_BG, format_args!("job {id} already in background"));
+            }
+        }
+    }
+    stdout.flush().unwrap_or_default();
+    stderr.flush().unwrap_or_default();
+    status
+}
+
+pub fn command_kill(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    mut stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> i32 {
+    let mut status = 0;
+    let mut signal = libc::SIGTERM;
+    let mut targets = arguments;
+
+    match targets {
+        [flag, rest @ ..] if flag == "-l" => {
+            if rest.is_empty() {
+                for (name, number) in SIGNALS {
+                    writeln!(stdout, "{number:>2}) SIG{name}").unwrap_or_default();
+                }
+            }
+            for spec in rest {
+                let Some(number) = parse_signal(spec) else {
+                    report_error(
+                        &mut stderr,
+                        COMMAND_KILL,
+                        format_args!("{spec}: invalid signal specification"),
+                    );
+                    status = 1;
+                    continue;
+                };
+                if spec.parse::<i32>().is_ok() {
+                    writeln!(stdout, "{}", signal_name(number).unwrap_or_default()).unwrap_or_default();
+                } else {
+                    writeln!(stdout, "{number}").unwrap_or_default();
+                }
+            }
+            stdout.flush().unwrap_or_default();
+            stderr.flush().unwrap_or_default();
+            return status;
+        }
+        [flag, spec, rest @ ..] if flag == "-s" || flag == "-n" => {
+            let Some(number) = parse_signal(spec) else {
+                report_error(
+                    &mut stderr,
+                    COMMAND_KILL,
+                    format_args!("{spec}: invalid signal specification"),
+                );
+                return 1;
+            };
+            signal = number;
+            targets = rest;
+        }
+        [flag, rest @ ..] if flag.len() > 1 && flag.starts_with('-') => {
+            let Some(number) = parse_signal(&flag[1..]) else {
+                report_error(
+                    &mut stderr,
+                    COMMAND_KILL,
+                    format_args!("{}: invalid signal specification", &flag[1..]),
+                );
+                return 1;
+            };
+            signal = number;
+            targets = rest;
+        }
+        _ => {}
+    }
+
+    if targets.is_empty() {
+        report_error(
+            &mut stderr,
+            COMMAND_KILL,
+            format_args!("usage: {COMMAND_KILL} [-s sigspec | -sigspec] pid | %job ... or {COMMAND_KILL} -l [sigspec]"),
+        );
+        status = 2;
+    }
+
+    for target in targets {
+        let pid = if target.starts_with('%') {
+            let Some(pid) = state.job_mgr.pid_of(target) else {
+                report_error(&mut stderr, COMMAND_KILL, format_args!("{target}: no such job"));
+                status = 1;
+                continue;
+            };
+            pid
+        } else if let Ok(pid) = target.parse::<u32>() {
+            pid
+        } else {
+            report_error(
+                &mut stderr,
+                COMMAND_KILL,
+                format_args!("{target}: arguments must be process or job IDs"),
+            );
+            status = 1;
+            continue;
+        };
+
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            report_error(&mut stderr, COMMAND_KILL, format_args!("({pid}) - No such process"));
+            status = 1;
+            continue;
+        };
+        if unsafe { libc::kill(pid, signal) } != 0 {
+            let error = io::Error::last_os_error();
+            report_error(
+                &mut stderr,
+                COMMAND_KILL,
+                format_args!("({pid}) - {}", io_error_message(&error)),
+            );
+            status = 1;
+        }
+    }
+    stdout.flush().unwrap_or_default();
+    stderr.flush().unwrap_or_default();
+    status
+}
+
+pub fn command_session(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    mut stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> i32 {
+    let (action, path) = match arguments {
+        [action] => (action.as_str(), Session::default_path(state)),
+        [action, file] => (action.as_str(), Some(PathBuf::from(file))),
+        _ => ("", None),
+    };
+
+    let status = match (action, path) {
+        (SESSION_SAVE | SESSION_RESTORE, None) => {
+            report_error(&mut stderr, COMMAND_SESSION, "HOME not set");
+            1
+        }
+        (SESSION_SAVE, Some(path)) => match Session::capture(state).save(&path) {
+            Ok(()) => 0,
+            Err(error) => {
+                report_error(
+                    &mut stderr,
+                    COMMAND_SESSION,
+                    format_args!("{}: {}", path.display(), io_error_message(&error)),
+                );
+                1
+            }
+        },
+        (SESSION_RESTORE, Some(path)) => match Session::load(&path) {
+            Ok(session) => {
+                let cwd = session.cwd.clone();
+                match (session.apply(state), cwd) {
+                    (Err(error), Some(cwd)) => {
+                        report_error(
+                            &mut stderr,
+                            COMMAND_SESSION,
+                            format_args!("{}: {}", cwd.display(), io_error_message(&error)),
+                        );
+                        1
+                    }
+                    _ => 0,
+                }
+            }
+            Err(error) => {
+                report_error(
+                    &mut stderr,
+                    COMMAND_SESSION,
+                    format_args!("{}: {}", path.display(), io_error_message(&error)),
+                );
+                1
+            }
+        },
+        _ => {
+            report_error(
+                &mut stderr,
+                COMMAND_SESSION,
+                format_args!("usage: {COMMAND_SESSION} save|restore [file]"),
+            );
+            2
+        }
+    };
+    stdout.flush().unwrap_or_default();
+    stderr.flush().unwrap_or_default();
+    status
+}
+
+pub fn command_hash(
+    arguments: &[String],
+    _stdin: Box<dyn Read>,
+    mut stdout: Box<dyn Write>,
+    mut stderr: Box<dyn Write>,
+    state: &mut ShellState,
+) -> i32 {
+    let mut status = 0;
+    match arguments.split_first() {
+        None if state.path_cache.is_empty() => {
+            report_error(&mut stderr, COMMAND_HASH, "hash table empty");
+        }
+        None => state.path_cache.list(&mut stdout),
+        Some((flag, names)) if flag == "-r" => {
+            state.path_cache.clear();
+            for name in names {
+                if state.path_cache.remember(name).is_none() {
+                    report_error(&mut stderr, COMMAND_HASH, format_args!("{name}: not found"));
+                    status = 1;
+                }
+            }
+        }
+        Some((flag, _)) if flag.starts_with('-') => {
+            report_error(&mut stderr, COMMAND_HASH, format_args!("{flag}: invalid option"));
+            report_error(
+                &mut stderr,
+                COMMAND_HASH,
+                format_args!("usage: {COMMAND_HASH} [-r] [name ...]"),
+            );
+            status = 2;
+        }
+        Some(_) => {
+            for name in arguments {
+                if BUILTIN_COMMANDS.contains(&name.as_str()) {
+                    continue;
+                }
+                if state.path_cache.remember(name).is_none() {
+                    report_error(&mut stderr, COMMAND_HASH, format_args!("{name}: not found"));
+                    status = 1;
+                }
+            }
         }
     }
     stdout.flush().unwrap_or_default();
     stderr.flush().unwrap_or_default();
+    status
 }
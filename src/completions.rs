@@ -0,0 +1,115 @@
+use crate::error::report_error;
+use crate::parser::parse_input;
+use crate::xdg::shell_file;
+use crate::xdg::BaseDirectory;
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+pub const COMPLETIONS_DIRECTORY_NAME: &str = "completions.d";
+/// Where completion files lived before they moved to the config directory.
+pub const LEGACY_COMPLETIONS_DIRECTORY_NAME: &str = ".ccsh_completions.d";
+const COMPLETE_COMMAND: &str = "complete";
+const COMPLETIONS_SOURCE: &str = "completions";
+const COMPLETIONS_COMMENT: char = '#';
+
+/// How the arguments of one command are completed, declared with `complete` in a completion file.
+#[derive(Clone, Debug, Default)]
+pub struct CompletionSpec {
+    /// Fixed candidate words (`-W "start stop status"`).
+    pub words: Vec<String>,
+    /// Offer directories (`-d`).
+    pub directories: bool,
+    /// Offer files and directories (`-f`).
+    pub files: bool,
+}
+
+/// Completion specs keyed by command name.
+pub type CompletionSpecs = HashMap<String, CompletionSpec>;
+
+/// `$XDG_CONFIG_HOME/ccsh/completions.d`, moving `~/.ccsh_completions.d` there if only that exists.
+#[must_use]
+pub fn completions_directory() -> Option<PathBuf> {
+    let directory = BaseDirectory::Config.shell_directory()?;
+    Some(shell_file(
+        &directory,
+        COMPLETIONS_DIRECTORY_NAME,
+        Some(LEGACY_COMPLETIONS_DIRECTORY_NAME),
+    ))
+}
+
+/// Read every file in `directory`, in name order, so later files override earlier ones.
+///
+/// Each line is a `complete [-W words] [-d] [-f] name...` invocation; blank lines and `#` comments
+/// are ignored, and invalid lines are reported on stderr and skipped.
+#[must_use]
+pub fn load_completion_specs(directory: &Path) -> CompletionSpecs {
+    let mut specs = CompletionSpecs::new();
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return specs;
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(COMPLETIONS_COMMENT) {
+                continue;
+            }
+            match parse_complete(line) {
+                Ok((names, spec)) => {
+                    for name in names {
+                        specs.insert(name, spec.clone());
+                    }
+                }
+                Err(message) => report_error(
+                    &mut std::io::stderr(),
+                    COMPLETIONS_SOURCE,
+                    format_args!("{}:{}: {message}", path.display(), number + 1),
+                ),
+            }
+        }
+    }
+    specs
+}
+
+/// Parse one `complete` invocation into the command names it applies to and their spec.
+fn parse_complete(line: &str) -> Result<(Vec<String>, CompletionSpec), String> {
+    let tokens = parse_input(line)
+        .ok()
+        .and_then(|pipeline| pipeline.into_iter().next())
+        .and_then(|command| command.tokens)
+        .unwrap_or_default();
+    let mut tokens = tokens.into_iter().map(|token| token.to_string_lossy().into_owned());
+    if tokens.next().as_deref() != Some(COMPLETE_COMMAND) {
+        return Err(format!("expected `{COMPLETE_COMMAND}`, found `{line}`"));
+    }
+
+    let mut spec = CompletionSpec::default();
+    let mut names = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token.as_str() {
+            "-W" => {
+                let words = tokens.next().ok_or("-W: missing word list")?;
+                spec.words.extend(words.split_whitespace().map(ToString::to_string));
+            }
+            "-d" => spec.directories = true,
+            "-f" => spec.files = true,
+            option if option.starts_with('-') => return Err(format!("{option}: unsupported option")),
+            _ => names.push(token),
+        }
+    }
+
+    if names.is_empty() {
+        return Err("missing command name".to_string());
+    }
+    Ok((names, spec))
+}
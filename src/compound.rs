@@ -0,0 +1,413 @@
+use crate::conditional::parse_conditional_expression;
+use crate::conditional::ConditionalExpression;
+use crate::lexer::arithmetic_command_end;
+use crate::lexer::ends_case_pattern;
+use crate::lexer::lex;
+use crate::lexer::reserved_word;
+use crate::lexer::starts_arithmetic_command;
+use crate::lexer::Lexed;
+use crate::lexer::Token;
+use crate::lexer::TokenKind;
+use crate::parser::ParseError;
+use crate::parser::CASE_BREAK;
+use crate::parser::CHAR_PAREN_OPEN;
+use crate::parser::CHAR_PIPE;
+use crate::parser::RESERVED_WORD_CASE;
+use crate::parser::RESERVED_WORD_CONDITIONAL_END;
+use crate::parser::RESERVED_WORD_CONDITIONAL_START;
+use crate::parser::RESERVED_WORD_ELIF;
+use crate::parser::RESERVED_WORD_ELSE;
+use crate::parser::RESERVED_WORD_ESAC;
+use crate::parser::RESERVED_WORD_FI;
+use crate::parser::RESERVED_WORD_IF;
+use crate::parser::RESERVED_WORD_IN;
+use crate::parser::RESERVED_WORD_NOT;
+use crate::parser::RESERVED_WORD_THEN;
+use std::ops::Range;
+
+/// A command built from command lists, which the shell runs itself instead of parsing it into a
+/// pipeline.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompoundCommand {
+    /// `if list; then list; [elif list; then list;]... [else list;] fi`.
+    If {
+        /// Each condition with the list run when it succeeds, tested in order.
+        branches: Vec<(String, String)>,
+        /// The `else` list, run when no condition succeeds.
+        otherwise: Option<String>,
+    },
+    /// `case word in [(]pattern[|pattern]...) list;; ... esac`.
+    Case {
+        /// The word matched against the patterns, as written.
+        subject: String,
+        /// The patterns of each arm, as written, with the list run when one of them matches,
+        /// tried in order.
+        arms: Vec<(Vec<String>, String)>,
+    },
+    /// `[[ expression ]]`.
+    Conditional(ConditionalExpression),
+    /// `(( expression ))`, with the expression as written.
+    Arithmetic(String),
+}
+
+/// Parse `input` as a compound command when it starts with one, as `if`, `case`, `[[`, and `((`
+/// do. Returns `None` for
+/// any other command, and a [`ParseError`] when the compound command is not valid syntax or
+/// anything but a comment follows it.
+#[must_use]
+pub fn parse_compound_command(input: &str) -> Option<Result<CompoundCommand, ParseError>> {
+    let lexed = lex(input);
+    let first = lexed.tokens.first()?;
+    if starts_arithmetic_command(input, first) {
+        return Some(parse_arithmetic(input, &lexed, first.span.start));
+    }
+    match reserved_word(input, first)? {
+        RESERVED_WORD_CASE => Some(parse_case(input)),
+        RESERVED_WORD_CONDITIONAL_START => Some(parse_conditional(input, first.span.end)),
+        RESERVED_WORD_IF => Some(parse_if(input)),
+        // `!` negates a pipeline, which may start with a compound command.
+        RESERVED_WORD_NOT => None,
+        // A reserved word that only continues a compound command cannot start one.
+        _ => Some(Err(ParseError::unexpected_token(input, first.span.clone()))),
+    }
+}
+
+/// Parse `input`, which starts with `if`, up to its `fi`.
+fn parse_if(input: &str) -> Result<CompoundCommand, ParseError> {
+    let lexed = lex(input);
+    // The reserved words of this `if`, leaving out those of nested ones.
+    let mut clauses: Vec<(&str, Range<usize>)> = Vec::new();
+    let mut depth = 0;
+    let mut end = None;
+    for index in lexed.command_word_indices(input) {
+        let token = &lexed.tokens[index];
+        let Some(word) = reserved_word(input, token) else {
+            continue;
+        };
+        let own = match word {
+            RESERVED_WORD_IF => {
+                depth += 1;
+                depth == 1
+            }
+            RESERVED_WORD_FI => {
+                depth -= 1;
+                depth == 0
+            }
+            RESERVED_WORD_THEN | RESERVED_WORD_ELIF | RESERVED_WORD_ELSE => depth == 1,
+            _ => continue,
+        };
+        if own {
+            clauses.push((word, token.span.clone()));
+        }
+        if depth == 0 {
+            end = Some(token.span.end);
+            break;
+        }
+    }
+    let Some(end) = end else {
+        return Err(ParseError::unexpected_end(input.trim_end(), None));
+    };
+    check_nothing_follows(input, &lexed, end)?;
+
+    let mut branches = Vec::new();
+    let mut otherwise = None;
+    let mut condition = None;
+    for pair in clauses.windows(2) {
+        let ((word, span), (next, next_span)) = (&pair[0], &pair[1]);
+        let follows = match *word {
+            RESERVED_WORD_IF | RESERVED_WORD_ELIF => *next == RESERVED_WORD_THEN,
+            RESERVED_WORD_THEN => [RESERVED_WORD_ELIF, RESERVED_WORD_ELSE, RESERVED_WORD_FI].contains(next),
+            _ => *next == RESERVED_WORD_FI,
+        };
+        if !follows {
+            return Err(ParseError::unexpected_token(input, next_span.clone()));
+        }
+        // A list needs a command, so its first token, or the next reserved word, is unexpected.
+        let mut tokens = lexed
+            .tokens
+            .iter()
+            .filter(|token| span.end <= token.span.start && token.span.end <= next_span.start);
+        if !tokens.clone().any(|token| token.kind == TokenKind::Word) {
+            let unexpected = tokens.next().map_or(next_span.clone(), |token| token.span.clone());
+            return Err(ParseError::unexpected_token(input, unexpected));
+        }
+        let list = input[span.end..next_span.start].trim();
+        match *word {
+            RESERVED_WORD_IF | RESERVED_WORD_ELIF => condition = Some(list.to_string()),
+            RESERVED_WORD_THEN => branches.push((condition.take().unwrap_or_default(), list.to_string())),
+            _ => otherwise = Some(list.to_string()),
+        }
+    }
+    Ok(CompoundCommand::If { branches, otherwise })
+}
+
+/// Parse `input`, which starts with `case`, up to its `esac`.
+fn parse_case(input: &str) -> Result<CompoundCommand, ParseError> {
+    let lexed = lex(input);
+    let command_words = lexed.command_word_indices(input);
+    let mut tokens = lexed
+        .tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| token.kind != TokenKind::Comment)
+        .skip(1)
+        .peekable();
+    let unexpected_end = || ParseError::unexpected_end(input.trim_end(), None);
+    let unexpected = |(_, token): (usize, &Token)| ParseError::unexpected_token(input, token.span.clone());
+
+    let subject = match tokens.next() {
+        Some((_, token)) if token.kind == TokenKind::Word => input[token.span.clone()].to_string(),
+        Some(token) => return Err(unexpected(token)),
+        None => return Err(unexpected_end()),
+    };
+    match tokens.next() {
+        Some((index, token))
+            if command_words.contains(&index) && reserved_word(input, token) == Some(RESERVED_WORD_IN) => {}
+        Some(token) => return Err(unexpected(token)),
+        None => return Err(unexpected_end()),
+    }
+
+    let mut arms = Vec::new();
+    loop {
+        let (index, token) = *tokens.peek().ok_or_else(unexpected_end)?;
+        if command_words.contains(&index) && reserved_word(input, token) == Some(RESERVED_WORD_ESAC) {
+            check_nothing_follows(input, &lexed, token.span.end)?;
+            return Ok(CompoundCommand::Case { subject, arms });
+        }
+
+        // The alternatives of the pattern, each a word, separated by `|` and ended by `)`.
+        let mut patterns = Vec::new();
+        let list_start = loop {
+            let (index, token) = tokens.next().ok_or_else(unexpected_end)?;
+            if token.kind != TokenKind::Word {
+                return Err(unexpected((index, token)));
+            }
+            let mut pattern = &input[token.span.clone()];
+            if patterns.is_empty() {
+                pattern = pattern.strip_prefix(CHAR_PAREN_OPEN).unwrap_or(pattern);
+            }
+            let ends = ends_case_pattern(input, token);
+            if ends {
+                pattern = &pattern[..pattern.len() - 1];
+            }
+            if !pattern.is_empty() {
+                patterns.push(pattern.to_string());
+            } else if !ends {
+                // A `(` of its own before the first alternative.
+                continue;
+            }
+            if ends {
+                if patterns.is_empty() {
+                    return Err(unexpected((index, token)));
+                }
+                break token.span.end;
+            }
+            match tokens.next() {
+                Some((_, token)) if input[token.span.clone()] == CHAR_PIPE.to_string() => {}
+                Some(token) => return Err(unexpected(token)),
+                None => return Err(unexpected_end()),
+            }
+        };
+
+        // The list runs up to this `case`'s `;;` or `esac`, skipping those of nested commands.
+        let mut depth = 0_usize;
+        let list_end = loop {
+            let (index, token) = *tokens.peek().ok_or_else(unexpected_end)?;
+            if token.kind == TokenKind::Operator && &input[token.span.clone()] == CASE_BREAK && depth == 0 {
+                tokens.next();
+                break token.span.start;
+            }
+            if command_words.contains(&index) {
+                match reserved_word(input, token) {
+                    Some(RESERVED_WORD_CASE | RESERVED_WORD_IF) => depth += 1,
+                    Some(RESERVED_WORD_ESAC) if depth == 0 => break token.span.start,
+                    Some(RESERVED_WORD_ESAC | RESERVED_WORD_FI) => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+            tokens.next();
+        };
+        arms.push((patterns, input[list_start..list_end].trim().to_string()));
+    }
+}
+
+/// Parse `input`, which starts with `[[`, ending at `start`, up to its `]]`.
+fn parse_conditional(input: &str, start: usize) -> Result<CompoundCommand, ParseError> {
+    let lexed = lex(input);
+    let end = lexed
+        .command_word_indices(input)
+        .into_iter()
+        .map(|index| &lexed.tokens[index])
+        .find(|token| reserved_word(input, token) == Some(RESERVED_WORD_CONDITIONAL_END))
+        .ok_or_else(|| ParseError::unexpected_end(input.trim_end(), None))?;
+    check_nothing_follows(input, &lexed, end.span.end)?;
+    parse_conditional_expression(input, start..end.span.start).map(CompoundCommand::Conditional)
+}
+
+/// Parse `input`, whose `((` is at `start`, up to its `))`.
+fn parse_arithmetic(input: &str, lexed: &Lexed, start: usize) -> Result<CompoundCommand, ParseError> {
+    let end = arithmetic_command_end(input, start).ok_or_else(|| ParseError::unexpected_end(input.trim_end(), None))?;
+    if !input[..end].ends_with("))") {
+        // The first `(` is closed by a single `)`, as in `((a); b)`, a subshell holding one.
+        return Err(ParseError::unexpected_token(input, end - 1..end));
+    }
+    check_nothing_follows(input, lexed, end)?;
+    Ok(CompoundCommand::Arithmetic(input[start + 2..end - 2].to_string()))
+}
+
+/// Fail with the first token after `end`, where a compound command closes, unless it is a comment.
+fn check_nothing_follows(input: &str, lexed: &Lexed, end: usize) -> Result<(), ParseError> {
+    match lexed
+        .tokens
+        .iter()
+        .find(|token| token.span.start >= end && token.kind != TokenKind::Comment)
+    {
+        Some(trailing) => Err(ParseError::unexpected_token(input, trailing.span.clone())),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_commands() {
+        let branch = |condition: &str, list: &str| (condition.to_string(), list.to_string());
+        let cases = [
+            ("if a; then b; fi", vec![branch("a;", "b;")], None),
+            (
+                "if a\nthen b\nelif c; d; then e; f\nelse g; fi # done",
+                vec![branch("a", "b"), branch("c; d;", "e; f")],
+                Some("g;"),
+            ),
+            (
+                "if a; then if b; then c; else d; fi; fi",
+                vec![branch("a;", "if b; then c; else d; fi;")],
+                None,
+            ),
+            (
+                "if echo then fi; then echo elif; fi",
+                vec![branch("echo then fi;", "echo elif;")],
+                None,
+            ),
+        ];
+        for (input, branches, otherwise) in cases {
+            let expected = CompoundCommand::If {
+                branches,
+                otherwise: otherwise.map(ToString::to_string),
+            };
+            assert_eq!(parse_compound_command(input), Some(Ok(expected)), "input: {input:?}");
+        }
+        assert_eq!(parse_compound_command("echo if; fi"), None);
+        assert_eq!(parse_compound_command("'if' a"), None);
+
+        let errors = [
+            ("if a; fi", "`fi` at column 7"),
+            ("if a; then; fi", "`;` at column 11"),
+            ("if a; then b; else c; elif d; then e; fi", "`elif` at column 23"),
+            ("if a; then b; fi c", "`c` at column 18"),
+            ("then b", "`then` at column 1"),
+            ("if a; then b", "end"),
+        ];
+        for (input, expected) in errors {
+            let error = parse_compound_command(input)
+                .and_then(Result::err)
+                .map(|error| error.to_string());
+            let expected = if expected == "end" {
+                "syntax error: unexpected end of input".to_string()
+            } else {
+                format!("syntax error near unexpected token {expected}")
+            };
+            assert_eq!(error, Some(expected), "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn case_commands() {
+        let arm =
+            |patterns: &[&str], list: &str| (patterns.iter().map(ToString::to_string).collect(), list.to_string());
+        let cases = [
+            ("case $x in a) b;; esac", "$x", vec![arm(&["a"], "b")]),
+            (
+                "case \"$f\" in\n  *.c | *.h) cc;;\n  (*.rs) cargo build ;;\n  *) ;;\nesac # done",
+                "\"$f\"",
+                vec![
+                    arm(&["*.c", "*.h"], "cc"),
+                    arm(&["*.rs"], "cargo build"),
+                    arm(&["*"], ""),
+                ],
+            ),
+            (
+                "case a in a) case b in b) c;; esac;; esac",
+                "a",
+                vec![arm(&["a"], "case b in b) c;; esac")],
+            ),
+            (
+                "case a in 'x)') echo esac; if b; then c; fi\nesac",
+                "a",
+                vec![arm(&["'x)'"], "echo esac; if b; then c; fi")],
+            ),
+            ("case a in esac", "a", vec![]),
+        ];
+        for (input, subject, arms) in cases {
+            let expected = CompoundCommand::Case {
+                subject: subject.to_string(),
+                arms,
+            };
+            assert_eq!(parse_compound_command(input), Some(Ok(expected)), "input: {input:?}");
+        }
+        assert_eq!(parse_compound_command("echo case a in"), None);
+
+        let errors = [
+            ("case a b) c;; esac", "`b)` at column 8"),
+            ("case a in b c) d;; esac", "`c)` at column 13"),
+            ("case a in ) d;; esac", "`)` at column 11"),
+            ("case a in b) c;; esac d", "`d` at column 23"),
+            ("esac", "`esac` at column 1"),
+            ("case a in b) c;;", "end"),
+        ];
+        for (input, expected) in errors {
+            let error = parse_compound_command(input)
+                .and_then(Result::err)
+                .map(|error| error.to_string());
+            let expected = if expected == "end" {
+                "syntax error: unexpected end of input".to_string()
+            } else {
+                format!("syntax error near unexpected token {expected}")
+            };
+            assert_eq!(error, Some(expected), "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn conditional_commands() {
+        assert!(matches!(
+            parse_compound_command("if [[ a ]]; then case b in b) [[ c ]];; esac; fi"),
+            Some(Ok(CompoundCommand::If { .. }))
+        ));
+    }
+
+    #[test]
+    fn arithmetic_commands() {
+        assert_eq!(
+            parse_compound_command("((x++)) # count"),
+            Some(Ok(CompoundCommand::Arithmetic("x++".to_string())))
+        );
+        assert_eq!(
+            parse_compound_command("(( x = 1 << 3 ))"),
+            Some(Ok(CompoundCommand::Arithmetic(" x = 1 << 3 ".to_string())))
+        );
+        assert_eq!(
+            parse_compound_command("(( a > (b) ))"),
+            Some(Ok(CompoundCommand::Arithmetic(" a > (b) ".to_string())))
+        );
+        let error = parse_compound_command("((a); b)")
+            .and_then(Result::err)
+            .map(|error| error.to_string());
+        assert_eq!(
+            error,
+            Some("syntax error near unexpected token `)` at column 8".to_string())
+        );
+    }
+}
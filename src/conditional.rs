@@ -0,0 +1,641 @@
+use crate::locale::collate;
+use crate::parser::expand_operand;
+use crate::parser::Expander;
+use crate::parser::ParseError;
+use crate::parser::CHAR_BACKSLASH;
+use crate::parser::CHAR_BRACE_CLOSE;
+use crate::parser::CHAR_BRACE_OPEN;
+use crate::parser::CHAR_DOLLAR_SIGN;
+use crate::parser::CHAR_DOUBLE_QUOTE;
+use crate::parser::CHAR_PAREN_CLOSE;
+use crate::parser::CHAR_PAREN_OPEN;
+use crate::parser::CHAR_SINGLE_QUOTE;
+use crate::pattern::pattern_matches;
+use crate::pattern::push_literal;
+use std::cmp::Ordering;
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+
+const CONDITIONAL_AND: &str = "&&";
+const CONDITIONAL_NOT: &str = "!";
+const CONDITIONAL_OR: &str = "||";
+const CONDITIONAL_REGEX_MATCH: &str = "=~";
+const TEST_AND: &str = "-a";
+const TEST_GROUP_CLOSE: &str = ")";
+const TEST_GROUP_OPEN: &str = "(";
+const TEST_OR: &str = "-o";
+/// The operators that end a word of a conditional expression, besides whitespace.
+const CONDITIONAL_OPERATORS: &[&str] = &[CONDITIONAL_AND, CONDITIONAL_OR, "(", ")", "<", ">"];
+
+/// A test of one word, written before it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryTest {
+    /// `-e`: a file exists.
+    Exists,
+    /// `-f`: a regular file exists.
+    RegularFile,
+    /// `-d`: a directory exists.
+    Directory,
+    /// `-s`: a file exists and is not empty.
+    NonEmptyFile,
+    /// `-L` or `-h`: a symbolic link exists.
+    SymbolicLink,
+    /// `-r`, `-w`, `-x`: a file exists and the shell may read, write, or execute it, as `access`
+    /// checks with this mode.
+    Access(libc::c_int),
+    /// `-n`: the string is not empty.
+    NonEmpty,
+    /// `-z`: the string is empty.
+    Empty,
+}
+
+impl UnaryTest {
+    fn from_operator(operator: &str) -> Option<Self> {
+        match operator {
+            "-e" => Some(Self::Exists),
+            "-f" => Some(Self::RegularFile),
+            "-d" => Some(Self::Directory),
+            "-s" => Some(Self::NonEmptyFile),
+            "-L" | "-h" => Some(Self::SymbolicLink),
+            "-r" => Some(Self::Access(libc::R_OK)),
+            "-w" => Some(Self::Access(libc::W_OK)),
+            "-x" => Some(Self::Access(libc::X_OK)),
+            "-n" => Some(Self::NonEmpty),
+            "-z" => Some(Self::Empty),
+            _ => None,
+        }
+    }
+
+    /// Whether `word` passes the test; file tests other than `-L` follow symbolic links.
+    fn test(self, word: &str) -> bool {
+        match self {
+            Self::Exists => fs::metadata(word).is_ok(),
+            Self::RegularFile => fs::metadata(word).is_ok_and(|metadata| metadata.is_file()),
+            Self::Directory => fs::metadata(word).is_ok_and(|metadata| metadata.is_dir()),
+            Self::NonEmptyFile => fs::metadata(word).is_ok_and(|metadata| metadata.len() > 0),
+            Self::SymbolicLink => fs::symlink_metadata(word).is_ok_and(|metadata| metadata.is_symlink()),
+            Self::Access(mode) => CString::new(word).is_ok_and(|path| {
+                // SAFETY: `access` only reads the path, a valid C string.
+                unsafe { libc::access(path.as_ptr(), mode) == 0 }
+            }),
+            Self::NonEmpty => !word.is_empty(),
+            Self::Empty => word.is_empty(),
+        }
+    }
+}
+
+/// A comparison of two words, written between them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryTest {
+    /// `==` or `=`: the left word matches the pattern on the right.
+    Matches,
+    /// `!=`: the left word does not match the pattern on the right.
+    DoesNotMatch,
+    /// `=~`: the left word matches the extended regular expression on the right.
+    RegexMatches,
+    /// `<`: the left word sorts first, in the collation locale.
+    SortsBefore,
+    /// `>`: the left word sorts last, in the collation locale.
+    SortsAfter,
+    /// `-eq`, `-ne`, `-lt`, `-le`, `-gt`, `-ge`: how the words compare as integers, and whether
+    /// the test holds when they compare that way or when they do not.
+    Integer(Ordering, bool),
+}
+
+impl BinaryTest {
+    fn from_operator(operator: &str) -> Option<Self> {
+        match operator {
+            "==" | "=" => Some(Self::Matches),
+            "!=" => Some(Self::DoesNotMatch),
+            CONDITIONAL_REGEX_MATCH => Some(Self::RegexMatches),
+            "<" => Some(Self::SortsBefore),
+            ">" => Some(Self::SortsAfter),
+            "-eq" => Some(Self::Integer(Ordering::Equal, true)),
+            "-ne" => Some(Self::Integer(Ordering::Equal, false)),
+            "-lt" => Some(Self::Integer(Ordering::Less, true)),
+            "-le" => Some(Self::Integer(Ordering::Greater, false)),
+            "-gt" => Some(Self::Integer(Ordering::Greater, true)),
+            "-ge" => Some(Self::Integer(Ordering::Less, false)),
+            _ => None,
+        }
+    }
+}
+
+/// The expression of a `[[ ... ]]` command, with its words as written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConditionalExpression {
+    /// A word alone: true when it is not empty.
+    Word(String),
+    Unary(UnaryTest, String),
+    Binary(String, BinaryTest, String),
+    /// `! expression`.
+    Not(Box<ConditionalExpression>),
+    /// `expression && expression`.
+    And(Box<ConditionalExpression>, Box<ConditionalExpression>),
+    /// `expression || expression`.
+    Or(Box<ConditionalExpression>, Box<ConditionalExpression>),
+}
+
+/// A conditional expression that could not be evaluated, such as `-gt` with a word that is not an
+/// integer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConditionalError {
+    pub word: String,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ConditionalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.word.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.word, self.message)
+        }
+    }
+}
+
+impl ConditionalExpression {
+    /// Whether the expression holds, its words expanded by `expander` as they are reached: `&&`
+    /// and `||` only expand their right side when it decides the result. A word has its parameters
+    /// and command substitutions expanded and its quotes removed, but is not split into fields or
+    /// globbed; the right side of `==` and `!=` is a pattern, where quoted characters match only
+    /// themselves.
+    pub fn evaluate(&self, expander: &mut dyn Expander) -> Result<bool, ConditionalError> {
+        self.evaluate_with(&mut |word, as_pattern| expand_operand(word, as_pattern, expander))
+    }
+
+    /// Whether the expression holds with its words taken as they are, as `test` takes arguments the
+    /// shell has already expanded: `=` and `!=` compare strings instead of matching patterns.
+    pub fn evaluate_literal(&self) -> Result<bool, ConditionalError> {
+        self.evaluate_with(&mut |word, as_pattern| {
+            if !as_pattern {
+                return word.to_string();
+            }
+            let mut pattern = String::with_capacity(word.len());
+            for c in word.chars() {
+                push_literal(&mut pattern, c);
+            }
+            pattern
+        })
+    }
+
+    /// Whether the expression holds, with `expand` turning each word into its text, or into a
+    /// pattern when its second argument is `true`.
+    fn evaluate_with(&self, expand: &mut dyn FnMut(&str, bool) -> String) -> Result<bool, ConditionalError> {
+        match self {
+            Self::Word(word) => Ok(!expand(word, false).is_empty()),
+            Self::Unary(test, word) => Ok(test.test(&expand(word, false))),
+            Self::Binary(left, test, right) => {
+                let left = expand(left, false);
+                let as_pattern = matches!(test, BinaryTest::Matches | BinaryTest::DoesNotMatch);
+                let right = expand(right, as_pattern);
+                match test {
+                    BinaryTest::Matches => Ok(pattern_matches(&right, &left)),
+                    BinaryTest::DoesNotMatch => Ok(!pattern_matches(&right, &left)),
+                    BinaryTest::RegexMatches => regex_matches(&right, &left),
+                    BinaryTest::SortsBefore => Ok(collate(&left, &right) == Ordering::Less),
+                    BinaryTest::SortsAfter => Ok(collate(&left, &right) == Ordering::Greater),
+                    BinaryTest::Integer(ordering, holds) => {
+                        Ok((parse_integer(&left)?.cmp(&parse_integer(&right)?) == *ordering) == *holds)
+                    }
+                }
+            }
+            Self::Not(expression) => Ok(!expression.evaluate_with(expand)?),
+            Self::And(left, right) => Ok(left.evaluate_with(expand)? && right.evaluate_with(expand)?),
+            Self::Or(left, right) => Ok(left.evaluate_with(expand)? || right.evaluate_with(expand)?),
+        }
+    }
+}
+
+fn parse_integer(word: &str) -> Result<i64, ConditionalError> {
+    word.trim().parse().map_err(|_| ConditionalError {
+        word: word.to_string(),
+        message: "integer expression expected",
+    })
+}
+
+/// Whether `text` matches the POSIX extended regular expression `regex` anywhere.
+fn regex_matches(regex: &str, text: &str) -> Result<bool, ConditionalError> {
+    let invalid = || ConditionalError {
+        word: regex.to_string(),
+        message: "invalid regular expression",
+    };
+    let (Ok(c_regex), Ok(c_text)) = (CString::new(regex), CString::new(text)) else {
+        return Err(invalid());
+    };
+    let mut compiled: libc::regex_t = unsafe { std::mem::zeroed() };
+    // SAFETY: `compiled` is only matched against and freed once `regcomp` has filled it in.
+    unsafe {
+        if libc::regcomp(&mut compiled, c_regex.as_ptr(), libc::REG_EXTENDED | libc::REG_NOSUB) != 0 {
+            return Err(invalid());
+        }
+        let matched = libc::regexec(&compiled, c_text.as_ptr(), 0, std::ptr::null_mut(), 0) == 0;
+        libc::regfree(&mut compiled);
+        Ok(matched)
+    }
+}
+
+/// A word or operator of a conditional expression, with its span in the command line.
+struct ConditionalToken<'a> {
+    text: &'a str,
+    span: Range<usize>,
+    /// An operator (`&&`, `||`, `(`, `)`, `<`, `>`) rather than a word.
+    operator: bool,
+}
+
+/// Parse the expression of a `[[ ... ]]` command, `input[span]`, where `span` runs from after the
+/// `[[` to the start of the `]]`. Errors point at the unexpected token of `input`, which is the
+/// `]]` when the expression ends too early.
+pub fn parse_conditional_expression(input: &str, span: Range<usize>) -> Result<ConditionalExpression, ParseError> {
+    let tokens = tokenize(input, span.clone());
+    let mut parser = ConditionalParser {
+        input,
+        tokens: &tokens,
+        position: 0,
+        end: span.end..span.end + 2,
+    };
+    let expression = parser.parse_or()?;
+    match tokens.get(parser.position) {
+        Some(token) => Err(ParseError::unexpected_token(input, token.span.clone())),
+        None => Ok(expression),
+    }
+}
+
+/// Split `input[span]` into words and operators. Words keep their quotes, so they are expanded as
+/// written; `$( ... )` and `${ ... }` stay within their word. The word after `=~` is a regular
+/// expression, which runs to a blank outside quotes and parentheses, so `(a|b)+` is one word.
+fn tokenize(input: &str, span: Range<usize>) -> Vec<ConditionalToken<'_>> {
+    let mut tokens: Vec<ConditionalToken<'_>> = Vec::new();
+    let mut index = span.start;
+    while index < span.end {
+        let rest = &input[index..span.end];
+        let Some(c) = rest.chars().next() else {
+            break;
+        };
+        if c.is_whitespace() {
+            index += c.len_utf8();
+            continue;
+        }
+        let regex = tokens
+            .last()
+            .is_some_and(|token| !token.operator && token.text == CONDITIONAL_REGEX_MATCH);
+        if !regex {
+            if let Some(operator) = CONDITIONAL_OPERATORS
+                .iter()
+                .find(|operator| rest.starts_with(**operator))
+            {
+                tokens.push(ConditionalToken {
+                    text: &input[index..index + operator.len()],
+                    span: index..index + operator.len(),
+                    operator: true,
+                });
+                index += operator.len();
+                continue;
+            }
+        }
+        let end = index + word_length(rest, regex);
+        tokens.push(ConditionalToken {
+            text: &input[index..end],
+            span: index..end,
+            operator: false,
+        });
+        index = end;
+    }
+    tokens
+}
+
+/// The length of the word `text` starts with: up to a blank or an operator outside quotes, escapes,
+/// and `$( ... )` or `${ ... }`, or, for a regular expression, up to a blank outside quotes,
+/// escapes, and parentheses.
+fn word_length(text: &str, regex: bool) -> usize {
+    let mut quote = None;
+    let mut escaped = false;
+    // Open parentheses, and braces of `${`, the word is inside of.
+    let mut depth = 0_usize;
+    let mut previous = None;
+    for (index, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if quote == Some(c) {
+            quote = None;
+        } else if c == CHAR_BACKSLASH && quote != Some(CHAR_SINGLE_QUOTE) {
+            escaped = true;
+        } else if quote.is_some() {
+            // Quoted text never ends the word.
+        } else if c == CHAR_SINGLE_QUOTE || c == CHAR_DOUBLE_QUOTE {
+            quote = Some(c);
+        } else if (c == CHAR_PAREN_OPEN && (regex || previous == Some(CHAR_DOLLAR_SIGN)))
+            || (c == CHAR_BRACE_OPEN && previous == Some(CHAR_DOLLAR_SIGN))
+        {
+            depth += 1;
+        } else if depth > 0 && (c == CHAR_PAREN_CLOSE || c == CHAR_BRACE_CLOSE) {
+            depth -= 1;
+        } else if depth == 0
+            && (c.is_whitespace()
+                || (!regex
+                    && CONDITIONAL_OPERATORS
+                        .iter()
+                        .any(|operator| text[index..].starts_with(operator))))
+        {
+            return index;
+        }
+        previous = Some(c);
+    }
+    text.len()
+}
+
+/// A recursive descent parser over the tokens of a conditional expression: `||` binds loosest,
+/// then `&&`, then `!`.
+struct ConditionalParser<'a> {
+    input: &'a str,
+    tokens: &'a [ConditionalToken<'a>],
+    position: usize,
+    /// The span of the closing `]]`, unexpected when the expression ends too early.
+    end: Range<usize>,
+}
+
+impl ConditionalParser<'_> {
+    fn peek(&self) -> Option<&ConditionalToken<'_>> {
+        self.tokens.get(self.position)
+    }
+
+    /// Whether the next token is the operator `operator`, consuming it when it is.
+    fn take_operator(&mut self, operator: &str) -> bool {
+        let matches = self
+            .peek()
+            .is_some_and(|token| token.operator && token.text == operator);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    /// The next token as a word, or an error pointing at it.
+    fn take_word(&mut self) -> Result<String, ParseError> {
+        match self.tokens.get(self.position) {
+            Some(token) if !token.operator => {
+                self.position += 1;
+                Ok(token.text.to_string())
+            }
+            token => Err(self.unexpected(token.map(|token| token.span.clone()))),
+        }
+    }
+
+    fn unexpected(&self, span: Option<Range<usize>>) -> ParseError {
+        ParseError::unexpected_token(self.input, span.unwrap_or_else(|| self.end.clone()))
+    }
+
+    fn parse_or(&mut self) -> Result<ConditionalExpression, ParseError> {
+        let mut expression = self.parse_and()?;
+        while self.take_operator(CONDITIONAL_OR) {
+            expression = ConditionalExpression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<ConditionalExpression, ParseError> {
+        let mut expression = self.parse_not()?;
+        while self.take_operator(CONDITIONAL_AND) {
+            expression = ConditionalExpression::And(Box::new(expression), Box::new(self.parse_not()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_not(&mut self) -> Result<ConditionalExpression, ParseError> {
+        if self
+            .peek()
+            .is_some_and(|token| !token.operator && token.text == CONDITIONAL_NOT)
+        {
+            self.position += 1;
+            return Ok(ConditionalExpression::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    /// A test, a word alone, or a parenthesized expression.
+    fn parse_primary(&mut self) -> Result<ConditionalExpression, ParseError> {
+        if self.take_operator("(") {
+            let expression = self.parse_or()?;
+            if !self.take_operator(")") {
+                return Err(self.unexpected(self.peek().map(|token| token.span.clone())));
+            }
+            return Ok(expression);
+        }
+        let word = self.take_word()?;
+        if let Some(test) = UnaryTest::from_operator(&word) {
+            return Ok(ConditionalExpression::Unary(test, self.take_word()?));
+        }
+        match self.peek().and_then(|token| BinaryTest::from_operator(token.text)) {
+            Some(test) => {
+                self.position += 1;
+                Ok(ConditionalExpression::Binary(word, test, self.take_word()?))
+            }
+            None => Ok(ConditionalExpression::Word(word)),
+        }
+    }
+}
+
+/// Parse the arguments of `test`, or of `[` without its `]`, which the shell has already expanded.
+///
+/// The tests are those of `[[`, except `=~`, and they combine with `!`, `-a`, and `-o`, which bind
+/// in that order, and with `(` and `)` as separate arguments. A binary operator is a comparison
+/// whenever two words surround it, so `[ ! = x ]` compares `!` with `x`, as POSIX has it for three
+/// arguments. No arguments is a test that fails.
+pub fn parse_test_arguments(arguments: &[String]) -> Result<ConditionalExpression, ConditionalError> {
+    if arguments.is_empty() {
+        return Ok(ConditionalExpression::Word(String::new()));
+    }
+    let mut parser = TestParser { arguments, position: 0 };
+    let expression = parser.parse_or()?;
+    match arguments.get(parser.position) {
+        None => Ok(expression),
+        Some(_) if parser.position == 1 && arguments.len() == 2 => {
+            Err(test_error(&arguments[0], "unary operator expected"))
+        }
+        Some(word) if parser.position == 1 => Err(test_error(word, "binary operator expected")),
+        Some(_) => Err(test_error("", "too many arguments")),
+    }
+}
+
+fn test_error(word: &str, message: &'static str) -> ConditionalError {
+    ConditionalError {
+        word: word.to_string(),
+        message,
+    }
+}
+
+/// A recursive descent parser over the arguments of `test`: `-o` binds loosest, then `-a`, then `!`.
+struct TestParser<'a> {
+    arguments: &'a [String],
+    position: usize,
+}
+
+impl TestParser<'_> {
+    fn peek(&self, offset: usize) -> Option<&str> {
+        self.arguments.get(self.position + offset).map(String::as_str)
+    }
+
+    /// Whether the next argument is `word`, consuming it when it is.
+    fn take(&mut self, word: &str) -> bool {
+        let matches = self.peek(0) == Some(word);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    /// The comparison of the next three arguments, when the middle one is a binary operator.
+    fn binary_test(&self) -> Option<BinaryTest> {
+        self.peek(2)?;
+        BinaryTest::from_operator(self.peek(1)?).filter(|test| *test != BinaryTest::RegexMatches)
+    }
+
+    fn parse_or(&mut self) -> Result<ConditionalExpression, ConditionalError> {
+        let mut expression = self.parse_and()?;
+        while self.take(TEST_OR) {
+            expression = ConditionalExpression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<ConditionalExpression, ConditionalError> {
+        let mut expression = self.parse_not()?;
+        while self.take(TEST_AND) {
+            expression = ConditionalExpression::And(Box::new(expression), Box::new(self.parse_not()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_not(&mut self) -> Result<ConditionalExpression, ConditionalError> {
+        if self.peek(0) == Some(CONDITIONAL_NOT) && self.peek(1).is_some() && self.binary_test().is_none() {
+            self.position += 1;
+            return Ok(ConditionalExpression::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    /// A comparison, a test of one word, a word alone, or a parenthesized expression.
+    fn parse_primary(&mut self) -> Result<ConditionalExpression, ConditionalError> {
+        if let Some(test) = self.binary_test() {
+            let left = self.arguments[self.position].clone();
+            let right = self.arguments[self.position + 2].clone();
+            self.position += 3;
+            return Ok(ConditionalExpression::Binary(left, test, right));
+        }
+        let Some(word) = self.peek(0).map(ToString::to_string) else {
+            return Err(test_error(
+                self.arguments.last().map_or("", String::as_str),
+                "argument expected",
+            ));
+        };
+        self.position += 1;
+        if word == TEST_GROUP_OPEN && self.peek(0).is_some() {
+            let expression = self.parse_or()?;
+            if !self.take(TEST_GROUP_CLOSE) {
+                return Err(test_error("", "`)' expected"));
+            }
+            return Ok(expression);
+        }
+        if let Some(test) = UnaryTest::from_operator(&word).filter(|_| self.peek(0).is_some()) {
+            let operand = self.arguments[self.position].clone();
+            self.position += 1;
+            return Ok(ConditionalExpression::Unary(test, operand));
+        }
+        Ok(ConditionalExpression::Word(word))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compound::parse_compound_command;
+    use crate::compound::CompoundCommand;
+    use crate::parser::tests::TestExpander;
+
+    #[test]
+    fn conditional_expressions() {
+        let cases = [
+            ("[[ $1 == \"a b\" ]]", true),
+            ("[[ $1 == a* ]]", true),
+            ("[[ $1 == \"a*\" ]]", false),
+            ("[[ $1 != a ]]", true),
+            ("[[ -n $_empty || -z $_empty ]]", true),
+            ("[[ ! -n $1 ]]", false),
+            ("[[ -d / && ( -f / || $USER == me ) ]]", true),
+            ("[[ $USER =~ ^(m|y)e+$ ]]", true),
+            ("[[ $USER =~ ^e ]]", false),
+            ("[[ 10 -gt 9 && 10 < 9 && $? -le 42 ]]", true),
+            ("[[ $(a b) == '<a_b>' ]]", true),
+            ("[[ $_empty ]] # no", false),
+        ];
+        for (input, expected) in cases {
+            let Some(Ok(CompoundCommand::Conditional(expression))) = parse_compound_command(input) else {
+                panic!("input: {input:?}");
+            };
+            assert_eq!(
+                expression.evaluate(&mut TestExpander::default()),
+                Ok(expected),
+                "input: {input:?}"
+            );
+        }
+
+        let errors = [
+            ("[[ ]]", "`]]` at column 4"),
+            ("[[ a == ]]", "`]]` at column 9"),
+            ("[[ ( a ]]", "`]]` at column 8"),
+            ("[[ a b ]]", "`b` at column 6"),
+            ("[[ a ]] b", "`b` at column 9"),
+        ];
+        for (input, expected) in errors {
+            let error = parse_compound_command(input)
+                .and_then(Result::err)
+                .map(|error| error.to_string());
+            assert_eq!(
+                error,
+                Some(format!("syntax error near unexpected token {expected}")),
+                "input: {input:?}"
+            );
+        }
+        let Some(Ok(CompoundCommand::Conditional(expression))) = parse_compound_command("[[ a -eq 1 ]]") else {
+            panic!("not a conditional");
+        };
+        let error = expression
+            .evaluate(&mut TestExpander::default())
+            .map_err(|error| error.to_string());
+        assert_eq!(error, Err("a: integer expression expected".to_string()));
+    }
+
+    #[test]
+    fn test_arguments() {
+        let cases: &[(&[&str], Result<bool, &str>)] = &[
+            (&[], Ok(false)),
+            (&["-n"], Ok(true)),
+            (&["!", ""], Ok(true)),
+            (&["-z", ""], Ok(true)),
+            (&["-d", "/", "-a", "-f", "/"], Ok(false)),
+            (&["a", "=", "a", "-o", "1", "-eq", "2"], Ok(true)),
+            (&["a", "=", "*"], Ok(false)),
+            (&["a*", "==", "a*"], Ok(true)),
+            (&["!", "=", "x"], Ok(false)),
+            (&["(", "1", "-lt", "2", ")", "-a", "!", "b", "<", "a"], Ok(true)),
+            (&["10", "-ge", "9"], Ok(true)),
+            (&["a", "=~", "a"], Err("=~: binary operator expected")),
+            (&["a", "b"], Err("a: unary operator expected")),
+            (&["a", "-eq", "1"], Err("a: integer expression expected")),
+            (&["(", "a", "-o"], Err("-o: argument expected")),
+            (&["a", "-a", "b", "c"], Err("too many arguments")),
+        ];
+        for (arguments, expected) in cases {
+            let arguments: Vec<String> = arguments.iter().map(ToString::to_string).collect();
+            let result = parse_test_arguments(&arguments).and_then(|expression| expression.evaluate_literal());
+            let result = result.map_err(|error| error.to_string());
+            assert_eq!(
+                result,
+                expected.map_err(ToString::to_string),
+                "arguments: {arguments:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use crate::error::report_error;
+use crate::home::home_directory;
+#[cfg(feature = "readline")]
+use crate::keybindings::KeyBinding;
+use crate::notify::NotifyStyle;
+use crate::notify::NotifyWhen;
+use crate::parser::ENVIRONMENT_VARIABLE_CONFIG;
+use crate::xdg::shell_file;
+use crate::xdg::BaseDirectory;
+#[cfg(feature = "readline")]
+use rustyline::CompletionType;
+use std::env::var;
+use std::path::PathBuf;
+
+pub const CONFIG_FILE_NAME: &str = "config";
+/// Where the config file lived before it moved to `$XDG_CONFIG_HOME/ccsh`.
+pub const LEGACY_CONFIG_FILE_NAME: &str = ".ccshrc";
+pub const HISTORY_FILE_NAME: &str = "history";
+const CONFIG_COMMENT: char = '#';
+const CONFIG_SOURCE: &str = "config";
+const CONFIG_SEPARATOR: char = '=';
+
+/// Settings read from the shell config file at startup.
+///
+/// The file holds one `key = value` per line; blank lines and `#` comments are ignored.
+#[derive(Clone, Debug)]
+pub struct ShellConfig {
+    /// Expand the `\g` prompt escape to the current git branch and dirty marker.
+    pub prompt_git: bool,
+    /// Upper bound on the `git` call used to detect uncommitted changes.
+    pub prompt_git_timeout_ms: u64,
+    /// How ambiguous completions are presented: a listing (`list`) or in-place cycling (`circular`).
+    #[cfg(feature = "readline")]
+    pub completion_type: CompletionType,
+    /// Most matches per row when listing filename completions; 0 puts them all on one row.
+    pub completion_max_columns: usize,
+    /// Ring the bell on ambiguous or failed completion.
+    pub completion_bell: bool,
+    /// Extra key bindings, one per `bind = <key> <action> [text]` line.
+    #[cfg(feature = "readline")]
+    pub key_bindings: Vec<KeyBinding>,
+    /// Replaces `$XDG_STATE_HOME/ccsh` for history, visited directories, and sessions.
+    pub state_directory: Option<PathBuf>,
+    /// Replaces `$XDG_CACHE_HOME/ccsh` for the command cache.
+    pub cache_directory: Option<PathBuf>,
+    /// History file used when `$HISTFILE` is unset, instead of `history` in the state directory.
+    pub history_file: Option<PathBuf>,
+    /// Announce foreground commands that run at least this long; 0 turns notifications off.
+    pub notify_after_ms: u64,
+    pub notify_style: NotifyStyle,
+    pub notify_when: NotifyWhen,
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        Self {
+            prompt_git: true,
+            prompt_git_timeout_ms: 200,
+            #[cfg(feature = "readline")]
+            completion_type: CompletionType::List,
+            completion_max_columns: 0,
+            completion_bell: true,
+            #[cfg(feature = "readline")]
+            key_bindings: Vec::new(),
+            state_directory: None,
+            cache_directory: None,
+            history_file: None,
+            notify_after_ms: 0,
+            notify_style: NotifyStyle::Bell,
+            notify_when: NotifyWhen::Unfocused,
+        }
+    }
+}
+
+impl ShellConfig {
+    /// `$CCSH_CONFIG` if set, otherwise `$XDG_CONFIG_HOME/ccsh/config`, moving `~/.ccshrc` there
+    /// if only that exists.
+    #[must_use]
+    pub fn path() -> Option<PathBuf> {
+        if let Ok(path) = var(ENVIRONMENT_VARIABLE_CONFIG) {
+            return Some(PathBuf::from(path));
+        }
+        let directory = BaseDirectory::Config.shell_directory()?;
+        Some(shell_file(&directory, CONFIG_FILE_NAME, Some(LEGACY_CONFIG_FILE_NAME)))
+    }
+
+    /// The `state_dir` setting, otherwise `$XDG_STATE_HOME/ccsh`.
+    #[must_use]
+    pub fn state_directory(&self) -> Option<PathBuf> {
+        self.state_directory
+            .clone()
+            .or_else(|| BaseDirectory::State.shell_directory())
+    }
+
+    /// The `cache_dir` setting, otherwise `$XDG_CACHE_HOME/ccsh`.
+    #[must_use]
+    pub fn cache_directory(&self) -> Option<PathBuf> {
+        self.cache_directory
+            .clone()
+            .or_else(|| BaseDirectory::Cache.shell_directory())
+    }
+
+    /// The `history_file` setting, otherwise `history` in the state directory.
+    #[must_use]
+    pub fn history_path(&self) -> Option<PathBuf> {
+        self.history_file
+            .clone()
+            .or_else(|| Some(self.state_directory()?.join(HISTORY_FILE_NAME)))
+    }
+
+    /// Load the config file, falling back to defaults when it is missing.
+    ///
+    /// Invalid lines are reported on stderr and skipped.
+    #[must_use]
+    pub fn load() -> Self {
+        let mut config = Self::default();
+        let Some(path) = Self::path() else {
+            return config;
+        };
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return config;
+        };
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(CONFIG_COMMENT) {
+                continue;
+            }
+            let result = match line.split_once(CONFIG_SEPARATOR) {
+                Some((key, value)) => config.set(key.trim(), unquote(value.trim())),
+                None => Err(format!("expected `key = value`, found `{line}`")),
+            };
+            if let Err(message) = result {
+                report_error(
+                    &mut std::io::stderr(),
+                    CONFIG_SOURCE,
+                    format_args!("{}:{}: {message}", path.display(), number + 1),
+                );
+            }
+        }
+
+        config
+    }
+
+    /// Apply a single setting.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "prompt_git" => self.prompt_git = parse_bool(key, value)?,
+            "prompt_git_timeout_ms" => {
+                self.prompt_git_timeout_ms = value
+                    .parse()
+                    .map_err(|_| format!("{key}: expected a number of milliseconds, found `{value}`"))?;
+            }
+            #[cfg(feature = "readline")]
+            "completion_type" => {
+                self.completion_type = match value {
+                    "list" => CompletionType::List,
+                    "circular" => CompletionType::Circular,
+                    _ => return Err(format!("{key}: expected list or circular, found `{value}`")),
+                };
+            }
+            "completion_max_columns" => {
+                self.completion_max_columns = value
+                    .parse()
+                    .map_err(|_| format!("{key}: expected a number of columns, found `{value}`"))?;
+            }
+            "completion_bell" => self.completion_bell = parse_bool(key, value)?,
+            "state_dir" => self.state_directory = Some(parse_path(key, value)?),
+            "cache_dir" => self.cache_directory = Some(parse_path(key, value)?),
+            "history_file" => self.history_file = Some(parse_path(key, value)?),
+            "notify_after_ms" => {
+                self.notify_after_ms = value
+                    .parse()
+                    .map_err(|_| format!("{key}: expected a number of milliseconds, found `{value}`"))?;
+            }
+            "notify_style" => {
+                self.notify_style = NotifyStyle::from_name(value)
+                    .ok_or_else(|| format!("{key}: expected bell or osc9, found `{value}`"))?;
+            }
+            "notify_when" => {
+                self.notify_when = NotifyWhen::from_name(value)
+                    .ok_or_else(|| format!("{key}: expected unfocused or always, found `{value}`"))?;
+            }
+            #[cfg(feature = "readline")]
+            "bind" => self.key_bindings.push(KeyBinding::parse(value)?),
+            // Without a line editor these are accepted and ignored, so one file serves both builds.
+            #[cfg(not(feature = "readline"))]
+            "completion_type" | "bind" => {}
+            _ => return Err(format!("unknown setting `{key}`")),
+        }
+        Ok(())
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "on" | "yes" | "1" => Ok(true),
+        "false" | "off" | "no" | "0" => Ok(false),
+        _ => Err(format!("{key}: expected true or false, found `{value}`")),
+    }
+}
+
+/// An absolute path, or one starting with `~/` for the home directory.
+fn parse_path(key: &str, value: &str) -> Result<PathBuf, String> {
+    let path = match value.strip_prefix("~/") {
+        Some(rest) => home_directory()
+            .ok_or_else(|| format!("{key}: cannot expand `~`, HOME not set"))?
+            .join(rest),
+        None => PathBuf::from(value),
+    };
+    if path.is_absolute() {
+        Ok(path)
+    } else {
+        Err(format!("{key}: expected an absolute path, found `{value}`"))
+    }
+}
+
+/// Strip one pair of matching surrounding quotes.
+pub fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|rest| rest.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::ShellOptions;
+
+    #[test]
+    fn settings() {
+        let mut config = ShellConfig::default();
+        let settings = [
+            ("prompt_git", "off"),
+            ("prompt_git_timeout_ms", "50"),
+            ("completion_type", "circular"),
+            ("completion_max_columns", "4"),
+            ("completion_bell", "no"),
+            ("state_dir", "/var/state"),
+            ("cache_dir", "/var/cache"),
+            ("history_file", "/var/history"),
+            ("notify_after_ms", "10000"),
+            ("notify_style", "osc9"),
+            ("notify_when", "always"),
+            ("bind", "C-g run git status"),
+        ];
+        for (key, value) in settings {
+            assert_eq!(config.set(key, value), Ok(()), "{key} = {value}");
+        }
+        assert!(!config.prompt_git);
+        assert_eq!(config.prompt_git_timeout_ms, 50);
+        assert_eq!(config.completion_max_columns, 4);
+        assert!(!config.completion_bell);
+        assert_eq!(config.state_directory(), Some(PathBuf::from("/var/state")));
+        assert_eq!(config.cache_directory(), Some(PathBuf::from("/var/cache")));
+        assert_eq!(config.history_path(), Some(PathBuf::from("/var/history")));
+        assert_eq!(config.notify_after_ms, 10000);
+        assert_eq!(config.notify_style, NotifyStyle::Osc9);
+        assert_eq!(config.notify_when, NotifyWhen::Always);
+        #[cfg(feature = "readline")]
+        {
+            assert_eq!(config.completion_type, CompletionType::Circular);
+            assert_eq!(config.key_bindings.len(), 1);
+        }
+
+        // The completion settings are where the matching `shopt` options start.
+        let options = ShellOptions::from(&config);
+        assert_eq!(options.menu_complete, cfg!(feature = "readline"));
+        assert!(!options.completion_bell);
+        assert_eq!(options.completion_max_columns, 4);
+
+        config.history_file = None;
+        assert_eq!(config.history_path(), Some(PathBuf::from("/var/state/history")));
+    }
+
+    #[test]
+    fn booleans() {
+        let mut config = ShellConfig::default();
+        for (value, expected) in [
+            ("true", true),
+            ("on", true),
+            ("yes", true),
+            ("1", true),
+            ("false", false),
+            ("off", false),
+            ("no", false),
+            ("0", false),
+        ] {
+            assert_eq!(config.set("completion_bell", value), Ok(()));
+            assert_eq!(config.completion_bell, expected, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let mut errors = vec![
+            ("colour", "on", "unknown setting `colour`"),
+            (
+                "prompt_git",
+                "maybe",
+                "prompt_git: expected true or false, found `maybe`",
+            ),
+            ("prompt_git", "True", "prompt_git: expected true or false, found `True`"),
+            (
+                "prompt_git_timeout_ms",
+                "-1",
+                "prompt_git_timeout_ms: expected a number of milliseconds, found `-1`",
+            ),
+            (
+                "completion_max_columns",
+                "wide",
+                "completion_max_columns: expected a number of columns, found `wide`",
+            ),
+            (
+                "state_dir",
+                "relative/dir",
+                "state_dir: expected an absolute path, found `relative/dir`",
+            ),
+            ("history_file", "", "history_file: expected an absolute path, found ``"),
+            (
+                "notify_style",
+                "flash",
+                "notify_style: expected bell or osc9, found `flash`",
+            ),
+            (
+                "notify_when",
+                "never",
+                "notify_when: expected unfocused or always, found `never`",
+            ),
+        ];
+        if cfg!(feature = "readline") {
+            errors.extend([
+                (
+                    "completion_type",
+                    "menu",
+                    "completion_type: expected list or circular, found `menu`",
+                ),
+                ("bind", "C-g", "bind: missing action for `C-g`"),
+            ]);
+        }
+        let mut config = ShellConfig::default();
+        for (key, value, expected) in errors {
+            assert_eq!(config.set(key, value), Err(expected.to_string()), "{key} = {value}");
+        }
+        assert!(config.prompt_git);
+        assert_eq!(config.state_directory, None);
+    }
+
+    #[test]
+    fn unquoting() {
+        let cases = [
+            ("\"a b\"", "a b"),
+            ("'a b'", "a b"),
+            ("\"\"", ""),
+            ("\"a'", "\"a'"),
+            ("\"", "\""),
+            ("plain", "plain"),
+            ("\"'a'\"", "'a'"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(unquote(value), expected, "{value:?}");
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use crate::color::color_enabled;
+use crate::command_cache::CommandCache;
+use crate::completions::completions_directory;
+use crate::completions::load_completion_specs;
+use crate::config::ShellConfig;
+use crate::error::ReadError;
+use crate::error::ShellError;
+use crate::frecency::SharedDirectoryDatabase;
+use crate::history::CommandHistory;
+use crate::history_search::FuzzyHistorySearch;
+use crate::history_search::HistorySnapshot;
+use crate::jobs::JobSummary;
+use crate::keybindings::apply_bindings;
+use crate::keybindings::take_pending_run;
+use crate::keybindings::PrefixHistorySearchHandler;
+use crate::options::ShellOptions;
+use crate::shell_helper::AcceptHintHandler;
+use crate::shell_helper::AcceptHintWordHandler;
+use crate::shell_helper::ShellCompleter;
+use crate::shell_helper::ShellHelper;
+use rustyline::config::BellStyle;
+use rustyline::config::Config;
+use rustyline::config::Configurer;
+use rustyline::hint::HistoryHinter;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
+use rustyline::EventHandler;
+use rustyline::KeyCode;
+use rustyline::KeyEvent;
+use rustyline::Modifiers;
+use std::io;
+use std::io::IsTerminal;
+
+/// The interactive line reader: rustyline with completion, hints, history search, and the
+/// key bindings from the config file.
+pub struct LineEditor {
+    editor: Editor<ShellHelper, DefaultHistory>,
+    history_snapshot: HistorySnapshot,
+}
+
+impl LineEditor {
+    /// An editor set up from `config`, completing directories from `directories`.
+    pub fn new(config: &ShellConfig, directories: SharedDirectoryDatabase) -> Result<Self, ShellError> {
+        let command_cache = CommandCache::load(config.cache_directory().as_deref());
+        let mut completer = ShellCompleter::new(directories, command_cache);
+        if let Some(directory) = completions_directory() {
+            completer.specs = load_completion_specs(&directory);
+        }
+        let helper = ShellHelper {
+            completer,
+            hinter: HistoryHinter::new(),
+            color: color_enabled(&io::stdout()),
+            // rustyline validates piped input too, and drops what it has read when the input ends
+            // before it is complete.
+            validate: io::stdin().is_terminal(),
+        };
+
+        let editor_config = Config::builder()
+            .history_ignore_dups(false)?
+            .bracketed_paste(true)
+            .build();
+
+        let mut editor = Editor::with_config(editor_config)?;
+        editor.set_helper(Some(helper));
+        editor.bind_sequence(
+            KeyEvent(KeyCode::End, Modifiers::NONE),
+            EventHandler::Conditional(Box::new(AcceptHintHandler)),
+        );
+        for modifiers in [Modifiers::ALT, Modifiers::CTRL] {
+            editor.bind_sequence(
+                KeyEvent(KeyCode::Right, modifiers),
+                EventHandler::Conditional(Box::new(AcceptHintWordHandler)),
+            );
+        }
+        let history_snapshot = HistorySnapshot::default();
+        editor.bind_sequence(
+            KeyEvent::ctrl('R'),
+            EventHandler::Conditional(Box::new(FuzzyHistorySearch {
+                history: history_snapshot.clone(),
+            })),
+        );
+        editor.bind_sequence(
+            KeyEvent(KeyCode::Up, Modifiers::NONE),
+            EventHandler::Conditional(Box::new(PrefixHistorySearchHandler {
+                backward: true,
+                history: history_snapshot.clone(),
+            })),
+        );
+        editor.bind_sequence(
+            KeyEvent(KeyCode::Down, Modifiers::NONE),
+            EventHandler::Conditional(Box::new(PrefixHistorySearchHandler {
+                backward: false,
+                history: history_snapshot.clone(),
+            })),
+        );
+        apply_bindings(&mut editor, &config.key_bindings);
+
+        Ok(Self {
+            editor,
+            history_snapshot,
+        })
+    }
+
+    #[must_use]
+    pub fn history(&self) -> &dyn CommandHistory {
+        self.editor.history()
+    }
+
+    pub fn history_mut(&mut self) -> &mut dyn CommandHistory {
+        self.editor.history_mut()
+    }
+
+    /// Offer `names` as command completions.
+    pub fn add_commands(&mut self, names: impl IntoIterator<Item = String>) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.completer.merge_commands(names);
+        }
+    }
+
+    /// Offer `names` as the alias names, replacing the previous ones.
+    pub fn set_aliases(&mut self, names: impl IntoIterator<Item = String>) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.completer.set_aliases(names);
+        }
+    }
+
+    /// Offer `names` as the shell variable names, replacing the previous ones.
+    pub fn set_variables(&mut self, names: impl IntoIterator<Item = String>) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.completer.set_variables(names);
+        }
+    }
+
+    /// Append the entries saved in `path` to the history.
+    pub fn load_history(&mut self, path: &str) {
+        let _ = self.editor.load_history(path);
+    }
+
+    /// Show `prompt` and read one line, completion following `options` and offering `jobs` as
+    /// `%N` specs. The caller adds the line to the history.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        options: &ShellOptions,
+        jobs: Vec<JobSummary>,
+    ) -> Result<String, ReadError> {
+        if let Ok(mut snapshot) = self.history_snapshot.lock() {
+            *snapshot = self.history().entries();
+        }
+        self.editor.set_completion_type(options.completion_type());
+        self.editor.set_bell_style(if options.completion_bell {
+            BellStyle::Audible
+        } else {
+            BellStyle::None
+        });
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.completer.apply_options(options);
+            helper.completer.jobs = jobs;
+            helper.color = color_enabled(&io::stdout());
+        }
+
+        let line = self.editor.readline(prompt)?;
+        Ok(take_pending_run().unwrap_or(line))
+    }
+}
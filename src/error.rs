@@ -0,0 +1,194 @@
+use crate::parser::ParseError;
+#[cfg(feature = "readline")]
+use rustyline::error::ReadlineError;
+use std::any::Any;
+use std::fmt;
+use std::io;
+use std::io::Write;
+
+/// The name every error message starts with.
+pub const SHELL_NAME: &str = "ccsh";
+pub const EXIT_STATUS_FAILURE: i32 = 1;
+pub const EXIT_STATUS_SYNTAX_ERROR: i32 = 2;
+pub const EXIT_STATUS_NOT_EXECUTABLE: i32 = 126;
+pub const EXIT_STATUS_NOT_FOUND: i32 = 127;
+
+/// Errors raised while parsing and running command lines.
+#[derive(Debug)]
+pub enum ShellError {
+    /// The command line is not valid syntax.
+    ParseError(ParseError),
+    /// No builtin or executable by that name; `suggestions` are close names to offer instead.
+    CommandNotFound {
+        command: String,
+        suggestions: Vec<String>,
+    },
+    /// A redirection target could not be opened.
+    RedirectionFailed {
+        path: String,
+        source: io::Error,
+    },
+    /// The executable was found but could not be started.
+    SpawnFailed {
+        command: String,
+        source: io::Error,
+    },
+    Io(io::Error),
+    /// The shell itself panicked while running `command`; the session carries on.
+    Panicked {
+        command: String,
+        message: String,
+    },
+}
+
+impl ShellError {
+    /// The status a command failing with this error leaves in `$?`.
+    #[must_use]
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::ParseError(_) => EXIT_STATUS_SYNTAX_ERROR,
+            Self::CommandNotFound { .. } => EXIT_STATUS_NOT_FOUND,
+            Self::SpawnFailed { source, .. } if source.kind() == io::ErrorKind::PermissionDenied => {
+                EXIT_STATUS_NOT_EXECUTABLE
+            }
+            Self::RedirectionFailed { .. } | Self::SpawnFailed { .. } | Self::Io(_) | Self::Panicked { .. } => {
+                EXIT_STATUS_FAILURE
+            }
+        }
+    }
+
+    /// Print the error on `stderr`, prefixed with the shell name, and return its exit code.
+    pub fn report(&self, stderr: &mut dyn Write) -> i32 {
+        let _ = writeln!(stderr, "{SHELL_NAME}: {self}");
+        let _ = stderr.flush();
+        self.exit_code()
+    }
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ParseError(error) => write!(f, "{error}"),
+            Self::CommandNotFound { command, suggestions } => {
+                write!(f, "{command}: command not found")?;
+                let quoted: Vec<String> = suggestions.iter().map(|name| format!("'{name}'")).collect();
+                match quoted.as_slice() {
+                    [] => Ok(()),
+                    [only] => write!(f, ", did you mean {only}?"),
+                    [rest @ .., last] => write!(f, ", did you mean {} or {last}?", rest.join(", ")),
+                }
+            }
+            Self::RedirectionFailed { path, source } => write!(f, "{path}: {}", io_error_message(source)),
+            Self::SpawnFailed { command, source } => write!(f, "{command}: {}", io_error_message(source)),
+            Self::Io(source) => write!(f, "{}", io_error_message(source)),
+            Self::Panicked { command, message } => write!(f, "{command}: internal error: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ShellError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::RedirectionFailed { source, .. } | Self::SpawnFailed { source, .. } | Self::Io(source) => {
+                Some(source)
+            }
+            Self::ParseError(error) => Some(error),
+            Self::CommandNotFound { .. } | Self::Panicked { .. } => None,
+        }
+    }
+}
+
+impl ShellError {
+    /// The error for a panic caught while running `command`, with the panic's message.
+    #[must_use]
+    pub fn from_panic(command: &str, payload: &(dyn Any + Send)) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(ToString::to_string)
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Self::Panicked {
+            command: command.to_string(),
+            message,
+        }
+    }
+}
+
+impl From<io::Error> for ShellError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+#[cfg(feature = "readline")]
+impl From<ReadlineError> for ShellError {
+    fn from(error: ReadlineError) -> Self {
+        match error {
+            ReadlineError::Io(error) => Self::Io(error),
+            error => Self::Io(io::Error::other(error.to_string())),
+        }
+    }
+}
+
+/// Why reading a line at the prompt returned no command, whichever line reader is built in.
+#[derive(Debug)]
+pub enum ReadError {
+    /// End of input: Ctrl-D at an empty prompt, or the end of a script.
+    Eof,
+    /// Ctrl-C at the prompt.
+    Interrupted,
+    /// Reading from the terminal or stdin failed.
+    Failed(ShellError),
+}
+
+#[cfg(feature = "readline")]
+impl From<ReadlineError> for ReadError {
+    fn from(error: ReadlineError) -> Self {
+        match error {
+            ReadlineError::Eof => Self::Eof,
+            ReadlineError::Interrupted => Self::Interrupted,
+            error => Self::Failed(ShellError::from(error)),
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::Interrupted => Self::Interrupted,
+            _ => Self::Failed(ShellError::Io(error)),
+        }
+    }
+}
+
+/// Print `ccsh: command: message` on `stderr`, the format of every builtin's error messages.
+pub fn report_error(stderr: &mut dyn Write, command: &str, message: impl fmt::Display) {
+    let _ = writeln!(stderr, "{SHELL_NAME}: {command}: {message}");
+    let _ = stderr.flush();
+}
+
+/// The POSIX description of `error` as shells print it: the OS text without its `(os error N)`
+/// suffix, or the `strerror` wording for errors raised by Rust itself.
+#[must_use]
+pub fn io_error_message(error: &io::Error) -> String {
+    if error.raw_os_error().is_some() {
+        let message = error.to_string();
+        return match message.find(" (os error ") {
+            Some(suffix) => message[..suffix].to_string(),
+            None => message,
+        };
+    }
+    let message = match error.kind() {
+        io::ErrorKind::NotFound => "No such file or directory",
+        io::ErrorKind::PermissionDenied => "Permission denied",
+        io::ErrorKind::AlreadyExists => "File exists",
+        io::ErrorKind::InvalidInput => "Invalid argument",
+        io::ErrorKind::BrokenPipe => "Broken pipe",
+        io::ErrorKind::Interrupted => "Interrupted system call",
+        io::ErrorKind::WouldBlock => "Resource temporarily unavailable",
+        io::ErrorKind::TimedOut => "Connection timed out",
+        io::ErrorKind::UnexpectedEof => "Unexpected end of file",
+        _ => return error.to_string(),
+    };
+    message.to_string()
+}
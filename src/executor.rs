@@ -1,156 +1,564 @@
+use crate::commands::command_not_found;
 use crate::commands::dispatch_builtin;
+use crate::commands::get_input_redirection;
 use crate::commands::get_redirection;
 use crate::commands::is_executable;
 use crate::commands::run_executable;
 use crate::commands::BuiltinAction;
-use crate::jobs::JobManager;
+use crate::error::ShellError;
+use crate::history::CommandHistory;
+use crate::options::ShellOptions;
+use crate::parser::quote_word;
+use crate::parser::InputRedirection;
+use crate::parser::OutputRedirection;
 use crate::parser::ParsedCommand;
-use crate::parser::COMMAND_CD;
-use crate::parser::COMMAND_ECHO;
-use crate::parser::COMMAND_EXIT;
-use crate::parser::COMMAND_HISTORY;
-use crate::parser::COMMAND_JOBS;
-use crate::parser::COMMAND_PWD;
-use crate::parser::COMMAND_TYPE;
-use rustyline::Editor;
+use crate::parser::Redirection;
+use crate::parser::BUILTIN_COMMANDS;
+use crate::parser::NULL_DEVICE;
+use crate::parser::STDERR_DESCRIPTOR;
+use crate::parser::STDIN_DESCRIPTOR;
+use crate::parser::STDOUT_DESCRIPTOR;
+use crate::state::ShellState;
+use crate::subshell::fork_subshell;
+use crate::subshell::Fork;
+use crate::subshell::Process;
+use crate::trace::trace;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::fs::OpenOptions;
 use std::io;
 use std::io::Read;
+use std::io::Seek;
 use std::io::Write;
-use std::os::unix::process::CommandExt;
+use std::os::fd::AsFd;
+use std::os::fd::OwnedFd;
+use std::os::fd::RawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::ExitStatusExt;
 use std::path::Path;
 use std::path::PathBuf;
-use std::process::Child;
-use std::process::Command;
+use std::process::ExitStatus;
 use std::process::Stdio;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 
-pub struct ShellContext<'a, H: rustyline::Helper, I: rustyline::history::History> {
-    pub editor: &'a mut Editor<H, I>,
-    pub last_appended_index: &'a mut usize,
+/// The shell state a command line runs against, independent of the interactive line editor.
+pub struct ExecutionContext<'a> {
+    pub history: &'a mut dyn CommandHistory,
+    pub state: &'a mut ShellState,
 }
 
-#[allow(clippy::too_many_lines)]
-pub fn execute_pipeline<H: rustyline::Helper, I: rustyline::history::History>(
-    pipeline: Vec<ParsedCommand>,
-    job_mgr: &mut JobManager,
-    ctx: &mut ShellContext<'_, H, I>,
-) -> io::Result<BuiltinAction> {
-    use crate::commands::search_executable;
+/// Where a command's stdout or stderr goes, usable both as a `Write` handle for builtins and as a
+/// `Stdio` for child processes, so redirected child output is written directly to its target.
+#[derive(Debug)]
+pub enum OutputTarget {
+    /// The shell's own stream.
+    Inherit,
+    File(File),
+    Pipe(os_pipe::PipeWriter),
+    /// A descriptor closed with `N>&-`, which a builtin fails to write to.
+    Closed,
+}
+
+/// The writer of a closed descriptor: every write fails with `EBADF`, as it would in bash.
+struct ClosedDescriptor;
+
+impl Write for ClosedDescriptor {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::from_raw_os_error(libc::EBADF))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl OutputTarget {
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            Self::Inherit => Self::Inherit,
+            Self::File(file) => Self::File(file.try_clone()?),
+            Self::Pipe(writer) => Self::Pipe(writer.try_clone()?),
+            Self::Closed => Self::Closed,
+        })
+    }
+
+    /// A writer for a builtin; `inherited` opens the shell's stream for [`OutputTarget::Inherit`].
+    #[must_use]
+    pub fn into_writer(self, inherited: fn() -> Box<dyn Write>) -> Box<dyn Write> {
+        match self {
+            Self::Inherit => inherited(),
+            Self::File(file) => Box::new(file),
+            Self::Pipe(writer) => Box::new(writer),
+            Self::Closed => Box::new(ClosedDescriptor),
+        }
+    }
+
+    /// The descriptor a forked subshell writes to, or `None` to keep the shell's own.
+    pub fn into_fd(self) -> io::Result<Option<OwnedFd>> {
+        Ok(match self {
+            Self::Inherit => None,
+            Self::File(file) => Some(file.into()),
+            Self::Pipe(writer) => Some(writer.into()),
+            Self::Closed => Some(OpenOptions::new().write(true).open(NULL_DEVICE)?.into()),
+        })
+    }
+}
 
+impl From<OutputTarget> for Stdio {
+    fn from(target: OutputTarget) -> Self {
+        match target {
+            OutputTarget::Inherit => Self::inherit(),
+            OutputTarget::File(file) => Self::from(file),
+            OutputTarget::Pipe(writer) => Self::from(writer),
+            OutputTarget::Closed => Self::null(),
+        }
+    }
+}
+
+/// Where a command's stdin comes from, usable both as a `Read` handle for builtins and as a
+/// `Stdio` for child processes.
+#[derive(Debug)]
+pub enum InputSource {
+    /// The shell's own stream.
+    Inherit,
+    File(File),
+    Pipe(os_pipe::PipeReader),
+    Null,
+}
+
+impl InputSource {
+    /// A reader for a builtin.
+    #[must_use]
+    pub fn into_reader(self) -> Box<dyn Read> {
+        match self {
+            Self::Inherit => Box::new(io::stdin()),
+            Self::File(file) => Box::new(file),
+            Self::Pipe(reader) => Box::new(reader),
+            Self::Null => Box::new(io::empty()),
+        }
+    }
+
+    /// The descriptor a forked subshell reads, or `None` to keep the shell's own.
+    pub fn into_fd(self) -> io::Result<Option<OwnedFd>> {
+        Ok(match self {
+            Self::Inherit => None,
+            Self::File(file) => Some(file.into()),
+            Self::Pipe(reader) => Some(reader.into()),
+            Self::Null => Some(File::open(NULL_DEVICE)?.into()),
+        })
+    }
+}
+
+impl From<InputSource> for Stdio {
+    fn from(source: InputSource) -> Self {
+        match source {
+            InputSource::Inherit => Self::inherit(),
+            InputSource::File(file) => Self::from(file),
+            InputSource::Pipe(reader) => Self::from(reader),
+            InputSource::Null => Self::null(),
+        }
+    }
+}
+
+fn shell_stdout() -> Box<dyn Write> {
+    Box::new(io::stdout())
+}
+
+fn shell_stderr() -> Box<dyn Write> {
+    Box::new(io::stderr())
+}
+
+/// Arguments for a builtin or plugin. Builtins work on text; only external commands see non-UTF-8
+/// arguments unchanged.
+fn text_arguments(arguments: &[OsString]) -> Vec<String> {
+    arguments
+        .iter()
+        .map(|argument| argument.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// A file that reads back `text`: a new file in the temporary directory, removed as soon as it is
+/// open. Unlike a pipe, nothing keeps writing to it, so a forked `( ... )` group inheriting the
+/// shell's descriptors cannot stop the commands it runs from reaching the end of the body.
+fn here_document(text: &str) -> io::Result<File> {
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+    let directory = std::env::temp_dir();
+    loop {
+        let created = CREATED.fetch_add(1, Ordering::Relaxed);
+        let path = directory.join(format!("ccsh-here-document-{}-{created}", std::process::id()));
+        let opened = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path);
+        match opened {
+            Ok(mut file) => {
+                std::fs::remove_file(&path)?;
+                file.write_all(text.as_bytes())?;
+                file.rewind()?;
+                return Ok(file);
+            }
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// The command line calling the function `command` names, with its words quoted so that the
+/// forked shell running it reads them back unchanged, or `None` when it names no function.
+fn function_call(command: &ParsedCommand, state: &ShellState) -> Option<String> {
+    let words = command.tokens.as_deref()?;
+    let name = words.first()?.to_str()?;
+    state.functions.contains_key(name).then(|| {
+        let words: Vec<String> = words.iter().map(|word| quote_word(&word.to_string_lossy())).collect();
+        words.join(" ")
+    })
+}
+
+/// Open the files of `command`'s redirections left to right, with a pipe for a here-document, into
+/// the table of descriptors they leave behind, `None` for a closed one: a later redirection of a
+/// descriptor replaces an earlier one, whose file is still created or truncated. A redirection to
+/// the same file as an earlier one (`&> file`) shares its handle, so both streams land in the file
+/// instead of overwriting each other.
+///
+/// `stdin` and `stdout` are the pipes the stage reads from and writes to, which `<&0` and `>&1`
+/// duplicate when no earlier redirection replaced them.
+fn open_redirections(
+    command: &ParsedCommand,
+    options: &ShellOptions,
+    stdin: Option<&os_pipe::PipeReader>,
+    stdout: Option<&os_pipe::PipeWriter>,
+) -> Result<BTreeMap<RawFd, Option<File>>, ShellError> {
+    let mut files: BTreeMap<RawFd, Option<File>> = BTreeMap::new();
+    let mut outputs: Vec<(&OutputRedirection, File)> = Vec::new();
+    for (descriptor, redirection) in &command.redirections {
+        let file = match redirection {
+            Redirection::Output(output) => {
+                let shared = outputs.iter().find(|(other, _)| *other == output);
+                match shared {
+                    Some((_, file)) => file.try_clone()?,
+                    None => {
+                        let operator = if output.append_to { ">>" } else { ">" };
+                        trace(options, || {
+                            format!("redirect: {descriptor}{operator} {}", output.file_name)
+                        });
+                        let file = get_redirection(output)?;
+                        outputs.push((output, file.try_clone()?));
+                        file
+                    }
+                }
+            }
+            Redirection::Input(InputRedirection::File(file_name)) => {
+                trace(options, || format!("redirect: {descriptor}< {file_name}"));
+                get_input_redirection(file_name)?
+            }
+            Redirection::Input(InputRedirection::HereDocument(text)) => here_document(text)?,
+            Redirection::Duplicate(source) => {
+                trace(options, || format!("redirect: {descriptor}>&{source}"));
+                duplicate_descriptor(*source, &files, stdin, stdout)?
+            }
+            Redirection::Close => {
+                trace(options, || format!("redirect: {descriptor}>&-"));
+                files.insert(*descriptor, None);
+                continue;
+            }
+        };
+        files.insert(*descriptor, Some(file));
+    }
+    Ok(files)
+}
+
+/// A copy of descriptor `source` as the redirections in `files` leave it, falling back to the
+/// stage's pipes and then to the shell's own standard streams. Any other descriptor is not open.
+fn duplicate_descriptor(
+    source: RawFd,
+    files: &BTreeMap<RawFd, Option<File>>,
+    stdin: Option<&os_pipe::PipeReader>,
+    stdout: Option<&os_pipe::PipeWriter>,
+) -> Result<File, ShellError> {
+    let not_open = || io::Error::from_raw_os_error(libc::EBADF);
+    let duplicate = match files.get(&source) {
+        Some(Some(file)) => file.try_clone(),
+        Some(None) => Err(not_open()),
+        None => match (source, stdin, stdout) {
+            (STDIN_DESCRIPTOR, Some(reader), _) => reader.try_clone().map(|reader| File::from(OwnedFd::from(reader))),
+            (STDOUT_DESCRIPTOR, _, Some(writer)) => writer.try_clone().map(|writer| File::from(OwnedFd::from(writer))),
+            (STDIN_DESCRIPTOR, ..) => io::stdin().as_fd().try_clone_to_owned().map(File::from),
+            (STDOUT_DESCRIPTOR, ..) => io::stdout().as_fd().try_clone_to_owned().map(File::from),
+            (STDERR_DESCRIPTOR, ..) => io::stderr().as_fd().try_clone_to_owned().map(File::from),
+            _ => Err(not_open()),
+        },
+    };
+    duplicate.map_err(|error| ShellError::RedirectionFailed {
+        path: source.to_string(),
+        source: error,
+    })
+}
+
+/// Take the file a redirection left for standard stream `descriptor` out of `files`: `Some(None)`
+/// when it was closed, which stays in `files` for a child to close as well.
+fn take_stream(files: &mut BTreeMap<RawFd, Option<File>>, descriptor: RawFd) -> Option<Option<File>> {
+    match files.remove(&descriptor)? {
+        Some(file) => Some(Some(file)),
+        None => {
+            files.insert(descriptor, None);
+            Some(None)
+        }
+    }
+}
+
+/// The command line of a background job, as `jobs` shows it.
+fn job_command(pipeline: &[ParsedCommand]) -> String {
+    pipeline
+        .iter()
+        .map(|command| match &command.subshell {
+            Some(list) => format!("({list})"),
+            None => command
+                .tokens
+                .iter()
+                .flatten()
+                .map(|token| token.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" "),
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+/// Map a child's exit status to a shell status code (`128 + signal` when killed by a signal).
+#[must_use]
+pub fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
+/// Run one parsed pipeline against `ctx`, leaving the status of its last stage in `ctx.state.last_status`.
+pub fn execute_pipeline(
+    pipeline: &[ParsedCommand],
+    ctx: &mut ExecutionContext<'_>,
+) -> Result<BuiltinAction, ShellError> {
     let pipeline_length = pipeline.len();
-    let mut children: Vec<Child> = Vec::new();
+    let mut children: Vec<(usize, Process)> = Vec::new();
     let mut previous_output: Option<os_pipe::PipeReader> = None;
+    let background = pipeline.last().is_some_and(|command| command.background);
 
-    for (current_index, current_command) in pipeline.into_iter().enumerate() {
-        let arguments_vec: Vec<String> = current_command.tokens.clone().unwrap_or_default();
-        let mut arguments = arguments_vec.into_iter().enumerate();
-
-        let (stdin_builtin, stdin_external) = if let Some(output) = previous_output.take() {
-            let output_for_external = output.try_clone()?;
-            (Box::new(output) as Box<dyn Read>, Stdio::from(output_for_external))
+    for (current_index, current_command) in pipeline.iter().enumerate() {
+        trace(&ctx.state.options, || {
+            format!("exec: {:?}", current_command.tokens.as_deref().unwrap_or_default())
+        });
+        // The pipe to the next stage comes first, so a redirection can duplicate it (`2>&1 |`). A
+        // stage redirected to a file, or whose redirections fail, still gets one, which the next
+        // stage then reads nothing from.
+        let previous_stage_output = previous_output.take();
+        let is_last_stage = current_index == pipeline_length - 1;
+        let pipe_writer = if is_last_stage {
+            None
         } else {
-            (Box::new(io::empty()) as Box<dyn Read>, Stdio::null())
+            let (reader, writer) = os_pipe::pipe()?;
+            previous_output = Some(reader);
+            Some(writer)
+        };
+        let opened = open_redirections(
+            current_command,
+            &ctx.state.options,
+            previous_stage_output.as_ref(),
+            pipe_writer.as_ref(),
+        );
+        let mut files = match opened {
+            Ok(files) => files,
+            Err(error) => {
+                ctx.state.last_status = error.report(&mut io::stderr());
+                continue;
+            }
         };
 
-        let (stdout_builtin, stdout_external, new_previous_output) = if current_index < pipeline_length - 1 {
-            let (reader, writer) = os_pipe::pipe()?;
-            let writer_for_external = writer.try_clone()?;
-            (
-                Box::new(writer) as Box<dyn Write>,
-                Stdio::from(writer_for_external),
-                Some(reader),
-            )
-        } else {
-            let stdout = get_redirection(current_command.stdout.clone()).unwrap_or(Box::new(io::stdout()));
-            (stdout, Stdio::inherit(), None)
+        // A redirection replaces the previous stage's output, which is then not read. A background
+        // command does not compete with the shell for the terminal. A closed standard stream reads
+        // nothing and fails writes in a builtin, and is closed in a child.
+        let stdin = match take_stream(&mut files, STDIN_DESCRIPTOR) {
+            Some(Some(file)) => InputSource::File(file),
+            Some(None) => InputSource::Null,
+            None => match previous_stage_output {
+                Some(output) => InputSource::Pipe(output),
+                None if background => InputSource::Null,
+                None => InputSource::Inherit,
+            },
+        };
+        let stdout = match (take_stream(&mut files, STDOUT_DESCRIPTOR), pipe_writer) {
+            (Some(Some(file)), _) => OutputTarget::File(file),
+            (Some(None), _) => OutputTarget::Closed,
+            (None, Some(writer)) => OutputTarget::Pipe(writer),
+            (None, None) => OutputTarget::Inherit,
         };
-        previous_output = new_previous_output;
+        let stderr = match take_stream(&mut files, STDERR_DESCRIPTOR) {
+            Some(Some(file)) => OutputTarget::File(file),
+            Some(None) => OutputTarget::Closed,
+            None => OutputTarget::Inherit,
+        };
+        // Builtins and plugins only have the standard streams; the other files are created all the same.
+        let descriptors: Vec<(RawFd, Option<File>)> = files.into_iter().collect();
 
-        let mut stderr_builtin = get_redirection(current_command.stderr.clone()).unwrap_or(Box::new(io::stderr()));
+        // A `( ... )` group, or a function call that is not a plain call the shell runs itself (see
+        // `Shell::function_call`), runs in a forked copy of the shell.
+        let group = current_command
+            .subshell
+            .clone()
+            .or_else(|| function_call(current_command, ctx.state));
+        if let Some(body) = group {
+            let mut group_descriptors = Vec::new();
+            for (descriptor, fd) in [
+                (STDIN_DESCRIPTOR, stdin.into_fd()?),
+                (STDOUT_DESCRIPTOR, stdout.into_fd()?),
+                (STDERR_DESCRIPTOR, stderr.into_fd()?),
+            ] {
+                group_descriptors.extend(fd.map(|fd| (descriptor, Some(fd))));
+            }
+            group_descriptors.extend(
+                descriptors
+                    .into_iter()
+                    .map(|(descriptor, file)| (descriptor, file.map(OwnedFd::from))),
+            );
+            match fork_subshell(group_descriptors)? {
+                Fork::Child => return Ok(BuiltinAction::Subshell(body)),
+                Fork::Parent(subshell) => {
+                    trace(&ctx.state.options, || format!("spawn: subshell pid {}", subshell.id()));
+                    children.push((current_index, Process::Subshell(subshell)));
+                }
+            }
+            continue;
+        }
 
-        let Some((_, command)) = arguments.next() else {
-            return Ok(BuiltinAction::Continue);
+        let Some((command, arguments)) = current_command.tokens.as_deref().and_then(<[OsString]>::split_first) else {
+            if current_command.assignments.is_empty() {
+                return Ok(BuiltinAction::Continue(ctx.state.last_status));
+            }
+            // Without a command, `NAME=value` words set variables, though not from a pipeline stage
+            // or the background, which bash runs in a subshell.
+            if pipeline_length == 1 && !background {
+                for (name, value) in &current_command.assignments {
+                    trace(&ctx.state.options, || format!("assign: {name}={value}"));
+                    ctx.state.set_variable(name, value);
+                }
+            }
+            // Like bash, the status is that of the last command substitution in the words, if any.
+            ctx.state.last_status = ctx.state.substitution_status.unwrap_or(0);
+            continue;
         };
 
         // Check if it's a built-in first (no resource consumption).
-        let is_builtin = matches!(
-            command.as_str(),
-            COMMAND_CD | COMMAND_ECHO | COMMAND_EXIT | COMMAND_PWD | COMMAND_TYPE | COMMAND_HISTORY | COMMAND_JOBS
-        );
+        let builtin = command.to_str().filter(|name| BUILTIN_COMMANDS.contains(name));
 
-        if is_builtin {
+        if let Some(builtin) = builtin {
+            trace(&ctx.state.options, || format!("resolve: {builtin} -> builtin"));
+            let arguments = text_arguments(arguments);
             // dispatch_builtin always returns Some for known built-ins.
             let action = dispatch_builtin(
-                &command,
-                arguments,
-                stdin_builtin,
-                stdout_builtin,
-                stderr_builtin,
-                ctx.editor,
-                ctx.last_appended_index,
-                job_mgr,
+                builtin,
+                &arguments,
+                stdin.into_reader(),
+                stdout.into_writer(shell_stdout),
+                stderr.into_writer(shell_stderr),
+                ctx,
             )
-            .unwrap_or(BuiltinAction::Continue);
-            if let BuiltinAction::Exit(code) = action {
-                return Ok(BuiltinAction::Exit(code));
+            .unwrap_or(BuiltinAction::Continue(0));
+            match action {
+                // Inside a pipeline or in the background `exit` ends only its own stage, as it would in a
+                // bash subshell.
+                BuiltinAction::Exit(code) if pipeline_length == 1 && !background => {
+                    return Ok(BuiltinAction::Exit(code))
+                }
+                BuiltinAction::Return(code) if pipeline_length == 1 && !background => {
+                    return Ok(BuiltinAction::Return(code))
+                }
+                BuiltinAction::Exit(status) | BuiltinAction::Return(status) | BuiltinAction::Continue(status) => {
+                    ctx.state.last_status = status;
+                }
+                action @ BuiltinAction::Subshell(_) => return Ok(action),
             }
             continue;
         }
 
+        if let Some(plugin) = command.to_str().and_then(|name| ctx.state.plugins.get_mut(name)) {
+            trace(&ctx.state.options, || format!("resolve: {} -> plugin", plugin.name()));
+            let mut stdin = stdin.into_reader();
+            let mut stdout = stdout.into_writer(shell_stdout);
+            let mut stderr = stderr.into_writer(shell_stderr);
+            ctx.state.last_status = plugin.run(&text_arguments(arguments), &mut stdin, &mut stdout, &mut stderr);
+            stdout.flush().unwrap_or_default();
+            stderr.flush().unwrap_or_default();
+            continue;
+        }
+
         // Resolve external command path.
-        let path = if let Some(p) = search_executable(&command) {
+        let path = if let Some(p) = ctx.state.path_cache.lookup(command) {
             p
-        } else if Path::new(&command).is_absolute() && is_executable(&PathBuf::from(&command)).unwrap_or(false) {
-            command.clone()
+        } else if Path::new(command).is_absolute() && is_executable(Path::new(command)).unwrap_or(false) {
+            PathBuf::from(command)
         } else {
-            let _ = writeln!(stderr_builtin, "{command}: command not found");
+            ctx.state.last_status =
+                command_not_found(&command.to_string_lossy()).report(&mut stderr.into_writer(shell_stderr));
             continue;
         };
 
-        if pipeline_length == 1 {
-            let mut stdout_builtin = stdout_builtin;
-            match run_executable(
-                &path,
-                &command,
-                arguments,
-                stdin_external,
-                &mut stdout_builtin,
-                &mut stderr_builtin,
-                current_command.stdout.file_name.is_none(),
-                current_command.stderr.file_name.is_none(),
-                None,
-            ) {
-                Ok(child) => {
-                    if current_command.background {
-                        let cmd_str = current_command.tokens.as_ref().map(|t| t.join(" ")).unwrap_or_default();
-                        job_mgr.add(child, cmd_str);
-                    } else {
-                        let mut child = child;
-                        let _ = child.wait();
-                    }
-                }
-                Err(e) => {
-                    let _ = writeln!(stderr_builtin, "Error: {e:?}");
+        trace(&ctx.state.options, || {
+            format!("resolve: {} -> {}", command.to_string_lossy(), path.display())
+        });
+
+        let stderr_for_errors = stderr.try_clone()?;
+        let spawned = run_executable(
+            &path,
+            command,
+            arguments,
+            &current_command.assignments,
+            stdin.into(),
+            stdout.into(),
+            stderr.into(),
+            &descriptors,
+        );
+        if let Ok(child) = &spawned {
+            trace(&ctx.state.options, || format!("spawn: pid {}", child.id()));
+        }
+        match spawned {
+            Ok(child) => children.push((current_index, Process::Command(child))),
+            Err(source) => {
+                ctx.state.last_status = ShellError::SpawnFailed {
+                    command: command.to_string_lossy().into_owned(),
+                    source,
                 }
-            }
-        } else {
-            // Pipeline case
-            if let Ok(spawned) = Command::new(&path)
-                .arg0(&command)
-                .args(arguments.map(|(_, arg)| arg))
-                .stdin(stdin_external)
-                .stdout(stdout_external)
-                .spawn()
-            {
-                children.push(spawned);
-            } else {
-                let _ = writeln!(stderr_builtin, "Error: Failed to spawn child process {command}");
+                .report(&mut stderr_for_errors.into_writer(shell_stderr));
             }
         }
     }
 
-    for mut child in children {
-        let _ = child.wait();
+    if background {
+        if !children.is_empty() {
+            let children = children.into_iter().map(|(_, child)| child).collect();
+            // Like bash, only an interactive shell announces the job.
+            let notify = ctx.state.interactive;
+            let pid = ctx.state.job_mgr.add(children, job_command(pipeline), notify);
+            ctx.state.last_background_pid = Some(pid);
+        }
+        ctx.state.last_status = 0;
+        return Ok(BuiltinAction::Continue(0));
+    }
+    for (index, mut child) in children {
+        let status = child.wait().map_or(1, exit_code);
+        trace(&ctx.state.options, || {
+            format!("wait: pid {} status {status}", child.id())
+        });
+        if index == pipeline_length - 1 {
+            ctx.state.last_status = status;
+        }
+    }
+    // The terminal echoed `^C` without a newline; start the next output on a fresh line.
+    if ctx.state.last_status == 128 + libc::SIGINT {
+        let _ = writeln!(io::stderr());
     }
 
-    Ok(BuiltinAction::Continue)
+    Ok(BuiltinAction::Continue(ctx.state.last_status))
 }
@@ -0,0 +1,180 @@
+use crate::xdg::shell_file;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+pub const DIRECTORY_DATABASE_FILE_NAME: &str = "dirs";
+/// Where the database lived before it moved to the state directory.
+pub const LEGACY_DIRECTORY_DATABASE_FILE_NAME: &str = ".ccsh_dirs";
+const DATABASE_FIELD_SEPARATOR: char = '|';
+const MAX_TOTAL_RANK: f64 = 10_000.0;
+const AGING_FACTOR: f64 = 0.9;
+const SECONDS_PER_HOUR: u64 = 60 * 60;
+const SECONDS_PER_DAY: u64 = 24 * SECONDS_PER_HOUR;
+const SECONDS_PER_WEEK: u64 = 7 * SECONDS_PER_DAY;
+
+#[derive(Clone, Copy, Debug)]
+struct DirectoryEntry {
+    rank: f64,
+    last_access: u64,
+}
+
+/// Visited directories scored by frequency and recency, persisted as `rank|last_access|path` lines.
+#[derive(Debug, Default)]
+pub struct DirectoryDatabase {
+    path: Option<PathBuf>,
+    entries: HashMap<PathBuf, DirectoryEntry>,
+}
+
+/// The database shared between the completer and the builtins.
+pub type SharedDirectoryDatabase = Arc<Mutex<DirectoryDatabase>>;
+
+impl DirectoryDatabase {
+    /// Load `dirs` in `state_directory`, starting empty when it is missing or unreadable.
+    /// A `~/.ccsh_dirs` left from older versions is moved there first.
+    #[must_use]
+    pub fn load(state_directory: Option<&Path>) -> Self {
+        let path = state_directory.map(|directory| {
+            shell_file(
+                directory,
+                DIRECTORY_DATABASE_FILE_NAME,
+                Some(LEGACY_DIRECTORY_DATABASE_FILE_NAME),
+            )
+        });
+        let mut database = Self {
+            path,
+            entries: HashMap::new(),
+        };
+
+        let Some(content) = database
+            .path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+        else {
+            return database;
+        };
+        for line in content.lines() {
+            let mut fields = line.splitn(3, DATABASE_FIELD_SEPARATOR);
+            let (Some(rank), Some(last_access), Some(directory)) = (fields.next(), fields.next(), fields.next()) else {
+                continue;
+            };
+            if let (Ok(rank), Ok(last_access)) = (rank.parse(), last_access.parse()) {
+                database
+                    .entries
+                    .insert(PathBuf::from(directory), DirectoryEntry { rank, last_access });
+            }
+        }
+        database
+    }
+
+    /// Record a visit to `directory` and persist the database.
+    pub fn visit(&mut self, directory: &Path) {
+        let now = now();
+        let entry = self.entries.entry(directory.to_path_buf()).or_insert(DirectoryEntry {
+            rank: 0.0,
+            last_access: now,
+        });
+        entry.rank += 1.0;
+        entry.last_access = now;
+
+        let total: f64 = self.entries.values().map(|entry| entry.rank).sum();
+        if total > MAX_TOTAL_RANK {
+            for entry in self.entries.values_mut() {
+                entry.rank *= AGING_FACTOR;
+            }
+            self.entries.retain(|_, entry| entry.rank >= 1.0);
+        }
+
+        self.save();
+    }
+
+    /// Existing directories whose path contains every space-separated keyword (case-insensitive),
+    /// best score first. Matches on the last path component rank higher.
+    #[must_use]
+    pub fn matches(&self, fragment: &str) -> Vec<PathBuf> {
+        self.scores(fragment)
+            .into_iter()
+            .map(|(_, directory)| directory)
+            .collect()
+    }
+
+    /// The directories [`matches`](Self::matches) returns, with their scores.
+    #[must_use]
+    pub fn scores(&self, fragment: &str) -> Vec<(f64, PathBuf)> {
+        let now = now();
+        let keywords: Vec<String> = fragment.split_whitespace().map(str::to_lowercase).collect();
+
+        let mut scored: Vec<(f64, &PathBuf)> = self
+            .entries
+            .iter()
+            .filter_map(|(directory, entry)| {
+                let text = directory.to_string_lossy().to_lowercase();
+                if !keywords.iter().all(|keyword| text.contains(keyword.as_str())) {
+                    return None;
+                }
+                let mut score = frecency(entry, now);
+                let last_component = text.rsplit('/').next().unwrap_or_default();
+                if keywords
+                    .last()
+                    .is_some_and(|keyword| last_component.contains(keyword.as_str()))
+                {
+                    score *= 2.0;
+                }
+                Some((score, directory))
+            })
+            .filter(|(_, directory)| directory.is_dir())
+            .collect();
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        scored
+            .into_iter()
+            .map(|(score, directory)| (score, directory.clone()))
+            .collect()
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(mut file) = std::fs::File::create(path) {
+            for (directory, entry) in &self.entries {
+                let _ = writeln!(
+                    file,
+                    "{}{DATABASE_FIELD_SEPARATOR}{}{DATABASE_FIELD_SEPARATOR}{}",
+                    entry.rank,
+                    entry.last_access,
+                    directory.display()
+                );
+            }
+        }
+    }
+}
+
+/// Weight the visit count by how long ago the directory was last used.
+fn frecency(entry: &DirectoryEntry, now: u64) -> f64 {
+    let age = now.saturating_sub(entry.last_access);
+    let factor = if age < SECONDS_PER_HOUR {
+        4.0
+    } else if age < SECONDS_PER_DAY {
+        2.0
+    } else if age < SECONDS_PER_WEEK {
+        0.5
+    } else {
+        0.25
+    };
+    entry.rank * factor
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
@@ -0,0 +1,212 @@
+const SCORE_PREFIX: i64 = 3000;
+const SCORE_SUBSTRING: i64 = 2000;
+const SCORE_SUBSEQUENCE: i64 = 1000;
+const SCORE_TRANSPOSITION: i64 = 500;
+
+/// Score `candidate` against `query`; higher is better, `None` means no match.
+///
+/// Matching is smart-case: an all-lowercase query matches case-insensitively.
+#[must_use]
+pub fn fuzzy_score(candidate: &str, query: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let (candidate, query) = if query.chars().any(char::is_uppercase) {
+        (candidate.to_string(), query.to_string())
+    } else {
+        (candidate.to_lowercase(), query.to_string())
+    };
+
+    let candidate_chars: Vec<char> = candidate.chars().collect();
+    let query_chars: Vec<char> = query.chars().collect();
+    let length_penalty = i64::try_from(candidate_chars.len().saturating_sub(query_chars.len())).unwrap_or(i64::MAX);
+    let length_penalty = length_penalty.min(SCORE_TRANSPOSITION - 1);
+
+    if candidate.starts_with(&query) {
+        return Some(SCORE_PREFIX - length_penalty);
+    }
+
+    if let Some(position) = candidate.find(&query) {
+        let position = i64::try_from(position).unwrap_or(i64::MAX);
+        return Some(SCORE_SUBSTRING - position.saturating_add(length_penalty).min(SCORE_TRANSPOSITION - 1));
+    }
+
+    if let Some(score) = subsequence_score(&candidate_chars, &query_chars) {
+        return Some(SCORE_SUBSEQUENCE + score.clamp(-(SCORE_TRANSPOSITION - 1), SCORE_TRANSPOSITION - 1));
+    }
+
+    if candidate_chars.len() >= query_chars.len()
+        && is_one_transposition(&candidate_chars[..query_chars.len()], &query_chars)
+    {
+        return Some(SCORE_TRANSPOSITION - length_penalty);
+    }
+
+    None
+}
+
+/// Rank `candidates` by descending score against `query`, dropping non-matches.
+///
+/// Ties keep their original order.
+pub fn rank<'a, I>(candidates: I, query: &str) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut scored: Vec<(i64, &str)> = candidates
+        .into_iter()
+        .filter_map(|candidate| fuzzy_score(candidate, query).map(|score| (score, candidate)))
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+/// Edit distance between `a` and `b` counting insertions, deletions, substitutions, and swaps of
+/// adjacent characters as one edit each.
+#[must_use]
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Up to `limit` candidates within a few edits of `query`, closest first, then alphabetically.
+///
+/// The allowed distance grows with the query: one edit per three characters, at least one.
+pub fn closest<'a, I>(candidates: I, query: &str, limit: usize) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = (query.chars().count() / 3).max(1);
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != query)
+        .map(|candidate| (edit_distance(candidate, query), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    scored.sort_unstable();
+    scored.dedup();
+    scored.into_iter().take(limit).map(|(_, candidate)| candidate).collect()
+}
+
+fn subsequence_score(candidate: &[char], query: &[char]) -> Option<i64> {
+    let mut score: i64 = 0;
+    let mut query_index = 0;
+    let mut previous_match: Option<usize> = None;
+
+    for (index, character) in candidate.iter().enumerate() {
+        if query_index == query.len() {
+            break;
+        }
+        if *character != query[query_index] {
+            continue;
+        }
+
+        score += 10;
+        if index == 0 {
+            score += 15;
+        } else if matches!(candidate[index - 1], '-' | '_' | '.' | '/' | ' ') {
+            score += 10;
+        }
+        match previous_match {
+            Some(previous) if previous + 1 == index => score += 15,
+            Some(previous) => score -= i64::try_from(index - previous - 1).unwrap_or(i64::MAX).min(10),
+            None => score -= i64::try_from(index).unwrap_or(i64::MAX).min(10),
+        }
+
+        previous_match = Some(index);
+        query_index += 1;
+    }
+
+    (query_index == query.len()).then_some(score)
+}
+
+fn is_one_transposition(a: &[char], b: &[char]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let differences: Vec<usize> = (0..a.len()).filter(|&i| a[i] != b[i]).collect();
+    matches!(differences.as_slice(), [i, j] if *j == *i + 1 && a[*i] == b[*j] && a[*j] == b[*i])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores() {
+        assert_eq!(fuzzy_score("cargo", ""), Some(0));
+        assert_eq!(fuzzy_score("cargo", "car"), Some(SCORE_PREFIX - 2));
+        assert_eq!(fuzzy_score("rustc", "st"), Some(SCORE_SUBSTRING - 2 - 3));
+        assert_eq!(fuzzy_score("git-commit", "gc"), Some(SCORE_SUBSEQUENCE + 25 + 20 - 3));
+        assert_eq!(fuzzy_score("sl", "ls"), Some(SCORE_TRANSPOSITION));
+        assert_eq!(fuzzy_score("cargo", "xyz"), None);
+        assert_eq!(fuzzy_score("", "a"), None);
+
+        // Smart case: a lowercase query ignores case, one with capitals does not.
+        assert_eq!(fuzzy_score("Makefile", "make"), Some(SCORE_PREFIX - 4));
+        assert_eq!(fuzzy_score("makefile", "Make"), None);
+        assert_eq!(fuzzy_score("Makefile", "Make"), Some(SCORE_PREFIX - 4));
+
+        // Each kind of match outranks the next, however long the candidate.
+        let long = "x".repeat(2000);
+        assert!(fuzzy_score(&format!("ab{long}"), "ab") > fuzzy_score("xab", "ab"));
+        assert!(fuzzy_score(&format!("x{long}ab"), "ab") > fuzzy_score("axb", "ab"));
+        assert!(fuzzy_score(&format!("a{long}b"), "ab") > fuzzy_score("ba", "ab"));
+    }
+
+    #[test]
+    fn ranking() {
+        let candidates = ["xgit", "gti", "g-i-t", "git", "gitk", "grep"];
+        assert_eq!(rank(candidates, "git"), ["git", "gitk", "xgit", "g-i-t", "gti"]);
+        assert_eq!(rank(candidates, ""), candidates);
+        assert!(rank(candidates, "zz").is_empty());
+    }
+
+    #[test]
+    fn edit_distances() {
+        let cases = [
+            ("", "", 0),
+            ("", "abc", 3),
+            ("abc", "abc", 0),
+            ("kitten", "sitting", 3),
+            ("ab", "ba", 1),
+            ("gti", "git", 1),
+            ("ca", "abc", 3),
+            ("héllo", "hello", 1),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(edit_distance(a, b), expected, "{a:?} -> {b:?}");
+            assert_eq!(edit_distance(b, a), expected, "{b:?} -> {a:?}");
+        }
+    }
+
+    #[test]
+    fn closest_candidates() {
+        let candidates = ["cat", "cut", "act", "cargo", "chmod", "cd", "cat"];
+        assert_eq!(closest(candidates, "cta", 5), ["cat"]);
+        assert_eq!(closest(candidates, "cat", 5), ["act", "cut"]);
+        assert_eq!(closest(candidates, "cat", 1), ["act"]);
+        assert_eq!(closest(candidates, "carog", 5), ["cargo"]);
+        assert!(closest(candidates, "zzzz", 5).is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+use crate::locale::collate;
+use crate::options::ShellOptions;
+use crate::pattern::has_wildcards;
+use crate::pattern::pattern_matches_with_case;
+use crate::pattern::unescape;
+use std::fs::read_dir;
+use std::fs::DirEntry;
+use std::path::Path;
+
+const GLOBSTAR: &str = "**";
+const HIDDEN_FILE_PREFIX: char = '.';
+const PATH_SEPARATOR: char = '/';
+
+/// The shell options that change how pathname expansion matches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GlobOptions {
+    /// A `**` component matches any number of nested directories (`shopt -s globstar`).
+    pub globstar: bool,
+    /// A pattern that matches nothing expands to no words instead of itself (`shopt -s nullglob`).
+    pub nullglob: bool,
+    /// Wildcards match names starting with `.` too (`shopt -s dotglob`).
+    pub dotglob: bool,
+    /// Letters match regardless of case (`shopt -s nocaseglob`).
+    pub nocaseglob: bool,
+}
+
+impl From<&ShellOptions> for GlobOptions {
+    fn from(options: &ShellOptions) -> Self {
+        Self {
+            globstar: options.globstar,
+            nullglob: options.nullglob,
+            dotglob: options.dotglob,
+            nocaseglob: options.nocaseglob,
+        }
+    }
+}
+
+/// The paths matching `pattern`, in collation order, or none when nothing matches.
+///
+/// Each `/`-separated component with a wildcard is matched against the entries of the
+/// directories matched so far; other components are taken as written, and must exist when they
+/// follow a wildcard. A name starting with `.` only matches a component that starts with a
+/// literal `.`, unless `dotglob` is set, and a trailing `/` only matches directories. With
+/// `nocaseglob`, wildcard components match names regardless of case.
+///
+/// With `globstar`, a `**` component matches the directory so far and every directory below it,
+/// or as the last component every file and directory below it. Symbolic links to directories are
+/// not followed, and directories that cannot be read are skipped. `.` and `..` are never matched
+/// by a wildcard.
+#[must_use]
+pub fn expand_pathname(pattern: &str, options: GlobOptions) -> Vec<String> {
+    let (mut paths, relative) = match pattern.strip_prefix(PATH_SEPARATOR) {
+        Some(relative) => (vec![PATH_SEPARATOR.to_string()], relative),
+        None => (vec![String::new()], pattern),
+    };
+    let mut matched_wildcard = false;
+
+    let mut components = relative.split(PATH_SEPARATOR).peekable();
+    while let Some(component) = components.next() {
+        if options.globstar && component == GLOBSTAR {
+            let last = components.peek().is_none();
+            paths = paths
+                .iter()
+                .flat_map(|directory| {
+                    let mut found = if last { Vec::new() } else { vec![directory.clone()] };
+                    descendants(directory, last, options, &mut found);
+                    found
+                })
+                .collect();
+            matched_wildcard = true;
+        } else if has_wildcards(component) {
+            paths = paths
+                .iter()
+                .flat_map(|directory| matching_entries(directory, component, options))
+                .collect();
+            matched_wildcard = true;
+        } else {
+            let name = unescape(component);
+            for path in &mut paths {
+                if !path.is_empty() && !path.ends_with(PATH_SEPARATOR) {
+                    path.push(PATH_SEPARATOR);
+                }
+                path.push_str(&name);
+            }
+            if matched_wildcard {
+                // A trailing `/` keeps directories only: `file/` does not exist.
+                paths.retain(|path| Path::new(path).symlink_metadata().is_ok());
+            }
+        }
+        if paths.is_empty() {
+            break;
+        }
+    }
+
+    if !matched_wildcard {
+        return Vec::new();
+    }
+    paths.sort_by(|a, b| collate(a, b));
+    paths
+}
+
+/// Add the paths below `directory` (the working directory when empty) to `found`, depth first:
+/// every directory, and with `include_files` every other entry too. Hidden names are skipped
+/// unless `dotglob` is set.
+fn descendants(directory: &str, include_files: bool, options: GlobOptions, found: &mut Vec<String>) {
+    let Ok(entries) = read_dir(if directory.is_empty() { "." } else { directory }) else {
+        return;
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if !options.dotglob && name.starts_with(HIDDEN_FILE_PREFIX) {
+            continue;
+        }
+        let path = join(directory, &name);
+        if is_directory(&entry) {
+            found.push(path.clone());
+            descendants(&path, include_files, options, found);
+        } else if include_files {
+            found.push(path);
+        }
+    }
+}
+
+/// Whether `entry` is a directory itself, not a symbolic link to one.
+fn is_directory(entry: &DirEntry) -> bool {
+    entry.file_type().is_ok_and(|file_type| file_type.is_dir())
+}
+
+/// `name` inside `directory`, or alone when `directory` is empty (the working directory).
+fn join(directory: &str, name: &str) -> String {
+    match directory {
+        "" => name.to_string(),
+        directory if directory.ends_with(PATH_SEPARATOR) => format!("{directory}{name}"),
+        directory => format!("{directory}{PATH_SEPARATOR}{name}"),
+    }
+}
+
+/// The paths of the entries of `directory` (the working directory when empty) whose names match
+/// the single-component `pattern`.
+fn matching_entries(directory: &str, pattern: &str, options: GlobOptions) -> Vec<String> {
+    let Ok(entries) = read_dir(if directory.is_empty() { "." } else { directory }) else {
+        return Vec::new();
+    };
+    let match_hidden = options.dotglob || pattern.starts_with(HIDDEN_FILE_PREFIX);
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| match_hidden || !name.starts_with(HIDDEN_FILE_PREFIX))
+        .filter(|name| pattern_matches_with_case(pattern, name, options.nocaseglob))
+        .map(|name| join(directory, &name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hidden_names() {
+        let directory = std::env::temp_dir().join(format!("ccsh-hidden-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join(".config")).unwrap();
+        for name in ["visible", ".hidden", ".config/file"] {
+            std::fs::write(directory.join(name), "").unwrap();
+        }
+        let d = directory.display();
+        let dotglob = GlobOptions {
+            dotglob: true,
+            ..GlobOptions::default()
+        };
+        let globstar = GlobOptions {
+            globstar: true,
+            ..GlobOptions::default()
+        };
+        let cases = [
+            (format!("{d}/*"), GlobOptions::default(), vec![format!("{d}/visible")]),
+            (format!("{d}/?hidden"), GlobOptions::default(), vec![]),
+            (format!("{d}/[.]hidden"), GlobOptions::default(), vec![]),
+            (
+                format!("{d}/.*"),
+                GlobOptions::default(),
+                vec![format!("{d}/.config"), format!("{d}/.hidden")],
+            ),
+            (
+                format!("{d}/.c*/*"),
+                GlobOptions::default(),
+                vec![format!("{d}/.config/file")],
+            ),
+            (
+                format!("{d}/*"),
+                dotglob,
+                vec![format!("{d}/.config"), format!("{d}/.hidden"), format!("{d}/visible")],
+            ),
+            (format!("{d}/**"), globstar, vec![format!("{d}/visible")]),
+        ];
+        for (pattern, options, expected) in cases {
+            assert_eq!(expand_pathname(&pattern, options), expected, "{pattern:?}");
+        }
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,343 @@
+#[cfg(feature = "readline")]
+use rustyline::history::DefaultHistory;
+#[cfg(feature = "readline")]
+use rustyline::history::History;
+#[cfg(feature = "readline")]
+use rustyline::history::SearchDirection;
+
+/// The command history seen by builtins, whichever line reader keeps it.
+pub trait CommandHistory {
+    /// Number of entries, oldest first.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The entry at `index`, if any.
+    fn entry(&self, index: usize) -> Option<String>;
+
+    /// Append `line` as the newest entry.
+    fn add(&mut self, line: &str);
+
+    /// All entries, oldest first.
+    fn entries(&self) -> Vec<String> {
+        (0..self.len()).filter_map(|index| self.entry(index)).collect()
+    }
+}
+
+#[cfg(feature = "readline")]
+impl CommandHistory for DefaultHistory {
+    fn len(&self) -> usize {
+        History::len(self)
+    }
+
+    fn entry(&self, index: usize) -> Option<String> {
+        self.get(index, SearchDirection::Forward)
+            .ok()
+            .flatten()
+            .map(|entry| entry.entry.into_owned())
+    }
+
+    fn add(&mut self, line: &str) {
+        let _ = History::add(self, line);
+    }
+}
+
+/// Plain in-memory history used without a line editor.
+impl CommandHistory for Vec<String> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn entry(&self, index: usize) -> Option<String> {
+        self.get(index).cloned()
+    }
+
+    fn add(&mut self, line: &str) {
+        self.push(line.to_string());
+    }
+}
+
+/// Marker byte zsh puts before bytes it stores "metafied" (xor 32) in its history file.
+const ZSH_META: u8 = 0x83;
+/// Bytes zsh metafies: NUL and its internal token range, which covers many UTF-8 continuation bytes.
+const ZSH_METAFIED: std::ops::RangeInclusive<u8> = 0x83..=0xa2;
+
+/// History file layouts of other shells, for `history -R` and `history -W`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryFormat {
+    /// One command per line, optionally preceded by `#<epoch>` lines that also delimit
+    /// multi-line commands.
+    Bash,
+    /// zsh extended history: `: <epoch>:<elapsed>;<command>`, with embedded newlines written as
+    /// a trailing `\` and non-ASCII bytes metafied. As in zsh, a command that itself ends in `\`
+    /// reads back joined with the next one.
+    Zsh,
+}
+
+impl HistoryFormat {
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bash" => Some(Self::Bash),
+            "zsh" => Some(Self::Zsh),
+            _ => None,
+        }
+    }
+
+    /// The commands in a history file of this format, oldest first.
+    #[must_use]
+    pub fn import(self, content: &[u8]) -> Vec<String> {
+        match self {
+            Self::Bash => import_bash(&String::from_utf8_lossy(content)),
+            Self::Zsh => import_zsh(&String::from_utf8_lossy(&unmetafy(content))),
+        }
+    }
+
+    /// `entries` as a history file of this format, stamped with `timestamp` since this shell
+    /// does not record when commands ran.
+    #[must_use]
+    pub fn export(self, entries: &[String], timestamp: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        for entry in entries {
+            match self {
+                Self::Bash => {
+                    out.extend_from_slice(format!("#{timestamp}\n{entry}\n").as_bytes());
+                }
+                Self::Zsh => {
+                    let command = entry.replace('\n', "\\\n");
+                    out.extend_from_slice(format!(": {timestamp}:0;").as_bytes());
+                    out.extend_from_slice(&metafy(command.as_bytes()));
+                    out.push(b'\n');
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Split bash history into entries: each line on its own, or, when `#<epoch>` lines are present,
+/// everything between two of them as one multi-line entry.
+fn import_bash(content: &str) -> Vec<String> {
+    let is_timestamp = |line: &str| {
+        line.strip_prefix('#')
+            .is_some_and(|digits| !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()))
+    };
+    if !content.lines().any(is_timestamp) {
+        return content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect();
+    }
+
+    let mut entries: Vec<String> = Vec::new();
+    let mut current: Option<String> = None;
+    for line in content.lines() {
+        if is_timestamp(line) {
+            entries.extend(current.take());
+            continue;
+        }
+        match &mut current {
+            Some(entry) => {
+                entry.push('\n');
+                entry.push_str(line);
+            }
+            None => current = Some(line.to_string()),
+        }
+    }
+    entries.extend(current);
+    entries.retain(|entry| !entry.is_empty());
+    entries
+}
+
+/// Split zsh history into entries, dropping the `: <epoch>:<elapsed>;` prefix where present and
+/// joining lines that end in `\` with the next one.
+fn import_zsh(content: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut current = String::new();
+    for line in content.lines() {
+        let line = if current.is_empty() {
+            strip_zsh_prefix(line)
+        } else {
+            line
+        };
+        match line.strip_suffix('\\') {
+            Some(continued) => {
+                current.push_str(continued);
+                current.push('\n');
+            }
+            None => {
+                current.push_str(line);
+                entries.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        entries.push(current);
+    }
+    entries.retain(|entry| !entry.is_empty());
+    entries
+}
+
+fn strip_zsh_prefix(line: &str) -> &str {
+    let Some(rest) = line.strip_prefix(": ") else {
+        return line;
+    };
+    let Some((stamp, command)) = rest.split_once(';') else {
+        return line;
+    };
+    let is_stamp = stamp.split_once(':').is_some_and(|(start, elapsed)| {
+        [start, elapsed]
+            .iter()
+            .all(|part| part.bytes().all(|byte| byte.is_ascii_digit()))
+    });
+    if is_stamp {
+        command
+    } else {
+        line
+    }
+}
+
+fn metafy(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    for &byte in bytes {
+        if byte == 0 || ZSH_METAFIED.contains(&byte) {
+            out.push(ZSH_META);
+            out.push(byte ^ 32);
+        } else {
+            out.push(byte);
+        }
+    }
+    out
+}
+
+fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut bytes = bytes.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte == ZSH_META {
+            if let Some(&next) = bytes.next() {
+                out.push(next ^ 32);
+            }
+        } else {
+            out.push(byte);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn format_names() {
+        assert_eq!(HistoryFormat::from_name("bash"), Some(HistoryFormat::Bash));
+        assert_eq!(HistoryFormat::from_name("zsh"), Some(HistoryFormat::Zsh));
+        assert_eq!(HistoryFormat::from_name("fish"), None);
+    }
+
+    #[test]
+    fn bash_import() {
+        let cases: [(&str, &[&str]); 6] = [
+            ("", &[]),
+            ("ls\n\ncd /tmp\n", &["ls", "cd /tmp"]),
+            ("# comment\nls\n", &["# comment", "ls"]),
+            ("#1700000000\nls\n#1700000001\ncd /tmp\n", &["ls", "cd /tmp"]),
+            (
+                "#1700000000\nfor x in a b\ndo echo $x\ndone\n#1700000001\npwd",
+                &["for x in a b\ndo echo $x\ndone", "pwd"],
+            ),
+            ("before\n#1700000000\n#1700000001\nafter\n", &["before", "after"]),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(
+                HistoryFormat::Bash.import(content.as_bytes()),
+                entries(expected),
+                "{content:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn zsh_import() {
+        let cases: [(&str, &[&str]); 6] = [
+            ("", &[]),
+            (": 1700000000:0;ls\n: 1700000001:12;cd /tmp\n", &["ls", "cd /tmp"]),
+            ("ls\ncd /tmp\n", &["ls", "cd /tmp"]),
+            (": 1700000000:0;echo a\\\necho b\n", &["echo a\necho b"]),
+            (
+                ": 1700000000:0;echo a\\\n: 1700000001:0;kept\n",
+                &["echo a\n: 1700000001:0;kept"],
+            ),
+            (": not a stamp;ls\n: 1:x;ls\n", &[": not a stamp;ls", ": 1:x;ls"]),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(
+                HistoryFormat::Zsh.import(content.as_bytes()),
+                entries(expected),
+                "{content:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn zsh_metafied_bytes() {
+        assert_eq!(metafy(b"plain"), b"plain");
+        assert_eq!(
+            metafy(&[0, 0x82, 0x83, 0xa2, 0xa3]),
+            [ZSH_META, 0x20, 0x82, ZSH_META, 0xa3, ZSH_META, 0x82, 0xa3]
+        );
+        assert_eq!(unmetafy(&metafy(&[0, 0x83, 0x94, 0xff])), [0, 0x83, 0x94, 0xff]);
+        assert_eq!(unmetafy(&[b'a', ZSH_META]), b"a");
+
+        // "à" is 0xc3 0xa0, whose second byte zsh metafies.
+        let content = [b": 1700000000:0;echo ".as_slice(), &[0xc3, ZSH_META, 0xa0 ^ 32], b"\n"].concat();
+        assert_eq!(HistoryFormat::Zsh.import(&content), ["echo à"]);
+    }
+
+    #[test]
+    fn export() {
+        let history = entries(&["ls", "echo à\necho —"]);
+        assert_eq!(
+            String::from_utf8_lossy(&HistoryFormat::Bash.export(&history, 1_700_000_000)),
+            "#1700000000\nls\n#1700000000\necho à\necho —\n"
+        );
+        assert_eq!(
+            HistoryFormat::Zsh.export(&history, 1_700_000_000),
+            [
+                b": 1700000000:0;ls\n: 1700000000:0;echo ".as_slice(),
+                &[0xc3, ZSH_META, 0x80],
+                b"\\\necho ",
+                &[0xe2, 0x80, ZSH_META, 0xb4],
+                b"\n",
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let history = entries(&[
+            "ls -l",
+            "if true\nthen echo yes\nfi",
+            "echo 'à — ü' \\\\ x",
+            "echo $'\\0'",
+        ]);
+        for format in [HistoryFormat::Bash, HistoryFormat::Zsh] {
+            assert_eq!(format.import(&format.export(&history, 0)), history, "{format:?}");
+        }
+
+        let trailing = entries(&["echo \\", "ls"]);
+        let bash = HistoryFormat::Bash;
+        assert_eq!(bash.import(&bash.export(&trailing, 0)), trailing);
+        let zsh = HistoryFormat::Zsh;
+        assert_eq!(zsh.import(&zsh.export(&trailing, 0)), ["echo \n: 0:0;ls"]);
+    }
+}
@@ -0,0 +1,334 @@
+use crate::history::CommandHistory;
+use crate::lexer::lex;
+use crate::parser::CHAR_BACKSLASH;
+use crate::parser::CHAR_DOLLAR_SIGN;
+use crate::parser::CHAR_DOUBLE_QUOTE;
+use crate::parser::CHAR_EXCLAMATION_MARK;
+use crate::parser::CHAR_SINGLE_QUOTE;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+const WORD_DESIGNATOR_ARGUMENTS: char = '*';
+const WORD_DESIGNATOR_FIRST_ARGUMENT: char = '^';
+const WORD_DESIGNATOR_LAST_ARGUMENT: char = '$';
+const WORD_DESIGNATOR_SEPARATOR: char = ':';
+const EVENT_RELATIVE: char = '-';
+/// Characters that end a `!prefix` event, besides whitespace.
+const EVENT_PREFIX_DELIMITERS: &[char] = &[';', '&', '|', '(', ')', '<', '>', '"', '\'', '\\', '`', ':'];
+/// Characters after a `!` that keep it literal, besides whitespace, as in bash's `!=` and `!(...)`.
+const LITERAL_AFTER_EXCLAMATION_MARK: &[char] = &['=', '('];
+
+/// Which words of an event a history expansion selects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WordDesignator {
+    /// `^`: the first argument.
+    FirstArgument,
+    /// `$`: the last word.
+    LastArgument,
+    /// `*`: every argument, or nothing when there are none.
+    Arguments,
+    /// `N`: word `N`, the command name being word 0.
+    Word(usize),
+}
+
+impl WordDesignator {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            WORD_DESIGNATOR_FIRST_ARGUMENT => Some(Self::FirstArgument),
+            WORD_DESIGNATOR_LAST_ARGUMENT => Some(Self::LastArgument),
+            WORD_DESIGNATOR_ARGUMENTS => Some(Self::Arguments),
+            _ => None,
+        }
+    }
+
+    /// The selected words of `words`, joined by spaces, or `None` when they do not exist.
+    fn select(self, words: &[&str]) -> Option<String> {
+        match self {
+            Self::FirstArgument => words.get(1).map(ToString::to_string),
+            Self::LastArgument => words.last().map(ToString::to_string),
+            Self::Arguments => Some(words.get(1..).unwrap_or_default().join(" ")),
+            Self::Word(index) => words.get(index).map(ToString::to_string),
+        }
+    }
+}
+
+/// The history entry a `!` reference names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Event {
+    /// `!!`, or the event of a bare `!$`, `!^`, `!*`: the previous command.
+    Previous,
+    /// `!N`: entry `N`, as `history` numbers it.
+    Number(usize),
+    /// `!-N`: the command `N` entries back.
+    Relative(usize),
+    /// `!prefix`: the most recent command starting with `prefix`.
+    Prefix(String),
+}
+
+impl Event {
+    /// The text of the entry this event names in `history`.
+    fn find(&self, history: &dyn CommandHistory) -> Option<String> {
+        match self {
+            Self::Previous => history.len().checked_sub(1).and_then(|index| history.entry(index)),
+            Self::Number(number) => number.checked_sub(1).and_then(|index| history.entry(index)),
+            Self::Relative(count) => history.len().checked_sub(*count).and_then(|index| history.entry(index)),
+            Self::Prefix(prefix) => (0..history.len())
+                .rev()
+                .filter_map(|index| history.entry(index))
+                .find(|entry| entry.starts_with(prefix.as_str())),
+        }
+    }
+}
+
+/// A history expansion that could not be performed; the line is not run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryExpansionError {
+    /// The expansion as written, such as `!$`.
+    pub expansion: String,
+    pub message: &'static str,
+}
+
+impl fmt::Display for HistoryExpansionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.expansion, self.message)
+    }
+}
+
+/// Expand csh-style history references in `line` against `history`, newest entry last.
+///
+/// An event is `!!` (the previous command), `!N` (entry `N`), `!-N` (`N` entries back), or
+/// `!prefix` (the latest command starting with `prefix`), and stands for the whole command. A word
+/// designator after a `:` selects words of it instead: `:^` the first argument, `:$` the last
+/// word, `:*` all arguments, `:N` word `N`. `!$`, `!^`, and `!*` are short for `!!:$`, `!!:^`, and
+/// `!!:*`. Words keep their quoting, as the lexer splits them. A `!` inside single quotes, after a
+/// backslash or `$`, or followed by a blank, `=`, `(`, or the end of the line, is kept as written.
+/// Returns `None` when the line has no history reference.
+pub fn expand_history(line: &str, history: &dyn CommandHistory) -> Result<Option<String>, HistoryExpansionError> {
+    let mut expanded = String::with_capacity(line.len());
+    let mut changed = false;
+    let mut in_single_quotes = false;
+    let mut in_double_quotes = false;
+    let mut escaped = false;
+    let mut characters = line.chars().peekable();
+
+    while let Some(c) = characters.next() {
+        if escaped {
+            escaped = false;
+            expanded.push(c);
+            continue;
+        }
+        match c {
+            CHAR_BACKSLASH if !in_single_quotes => escaped = true,
+            CHAR_SINGLE_QUOTE if !in_double_quotes => in_single_quotes = !in_single_quotes,
+            CHAR_DOUBLE_QUOTE if !in_single_quotes => in_double_quotes = !in_double_quotes,
+            CHAR_EXCLAMATION_MARK if !in_single_quotes && !expanded.ends_with(CHAR_DOLLAR_SIGN) => {
+                if let Some((event, designator, written)) = take_reference(&mut characters) {
+                    let expansion = format!("{c}{written}");
+                    expanded.push_str(&expand_reference(&event, designator, history, expansion)?);
+                    changed = true;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        expanded.push(c);
+    }
+
+    Ok(changed.then_some(expanded))
+}
+
+/// Consume the history reference after a `!`: its event, word designator, and the text they were
+/// written as. Returns `None`, consuming nothing, when the `!` does not start a reference.
+fn take_reference(characters: &mut Peekable<Chars>) -> Option<(Event, Option<WordDesignator>, String)> {
+    let mut written = String::new();
+    let next = *characters.peek()?;
+    let event = if next == CHAR_EXCLAMATION_MARK {
+        written.extend(characters.next());
+        Event::Previous
+    } else if let Some(designator) = WordDesignator::from_char(next) {
+        written.extend(characters.next());
+        return Some((Event::Previous, Some(designator), written));
+    } else if next.is_ascii_digit() {
+        Event::Number(take_number(characters, &mut written))
+    } else if next == EVENT_RELATIVE && characters.clone().nth(1).is_some_and(|c| c.is_ascii_digit()) {
+        written.extend(characters.next());
+        Event::Relative(take_number(characters, &mut written))
+    } else if next.is_whitespace()
+        || LITERAL_AFTER_EXCLAMATION_MARK.contains(&next)
+        || EVENT_PREFIX_DELIMITERS.contains(&next)
+    {
+        return None;
+    } else {
+        while let Some(c) = characters.next_if(|&c| !c.is_whitespace() && !EVENT_PREFIX_DELIMITERS.contains(&c)) {
+            written.push(c);
+        }
+        Event::Prefix(written.clone())
+    };
+    let designator = take_word_designator(characters, &mut written);
+    Some((event, designator, written))
+}
+
+/// Consume a `:` word designator, if one follows an event.
+fn take_word_designator(characters: &mut Peekable<Chars>, written: &mut String) -> Option<WordDesignator> {
+    let mut lookahead = characters.clone();
+    if lookahead.next() != Some(WORD_DESIGNATOR_SEPARATOR) {
+        return None;
+    }
+    let next = lookahead.next()?;
+    let designator = WordDesignator::from_char(next);
+    if designator.is_none() && !next.is_ascii_digit() {
+        return None;
+    }
+    written.extend(characters.next());
+    match designator {
+        Some(designator) => {
+            written.extend(characters.next());
+            Some(designator)
+        }
+        None => Some(WordDesignator::Word(take_number(characters, written))),
+    }
+}
+
+/// Consume a run of digits, appending them to `written`.
+fn take_number(characters: &mut Peekable<Chars>, written: &mut String) -> usize {
+    let mut number: usize = 0;
+    while let Some(digit) = characters.next_if(char::is_ascii_digit) {
+        written.push(digit);
+        number = number
+            .saturating_mul(10)
+            .saturating_add(digit.to_digit(10).unwrap_or_default() as usize);
+    }
+    number
+}
+
+/// The text `event` names, or the words `designator` selects from it.
+fn expand_reference(
+    event: &Event,
+    designator: Option<WordDesignator>,
+    history: &dyn CommandHistory,
+    expansion: String,
+) -> Result<String, HistoryExpansionError> {
+    let Some(entry) = event.find(history) else {
+        return Err(HistoryExpansionError {
+            expansion,
+            message: "event not found",
+        });
+    };
+    let Some(designator) = designator else {
+        return Ok(entry);
+    };
+    let lexed = lex(&entry);
+    let words: Vec<&str> = lexed.tokens.iter().map(|token| &entry[token.span.clone()]).collect();
+    designator.select(&words).ok_or(HistoryExpansionError {
+        expansion,
+        message: "bad word specifier",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(line: &str, history: &[&str]) -> Result<Option<String>, HistoryExpansionError> {
+        let history: Vec<String> = history.iter().map(ToString::to_string).collect();
+        expand_history(line, &history)
+    }
+
+    #[test]
+    fn word_designators() {
+        let history = ["echo first", "cp 'a file' \"b c\" dest"];
+        let cases = [
+            ("echo !$", "echo dest"),
+            ("echo !^", "echo 'a file'"),
+            ("echo !*", "echo 'a file' \"b c\" dest"),
+            ("echo !$!^", "echo dest'a file'"),
+            ("echo \"!$\"", "echo \"dest\""),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(expand(line, &history), Ok(Some(expected.to_string())), "{line:?}");
+        }
+
+        assert_eq!(expand("echo !*", &["ls"]), Ok(Some("echo ".to_string())));
+        assert_eq!(expand("echo !$", &["ls"]), Ok(Some("echo ls".to_string())));
+    }
+
+    #[test]
+    fn literal_exclamation_marks() {
+        let history = ["echo previous"];
+        for line in [
+            "echo hi",
+            "echo '!$'",
+            "echo \\!$",
+            "echo $!",
+            "echo ! x",
+            "echo hi!",
+            "[ a != b ]",
+            "echo !(x)",
+            "echo !;",
+        ] {
+            assert_eq!(expand(line, &history), Ok(None), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn word_designator_errors() {
+        let cases = [
+            ("echo !$", &[][..], "!$: event not found"),
+            ("echo !^", &["ls"][..], "!^: bad word specifier"),
+        ];
+        for (line, history, expected) in cases {
+            let error = expand(line, history).unwrap_err();
+            assert_eq!(error.to_string(), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn events() {
+        let history = ["git status", "ls -la /tmp", "git commit -m 'x y'", "echo done"];
+        let cases = [
+            ("!!", "echo done"),
+            ("sudo !!", "sudo echo done"),
+            ("!1", "git status"),
+            ("!3 --amend", "git commit -m 'x y' --amend"),
+            ("!-1", "echo done"),
+            ("!-4", "git status"),
+            ("!git", "git commit -m 'x y'"),
+            ("!l;!e", "ls -la /tmp;echo done"),
+            ("!ls:2", "/tmp"),
+            ("!!:0 again", "echo again"),
+            ("!3:3", "'x y'"),
+            ("!-3:$", "/tmp"),
+            ("!git:^", "commit"),
+            ("!1:*", "status"),
+            ("!1:x", "git status:x"),
+            ("!1:", "git status:"),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(expand(line, &history), Ok(Some(expected.to_string())), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn event_errors() {
+        let history = ["ls -l"];
+        let cases = [
+            ("!!", &[][..], "!!: event not found"),
+            ("!0", &history[..], "!0: event not found"),
+            ("!2", &history[..], "!2: event not found"),
+            ("!-2", &history[..], "!-2: event not found"),
+            ("!-0", &history[..], "!-0: event not found"),
+            ("!cd", &history[..], "!cd: event not found"),
+            ("!ls:2", &history[..], "!ls:2: bad word specifier"),
+            (
+                "!1:99999999999999999999999",
+                &history[..],
+                "!1:99999999999999999999999: bad word specifier",
+            ),
+        ];
+        for (line, history, expected) in cases {
+            let error = expand(line, history).unwrap_err();
+            assert_eq!(error.to_string(), expected, "{line:?}");
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use crate::fuzzy::rank;
+use rustyline::Cmd;
+use rustyline::ConditionalEventHandler;
+use rustyline::Event;
+use rustyline::EventContext;
+use rustyline::Movement;
+use rustyline::RepeatCount;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::mem::ManuallyDrop;
+use std::os::fd::FromRawFd;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+const ANSI_CLEAR_BELOW: &str = "\x1b[J";
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_REVERSE: &str = "\x1b[7m";
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
+const KEY_BACKSPACE: u8 = 0x7f;
+const KEY_CTRL_C: u8 = 0x03;
+const KEY_CTRL_G: u8 = 0x07;
+const KEY_CTRL_H: u8 = 0x08;
+const KEY_CTRL_N: u8 = 0x0e;
+const KEY_CTRL_P: u8 = 0x10;
+const KEY_CTRL_R: u8 = 0x12;
+const KEY_CTRL_U: u8 = 0x15;
+const KEY_ENTER: u8 = b'\r';
+const KEY_ESCAPE: u8 = 0x1b;
+const KEY_LINE_FEED: u8 = b'\n';
+const MAX_VISIBLE_MATCHES: usize = 10;
+const SEQUENCE_DOWN: &[u8] = b"\x1b[B";
+const SEQUENCE_UP: &[u8] = b"\x1b[A";
+
+/// History entries visible to the search overlay, newest last.
+///
+/// The REPL refreshes it before each prompt since key handlers cannot reach the editor's history.
+pub type HistorySnapshot = Arc<Mutex<Vec<String>>>;
+
+/// Ctrl-R handler: an fzf-style overlay below the prompt that filters history as you type.
+///
+/// Up/Down (or Ctrl-P/Ctrl-N, Ctrl-R) move the selection, Enter places the entry on the line,
+/// Esc/Ctrl-G/Ctrl-C cancel.
+pub struct FuzzyHistorySearch {
+    pub history: HistorySnapshot,
+}
+
+enum Outcome {
+    Selected(String),
+    Cancelled,
+}
+
+impl ConditionalEventHandler for FuzzyHistorySearch {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        let entries = self.history.lock().ok()?.clone();
+        match run_overlay(&entries, ctx.line()) {
+            Outcome::Selected(entry) => Some(Cmd::Replace(Movement::WholeBuffer, Some(entry))),
+            Outcome::Cancelled => Some(Cmd::Repaint),
+        }
+    }
+}
+
+/// Newest-first, de-duplicated entries ranked against `query`.
+fn matching_entries<'a>(entries: &'a [String], query: &str) -> Vec<&'a str> {
+    let mut seen = std::collections::HashSet::new();
+    let newest_first = entries
+        .iter()
+        .rev()
+        .map(String::as_str)
+        .filter(|entry| seen.insert(*entry));
+    rank(newest_first, query)
+}
+
+fn run_overlay(entries: &[String], initial_query: &str) -> Outcome {
+    // The terminal is already in raw mode while rustyline dispatches key handlers.
+    let mut stdin = ManuallyDrop::new(unsafe { File::from_raw_fd(libc::STDIN_FILENO) });
+    let mut stdout = std::io::stdout();
+    let mut query = initial_query.to_string();
+    let mut selected = 0;
+
+    let outcome = loop {
+        let matches = matching_entries(entries, &query);
+        selected = selected.min(matches.len().saturating_sub(1));
+        draw(&mut stdout, &query, &matches, selected);
+
+        let mut buffer = [0u8; 32];
+        let Ok(count) = stdin.read(&mut buffer) else {
+            break Outcome::Cancelled;
+        };
+        let input = &buffer[..count];
+
+        match input {
+            [] | [KEY_ESCAPE] | [KEY_CTRL_C | KEY_CTRL_G, ..] => break Outcome::Cancelled,
+            [KEY_ENTER | KEY_LINE_FEED, ..] => {
+                break matches
+                    .get(selected)
+                    .map_or(Outcome::Cancelled, |entry| Outcome::Selected((*entry).to_string()));
+            }
+            SEQUENCE_UP | [KEY_CTRL_P | KEY_CTRL_R] => selected = selected.saturating_sub(1),
+            SEQUENCE_DOWN | [KEY_CTRL_N] => selected += 1,
+            [KEY_BACKSPACE | KEY_CTRL_H] => {
+                query.pop();
+                selected = 0;
+            }
+            [KEY_CTRL_U] => {
+                query.clear();
+                selected = 0;
+            }
+            [KEY_ESCAPE, ..] => {}
+            _ => {
+                if let Ok(text) = std::str::from_utf8(input) {
+                    query.extend(text.chars().filter(|character| !character.is_control()));
+                    selected = 0;
+                }
+            }
+        }
+    };
+
+    let _ = write!(stdout, "\r\n{ANSI_CLEAR_BELOW}\x1b[1A\r");
+    let _ = stdout.flush();
+    outcome
+}
+
+/// Draw the query line and matches below the prompt, then return the cursor to the prompt row.
+fn draw(stdout: &mut impl Write, query: &str, matches: &[&str], selected: usize) {
+    let width = terminal_width();
+    let visible = &matches[..matches.len().min(MAX_VISIBLE_MATCHES)];
+
+    let mut frame = format!("\r\n{ANSI_CLEAR_BELOW}");
+    frame.push_str(&truncate(
+        &format!("search> {query}  ({}/{})", visible.len(), matches.len()),
+        width,
+    ));
+    for (index, entry) in visible.iter().enumerate() {
+        let line = truncate(&format!("  {entry}"), width);
+        if index == selected {
+            frame.push_str(&format!("\r\n{ANSI_REVERSE}{line}{ANSI_RESET}"));
+        } else {
+            frame.push_str(&format!("\r\n{line}"));
+        }
+    }
+    frame.push_str(&format!("\x1b[{}A\r", visible.len() + 1));
+
+    let _ = stdout.write_all(frame.as_bytes());
+    let _ = stdout.flush();
+}
+
+fn truncate(line: &str, width: usize) -> String {
+    line.chars()
+        .map(|character| if character.is_control() { ' ' } else { character })
+        .take(width.saturating_sub(1))
+        .collect()
+}
+
+fn terminal_width() -> usize {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if result == 0 && size.ws_col > 0 {
+        usize::from(size.ws_col)
+    } else {
+        DEFAULT_TERMINAL_WIDTH
+    }
+}
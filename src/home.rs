@@ -0,0 +1,86 @@
+use crate::parser::ENVIRONMENT_VARIABLE_HOME;
+use crate::parser::ENVIRONMENT_VARIABLE_OLDPWD;
+use crate::parser::ENVIRONMENT_VARIABLE_PWD;
+use std::env::current_dir;
+use std::env::var_os;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+/// Buffer size for `getpwuid_r` when `sysconf` gives no hint.
+const PASSWD_BUFFER_SIZE: usize = 1024;
+/// Largest buffer tried before giving up on an oversized passwd entry.
+const MAX_PASSWD_BUFFER_SIZE: usize = 1 << 20;
+
+/// The user's home directory: `$HOME` when set and non-empty, otherwise the home field of the
+/// current user's passwd entry. `None` when neither is available.
+#[must_use]
+pub fn home_directory() -> Option<PathBuf> {
+    var_os(ENVIRONMENT_VARIABLE_HOME)
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .or_else(passwd_home_directory)
+}
+
+/// The directory a tilde prefix names: `~` the home directory, `~+` the working directory
+/// (`$PWD`), `~-` the previous one (`$OLDPWD`), and `~name` the home directory of user `name`.
+/// `prefix` is what follows the `~`; `None` when it names nothing known.
+#[must_use]
+pub fn expand_tilde(prefix: &str) -> Option<PathBuf> {
+    match prefix {
+        "" => home_directory(),
+        "+" => var_os(ENVIRONMENT_VARIABLE_PWD)
+            .filter(|pwd| !pwd.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| current_dir().ok()),
+        "-" => var_os(ENVIRONMENT_VARIABLE_OLDPWD)
+            .filter(|oldpwd| !oldpwd.is_empty())
+            .map(PathBuf::from),
+        name => {
+            let name = CString::new(name).ok()?;
+            // SAFETY: every pointer comes from `passwd_entry_home`, valid for the call.
+            passwd_entry_home(|entry, buffer, size, result| unsafe {
+                libc::getpwnam_r(name.as_ptr(), entry, buffer, size, result)
+            })
+        }
+    }
+}
+
+/// The home directory recorded for the real user id in the passwd database.
+fn passwd_home_directory() -> Option<PathBuf> {
+    // SAFETY: every pointer comes from `passwd_entry_home`, valid for the call.
+    passwd_entry_home(|entry, buffer, size, result| unsafe {
+        libc::getpwuid_r(libc::getuid(), entry, buffer, size, result)
+    })
+}
+
+/// The home field of the passwd entry that `lookup` (`getpwuid_r` or `getpwnam_r` with their
+/// key applied) finds, growing the buffer while it reports `ERANGE`.
+fn passwd_entry_home(
+    lookup: impl Fn(*mut libc::passwd, *mut libc::c_char, usize, *mut *mut libc::passwd) -> libc::c_int,
+) -> Option<PathBuf> {
+    let hint = unsafe { libc::sysconf(libc::_SC_GETPW_R_SIZE_MAX) };
+    let mut size = usize::try_from(hint)
+        .ok()
+        .filter(|&size| size > 0)
+        .unwrap_or(PASSWD_BUFFER_SIZE);
+
+    loop {
+        let mut buffer = vec![0 as libc::c_char; size];
+        let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::passwd = std::ptr::null_mut();
+        let error = lookup(&mut entry, buffer.as_mut_ptr(), size, &mut result);
+        if error == libc::ERANGE && size < MAX_PASSWD_BUFFER_SIZE {
+            size *= 2;
+            continue;
+        }
+        if error != 0 || result.is_null() || entry.pw_dir.is_null() {
+            return None;
+        }
+        // `pw_dir` points into `buffer`, which is still alive here.
+        let home = unsafe { CStr::from_ptr(entry.pw_dir) };
+        return Some(PathBuf::from(OsStr::from_bytes(home.to_bytes()))).filter(|home| !home.as_os_str().is_empty());
+    }
+}
@@ -1,51 +1,100 @@
+use crate::subshell::Process;
+use std::io;
 use std::io::Write;
-use std::process::Child;
 
 pub struct BackgroundJob {
     pub id: usize,
-    #[allow(dead_code)]
+    /// The pid of the last process of the pipeline, as bash reports it.
+    pub pid: u32,
+    pub command: String,
+    /// Every process of the pipeline, in order.
+    pub children: Vec<Process>,
+    /// Stopped by a signal, as last checked, until `bg` resumes it.
+    pub stopped: bool,
+}
+
+impl BackgroundJob {
+    /// Whether every process of the job has exited.
+    fn is_done(&mut self) -> bool {
+        self.children
+            .iter_mut()
+            .all(|child| matches!(child.try_wait(), Ok(Some(_))))
+    }
+
+    /// Record whether any process of the job is stopped.
+    fn update_stopped(&mut self) {
+        self.stopped = self.children.iter().any(Process::is_stopped);
+    }
+}
+
+/// Why [`JobManager::resume`] did not resume a job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResumeError {
+    /// No job matches the job spec.
+    NoSuchJob,
+    /// The job with this id is not stopped.
+    AlreadyRunning(usize),
+}
+
+/// Id, pid, and command line of a running job, as offered by `kill` completion.
+#[derive(Clone, Debug)]
+pub struct JobSummary {
+    pub id: usize,
     pub pid: u32,
     pub command: String,
-    pub child: Child,
 }
 
 pub struct JobManager {
     jobs: Vec<BackgroundJob>,
 }
 
+impl Default for JobManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl JobManager {
     #[must_use]
     pub fn new() -> Self {
         Self { jobs: Vec::new() }
     }
 
-    #[allow(clippy::maybe_infinite_iter)]
+    /// The lowest id not in use; with `n` jobs one of `1..=n + 1` is always free.
     fn next_id(&self) -> usize {
-        (1..).find(|n| !self.jobs.iter().any(|j| j.id == *n)).unwrap()
+        let last = self.jobs.len() + 1;
+        (1..last)
+            .find(|n| !self.jobs.iter().any(|j| j.id == *n))
+            .unwrap_or(last)
     }
 
-    /// Add a background job. Prints `[id] pid` to stdout.
-    pub fn add(&mut self, child: Child, command: String) {
+    /// Add a background job running the pipeline of `children`. Prints `[id] pid` to stdout when
+    /// `notify` is set and returns the pid.
+    pub fn add(&mut self, children: Vec<Process>, command: String, notify: bool) -> u32 {
         let id = self.next_id();
-        let pid = child.id();
-        println!("[{id}] {pid}");
+        let pid = children.last().map_or(0, Process::id);
+        if notify {
+            let _ = writeln!(io::stdout(), "[{id}] {pid}");
+        }
         self.jobs.push(BackgroundJob {
             id,
             pid,
             command,
-            child,
+            children,
+            stopped: false,
         });
+        pid
     }
 
-    /// Check all jobs; print "Done" for finished ones and remove them.
-    pub fn reap(&mut self) {
+    /// Check all jobs; print "Done" for finished ones when `notify` is set and remove them.
+    pub fn reap(&mut self, notify: bool) {
         let len = self.jobs.len();
         let done_indices: Vec<usize> = self
             .jobs
             .iter_mut()
             .enumerate()
             .filter_map(|(i, job)| {
-                if matches!(job.child.try_wait(), Ok(Some(_))) {
+                if job.is_done() {
                     let marker = if i + 1 == len {
                         '+'
                     } else if i + 2 == len {
@@ -53,7 +102,9 @@ impl JobManager {
                     } else {
                         ' '
                     };
-                    println!("[{}]{}  {:<24}{}", job.id, marker, "Done", job.command);
+                    if notify {
+                        let _ = writeln!(io::stdout(), "[{}]{}  {:<24}{}", job.id, marker, "Done", job.command);
+                    }
                     Some(i)
                 } else {
                     None
@@ -65,13 +116,20 @@ impl JobManager {
         }
     }
 
-    /// Print all jobs (Running/Done) to `out`. Reaps Done jobs after listing.
+    /// Print all jobs (Running/Stopped/Done) to `out`. Reaps Done jobs after listing.
     pub fn list_jobs(&mut self, out: &mut dyn Write) {
         let len = self.jobs.len();
         let mut done_indices = Vec::new();
         for (i, job) in self.jobs.iter_mut().enumerate() {
-            let is_done = matches!(job.child.try_wait(), Ok(Some(_)));
-            let status = if is_done { "Done" } else { "Running" };
+            let is_done = job.is_done();
+            job.update_stopped();
+            let status = if is_done {
+                "Done"
+            } else if job.stopped {
+                "Stopped"
+            } else {
+                "Running"
+            };
             let marker = if i + 1 == len {
                 '+'
             } else if i + 2 == len {
@@ -80,8 +138,10 @@ impl JobManager {
                 ' '
             };
             if is_done {
-                let _ = writeln!(out, "[{}]{}  {:<24}{}", job.id, marker, status, job.command);
                 done_indices.push(i);
+            }
+            if is_done || job.stopped {
+                let _ = writeln!(out, "[{}]{}  {:<24}{}", job.id, marker, status, job.command);
             } else {
                 let _ = writeln!(out, "[{}]{}  {:<24}{} &", job.id, marker, status, job.command);
             }
@@ -91,10 +151,73 @@ impl JobManager {
         }
     }
 
-    /// Wait for all remaining background jobs (called at REPL exit).
-    pub fn wait_all(&mut self) {
+    /// Id, pid, and command of every tracked job, oldest first.
+    #[must_use]
+    pub fn summaries(&self) -> Vec<JobSummary> {
+        self.jobs
+            .iter()
+            .map(|job| JobSummary {
+                id: job.id,
+                pid: job.pid,
+                command: job.command.clone(),
+            })
+            .collect()
+    }
+
+    /// Resolve a job spec (`%N`, `%%`, `%+`, or `%-`) to the job's pid.
+    #[must_use]
+    pub fn pid_of(&self, spec: &str) -> Option<u32> {
+        self.position(spec).map(|index| self.jobs[index].pid)
+    }
+
+    /// The index of the job a job spec (`%N`, `%%`, `%+`, or `%-`) names.
+    fn position(&self, spec: &str) -> Option<usize> {
+        match spec.strip_prefix('%')? {
+            "" | "%" | "+" => self.jobs.len().checked_sub(1),
+            "-" => self.jobs.len().checked_sub(2),
+            id => {
+                let id: usize = id.parse().ok()?;
+                self.jobs.iter().position(|job| job.id == id)
+            }
+        }
+    }
+
+    /// Continue the stopped job `spec` names in the background with `SIGCONT`, or the most recently
+    /// started stopped job without one, and mark it running. Prints `[id]+ command &` to `out` and
+    /// returns the job's id.
+    pub fn resume(&mut self, spec: Option<&str>, out: &mut dyn Write) -> Result<usize, ResumeError> {
         for job in &mut self.jobs {
-            let _ = job.child.wait();
+            job.update_stopped();
+        }
+        let index = match spec {
+            Some(spec) => self.position(spec),
+            None => self
+                .jobs
+                .iter()
+                .rposition(|job| job.stopped)
+                .or_else(|| self.jobs.len().checked_sub(1)),
+        };
+        let job = index.map(|index| &mut self.jobs[index]).ok_or(ResumeError::NoSuchJob)?;
+        if !job.stopped {
+            return Err(ResumeError::AlreadyRunning(job.id));
+        }
+        for child in &job.children {
+            if let Ok(pid) = libc::pid_t::try_from(child.id()) {
+                // A process of the job that already exited is left alone.
+                unsafe { libc::kill(pid, libc::SIGCONT) };
+            }
+        }
+        job.stopped = false;
+        let _ = writeln!(out, "[{}]+ {} &", job.id, job.command);
+        Ok(job.id)
+    }
+
+    /// Wait for all remaining background jobs (called at REPL exit). Stopped ones are continued
+    /// first, since they would never finish otherwise.
+    pub fn wait_all(&mut self) {
+        while self.resume(None, &mut io::sink()).is_ok() {}
+        for child in self.jobs.iter_mut().flat_map(|job| &mut job.children) {
+            let _ = child.wait();
         }
         self.jobs.clear();
     }
@@ -0,0 +1,315 @@
+use crate::config::unquote;
+use crate::history_search::HistorySnapshot;
+use rustyline::Anchor;
+use rustyline::At;
+use rustyline::Cmd;
+use rustyline::ConditionalEventHandler;
+use rustyline::Editor;
+use rustyline::Event;
+use rustyline::EventContext;
+use rustyline::EventHandler;
+use rustyline::KeyCode;
+use rustyline::KeyEvent;
+use rustyline::Modifiers;
+use rustyline::Movement;
+use rustyline::RepeatCount;
+use rustyline::Word;
+use std::sync::Mutex;
+
+const ACTION_INSERT: &str = "insert";
+const ACTION_RUN: &str = "run";
+
+/// Command line queued by a `run` binding, picked up by the REPL after the line is accepted.
+static PENDING_RUN: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Clone, Debug)]
+pub enum BindAction {
+    /// A built-in line editing command.
+    Edit(Cmd),
+    /// Insert literal text at the cursor.
+    Insert(String),
+    /// Execute a command line immediately, discarding the current input.
+    Run(String),
+}
+
+/// A key bound to an action through `bind = <key> <action> [text]` in the config file.
+#[derive(Clone, Debug)]
+pub struct KeyBinding {
+    pub key: KeyEvent,
+    pub action: BindAction,
+}
+
+impl KeyBinding {
+    /// Parse `<key> <action> [text]`, e.g. `C-g run git status` or `M-u upcase-word`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut parts = value.trim().splitn(3, char::is_whitespace);
+        let key_spec = parts
+            .next()
+            .filter(|spec| !spec.is_empty())
+            .ok_or("bind: missing key")?;
+        let action_name = parts
+            .next()
+            .ok_or_else(|| format!("bind: missing action for `{key_spec}`"))?;
+        let text = parts.next().map(|text| unquote(text.trim()).to_string());
+
+        let key = parse_key(key_spec)?;
+        let action = match (action_name, text) {
+            (ACTION_INSERT, Some(text)) => BindAction::Insert(text),
+            (ACTION_RUN, Some(text)) => BindAction::Run(text),
+            (ACTION_INSERT | ACTION_RUN, None) => return Err(format!("bind: `{action_name}` needs text")),
+            (name, None) => BindAction::Edit(parse_edit_command(name)?),
+            (name, Some(_)) => return Err(format!("bind: `{name}` takes no text")),
+        };
+
+        Ok(Self { key, action })
+    }
+}
+
+/// Queue-and-accept handler behind `run` bindings.
+struct RunHandler {
+    command: String,
+}
+
+impl ConditionalEventHandler for RunHandler {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, _ctx: &EventContext) -> Option<Cmd> {
+        if let Ok(mut pending) = PENDING_RUN.lock() {
+            *pending = Some(self.command.clone());
+        }
+        Some(Cmd::AcceptLine)
+    }
+}
+
+/// Up/Down handler that searches history by the text before the cursor once the user has typed
+/// something (zsh `history-search-backward`/`-forward`), and behaves as plain Up/Down otherwise.
+///
+/// A line that is exactly a history entry with the cursor at its end was recalled by plain Up/Down,
+/// so navigation keeps stepping through all entries instead of searching by that whole entry.
+pub struct PrefixHistorySearchHandler {
+    pub backward: bool,
+    pub history: HistorySnapshot,
+}
+
+impl ConditionalEventHandler for PrefixHistorySearchHandler {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        let line = ctx.line();
+        if line.is_empty() {
+            return None;
+        }
+        let recalled = ctx.pos() == line.len()
+            && self
+                .history
+                .lock()
+                .is_ok_and(|entries| entries.iter().any(|entry| entry == line));
+        if recalled {
+            None
+        } else if self.backward {
+            Some(Cmd::HistorySearchBackward)
+        } else {
+            Some(Cmd::HistorySearchForward)
+        }
+    }
+}
+
+/// Install `bindings` on the editor, overriding any default binding for the same key.
+pub fn apply_bindings<H: rustyline::Helper, I: rustyline::history::History>(
+    editor: &mut Editor<H, I>,
+    bindings: &[KeyBinding],
+) {
+    for binding in bindings {
+        let handler = match &binding.action {
+            BindAction::Edit(cmd) => EventHandler::Simple(cmd.clone()),
+            BindAction::Insert(text) => EventHandler::Simple(Cmd::Insert(1, text.clone())),
+            BindAction::Run(command) => EventHandler::Conditional(Box::new(RunHandler {
+                command: command.clone(),
+            })),
+        };
+        editor.bind_sequence(binding.key, handler);
+    }
+}
+
+/// Take the command line queued by a `run` binding, if one fired.
+pub fn take_pending_run() -> Option<String> {
+    PENDING_RUN.lock().ok().and_then(|mut pending| pending.take())
+}
+
+/// Parse key specs such as `C-g`, `M-f`, `C-M-x`, `F5`, `Up`, or a single character.
+fn parse_key(spec: &str) -> Result<KeyEvent, String> {
+    let mut modifiers = Modifiers::NONE;
+    let mut rest = spec;
+    loop {
+        if let Some(stripped) = rest.strip_prefix("C-") {
+            modifiers |= Modifiers::CTRL;
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix("M-") {
+            modifiers |= Modifiers::ALT;
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix("S-") {
+            modifiers |= Modifiers::SHIFT;
+            rest = stripped;
+        } else {
+            break;
+        }
+    }
+
+    let code = match rest {
+        "Backspace" => KeyCode::Backspace,
+        "Delete" => KeyCode::Delete,
+        "Down" => KeyCode::Down,
+        "End" => KeyCode::End,
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Home" => KeyCode::Home,
+        "Insert" => KeyCode::Insert,
+        "Left" => KeyCode::Left,
+        "PageDown" => KeyCode::PageDown,
+        "PageUp" => KeyCode::PageUp,
+        "Right" => KeyCode::Right,
+        "Space" => KeyCode::Char(' '),
+        "Tab" => KeyCode::Tab,
+        "Up" => KeyCode::Up,
+        function if function.len() > 1 && function.starts_with('F') => function[1..]
+            .parse()
+            .ok()
+            .filter(|number| (1..=24).contains(number))
+            .map(KeyCode::F)
+            .ok_or_else(|| format!("bind: unknown key `{spec}`"))?,
+        _ => {
+            let mut characters = rest.chars();
+            match (characters.next(), characters.next()) {
+                (Some(character), None) => return Ok(KeyEvent::new(character, modifiers)),
+                _ => return Err(format!("bind: unknown key `{spec}`")),
+            }
+        }
+    };
+
+    Ok(KeyEvent(code, modifiers))
+}
+
+/// Map readline-style command names to rustyline commands.
+fn parse_edit_command(name: &str) -> Result<Cmd, String> {
+    let cmd = match name {
+        "abort" => Cmd::Abort,
+        "accept-line" => Cmd::AcceptLine,
+        "backward-char" => Cmd::Move(Movement::BackwardChar(1)),
+        "backward-kill-word" => Cmd::Kill(Movement::BackwardWord(1, Word::Emacs)),
+        "backward-word" => Cmd::Move(Movement::BackwardWord(1, Word::Emacs)),
+        "beginning-of-history" => Cmd::BeginningOfHistory,
+        "beginning-of-line" => Cmd::Move(Movement::BeginningOfLine),
+        "capitalize-word" => Cmd::CapitalizeWord,
+        "clear-screen" => Cmd::ClearScreen,
+        "complete" => Cmd::Complete,
+        "complete-hint" => Cmd::CompleteHint,
+        "delete-char" => Cmd::Kill(Movement::ForwardChar(1)),
+        "downcase-word" => Cmd::DowncaseWord,
+        "end-of-history" => Cmd::EndOfHistory,
+        "end-of-line" => Cmd::Move(Movement::EndOfLine),
+        "forward-char" => Cmd::Move(Movement::ForwardChar(1)),
+        "forward-search-history" => Cmd::ForwardSearchHistory,
+        "forward-word" => Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+        "history-search-backward" => Cmd::HistorySearchBackward,
+        "history-search-forward" => Cmd::HistorySearchForward,
+        "kill-line" => Cmd::Kill(Movement::EndOfLine),
+        "kill-whole-line" => Cmd::Kill(Movement::WholeLine),
+        "kill-word" => Cmd::Kill(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+        "next-history" => Cmd::NextHistory,
+        "noop" => Cmd::Noop,
+        "previous-history" => Cmd::PreviousHistory,
+        "quoted-insert" => Cmd::QuotedInsert,
+        "reverse-search-history" => Cmd::ReverseSearchHistory,
+        "transpose-chars" => Cmd::TransposeChars,
+        "transpose-words" => Cmd::TransposeWords(1),
+        "undo" => Cmd::Undo(1),
+        "unix-line-discard" => Cmd::Kill(Movement::BeginningOfLine),
+        "upcase-word" => Cmd::UpcaseWord,
+        "yank" => Cmd::Yank(1, Anchor::Before),
+        _ => return Err(format!("bind: unknown command `{name}`")),
+    };
+    Ok(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The key `spec` binds, normalized as the editor does when binding it.
+    fn bound_key(spec: &str) -> Result<KeyEvent, String> {
+        parse_key(spec).map(KeyEvent::normalize)
+    }
+
+    #[test]
+    fn keys() {
+        let cases = [
+            ("C-g", KeyEvent::ctrl('G')),
+            ("C-G", KeyEvent::ctrl('G')),
+            ("M-f", KeyEvent::alt('f')),
+            ("M-F", KeyEvent::alt('F')),
+            ("C-M-x", KeyEvent(KeyCode::Char('X'), Modifiers::CTRL_ALT)),
+            ("M-C-x", KeyEvent(KeyCode::Char('X'), Modifiers::CTRL_ALT)),
+            ("S-Tab", KeyEvent(KeyCode::BackTab, Modifiers::NONE)),
+            ("C-Left", KeyEvent(KeyCode::Left, Modifiers::CTRL)),
+            ("Up", KeyEvent(KeyCode::Up, Modifiers::NONE)),
+            ("PageDown", KeyEvent(KeyCode::PageDown, Modifiers::NONE)),
+            ("Space", KeyEvent::from(' ')),
+            ("F5", KeyEvent(KeyCode::F(5), Modifiers::NONE)),
+            ("F24", KeyEvent(KeyCode::F(24), Modifiers::NONE)),
+            ("F", KeyEvent::from('F')),
+            ("x", KeyEvent::from('x')),
+            ("é", KeyEvent::from('é')),
+            ("-", KeyEvent::from('-')),
+        ];
+        for (spec, expected) in cases {
+            assert_eq!(bound_key(spec), Ok(expected), "{spec:?}");
+        }
+
+        for spec in ["", "C-", "up", "ESC", "F0", "F25", "Fx", "ab", "X-a", "C-ab"] {
+            assert_eq!(parse_key(spec), Err(format!("bind: unknown key `{spec}`")), "{spec:?}");
+        }
+    }
+
+    #[test]
+    fn edit_commands() {
+        assert_eq!(parse_edit_command("accept-line"), Ok(Cmd::AcceptLine));
+        assert_eq!(
+            parse_edit_command("backward-kill-word"),
+            Ok(Cmd::Kill(Movement::BackwardWord(1, Word::Emacs)))
+        );
+        assert_eq!(parse_edit_command("yank"), Ok(Cmd::Yank(1, Anchor::Before)));
+        for name in ["Accept-Line", "accept_line", "", "self-insert"] {
+            assert_eq!(
+                parse_edit_command(name),
+                Err(format!("bind: unknown command `{name}`")),
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn bindings() {
+        let binding = KeyBinding::parse("  C-g run git status ").unwrap();
+        assert_eq!(KeyEvent::normalize(binding.key), KeyEvent::ctrl('G'));
+        assert!(matches!(binding.action, BindAction::Run(ref text) if text == "git status"));
+
+        let binding = KeyBinding::parse("M-s insert \"sudo \"").unwrap();
+        assert!(matches!(binding.action, BindAction::Insert(ref text) if text == "sudo "));
+
+        let binding = KeyBinding::parse("M-u upcase-word").unwrap();
+        assert!(matches!(binding.action, BindAction::Edit(Cmd::UpcaseWord)));
+
+        let errors = [
+            ("", "bind: missing key"),
+            ("C-g", "bind: missing action for `C-g`"),
+            ("C-g run", "bind: `run` needs text"),
+            ("C-g insert", "bind: `insert` needs text"),
+            ("C-g upcase-word now", "bind: `upcase-word` takes no text"),
+            ("C-g bogus", "bind: unknown command `bogus`"),
+            ("Hyper-g run ls", "bind: unknown key `Hyper-g`"),
+        ];
+        for (value, expected) in errors {
+            assert_eq!(
+                KeyBinding::parse(value).map(|_| ()),
+                Err(expected.to_string()),
+                "{value:?}"
+            );
+        }
+    }
+}
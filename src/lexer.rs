@@ -0,0 +1,760 @@
+use crate::parser::ends_in_function_body;
+use crate::parser::CASE_BREAK;
+use crate::parser::CHAR_BACKSLASH;
+use crate::parser::CHAR_COMMENT;
+use crate::parser::CHAR_DOLLAR_SIGN;
+use crate::parser::CHAR_DOUBLE_QUOTE;
+use crate::parser::CHAR_GREATER_THAN;
+use crate::parser::CHAR_LESS_THAN;
+use crate::parser::CHAR_NEWLINE;
+use crate::parser::CHAR_PAREN_CLOSE;
+use crate::parser::CHAR_PAREN_OPEN;
+use crate::parser::CHAR_PIPE;
+use crate::parser::CHAR_SEMICOLON;
+use crate::parser::CHAR_SINGLE_QUOTE;
+use crate::parser::CHAR_TAB;
+use crate::parser::HERE_DOCUMENT;
+use crate::parser::HERE_DOCUMENT_STRIP_TABS;
+use crate::parser::RESERVED_WORDS;
+use crate::parser::RESERVED_WORDS_BEFORE_COMMAND;
+use crate::parser::RESERVED_WORD_CASE;
+use crate::parser::RESERVED_WORD_CONDITIONAL_END;
+use crate::parser::RESERVED_WORD_CONDITIONAL_START;
+use crate::parser::RESERVED_WORD_ESAC;
+use crate::parser::RESERVED_WORD_FI;
+use crate::parser::RESERVED_WORD_IF;
+use crate::parser::RESERVED_WORD_IN;
+use crate::parser::STDOUT_STDERR_FILE_DESCRIPTOR;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Word,
+    Redirection,
+    Operator,
+    /// From an unquoted `#` starting a word to the end of its line.
+    Comment,
+}
+
+/// What the next word of a command line is, as far as reserved words go.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WordPosition {
+    /// The first word of a command, where reserved words are recognized.
+    Command,
+    /// An argument of a command.
+    Argument,
+    /// The word after `case`.
+    CaseSubject,
+    /// The `in` after the word of a `case`.
+    CaseIn,
+    /// A pattern of a `case`, up to the word ending in `)`, where `esac` is recognized.
+    CasePattern,
+    /// Inside `[[ ... ]]`, where operators and redirections are part of the expression.
+    Conditional,
+    /// Inside `(( ... ))`, up to the end of its `))`.
+    Arithmetic(usize),
+}
+
+/// A token with its byte span in the input and the spans of any quoted regions inside it.
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+    pub quoted: Vec<Range<usize>>,
+}
+
+/// Result of lexing a possibly incomplete line.
+#[derive(Clone, Debug, Default)]
+pub struct Lexed {
+    pub tokens: Vec<Token>,
+    /// The quote character left open at the end of the input, if any.
+    pub open_quote: Option<char>,
+    /// The input ends with an unescaped backslash.
+    pub trailing_escape: bool,
+    /// The spans of here-document bodies, each from its first line to the end of its delimiter
+    /// line. Bodies are not tokenized.
+    pub here_documents: Vec<Range<usize>>,
+    /// The input ends inside a here-document body, before its delimiter line.
+    pub open_here_document: bool,
+}
+
+impl Lexed {
+    /// Indices of the tokens of `input` that sit in command position: the first word of each
+    /// command, at the start of a line, after an operator, after a reserved word such as `then`,
+    /// or after a `case` pattern. Reserved words are in command position themselves, as are the
+    /// `in` and `esac` of a `case` and the `]]` closing a `[[`, but not the words between them.
+    pub fn command_word_indices(&self, input: &str) -> Vec<usize> {
+        let mut indices = Vec::new();
+        let mut position = WordPosition::Command;
+        let mut skip_target = false;
+        let mut open_cases = 0_usize;
+        let mut previous_end = 0;
+        for (index, token) in self.tokens.iter().enumerate() {
+            if let WordPosition::Arithmetic(end) = position {
+                if token.span.start < end {
+                    continue;
+                }
+                position = WordPosition::Argument;
+            }
+            if position == WordPosition::Argument && input[previous_end..token.span.start].contains(CHAR_NEWLINE) {
+                position = WordPosition::Command;
+            }
+            previous_end = token.span.end;
+            match token.kind {
+                TokenKind::Operator | TokenKind::Redirection if position == WordPosition::Conditional => {}
+                TokenKind::Operator => {
+                    skip_target = false;
+                    let operator = &input[token.span.clone()];
+                    position = if operator == CASE_BREAK && open_cases > 0
+                        || operator == CHAR_PIPE.to_string() && position == WordPosition::CasePattern
+                    {
+                        WordPosition::CasePattern
+                    } else {
+                        WordPosition::Command
+                    };
+                }
+                TokenKind::Redirection => skip_target = true,
+                TokenKind::Word if skip_target => skip_target = false,
+                TokenKind::Word => match position {
+                    WordPosition::Command if starts_arithmetic_command(input, token) => {
+                        indices.push(index);
+                        let end = arithmetic_command_end(input, token.span.start).unwrap_or(input.len());
+                        previous_end = end;
+                        position = WordPosition::Arithmetic(end);
+                    }
+                    WordPosition::Command => {
+                        indices.push(index);
+                        position = match reserved_word(input, token) {
+                            Some(RESERVED_WORD_CASE) => {
+                                open_cases += 1;
+                                WordPosition::CaseSubject
+                            }
+                            Some(RESERVED_WORD_ESAC) => {
+                                open_cases = open_cases.saturating_sub(1);
+                                WordPosition::Argument
+                            }
+                            Some(RESERVED_WORD_CONDITIONAL_START) => WordPosition::Conditional,
+                            Some(word) if RESERVED_WORDS_BEFORE_COMMAND.contains(&word) => WordPosition::Command,
+                            _ => WordPosition::Argument,
+                        };
+                    }
+                    WordPosition::CaseSubject => position = WordPosition::CaseIn,
+                    WordPosition::CaseIn if reserved_word(input, token) == Some(RESERVED_WORD_IN) => {
+                        indices.push(index);
+                        position = WordPosition::CasePattern;
+                    }
+                    WordPosition::CasePattern if reserved_word(input, token) == Some(RESERVED_WORD_ESAC) => {
+                        indices.push(index);
+                        open_cases -= 1;
+                        position = WordPosition::Argument;
+                    }
+                    WordPosition::CasePattern if ends_case_pattern(input, token) => position = WordPosition::Command,
+                    WordPosition::Conditional if reserved_word(input, token) == Some(RESERVED_WORD_CONDITIONAL_END) => {
+                        indices.push(index);
+                        position = WordPosition::Argument;
+                    }
+                    WordPosition::CaseIn => position = WordPosition::Argument,
+                    WordPosition::CasePattern
+                    | WordPosition::Conditional
+                    | WordPosition::Arithmetic(_)
+                    | WordPosition::Argument => {}
+                },
+                TokenKind::Comment => {}
+            }
+        }
+        indices
+    }
+
+    /// The spans of the outermost `if ... fi`, `case ... esac`, `[[ ... ]]`, and `(( ... ))` blocks
+    /// of `input`, from the word opening one to the end of the one closing it, or to the end of the input when
+    /// it is not closed.
+    pub fn compound_spans(&self, input: &str) -> Vec<Range<usize>> {
+        let (mut spans, open) = self.compound_blocks(input);
+        spans.extend(open.map(|start| start..input.len()));
+        spans
+    }
+
+    /// The spans of the closed outermost compound command blocks of `input`, and the start of the
+    /// block left open at its end, if any.
+    fn compound_blocks(&self, input: &str) -> (Vec<Range<usize>>, Option<usize>) {
+        let mut spans = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        for index in self.command_word_indices(input) {
+            let token = &self.tokens[index];
+            match reserved_word(input, token) {
+                Some(RESERVED_WORD_CASE | RESERVED_WORD_CONDITIONAL_START | RESERVED_WORD_IF) => {
+                    if depth == 0 {
+                        start = token.span.start;
+                    }
+                    depth += 1;
+                }
+                Some(RESERVED_WORD_CONDITIONAL_END | RESERVED_WORD_ESAC | RESERVED_WORD_FI) if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        spans.push(start..token.span.end);
+                    }
+                }
+                None if depth == 0 && starts_arithmetic_command(input, token) => {
+                    match arithmetic_command_end(input, token.span.start) {
+                        Some(end) => spans.push(token.span.start..end),
+                        None => return (spans, Some(token.span.start)),
+                    }
+                }
+                _ => {}
+            }
+        }
+        (spans, (depth > 0).then_some(start))
+    }
+}
+
+/// The reserved word `token` spells, such as `if`, when it is a plain word; it only acts as one in
+/// command position.
+#[must_use]
+pub fn reserved_word<'a>(input: &'a str, token: &Token) -> Option<&'a str> {
+    let text = &input[token.span.clone()];
+    (token.kind == TokenKind::Word && token.quoted.is_empty() && RESERVED_WORDS.contains(&text)).then_some(text)
+}
+
+/// Whether `token` starts an arithmetic command, `(( ... ))`: an unquoted word starting with `((`.
+#[must_use]
+pub fn starts_arithmetic_command(input: &str, token: &Token) -> bool {
+    token.kind == TokenKind::Word
+        && input[token.span.clone()].starts_with("((")
+        && !token.quoted.iter().any(|quoted| quoted.start < token.span.start + 2)
+}
+
+/// The end of the `))` closing the arithmetic command at `start` of `input`: just after the `)`
+/// that balances its first `(`, outside quotes. `None` when it is not closed.
+#[must_use]
+pub fn arithmetic_command_end(input: &str, start: usize) -> Option<usize> {
+    let mut depth = 0_usize;
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in input[start..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            _ if quote == Some(c) => quote = None,
+            _ if quote.is_some() => {}
+            CHAR_BACKSLASH => escaped = true,
+            CHAR_SINGLE_QUOTE | CHAR_DOUBLE_QUOTE => quote = Some(c),
+            CHAR_PAREN_OPEN => depth += 1,
+            CHAR_PAREN_CLOSE => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start + index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether `token` is the last word of a `case` pattern: a word ending in an unquoted, unescaped
+/// `)`, as in `*.rs)`.
+#[must_use]
+pub fn ends_case_pattern(input: &str, token: &Token) -> bool {
+    let text = &input[token.span.clone()];
+    let close = token.span.end - 1;
+    token.kind == TokenKind::Word
+        && text.ends_with(CHAR_PAREN_CLOSE)
+        && !token.quoted.iter().any(|quoted| quoted.contains(&close))
+        && text[..text.len() - 1]
+            .chars()
+            .rev()
+            .take_while(|&c| c == CHAR_BACKSLASH)
+            .count()
+            % 2
+            == 0
+}
+
+/// Whether `input` cannot run as-is: it ends inside a quote, a here-document, an `if`, `case`, or
+/// `[[` without its `fi`, `esac`, or `]]`, a `( ... )` or `$( ... )`, or a function body without its
+/// `}`, after a backslash, or after `|`, `||`, `&&`.
+pub fn is_incomplete(input: &str) -> bool {
+    let lexed = lex(input);
+    if lexed.open_quote.is_some() || lexed.trailing_escape || lexed.open_here_document {
+        return true;
+    }
+    if ends_in_function_body(input) {
+        return true;
+    }
+    if lexed.compound_blocks(input).1.is_some() {
+        return true;
+    }
+    let mut depth = 0;
+    for token in &lexed.tokens {
+        update_paren_depth(&mut depth, input, token);
+    }
+    if depth > 0 {
+        return true;
+    }
+    lexed
+        .tokens
+        .iter()
+        .rfind(|token| token.kind != TokenKind::Comment)
+        .is_some_and(|last| last.kind == TokenKind::Operator && matches!(&input[last.span.clone()], "|" | "||" | "&&"))
+}
+
+/// Split multi-line input (e.g. a bracketed paste) into the command lines it contains.
+///
+/// A newline only ends a command line when the text before it is complete, so quoted newlines,
+/// backslash continuations, lines inside `( ... )`, `$( ... )`, `if ... fi`, `case ... esac`, or
+/// `[[ ... ]]`, and lines ending in `|`, `||`, `&&` stay joined with the next line, and a line
+/// starting here-documents keeps their bodies. The input is lexed once, so long pastes split in linear time.
+pub fn split_command_lines(input: &str) -> Vec<String> {
+    let lexed = lex(input);
+    let compounds = lexed.compound_spans(input);
+    let mut command_lines = Vec::new();
+    let mut start = 0;
+    let mut tokens = lexed.tokens.iter().peekable();
+    let mut continues = false;
+    let mut tokens_end = 0;
+    let mut depth = 0;
+
+    for (newline, _) in input.match_indices(CHAR_NEWLINE) {
+        while let Some(token) = tokens.next_if(|token| token.span.start < newline) {
+            if token.kind == TokenKind::Comment {
+                continue;
+            }
+            update_paren_depth(&mut depth, input, token);
+            tokens_end = token.span.end;
+            continues = token.kind == TokenKind::Operator && matches!(&input[token.span.clone()], "|" | "||" | "&&");
+        }
+        let in_here_document = lexed
+            .here_documents
+            .iter()
+            .any(|body| body.start <= newline + 1 && newline < body.end);
+        let in_compound = compounds.iter().any(|span| span.start < newline && newline < span.end);
+        // A newline inside a token is quoted or escaped.
+        if newline < tokens_end || continues || in_here_document || in_compound || depth > 0 {
+            continue;
+        }
+        let command_line = &input[start..newline];
+        if !command_line.trim().is_empty() {
+            command_lines.push(command_line.to_string());
+        }
+        start = newline + 1;
+    }
+
+    let command_line = &input[start..];
+    if !command_line.trim().is_empty() {
+        command_lines.push(command_line.to_string());
+    }
+    command_lines
+}
+
+/// Split `input` into spanned tokens without interpreting them.
+///
+/// Never fails: unterminated quotes and trailing escapes are reported on [`Lexed`] so callers
+/// can work on partial lines (highlighting, validation, completion).
+///
+/// Scans bytes: every character with a meaning to the lexer is ASCII, so runs of plain word
+/// characters and quoted text are skipped with `find` instead of one character at a time.
+pub fn lex(input: &str) -> Lexed {
+    let bytes = input.as_bytes();
+    let mut lexed = Lexed::default();
+    let mut word_start: Option<usize> = None;
+    let mut quoted: Vec<Range<usize>> = Vec::new();
+    let mut index = 0;
+    // Tokens before this one have been checked for here-document operators.
+    let mut here_documents_checked = 0;
+
+    let finish_word = |lexed: &mut Lexed, word_start: &mut Option<usize>, quoted: &mut Vec<Range<usize>>, end| {
+        if let Some(start) = word_start.take() {
+            lexed.tokens.push(Token {
+                kind: TokenKind::Word,
+                span: start..end,
+                quoted: std::mem::take(quoted),
+            });
+        }
+    };
+    let byte_is = |index: usize, character: char| bytes.get(index) == Some(&(character as u8));
+
+    while index < bytes.len() {
+        let byte = bytes[index];
+        let character = char::from(byte);
+        match character {
+            CHAR_SINGLE_QUOTE | CHAR_DOUBLE_QUOTE => {
+                word_start.get_or_insert(index);
+                match closing_quote(input, index + 1, character) {
+                    Some(end) => {
+                        quoted.push(index..end + 1);
+                        index = end + 1;
+                    }
+                    None => {
+                        quoted.push(index..input.len());
+                        lexed.open_quote = Some(character);
+                        index = input.len();
+                    }
+                }
+            }
+
+            CHAR_COMMENT if word_start.is_none() => {
+                let end = input[index..]
+                    .find(CHAR_NEWLINE)
+                    .map_or(input.len(), |offset| index + offset);
+                lexed.tokens.push(Token {
+                    kind: TokenKind::Comment,
+                    span: index..end,
+                    quoted: Vec::new(),
+                });
+                index = end;
+            }
+
+            CHAR_BACKSLASH => {
+                word_start.get_or_insert(index);
+                match input[index + 1..].chars().next() {
+                    Some(escaped) => index += 1 + escaped.len_utf8(),
+                    None => {
+                        lexed.trailing_escape = true;
+                        index += 1;
+                    }
+                }
+            }
+
+            CHAR_PIPE | STDOUT_STDERR_FILE_DESCRIPTOR | CHAR_SEMICOLON => {
+                let is_redirection = character == STDOUT_STDERR_FILE_DESCRIPTOR
+                    && word_start.is_none()
+                    && byte_is(index + 1, CHAR_GREATER_THAN);
+                if is_redirection {
+                    let mut end = index + 2;
+                    if byte_is(end, CHAR_GREATER_THAN) {
+                        end += 1;
+                    }
+                    lexed.tokens.push(Token {
+                        kind: TokenKind::Redirection,
+                        span: index..end,
+                        quoted: Vec::new(),
+                    });
+                    index = end;
+                    continue;
+                }
+                finish_word(&mut lexed, &mut word_start, &mut quoted, index);
+                let mut end = index + 1;
+                if byte_is(end, character) {
+                    end += 1;
+                }
+                lexed.tokens.push(Token {
+                    kind: TokenKind::Operator,
+                    span: index..end,
+                    quoted: Vec::new(),
+                });
+                index = end;
+            }
+
+            CHAR_GREATER_THAN | CHAR_LESS_THAN => {
+                // A lone fd digit immediately before the operator belongs to the redirection.
+                let start = match word_start {
+                    Some(start) if is_file_descriptor_prefix(&input[start..index]) => {
+                        word_start = None;
+                        start
+                    }
+                    _ => {
+                        finish_word(&mut lexed, &mut word_start, &mut quoted, index);
+                        index
+                    }
+                };
+                let mut end = index + 1;
+                if byte_is(end, character) {
+                    end += 1;
+                    if character == CHAR_LESS_THAN && byte_is(end, '-') {
+                        end += 1;
+                    }
+                } else if byte_is(end, STDOUT_STDERR_FILE_DESCRIPTOR) {
+                    // `>&` and `<&` duplicate a descriptor.
+                    end += 1;
+                }
+                lexed.tokens.push(Token {
+                    kind: TokenKind::Redirection,
+                    span: start..end,
+                    quoted: Vec::new(),
+                });
+                index = end;
+            }
+
+            character if character.is_ascii_whitespace() => {
+                finish_word(&mut lexed, &mut word_start, &mut quoted, index);
+                index += 1;
+                if character == CHAR_NEWLINE {
+                    let delimiters = here_document_delimiters(input, &lexed.tokens[here_documents_checked..]);
+                    here_documents_checked = lexed.tokens.len();
+                    for (position, (delimiter, strip_tabs)) in delimiters.into_iter().enumerate() {
+                        // A later body starts on the line after the previous delimiter line.
+                        if position > 0 && index < input.len() {
+                            index += 1;
+                        }
+                        let body_start = index;
+                        match here_document_end(input, index, &delimiter, strip_tabs) {
+                            Some(end) => index = end,
+                            None => {
+                                lexed.open_here_document = true;
+                                index = input.len();
+                            }
+                        }
+                        lexed.here_documents.push(body_start..index);
+                    }
+                }
+            }
+
+            // `$( ... )`, `$(( ... ))`, and `(( ... ))` belong to the word they are in, whatever
+            // operators they hold: the `<<` of `$((1 << 4))` is a shift, not a here-document.
+            CHAR_DOLLAR_SIGN if byte_is(index + 1, CHAR_PAREN_OPEN) => {
+                word_start.get_or_insert(index);
+                index = parenthesized_end(input, index + 1, &mut quoted);
+            }
+            CHAR_PAREN_OPEN if word_start.is_none() && byte_is(index + 1, CHAR_PAREN_OPEN) => {
+                word_start = Some(index);
+                index = parenthesized_end(input, index, &mut quoted);
+            }
+
+            _ if !byte.is_ascii() && starts_with_whitespace(&input[index..]) => {
+                finish_word(&mut lexed, &mut word_start, &mut quoted, index);
+                index += input[index..].chars().next().map_or(1, char::len_utf8);
+            }
+
+            _ => {
+                word_start.get_or_insert(index);
+                index = plain_run_end(input, index);
+            }
+        }
+    }
+
+    finish_word(&mut lexed, &mut word_start, &mut quoted, input.len());
+    // A here-document opened on the last line has not reached its body yet.
+    if !here_document_delimiters(input, &lexed.tokens[here_documents_checked..]).is_empty() {
+        lexed.open_here_document = true;
+    }
+    lexed
+}
+
+/// The delimiters of the here-documents that `tokens` open, in order, with whether each strips
+/// leading tabs (`<<-`). A delimiter is the word after the operator, with its quotes removed.
+fn here_document_delimiters(input: &str, tokens: &[Token]) -> Vec<(String, bool)> {
+    tokens
+        .windows(2)
+        .filter_map(|pair| {
+            let operator = &input[pair[0].span.clone()];
+            let strip_tabs = match operator {
+                HERE_DOCUMENT => false,
+                HERE_DOCUMENT_STRIP_TABS => true,
+                _ => return None,
+            };
+            if pair[0].kind != TokenKind::Redirection || pair[1].kind != TokenKind::Word {
+                return None;
+            }
+            let delimiter = input[pair[1].span.clone()]
+                .chars()
+                .filter(|&c| !matches!(c, CHAR_SINGLE_QUOTE | CHAR_DOUBLE_QUOTE | CHAR_BACKSLASH))
+                .collect();
+            Some((delimiter, strip_tabs))
+        })
+        .collect()
+}
+
+/// The end of the line holding just `delimiter` (after leading tabs with `strip_tabs`), searching
+/// the lines starting at `from`, or `None` when no line does.
+fn here_document_end(input: &str, mut from: usize, delimiter: &str, strip_tabs: bool) -> Option<usize> {
+    while from < input.len() {
+        let end = input[from..]
+            .find(CHAR_NEWLINE)
+            .map_or(input.len(), |offset| from + offset);
+        let line = &input[from..end];
+        let line = if strip_tabs {
+            line.trim_start_matches(CHAR_TAB)
+        } else {
+            line
+        };
+        if line == delimiter {
+            return Some(end);
+        }
+        from = end + 1;
+    }
+    None
+}
+
+/// Index of the quote closing a `quote` string whose contents start at `from`, honoring
+/// backslash escapes inside double quotes.
+fn closing_quote(input: &str, mut from: usize, quote: char) -> Option<usize> {
+    if quote == CHAR_SINGLE_QUOTE {
+        return input[from..].find(quote).map(|offset| from + offset);
+    }
+    loop {
+        let offset = input[from..].find([quote, CHAR_BACKSLASH])?;
+        let found = from + offset;
+        if input.as_bytes()[found] == quote as u8 {
+            return Some(found);
+        }
+        // Skip the escaped character, which may be multi-byte.
+        from = found + 1 + input[found + 1..].chars().next().map_or(0, char::len_utf8);
+        if from > input.len() {
+            return None;
+        }
+    }
+}
+
+/// The end of the text in parentheses opening at `open`: just after the `)` that balances it, or
+/// the end of the input when none does. The quoted regions inside are added to `quoted`.
+fn parenthesized_end(input: &str, open: usize, quoted: &mut Vec<Range<usize>>) -> usize {
+    let mut depth = 0_usize;
+    let mut from = open;
+    let delimiters = [
+        CHAR_PAREN_OPEN,
+        CHAR_PAREN_CLOSE,
+        CHAR_SINGLE_QUOTE,
+        CHAR_DOUBLE_QUOTE,
+        CHAR_BACKSLASH,
+    ];
+    while let Some(offset) = input[from..].find(delimiters) {
+        let found = from + offset;
+        let character = char::from(input.as_bytes()[found]);
+        from = found + 1;
+        match character {
+            CHAR_PAREN_OPEN => depth += 1,
+            CHAR_PAREN_CLOSE => {
+                depth -= 1;
+                if depth == 0 {
+                    return from;
+                }
+            }
+            CHAR_BACKSLASH => from += input[from..].chars().next().map_or(0, char::len_utf8),
+            _ => match closing_quote(input, from, character) {
+                Some(end) => {
+                    quoted.push(found..end + 1);
+                    from = end + 1;
+                }
+                None => {
+                    quoted.push(found..input.len());
+                    return input.len();
+                }
+            },
+        }
+    }
+    input.len()
+}
+
+/// End of the run of word characters starting at `from`: the next byte the lexer must look at.
+fn plain_run_end(input: &str, from: usize) -> usize {
+    let rest = &input[from..];
+    let mut characters = rest.char_indices();
+    characters.next();
+    characters
+        .find(|(_, character)| is_special(*character))
+        .map_or(input.len(), |(offset, _)| from + offset)
+}
+
+fn is_special(character: char) -> bool {
+    matches!(
+        character,
+        CHAR_SINGLE_QUOTE
+            | CHAR_DOUBLE_QUOTE
+            | CHAR_BACKSLASH
+            | CHAR_DOLLAR_SIGN
+            | CHAR_PIPE
+            | STDOUT_STDERR_FILE_DESCRIPTOR
+            | CHAR_SEMICOLON
+            | CHAR_GREATER_THAN
+            | CHAR_LESS_THAN
+    ) || character.is_whitespace()
+}
+
+fn starts_with_whitespace(text: &str) -> bool {
+    text.chars().next().is_some_and(char::is_whitespace)
+}
+
+/// Track the nesting of `( ... )` and `$( ... )` through `token`: its unquoted, unescaped
+/// parentheses open and close a level.
+fn update_paren_depth(depth: &mut usize, input: &str, token: &Token) {
+    if token.kind != TokenKind::Word {
+        return;
+    }
+    let mut escaped = false;
+    for (index, character) in input[token.span.clone()].char_indices() {
+        let index = token.span.start + index;
+        if escaped || token.quoted.iter().any(|quoted| quoted.contains(&index)) {
+            escaped = false;
+            continue;
+        }
+        match character {
+            CHAR_BACKSLASH => escaped = true,
+            CHAR_PAREN_OPEN => *depth += 1,
+            CHAR_PAREN_CLOSE => *depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+}
+
+fn is_file_descriptor_prefix(word: &str) -> bool {
+    let mut characters = word.chars();
+    matches!((characters.next(), characters.next()), (Some(digit), None) if digit.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_blocks() {
+        assert!(is_incomplete("if a; then\n  b"));
+        assert!(is_incomplete("if a; then if b; then c; fi"));
+        assert!(!is_incomplete("if a; then b; fi"));
+        assert!(!is_incomplete("echo if"));
+        assert_eq!(
+            split_command_lines("if a\nthen\n  b\nfi\nc\nif d; then e; fi"),
+            ["if a\nthen\n  b\nfi", "c", "if d; then e; fi"]
+        );
+    }
+
+    #[test]
+    fn case_blocks() {
+        assert!(is_incomplete("case a in\n  a) b;;"));
+        assert!(is_incomplete("if a; then case b in fi) c;; esac"));
+        assert!(!is_incomplete("case a in a) b;; esac"));
+        assert_eq!(
+            split_command_lines("case a in\n  a) b\nesac\nc"),
+            ["case a in\n  a) b\nesac", "c"]
+        );
+    }
+
+    #[test]
+    fn conditional_blocks() {
+        assert!(is_incomplete("[[ a &&"));
+        assert!(!is_incomplete("[[ a ]]"));
+    }
+
+    #[test]
+    fn substitutions_and_arithmetic_are_words() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo $((1<<4))\necho after", &["echo", "$((1<<4))", "echo", "after"]),
+            ("x=$((1 << 2)) y", &["x=$((1 << 2))", "y"]),
+            ("(( x = 1 << 3 )); z", &["(( x = 1 << 3 ))", ";", "z"]),
+            ("a$(b | c \")\"; d)e", &["a$(b | c \")\"; d)e"]),
+            ("cat <<A\n$((1<<2))\nA", &["cat", "<<", "A"]),
+        ];
+        for (input, expected) in cases {
+            let lexed = lex(input);
+            let tokens: Vec<&str> = lexed.tokens.iter().map(|token| &input[token.span.clone()]).collect();
+            assert_eq!(tokens, *expected, "input: {input:?}");
+            assert!(!lexed.open_here_document, "input: {input:?}");
+        }
+        assert!(lex("echo $((1<<4))\n").here_documents.is_empty());
+        assert!(is_incomplete("echo $(a"));
+    }
+
+    #[test]
+    fn function_bodies() {
+        let cases = [
+            ("precmd() {", true),
+            ("f() {\n  echo a", true),
+            ("f() { echo }", true),
+            ("f() { echo a; }; g() {\n", true),
+            ("f() {\n  echo a\n}", false),
+            ("f() { return 3; }; f", false),
+            ("f() { echo '}' \\}; }", false),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(is_incomplete(input), expected, "input: {input:?}");
+        }
+    }
+}
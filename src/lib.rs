@@ -0,0 +1,55 @@
+//! The shell engine: parsing, expansion, and execution behind [`Shell`], with the line editor
+//! integration used by the interactive frontend in `main.rs`.
+
+pub mod alias;
+pub mod arithmetic;
+pub mod brace;
+pub mod color;
+pub mod command_cache;
+pub mod commands;
+pub mod completions;
+pub mod compound;
+pub mod conditional;
+pub mod config;
+#[cfg(feature = "readline")]
+pub mod editor;
+pub mod error;
+pub mod executor;
+pub mod frecency;
+pub mod fuzzy;
+pub mod glob;
+pub mod history;
+pub mod history_expansion;
+#[cfg(feature = "readline")]
+pub mod history_search;
+pub mod home;
+pub mod jobs;
+#[cfg(feature = "readline")]
+pub mod keybindings;
+pub mod lexer;
+pub mod locale;
+pub mod notify;
+pub mod options;
+pub mod parse_dump;
+pub mod parser;
+pub mod path_cache;
+pub mod pattern;
+#[cfg(not(feature = "readline"))]
+pub mod plain_reader;
+pub mod plugins;
+pub mod printf;
+pub mod prompt;
+pub mod session;
+mod shell;
+#[cfg(feature = "readline")]
+pub mod shell_helper;
+pub mod signals;
+pub mod state;
+pub mod subshell;
+pub mod terminal;
+pub mod trace;
+pub mod xdg;
+
+pub use error::ShellError;
+pub use shell::ExitStatus;
+pub use shell::Shell;
@@ -0,0 +1,39 @@
+use std::cmp::Ordering;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::sync::OnceLock;
+
+/// Locale names whose collation is plain byte order.
+const BYTE_ORDER_LOCALES: &[&str] = &["C", "POSIX"];
+
+/// Whether `strcoll` sorts differently from byte order, decided once from `LC_ALL`,
+/// `LC_COLLATE`, and `LANG`.
+static LOCALE_COLLATION: OnceLock<bool> = OnceLock::new();
+
+/// Order `a` and `b` as the user's collation locale does, so non-ASCII names sort where users
+/// expect. Falls back to byte order in the C locale, when the locale is not installed, or for
+/// strings with a NUL; strings the locale considers equal are ordered by bytes.
+#[must_use]
+pub fn collate(a: &str, b: &str) -> Ordering {
+    if !*LOCALE_COLLATION.get_or_init(load_collation_locale) {
+        return a.cmp(b);
+    }
+    let (Ok(c_a), Ok(c_b)) = (CString::new(a), CString::new(b)) else {
+        return a.cmp(b);
+    };
+    unsafe { libc::strcoll(c_a.as_ptr(), c_b.as_ptr()) }
+        .cmp(&0)
+        .then_with(|| a.cmp(b))
+}
+
+/// Apply the environment's collation locale and report whether it differs from byte order.
+fn load_collation_locale() -> bool {
+    // Only the collation category changes; messages and number formatting stay in the C locale.
+    let name = unsafe { libc::setlocale(libc::LC_COLLATE, c"".as_ptr()) };
+    if name.is_null() {
+        return false;
+    }
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+    let language = name.split('.').next().unwrap_or_default();
+    !BYTE_ORDER_LOCALES.contains(&language)
+}
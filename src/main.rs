@@ -1,99 +1,112 @@
-mod commands;
-mod executor;
-mod jobs;
-mod parser;
-mod shell_helper;
-
-use crate::commands::BuiltinAction;
-use crate::executor::execute_pipeline;
-use crate::executor::ShellContext;
-use crate::jobs::JobManager;
-use crate::parser::parse_input;
-use crate::parser::SHELL_PROMPT;
-use crate::shell_helper::ShellCompleter;
-use crate::shell_helper::ShellHelper;
-use rustyline::config::BellStyle;
-use rustyline::config::CompletionType;
-use rustyline::config::Config;
-use rustyline::error::ReadlineError;
-use rustyline::history::History;
-use rustyline::history::SearchDirection;
-use rustyline::Editor;
+use codecrafters_shell::config::ShellConfig;
+use codecrafters_shell::error::ReadError;
+use codecrafters_shell::options::IGNOREEOF_LIMIT;
+use codecrafters_shell::parser::ENVIRONMENT_VARIABLE_HISTFILE;
+use codecrafters_shell::parser::ENVIRONMENT_VARIABLE_SHELL;
+use codecrafters_shell::signals;
+use codecrafters_shell::Shell;
+use codecrafters_shell::ShellError;
+use std::io::IsTerminal;
 use std::io::Write;
+use std::time::Instant;
 
-fn save_history_plain<H: rustyline::Helper, I: History>(readline: &Editor<H, I>, path: &str) {
-    if let Ok(mut file) = std::fs::File::create(path) {
-        let history = readline.history();
-        for i in 0..history.len() {
-            if let Ok(Some(entry)) = history.get(i, SearchDirection::Forward) {
-                let _ = writeln!(file, "{}", entry.entry);
-            }
-        }
-    }
-}
+/// Failed reads in a row after which the terminal is assumed gone and the shell exits.
+const MAX_CONSECUTIVE_READ_ERRORS: usize = 10;
+/// Print how each line parses instead of running it.
+const FLAG_PARSE_ONLY: &str = "--parse-only";
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let helper = ShellHelper {
-        completer: ShellCompleter::new(),
+    let config = ShellConfig::load();
+    // Like bash, only an interactive shell survives Ctrl-C; a script is interrupted with its command.
+    let interactive = std::io::stdin().is_terminal();
+    // `$HISTFILE` wins, an empty one disabling history; otherwise only an interactive shell keeps
+    // the history file from the config (by default in the XDG state directory).
+    let histfile_path: Option<String> = match std::env::var_os(ENVIRONMENT_VARIABLE_HISTFILE) {
+        Some(path) => Some(path.to_string_lossy().into_owned()).filter(|path| !path.is_empty()),
+        None if interactive => config.history_path().map(|path| path.to_string_lossy().into_owned()),
+        None => None,
     };
 
-    let config = Config::builder()
-        .completion_type(CompletionType::List)
-        .bell_style(BellStyle::Audible)
-        .history_ignore_dups(false)?
-        .build();
-
-    let mut readline = Editor::with_config(config)?;
-    readline.set_helper(Some(helper));
+    let mut shell = Shell::with_config(config)?;
+    let mut arguments = std::env::args_os()
+        .map(|argument| argument.to_string_lossy().into_owned())
+        .peekable();
+    if let Some(arg0) = arguments.next() {
+        shell.set_arg0(&arg0);
+    }
+    let parse_only = arguments.next_if(|argument| argument == FLAG_PARSE_ONLY).is_some();
+    // Like `sh -s`, the arguments are the positional parameters of the commands read from stdin.
+    shell.set_positional(arguments.collect());
+    if interactive {
+        if let Err(error) = signals::install_handlers() {
+            ShellError::from(error).report(&mut std::io::stderr());
+        }
+        // Programs that spawn "the user's shell", such as editors and pagers, start this one.
+        if let Ok(executable) = std::env::current_exe() {
+            std::env::set_var(ENVIRONMENT_VARIABLE_SHELL, executable);
+        }
+    }
 
-    let histfile_path: Option<String> = std::env::var("HISTFILE").ok();
     if let Some(ref path) = histfile_path {
-        let _ = readline.load_history(path);
+        shell.load_history(path);
     }
 
-    let mut last_appended_index: usize = readline.history().len();
-    let mut job_mgr = JobManager::new();
+    let mut consecutive_eofs: usize = 0;
+    let mut consecutive_read_errors: usize = 0;
 
-    'repl: loop {
-        job_mgr.reap();
-        let input = match readline.readline(SHELL_PROMPT) {
+    let exit_code = loop {
+        match shell.read_line() {
             Ok(line) => {
-                let _ = readline.add_history_entry(line.as_str());
-                line
+                consecutive_eofs = 0;
+                consecutive_read_errors = 0;
+                let started = Instant::now();
+                let status = if parse_only {
+                    shell.dump_parse(&line)
+                } else {
+                    shell.eval(&line)
+                };
+                if interactive {
+                    shell.notify_if_slow(&line, status, started.elapsed());
+                }
+                if let Some(code) = shell.exit_requested() {
+                    break code;
+                }
+                if !interactive && status.code() == 128 + libc::SIGINT {
+                    break status.code();
+                }
             }
-            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break 'repl,
-            Err(e) => {
-                eprintln!("Error: {e:?}");
-                break 'repl;
+            // Like bash, only an interactive shell ignores end of input.
+            Err(ReadError::Eof)
+                if interactive && shell.options().ignoreeof && consecutive_eofs + 1 < IGNOREEOF_LIMIT =>
+            {
+                consecutive_eofs += 1;
+                let _ = writeln!(std::io::stdout(), "Use \"exit\" to leave the shell.");
             }
-        };
-
-        let input = input.trim();
-        if input.is_empty() {
-            continue 'repl;
-        }
-
-        if let Some(pipeline) = parse_input(input) {
-            let mut ctx = ShellContext {
-                editor: &mut readline,
-                last_appended_index: &mut last_appended_index,
-            };
-            match execute_pipeline(pipeline, &mut job_mgr, &mut ctx)? {
-                BuiltinAction::Exit(code) => {
-                    if let Some(ref path) = histfile_path {
-                        save_history_plain(&readline, path);
-                    }
-                    std::process::exit(code);
+            // Ctrl-C at an interactive prompt only discards the line.
+            Err(ReadError::Interrupted) if interactive => {
+                consecutive_eofs = 0;
+                shell.interrupt_line();
+            }
+            Err(ReadError::Interrupted) => {
+                shell.wait_for_jobs();
+                break 128 + libc::SIGINT;
+            }
+            Err(ReadError::Eof) => {
+                shell.wait_for_jobs();
+                break shell.last_status().code();
+            }
+            Err(ReadError::Failed(error)) => {
+                let code = error.report(&mut std::io::stderr());
+                shell.reset_terminal();
+                consecutive_read_errors += 1;
+                if consecutive_read_errors >= MAX_CONSECUTIVE_READ_ERRORS {
+                    shell.wait_for_jobs();
+                    break code;
                 }
-                BuiltinAction::Continue => {}
             }
         }
-    }
-
-    job_mgr.wait_all();
-    if let Some(ref path) = histfile_path {
-        save_history_plain(&readline, path);
-    }
+    };
 
-    Ok(())
+    shell.shutdown(histfile_path.as_deref());
+    std::process::exit(exit_code);
 }
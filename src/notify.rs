@@ -0,0 +1,108 @@
+use crate::parser::ENVIRONMENT_VARIABLE_WINDOWID;
+use std::env::var;
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+use std::time::Duration;
+
+const BELL: &str = "\x07";
+const OSC_9_START: &str = "\x1b]9;";
+const OSC_END: &str = "\x07";
+/// Longest command text shown in a notification before it is cut with `...`.
+const MAX_NOTIFICATION_COMMAND_CHARS: usize = 60;
+
+/// How a long command announces that it finished.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NotifyStyle {
+    /// Ring the terminal bell, which most terminals turn into an urgency hint.
+    #[default]
+    Bell,
+    /// Send an OSC 9 desktop notification with the command and its status.
+    Osc9,
+}
+
+impl NotifyStyle {
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bell" => Some(Self::Bell),
+            "osc9" => Some(Self::Osc9),
+            _ => None,
+        }
+    }
+}
+
+/// When a long command announces that it finished.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NotifyWhen {
+    /// Only when the terminal window does not have focus, or focus cannot be determined.
+    #[default]
+    Unfocused,
+    Always,
+}
+
+impl NotifyWhen {
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "unfocused" => Some(Self::Unfocused),
+            "always" => Some(Self::Always),
+            _ => None,
+        }
+    }
+}
+
+/// Announce on `terminal` that `command_line` finished with `status` after `elapsed`, in `style`,
+/// unless `when` asks for unfocused windows only and this one has focus.
+pub fn notify_finished(
+    terminal: &mut dyn Write,
+    style: NotifyStyle,
+    when: NotifyWhen,
+    command_line: &str,
+    status: i32,
+    elapsed: Duration,
+) {
+    if when == NotifyWhen::Unfocused && terminal_focused() == Some(true) {
+        return;
+    }
+    let _ = match style {
+        NotifyStyle::Bell => write!(terminal, "{BELL}"),
+        NotifyStyle::Osc9 => write!(
+            terminal,
+            "{OSC_9_START}{} finished with status {status} after {}s{OSC_END}",
+            notification_text(command_line),
+            elapsed.as_secs()
+        ),
+    };
+    let _ = terminal.flush();
+}
+
+/// Whether this terminal's window has focus, as far as X11 tells: `$WINDOWID` compared with the
+/// active window reported by `xdotool`. `None` when either is unavailable.
+fn terminal_focused() -> Option<bool> {
+    let window: u64 = var(ENVIRONMENT_VARIABLE_WINDOWID).ok()?.trim().parse().ok()?;
+    let output = Command::new("xdotool")
+        .arg("getactivewindow")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let active: u64 = String::from_utf8_lossy(&output.stdout).trim().parse().ok()?;
+    Some(active == window)
+}
+
+/// The first line of `command_line`, shortened and without control characters, which would end
+/// the escape sequence early.
+fn notification_text(command_line: &str) -> String {
+    let first_line = command_line.trim().lines().next().unwrap_or_default();
+    let mut text: String = first_line
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(MAX_NOTIFICATION_COMMAND_CHARS)
+        .collect();
+    if first_line.chars().count() > MAX_NOTIFICATION_COMMAND_CHARS || command_line.trim().contains('\n') {
+        text.push_str("...");
+    }
+    text
+}
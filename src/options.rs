@@ -0,0 +1,177 @@
+use crate::config::ShellConfig;
+use crate::parser::ENVIRONMENT_VARIABLE_TRACE;
+#[cfg(feature = "readline")]
+use rustyline::CompletionType;
+use std::env::var;
+use std::io;
+use std::io::IsTerminal;
+use std::io::Write;
+
+pub const OPTION_ERREXIT: &str = "errexit";
+pub const OPTION_HISTEXPAND: &str = "histexpand";
+pub const OPTION_IGNOREEOF: &str = "ignoreeof";
+pub const OPTION_NOUNSET: &str = "nounset";
+pub const OPTION_TRACE: &str = "trace";
+pub const OPTION_XTRACE: &str = "xtrace";
+pub const SHOPT_COMPLETION_BELL: &str = "completion_bell";
+pub const SHOPT_DOTGLOB: &str = "dotglob";
+pub const SHOPT_GLOBSTAR: &str = "globstar";
+pub const SHOPT_LOGIN_SHELL: &str = "login_shell";
+pub const SHOPT_MENU_COMPLETE: &str = "menu_complete";
+pub const SHOPT_NOCASEGLOB: &str = "nocaseglob";
+pub const SHOPT_NULLGLOB: &str = "nullglob";
+/// Consecutive EOFs tolerated by `ignoreeof` before the shell exits anyway (bash's default).
+pub const IGNOREEOF_LIMIT: usize = 10;
+
+/// Shell options toggled with `set -o name` / `set +o name` and `shopt -s name` / `shopt -u name`.
+#[derive(Clone, Debug)]
+pub struct ShellOptions {
+    /// Exit when a command fails, unless its status is being tested.
+    pub errexit: bool,
+    /// Expand `!` history references in lines read at the prompt. Starts on when stdin is a terminal.
+    pub histexpand: bool,
+    /// Ignore Ctrl-D at an empty prompt instead of exiting.
+    pub ignoreeof: bool,
+    /// Treat expanding an unset parameter as an error.
+    pub nounset: bool,
+    /// Log tokens, resolved commands, redirections, spawns, and exit statuses on stderr.
+    /// Starts on when `CCSH_TRACE=1`.
+    pub trace: bool,
+    /// Print each simple command, after expansion, on stderr before it runs, prefixed by `$PS4`.
+    pub xtrace: bool,
+    /// Cycle through completion candidates in place instead of listing them.
+    pub menu_complete: bool,
+    /// Ring the bell on ambiguous or failed completion.
+    pub completion_bell: bool,
+    /// Most matches per row when listing filename completions; 0 puts them all on one row.
+    pub completion_max_columns: usize,
+    /// Started as a login shell (`argv[0]` begins with `-`). Read-only for `shopt`.
+    pub login_shell: bool,
+    /// Let a `**` path component match any number of nested directories.
+    pub globstar: bool,
+    /// Expand a pattern that matches no file to nothing instead of leaving it as written.
+    pub nullglob: bool,
+    /// Let wildcards match file names starting with `.`.
+    pub dotglob: bool,
+    /// Match file names regardless of case during pathname expansion.
+    pub nocaseglob: bool,
+}
+
+impl Default for ShellOptions {
+    fn default() -> Self {
+        Self::from(&ShellConfig::default())
+    }
+}
+
+impl From<&ShellConfig> for ShellOptions {
+    fn from(config: &ShellConfig) -> Self {
+        Self {
+            errexit: false,
+            histexpand: io::stdin().is_terminal(),
+            ignoreeof: false,
+            nounset: false,
+            trace: var(ENVIRONMENT_VARIABLE_TRACE).is_ok_and(|value| value == "1"),
+            xtrace: false,
+            #[cfg(feature = "readline")]
+            menu_complete: config.completion_type == CompletionType::Circular,
+            #[cfg(not(feature = "readline"))]
+            menu_complete: false,
+            completion_bell: config.completion_bell,
+            completion_max_columns: config.completion_max_columns,
+            login_shell: false,
+            globstar: false,
+            nullglob: false,
+            dotglob: false,
+            nocaseglob: false,
+        }
+    }
+}
+
+impl ShellOptions {
+    /// Set the `set -o` option called `name`.
+    pub fn set_named(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        match name {
+            OPTION_ERREXIT => self.errexit = enabled,
+            OPTION_HISTEXPAND => self.histexpand = enabled,
+            OPTION_IGNOREEOF => self.ignoreeof = enabled,
+            OPTION_NOUNSET => self.nounset = enabled,
+            OPTION_TRACE => self.trace = enabled,
+            OPTION_XTRACE => self.xtrace = enabled,
+            _ => return Err(format!("{name}: invalid option name")),
+        }
+        Ok(())
+    }
+
+    /// Set the option with the one-letter `set` flag `flag`: `e` for `errexit`, `u` for `nounset`,
+    /// and `x` for `xtrace`.
+    pub fn set_flag(&mut self, flag: char, enabled: bool) -> Result<(), String> {
+        let name = match flag {
+            'e' => OPTION_ERREXIT,
+            'u' => OPTION_NOUNSET,
+            'x' => OPTION_XTRACE,
+            _ => return Err(format!("-{flag}: invalid option")),
+        };
+        self.set_named(name, enabled)
+    }
+
+    /// All `set -o` options as `(name, enabled)` pairs, sorted by name.
+    #[must_use]
+    pub fn named(&self) -> Vec<(&'static str, bool)> {
+        vec![
+            (OPTION_ERREXIT, self.errexit),
+            (OPTION_HISTEXPAND, self.histexpand),
+            (OPTION_IGNOREEOF, self.ignoreeof),
+            (OPTION_NOUNSET, self.nounset),
+            (OPTION_TRACE, self.trace),
+            (OPTION_XTRACE, self.xtrace),
+        ]
+    }
+
+    /// Set the `shopt` option called `name`.
+    pub fn set_shopt(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        match name {
+            SHOPT_COMPLETION_BELL => self.completion_bell = enabled,
+            SHOPT_DOTGLOB => self.dotglob = enabled,
+            SHOPT_GLOBSTAR => self.globstar = enabled,
+            SHOPT_MENU_COMPLETE => self.menu_complete = enabled,
+            SHOPT_NOCASEGLOB => self.nocaseglob = enabled,
+            SHOPT_NULLGLOB => self.nullglob = enabled,
+            SHOPT_LOGIN_SHELL => return Err(format!("{name}: cannot set option")),
+            _ => return Err(format!("{name}: invalid shell option name")),
+        }
+        Ok(())
+    }
+
+    /// All `shopt` options as `(name, enabled)` pairs, sorted by name.
+    #[must_use]
+    pub fn shopt_named(&self) -> Vec<(&'static str, bool)> {
+        vec![
+            (SHOPT_COMPLETION_BELL, self.completion_bell),
+            (SHOPT_DOTGLOB, self.dotglob),
+            (SHOPT_GLOBSTAR, self.globstar),
+            (SHOPT_LOGIN_SHELL, self.login_shell),
+            (SHOPT_MENU_COMPLETE, self.menu_complete),
+            (SHOPT_NOCASEGLOB, self.nocaseglob),
+            (SHOPT_NULLGLOB, self.nullglob),
+        ]
+    }
+
+    /// The editor completion mode selected by `menu_complete`.
+    #[cfg(feature = "readline")]
+    #[must_use]
+    pub fn completion_type(&self) -> CompletionType {
+        if self.menu_complete {
+            CompletionType::Circular
+        } else {
+            CompletionType::List
+        }
+    }
+}
+
+/// Print `(name, enabled)` pairs in `set -o` / `shopt` format.
+pub fn list_options(named: &[(&str, bool)], out: &mut dyn Write) {
+    for (name, enabled) in named {
+        let state = if *enabled { "on" } else { "off" };
+        let _ = writeln!(out, "{name:<15}\t{state}");
+    }
+}
@@ -0,0 +1,164 @@
+use crate::alias::expand_aliases;
+use crate::compound::parse_compound_command;
+use crate::error::ShellError;
+use crate::lexer::split_command_lines;
+use crate::parser::parse_function_header;
+use crate::parser::parse_input;
+use crate::parser::split_command_list;
+use crate::parser::strip_negation;
+use crate::parser::take_function_body;
+use crate::parser::Connector;
+use crate::parser::ParseError;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+
+const INDENT: &str = "  ";
+
+/// Print the structure `input` parses into on `out`, without running or expanding anything, for
+/// `--parse-only` and `debug parse`.
+///
+/// Each command line is shown after alias expansion, then each pipeline of its command list with
+/// the connector before it, then the commands of the pipeline as the parser leaves them:
+/// `NAME="value" ["arg", ...] 1>file 2>&1 &`, with `$(...)` and parameters as written. A
+/// compound command is shown as its syntax tree and a function definition as its name and body.
+/// Syntax errors go to `errors` as the shell reports them, and the rest of the input is still
+/// shown. Returns `2` when there was one, as running the input would, and `0` otherwise.
+pub fn dump_parse(
+    input: &str,
+    aliases: &BTreeMap<String, String>,
+    out: &mut dyn Write,
+    errors: &mut dyn Write,
+) -> io::Result<i32> {
+    let mut status = 0;
+    let mut report = |error: ParseError| status = ShellError::ParseError(error).report(errors);
+    let mut command_lines = split_command_lines(input).into_iter();
+    while let Some(command_line) = command_lines.next() {
+        let mut command_line = command_line.trim().to_string();
+        while let Some((name, rest)) = parse_function_header(&command_line) {
+            match take_function_body(rest, &mut command_lines) {
+                Ok((body, after)) => {
+                    writeln!(out, "function: {name}")?;
+                    writeln!(out, "{INDENT}body: {body}")?;
+                    command_line = after;
+                }
+                Err(error) => {
+                    report(error);
+                    command_line.clear();
+                }
+            }
+        }
+        if command_line.is_empty() {
+            continue;
+        }
+
+        let command_line = expand_aliases(&command_line, aliases);
+        writeln!(out, "line: {command_line}")?;
+        let list = match split_command_list(&command_line) {
+            Ok(list) => list,
+            Err(error) => {
+                report(error);
+                continue;
+            }
+        };
+        for (connector, pipeline) in list {
+            let connector = match connector {
+                Connector::Always => "",
+                Connector::And => "&& ",
+                Connector::Or => "|| ",
+            };
+            writeln!(out, "{INDENT}{connector}pipeline: {}", pipeline.trim())?;
+            if let Err(error) = dump_pipeline(&pipeline, out)? {
+                report(error);
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// Print what one pipeline of a command list is made of: a `negated` line for each leading `!`,
+/// then its compound command or the commands of its stages.
+fn dump_pipeline(mut pipeline: &str, out: &mut dyn Write) -> io::Result<Result<(), ParseError>> {
+    let indent = INDENT.repeat(2);
+    while let Some(negated) = strip_negation(pipeline) {
+        writeln!(out, "{indent}negated")?;
+        pipeline = negated;
+    }
+    match parse_compound_command(pipeline) {
+        Some(Ok(compound)) => writeln!(out, "{indent}compound: {compound:?}")?,
+        Some(Err(error)) => return Ok(Err(error)),
+        None => match parse_input(pipeline) {
+            Ok(commands) => {
+                for command in commands {
+                    writeln!(out, "{indent}command: {command}")?;
+                }
+            }
+            Err(error) => return Ok(Err(error)),
+        },
+    }
+    Ok(Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What `input` dumps to on `out` and on `errors`, and the status.
+    fn dump(input: &str) -> (String, String, i32) {
+        let aliases = BTreeMap::from([("ll".to_string(), "ls -l".to_string())]);
+        let (mut out, mut errors) = (Vec::new(), Vec::new());
+        let status = dump_parse(input, &aliases, &mut out, &mut errors).unwrap();
+        (
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(errors).unwrap(),
+            status,
+        )
+    }
+
+    #[test]
+    fn pipelines() {
+        let input =
+            "X=1 ll \"$HOME\"/*.rs 2>&1 >out.txt <in.txt | grep -v $(id -u) && ! true || cat <<EOF &\nbody $x\nEOF";
+        let expected = r#"line: X=1 ls -l "$HOME"/*.rs 2>&1 >out.txt <in.txt | grep -v $(id -u) && ! true || cat <<EOF &
+body $x
+EOF
+  pipeline: X=1 ls -l "$HOME"/*.rs 2>&1 >out.txt <in.txt | grep -v $(id -u)
+    command: X="1" ["ls", "-l", "$HOME/*.rs"] 2>&1 1>out.txt <in.txt
+    command: ["grep", "-v", "$(id -u)"]
+  && pipeline: ! true
+    negated
+    command: ["true"]
+  || pipeline: cat <<EOF &
+body $x
+EOF
+    command: ["cat"] <<"body $x\n" &
+"#;
+        assert_eq!(dump(input), (expected.to_string(), String::new(), 0));
+    }
+
+    #[test]
+    fn functions_compounds_and_errors() {
+        let input = "f() {\n  echo hi\n}; f\nif true; then :; fi; cat <\necho after";
+        let expected = r#"function: f
+  body: echo hi
+line: f
+  pipeline: f
+    command: ["f"]
+line: if true; then :; fi; cat <
+  pipeline: if true; then :; fi
+    compound: If { branches: [("true;", ":;")], otherwise: None }
+  pipeline: cat <
+line: echo after
+  pipeline: echo after
+    command: ["echo", "after"]
+"#;
+        assert_eq!(
+            dump(input),
+            (
+                expected.to_string(),
+                "ccsh: syntax error: unexpected end of input\n".to_string(),
+                2
+            )
+        );
+    }
+}
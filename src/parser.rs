@@ -1,97 +1,535 @@
+use crate::arithmetic::ArithmeticError;
+use crate::brace::expand_braces;
+use crate::brace::BRACE_SPECIAL_CHARS;
+use crate::glob::expand_pathname;
+use crate::glob::GlobOptions;
+use crate::home::expand_tilde;
+use crate::lexer::lex;
+use crate::lexer::reserved_word;
+use crate::lexer::split_command_lines;
+use crate::pattern::has_wildcards;
+use crate::pattern::push_literal;
+use crate::pattern::remove_matching_prefix;
+use crate::pattern::remove_matching_suffix;
+use crate::pattern::replace_matches;
+use crate::pattern::unescape;
+use crate::pattern::PATTERN_ESCAPE;
+use std::ffi::OsString;
+use std::fmt;
+use std::iter::Peekable;
+use std::ops::Range;
+use std::os::fd::RawFd;
+use std::str::Chars;
+use std::str::Lines;
+
+pub const CHAR_AMPERSAND: char = '&';
 pub const CHAR_BACKSLASH: char = '\\';
 pub const CHAR_BACKTICK: char = '`';
+pub const CHAR_BRACE_CLOSE: char = '}';
+pub const CHAR_BRACE_OPEN: char = '{';
 pub const CHAR_CARRIAGE_RETURN: char = '\r';
+pub const CHAR_COMMENT: char = '#';
 pub const CHAR_EXCLAMATION_MARK: char = '!';
 pub const CHAR_DOLLAR_SIGN: char = '$';
 pub const CHAR_DOUBLE_QUOTE: char = '"';
+pub const CHAR_EQUALS_SIGN: char = '=';
 pub const CHAR_GREATER_THAN: char = '>';
+pub const CHAR_LESS_THAN: char = '<';
 pub const CHAR_NEWLINE: char = '\n';
 pub const CHAR_NULL: char = '\0';
+pub const CHAR_PAREN_CLOSE: char = ')';
+pub const CHAR_PAREN_OPEN: char = '(';
 pub const CHAR_PIPE: char = '|';
+pub const CHAR_SEMICOLON: char = ';';
 pub const CHAR_SINGLE_QUOTE: char = '\'';
 pub const CHAR_TAB: char = '\t';
+pub const CHAR_TILDE: char = '~';
+pub const COMMAND_ALIAS: &str = "alias";
+pub const COMMAND_BG: &str = "bg";
 pub const COMMAND_CD: &str = "cd";
+/// `:`, which does nothing and succeeds, for the side effects of expanding its arguments.
+pub const COMMAND_COLON: &str = ":";
+pub const COMMAND_DEBUG: &str = "debug";
 pub const COMMAND_ECHO: &str = "echo";
 pub const COMMAND_ECHO_FLAG_EXPAND_ESCAPE: &str = "-e";
 pub const COMMAND_EXIT: &str = "exit";
+pub const COMMAND_PRINTF: &str = "printf";
+pub const COMMAND_RETURN: &str = "return";
 pub const COMMAND_PWD: &str = "pwd";
+pub const COMMAND_SET: &str = "set";
+pub const COMMAND_SHOPT: &str = "shopt";
+pub const COMMAND_TEST: &str = "test";
+/// `[ expression ]`, the same as `test expression`.
+pub const COMMAND_TEST_BRACKET: &str = "[";
 pub const COMMAND_TYPE: &str = "type";
+pub const COMMAND_UNALIAS: &str = "unalias";
+pub const COMMAND_HASH: &str = "hash";
 pub const COMMAND_HISTORY: &str = "history";
 pub const COMMAND_JOBS: &str = "jobs";
+pub const COMMAND_JUMP: &str = "j";
+pub const COMMAND_KILL: &str = "kill";
+pub const COMMAND_SESSION: &str = "session";
+pub const COMMAND_Z: &str = "z";
+pub const BUILTIN_COMMANDS: &[&str] = &[
+    COMMAND_ALIAS,
+    COMMAND_BG,
+    COMMAND_CD,
+    COMMAND_COLON,
+    COMMAND_DEBUG,
+    COMMAND_ECHO,
+    COMMAND_EXIT,
+    COMMAND_PRINTF,
+    COMMAND_PWD,
+    COMMAND_RETURN,
+    COMMAND_TYPE,
+    COMMAND_HISTORY,
+    COMMAND_HASH,
+    COMMAND_JOBS,
+    COMMAND_JUMP,
+    COMMAND_KILL,
+    COMMAND_SESSION,
+    COMMAND_SET,
+    COMMAND_SHOPT,
+    COMMAND_TEST,
+    COMMAND_TEST_BRACKET,
+    COMMAND_UNALIAS,
+    COMMAND_Z,
+];
+pub const ENVIRONMENT_VARIABLE_CLICOLOR: &str = "CLICOLOR";
+pub const ENVIRONMENT_VARIABLE_CLICOLOR_FORCE: &str = "CLICOLOR_FORCE";
+pub const ENVIRONMENT_VARIABLE_CONFIG: &str = "CCSH_CONFIG";
+pub const ENVIRONMENT_VARIABLE_EXECIGNORE: &str = "EXECIGNORE";
+pub const ENVIRONMENT_VARIABLE_FUZZY_COMPLETION: &str = "CCSH_FUZZY_COMPLETION";
+pub const ENVIRONMENT_VARIABLE_HISTFILE: &str = "HISTFILE";
 pub const ENVIRONMENT_VARIABLE_HOME: &str = "HOME";
+pub const ENVIRONMENT_VARIABLE_IFS: &str = "IFS";
+pub const ENVIRONMENT_VARIABLE_NO_COLOR: &str = "NO_COLOR";
+pub const ENVIRONMENT_VARIABLE_OLDPWD: &str = "OLDPWD";
 pub const ENVIRONMENT_VARIABLE_PATH: &str = "PATH";
 pub const ENVIRONMENT_VARIABLE_PATH_DELIMITER: char = ':';
+pub const ENVIRONMENT_VARIABLE_PROMPT_DIRTRIM: &str = "PROMPT_DIRTRIM";
+pub const ENVIRONMENT_VARIABLE_PS1: &str = "PS1";
+pub const ENVIRONMENT_VARIABLE_PS2: &str = "PS2";
+pub const ENVIRONMENT_VARIABLE_PS4: &str = "PS4";
+pub const ENVIRONMENT_VARIABLE_PWD: &str = "PWD";
+pub const ENVIRONMENT_VARIABLE_SHELL: &str = "SHELL";
+pub const ENVIRONMENT_VARIABLE_TERM: &str = "TERM";
+pub const ENVIRONMENT_VARIABLE_TRACE: &str = "CCSH_TRACE";
+pub const ENVIRONMENT_VARIABLE_WINDOWID: &str = "WINDOWID";
+pub const ENVIRONMENT_VARIABLE_XDG_CACHE_HOME: &str = "XDG_CACHE_HOME";
+pub const ENVIRONMENT_VARIABLE_XDG_CONFIG_HOME: &str = "XDG_CONFIG_HOME";
+pub const ENVIRONMENT_VARIABLE_XDG_STATE_HOME: &str = "XDG_STATE_HOME";
+/// Ends the list of a `case` pattern.
+pub const CASE_BREAK: &str = ";;";
+pub const HERE_DOCUMENT: &str = "<<";
+pub const HERE_DOCUMENT_STRIP_TABS: &str = "<<-";
+/// The field separators when `IFS` is unset: space, tab, and newline.
+pub const DEFAULT_FIELD_SEPARATORS: &str = " \t\n";
 pub const HOME_DIRECTORY: &str = "~";
+pub const NULL_DEVICE: &str = "/dev/null";
+pub const RESERVED_WORD_CASE: &str = "case";
+pub const RESERVED_WORD_CONDITIONAL_END: &str = "]]";
+pub const RESERVED_WORD_CONDITIONAL_START: &str = "[[";
+pub const RESERVED_WORD_ELIF: &str = "elif";
+pub const RESERVED_WORD_ELSE: &str = "else";
+pub const RESERVED_WORD_ESAC: &str = "esac";
+pub const RESERVED_WORD_FI: &str = "fi";
+pub const RESERVED_WORD_IF: &str = "if";
+pub const RESERVED_WORD_IN: &str = "in";
+/// Negates the status of the pipeline it starts.
+pub const RESERVED_WORD_NOT: &str = "!";
+pub const RESERVED_WORD_THEN: &str = "then";
+/// The words with a meaning to the shell when they start a command, as `if` does.
+pub const RESERVED_WORDS: &[&str] = &[
+    RESERVED_WORD_CASE,
+    RESERVED_WORD_CONDITIONAL_END,
+    RESERVED_WORD_CONDITIONAL_START,
+    RESERVED_WORD_ELIF,
+    RESERVED_WORD_ELSE,
+    RESERVED_WORD_ESAC,
+    RESERVED_WORD_FI,
+    RESERVED_WORD_IF,
+    RESERVED_WORD_IN,
+    RESERVED_WORD_NOT,
+    RESERVED_WORD_THEN,
+];
+/// The reserved words a command can follow, as in `if true`, `then echo`, or `! grep`.
+pub const RESERVED_WORDS_BEFORE_COMMAND: &[&str] = &[
+    RESERVED_WORD_ELIF,
+    RESERVED_WORD_ELSE,
+    RESERVED_WORD_IF,
+    RESERVED_WORD_NOT,
+    RESERVED_WORD_THEN,
+];
+/// The operators of `${name:-word}`, `${name:=word}`, `${name:?message}`, and `${name:+word}`.
+pub const PARAMETER_OPERATOR_ALTERNATIVE: char = '+';
+pub const PARAMETER_OPERATOR_ASSIGN: char = '=';
+pub const PARAMETER_OPERATOR_DEFAULT: char = '-';
+pub const PARAMETER_OPERATOR_ERROR: char = '?';
+pub const PARAMETER_OPERATOR_PREFIX: char = ':';
+/// `${#name}`, and the operators of `${name#pattern}`, `${name%pattern}`, and `${name/pattern/string}`,
+/// which are doubled for their greedy or global forms.
+pub const PARAMETER_LENGTH: char = '#';
+pub const PARAMETER_OPERATOR_REMOVE_PREFIX: char = '#';
+pub const PARAMETER_OPERATOR_REMOVE_SUFFIX: char = '%';
+pub const PARAMETER_OPERATOR_REPLACE: char = '/';
+pub const PARAMETER_NULL_OR_UNSET: &str = "parameter null or not set";
+pub const SHELL_CONTINUATION_PROMPT: &str = "> ";
 pub const SHELL_PROMPT: &str = "$ ";
-pub const STDERR_FILE_DESCRIPTOR: char = '2';
-pub const STDOUT_FILE_DESCRIPTOR: char = '1';
+/// The one-character parameters besides the digits: `$!`, `$#`, `$$`, `$*`, `$?`, `$@`.
+pub const SPECIAL_PARAMETERS: [char; 6] = ['!', '#', '$', '*', '?', '@'];
+pub const STDERR_DESCRIPTOR: RawFd = 2;
+pub const STDIN_DESCRIPTOR: RawFd = 0;
+pub const STDOUT_DESCRIPTOR: RawFd = 1;
 pub const STDOUT_STDERR_FILE_DESCRIPTOR: char = '&';
+/// Starts each command `set -x` prints when `PS4` is unset.
+pub const XTRACE_PREFIX: &str = "+ ";
 
-#[derive(Clone, Debug)]
+/// `N> file` (truncating) or `N>> file` (`append_to`).
+#[derive(Clone, Debug, PartialEq)]
 pub struct OutputRedirection {
-    pub file_name: Option<String>,
+    pub file_name: String,
     pub append_to: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct ParsedCommand {
-    pub tokens: Option<Vec<String>>,
-    pub stdout: OutputRedirection,
-    pub stderr: OutputRedirection,
+    /// The command name and its arguments, as passed to the executable.
+    pub tokens: Option<Vec<OsString>>,
+    /// Leading `NAME=value` words, set in the environment of this command only.
+    pub assignments: Vec<(String, String)>,
+    /// Redirections with the file descriptor they replace, in the order written. They are applied
+    /// left to right, so a later one for the same descriptor wins.
+    pub redirections: Vec<(RawFd, Redirection)>,
+    /// The command list of a `( ... )` group, run in a subshell in place of `tokens`.
+    pub subshell: Option<String>,
     pub background: bool,
 }
 
-pub fn expand_escape_sequences(string: &str) -> String {
-    let mut result = String::with_capacity(string.len());
-    let mut characters = string.chars();
+/// One pipeline stage as `NAME="value" ["arg", ...] 1>file 2>>file 2>&1 &`, or `(list) ...` for a
+/// group, with the redirections in order, so it reads like the input.
+impl fmt::Display for ParsedCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.assignments {
+            write!(f, "{name}={value:?} ")?;
+        }
+        match &self.subshell {
+            Some(list) => write!(f, "({list})")?,
+            None => write!(f, "{:?}", self.tokens.as_deref().unwrap_or_default())?,
+        }
+        for (number, redirection) in &self.redirections {
+            let descriptor = if *number == STDIN_DESCRIPTOR {
+                String::new()
+            } else {
+                number.to_string()
+            };
+            match redirection {
+                Redirection::Output(output) => {
+                    let operator = if output.append_to { ">>" } else { ">" };
+                    write!(f, " {descriptor}{operator}{}", output.file_name)?;
+                }
+                Redirection::Input(InputRedirection::File(file_name)) => write!(f, " {descriptor}<{file_name}")?,
+                Redirection::Input(InputRedirection::HereDocument(body)) => write!(f, " {descriptor}<<{body:?}")?,
+                Redirection::Duplicate(source) => write!(f, " {number}>&{source}")?,
+                Redirection::Close => write!(f, " {number}>&-")?,
+            }
+        }
+        if self.background {
+            write!(f, " &")?;
+        }
+        Ok(())
+    }
+}
+
+/// What a redirected file descriptor is opened on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Redirection {
+    Output(OutputRedirection),
+    Input(InputRedirection),
+    /// `N>&M` or `N<&M`: a copy of descriptor `M`, as the redirections before this one leave it.
+    Duplicate(RawFd),
+    /// `N>&-` or `N<&-`: the descriptor is closed.
+    Close,
+}
+
+/// Where a descriptor reads from, when not inherited from the shell or the previous pipeline stage.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputRedirection {
+    /// `N< file`.
+    File(String),
+    /// `N<< delimiter`: the text of the here-document body, expanded unless the delimiter was quoted.
+    HereDocument(String),
+}
+
+/// What makes a command line invalid syntax.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A token that cannot appear where it is, such as a `|` with no command before it.
+    UnexpectedToken(String),
+    /// The input ends where more is needed: after `|`, `&&`, or a redirection operator, or before
+    /// the closing token it expects.
+    UnexpectedEnd(Option<char>),
+}
+
+/// A command line that is not valid syntax, and where in it the problem is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// The byte span of the offending token in the input; empty at the end of the input.
+    pub span: Range<usize>,
+    /// The column where `span` starts on its line, counting characters from 1.
+    pub column: usize,
+}
+
+impl ParseError {
+    /// The error for the token at `span` in `input`.
+    #[must_use]
+    pub fn unexpected_token(input: &str, span: Range<usize>) -> Self {
+        Self::new(
+            input,
+            ParseErrorKind::UnexpectedToken(input[span.clone()].to_string()),
+            span,
+        )
+    }
+
+    /// The error for `input` ending early, before the closing token `expected` if there is one.
+    #[must_use]
+    pub fn unexpected_end(input: &str, expected: Option<char>) -> Self {
+        Self::new(input, ParseErrorKind::UnexpectedEnd(expected), input.len()..input.len())
+    }
+
+    fn new(input: &str, kind: ParseErrorKind, span: Range<usize>) -> Self {
+        let line = input[..span.start].rsplit(CHAR_NEWLINE).next().unwrap_or_default();
+        Self {
+            kind,
+            column: line.chars().count() + 1,
+            span,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ParseErrorKind::UnexpectedToken(token) => {
+                write!(
+                    f,
+                    "syntax error near unexpected token `{token}` at column {}",
+                    self.column
+                )
+            }
+            ParseErrorKind::UnexpectedEnd(None) => write!(f, "syntax error: unexpected end of input"),
+            ParseErrorKind::UnexpectedEnd(Some(expected)) => {
+                write!(f, "syntax error: unexpected end of input, expected `{expected}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The words of the command being parsed, in pattern form: leading `NAME=value` assignments, then
+/// the command name and its arguments.
+#[derive(Default)]
+struct Words {
+    assignments: Vec<(String, String)>,
+    arguments: Vec<String>,
+}
+
+/// The redirections of the command being parsed, and which of them the next word is the target of.
+#[derive(Default)]
+struct Redirections {
+    table: Vec<(RawFd, Redirection)>,
+    /// The descriptors the next word is the output file of: one for `N>`, stdout and stderr for `&>`.
+    in_output: Vec<RawFd>,
+    append_to: bool,
+    /// `N<` was read, so the next word is the file descriptor `N` reads.
+    in_input: Option<RawFd>,
+    /// `N<<` (`false`) or `N<<-` (`true`) was read, so the next word is a here-document delimiter.
+    in_here_document: Option<(RawFd, bool)>,
+    /// The pending `>` or `<` was `>&` or `<&`, so the next word is a descriptor to duplicate or `-`.
+    duplicate: bool,
+}
+
+impl Redirections {
+    /// Whether a redirection operator was read and still waits for its target.
+    fn is_pending(&self) -> bool {
+        self.is_target_pending() || self.in_here_document.is_some()
+    }
+
+    /// Whether the next word is the file name, or descriptor, of an input or output redirection.
+    fn is_target_pending(&self) -> bool {
+        !self.in_output.is_empty() || self.in_input.is_some()
+    }
+}
+
+/// The bytes `string` stands for with the escape sequences of `echo -e` expanded, and whether a
+/// `\c` ended it, which suppresses all further output.
+///
+/// Besides `\n`, `\t`, `\r`, `\\`, `\"`, and `\'`, these are `\a` (bell), `\b` (backspace),
+/// `\e` (escape), `\f` (form feed), `\v` (vertical tab), `\0NNN` (the byte with up to three
+/// octal digits), `\xHH` (the byte with up to two hex digits), and `\uXXXX` (the character with
+/// up to four hex digits, in UTF-8). Any other backslash, or one without the digits it needs, is
+/// kept as written.
+#[must_use]
+pub fn expand_escape_sequences(string: &str) -> (Vec<u8>, bool) {
+    let mut result = Vec::with_capacity(string.len());
+    let mut characters = string.chars().peekable();
+    let mut buffer = [0; 4];
+    let mut push = |result: &mut Vec<u8>, character: char| {
+        result.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+    };
 
     while let Some(character) = characters.next() {
-        if character == CHAR_BACKSLASH {
-            if let Some(next) = characters.next() {
-                match next {
-                    'n' => result.push(CHAR_NEWLINE),
-                    't' => result.push(CHAR_TAB),
-                    'r' => result.push(CHAR_CARRIAGE_RETURN),
-                    CHAR_BACKSLASH => result.push(CHAR_BACKSLASH),
-                    '0' => result.push(CHAR_NULL),
-                    CHAR_DOUBLE_QUOTE => result.push(CHAR_DOUBLE_QUOTE),
-                    CHAR_SINGLE_QUOTE => result.push(CHAR_SINGLE_QUOTE),
-                    _ => {
-                        result.push(CHAR_BACKSLASH);
-                        result.push(next);
-                    }
-                }
+        if character != CHAR_BACKSLASH {
+            push(&mut result, character);
+            continue;
+        }
+        let Some(next) = characters.next() else {
+            push(&mut result, character);
+            break;
+        };
+        match next {
+            'a' => result.push(0x07),
+            'b' => result.push(0x08),
+            'c' => return (result, true),
+            'e' | 'E' => result.push(0x1b),
+            'f' => result.push(0x0c),
+            'n' => push(&mut result, CHAR_NEWLINE),
+            'r' => push(&mut result, CHAR_CARRIAGE_RETURN),
+            't' => push(&mut result, CHAR_TAB),
+            'v' => result.push(0x0b),
+            CHAR_BACKSLASH | CHAR_DOUBLE_QUOTE | CHAR_SINGLE_QUOTE => push(&mut result, next),
+            // Octal values above 0o377 wrap to a byte, as in bash.
+            '0' => result.push(take_digits(&mut characters, 8, 3).unwrap_or_default() as u8),
+            'x' => match take_digits(&mut characters, 16, 2) {
+                Some(value) => result.push(value as u8),
+                None => result.extend_from_slice(b"\\x"),
+            },
+            'u' => match take_digits(&mut characters, 16, 4).and_then(char::from_u32) {
+                Some(value) => push(&mut result, value),
+                None => result.extend_from_slice(b"\\u"),
+            },
+            _ => {
+                push(&mut result, CHAR_BACKSLASH);
+                push(&mut result, next);
             }
-        } else {
-            result.push(character);
         }
     }
 
-    result
+    (result, false)
+}
+
+/// Consume up to `most` digits in `radix`, returning their value, or `None` when there are none.
+fn take_digits(characters: &mut Peekable<Chars>, radix: u32, most: usize) -> Option<u32> {
+    let mut value = None;
+    for _ in 0..most {
+        let Some(digit) = characters.peek().and_then(|c| c.to_digit(radix)) else {
+            break;
+        };
+        characters.next();
+        value = Some(value.unwrap_or(0) * radix + digit);
+    }
+    value
 }
 
+/// The expansions the parser leaves to its caller, because they need the shell's state.
+pub trait Expander {
+    /// The output of `command` for a `$(command)` substitution, or `None` to leave it as written.
+    fn substitute_command(&mut self, _command: &str) -> Option<String> {
+        None
+    }
+
+    /// The value of `expression` for a `$((expression))` expansion, usually from
+    /// [`evaluate_arithmetic`](crate::arithmetic::evaluate_arithmetic), or `None` to leave it as
+    /// written.
+    fn arithmetic(&mut self, _expression: &str) -> Option<Result<i64, ArithmeticError>> {
+        None
+    }
+
+    /// The value of the parameter `name` for a `$name` or `${name}` expansion (`HOME` for `$HOME`,
+    /// `?` for `$?`, `1` for `$1`), or `None` to leave it as written.
+    fn parameter(&mut self, _name: &str) -> Option<String> {
+        None
+    }
+
+    /// The positional parameters, which `$@` and `$*` expand to, or `None` to leave those as written.
+    fn arguments(&mut self) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Report through [`Expander::parameter_error`] that the parameter `name` is unset when that is
+    /// an error, as `set -u` makes it. Called before expanding `name`, except for `$@`, `$*`, and the
+    /// operators that test whether it is set (`:-`, `:=`, `:?`, and `:+`).
+    fn check_set(&mut self, _name: &str) {}
+
+    /// Set the variable `name` to `value`, for a `${name:=word}` expansion.
+    fn assign(&mut self, _name: &str, _value: &str) {}
+
+    /// Report that the parameter `name` could not be expanded, as `${name:?message}` does when it is
+    /// unset or empty. The command being parsed must not run.
+    fn parameter_error(&mut self, _name: &str, _message: &str) {}
+
+    /// The characters unquoted expansions are split into fields on, as `$IFS` holds them.
+    fn field_separators(&mut self) -> String {
+        DEFAULT_FIELD_SEPARATORS.to_string()
+    }
+
+    /// How wildcards match during pathname expansion.
+    fn glob_options(&self) -> GlobOptions {
+        GlobOptions::default()
+    }
+}
+
+/// An [`Expander`] without shell state: `$(...)` and parameters stay as written and globbing uses the defaults.
+pub struct NoExpansion;
+
+impl Expander for NoExpansion {}
+
+/// Parse `input` into a pipeline, leaving any `$(...)` command substitution or parameter as written.
+/// Fails as [`parse_input_with`] does.
+pub fn parse_input(input: &str) -> Result<Vec<ParsedCommand>, ParseError> {
+    parse_input_with(input, &mut NoExpansion)
+}
+
+/// Parse `input` into a pipeline, replacing each `$(command)` with what `expander` substitutes
+/// for `command`, or leaving it as written when that is `None`.
+///
+/// Trailing newlines are stripped from the output. Inside double quotes it is part of the
+/// current word; unquoted, it is split into fields on the expander's field separators
+/// (`$IFS`). A parameter (`$HOME`, `$?`, `$1`,
+/// `${10}`, `$*`) is replaced by its value from `expander` in the same way, except that `"$@"` is one word
+/// per positional parameter.
+///
+/// An unquoted `~` starting a word is expanded with [`expand_tilde`], along with the login name
+/// or `+`/`-` after it, up to the first `/`.
+///
+/// A `|` without a command on both sides, or a redirection operator without a target word, is a
+/// [`ParseError`] at that token, or at the end of the input.
 #[allow(clippy::too_many_lines)]
-pub fn parse_input(input: &str) -> Option<Vec<ParsedCommand>> {
+pub fn parse_input_with(input: &str, expander: &mut dyn Expander) -> Result<Vec<ParsedCommand>, ParseError> {
     let mut pipeline = Vec::new();
-    let mut characters = input.trim().chars().peekable();
+    let glob_options = expander.glob_options();
+    let (command_text, here_document_text) = split_here_documents(input.trim());
+    let text_offset = input.len() - input.trim_start().len();
+    // The span in `input` from `start` in `command_text` up to the next character.
+    let span_to = |start: usize, characters: &Peekable<Chars>| {
+        text_offset + start..text_offset + offset_of(command_text, characters)
+    };
+    let mut here_document_lines = here_document_text.lines();
+    let mut characters = command_text.chars().peekable();
+    // One scratch buffer for every word; `finish_word` copies each word out and clears it.
+    let mut current_token = WordBuffer::default();
 
     'pipeline: loop {
-        let mut tokens = Vec::new();
-        let mut stdout: OutputRedirection = OutputRedirection {
-            file_name: None,
-            append_to: false,
-        };
-        let mut stderr: OutputRedirection = OutputRedirection {
-            file_name: None,
-            append_to: false,
-        };
+        let mut words = Words::default();
+        let mut redirections = Redirections::default();
+        let mut background = false;
+        let mut subshell = None;
 
-        let mut current_token = String::new();
         let mut in_single_quotes = false;
         let mut in_double_quotes = false;
         let mut escape_next_char = false;
-        let mut in_stdout_redirection = false;
-        let mut in_stderr_redirection = false;
 
         while let Some(character) = characters.next() {
             match character {
@@ -102,6 +540,7 @@ pub fn parse_input(input: &str) -> Option<Vec<ParsedCommand>> {
                     } else {
                         // Toggle single-quote state
                         in_single_quotes = !in_single_quotes;
+                        current_token.quoted = true;
                     }
                 }
 
@@ -110,13 +549,17 @@ pub fn parse_input(input: &str) -> Option<Vec<ParsedCommand>> {
                         // Inside single quotes, a double quote is literal
                         current_token.push(character);
                     } else {
-                        // Toggle double-quote state
+                        // Toggle double-quote state; the opening quote makes the word quoted
                         in_double_quotes = !in_double_quotes;
+                        current_token.quoted |= in_double_quotes;
                     }
                 }
 
                 CHAR_BACKSLASH if !escape_next_char => {
-                    if in_single_quotes {
+                    if !in_single_quotes && characters.peek() == Some(&CHAR_NEWLINE) {
+                        // Line continuation: drop both the backslash and the newline
+                        characters.next();
+                    } else if in_single_quotes {
                         current_token.push(character);
                     } else if in_double_quotes {
                         if let Some(next_character) = characters.peek() {
@@ -131,127 +574,2234 @@ pub fn parse_input(input: &str) -> Option<Vec<ParsedCommand>> {
                         }
                     } else {
                         escape_next_char = true;
+                        current_token.quoted = true;
                     }
                 }
 
                 CHAR_PIPE if !escape_next_char && !in_single_quotes && !in_double_quotes => {
+                    finish_word(
+                        &mut current_token,
+                        &mut words,
+                        &mut redirections,
+                        &mut here_document_lines,
+                        expander,
+                    );
+                    let is_empty = words.arguments.is_empty()
+                        && words.assignments.is_empty()
+                        && redirections.table.is_empty()
+                        && subshell.is_none();
+                    if is_empty || redirections.is_pending() {
+                        let start = offset_of(command_text, &characters) - character.len_utf8();
+                        return Err(ParseError::unexpected_token(input, span_to(start, &characters)));
+                    }
                     pipeline.push(ParsedCommand {
-                        tokens: if tokens.is_empty() { None } else { Some(tokens) },
-                        stdout,
-                        stderr,
+                        tokens: expand_words(words.arguments, glob_options),
+                        assignments: words.assignments,
+                        redirections: redirections.table,
+                        subshell,
                         background: false,
                     });
                     continue 'pipeline;
                 }
 
-                file_descriptor if file_descriptor == STDOUT_FILE_DESCRIPTOR && current_token.is_empty() => {
-                    if let Some(next_character) = characters.peek() {
-                        if *next_character == CHAR_GREATER_THAN {
-                            in_stdout_redirection = true;
-                            characters.next();
-                        } else {
-                            current_token.push(file_descriptor);
-                        }
+                file_descriptor
+                    if file_descriptor.is_ascii_digit()
+                        && current_token.is_empty()
+                        && matches!(characters.peek(), Some(&(CHAR_GREATER_THAN | CHAR_LESS_THAN)))
+                        && !escape_next_char
+                        && !in_single_quotes
+                        && !in_double_quotes =>
+                {
+                    let start = offset_of(command_text, &characters) - character.len_utf8();
+                    let pending = redirections.is_pending();
+                    let descriptor = file_descriptor
+                        .to_digit(10)
+                        .and_then(|digit| RawFd::try_from(digit).ok());
+                    let descriptor = descriptor.unwrap_or_default();
+                    if characters.next() == Some(CHAR_GREATER_THAN) {
+                        redirections.in_output = vec![descriptor];
+                        redirections.append_to = characters.next_if_eq(&CHAR_GREATER_THAN).is_some();
+                        redirections.duplicate =
+                            !redirections.append_to && characters.next_if_eq(&CHAR_AMPERSAND).is_some();
+                    } else if characters.next_if_eq(&CHAR_LESS_THAN).is_some() {
+                        redirections.in_here_document = Some((descriptor, characters.next_if_eq(&'-').is_some()));
                     } else {
-                        current_token.push(file_descriptor);
+                        redirections.in_input = Some(descriptor);
+                        redirections.duplicate = characters.next_if_eq(&CHAR_AMPERSAND).is_some();
+                    }
+                    if pending {
+                        return Err(ParseError::unexpected_token(input, span_to(start, &characters)));
                     }
                 }
 
-                file_descriptor if file_descriptor == STDERR_FILE_DESCRIPTOR && current_token.is_empty() => {
-                    if let Some(next_character) = characters.peek() {
-                        if *next_character == CHAR_GREATER_THAN {
-                            in_stderr_redirection = true;
-                            characters.next();
-                        } else {
-                            current_token.push(file_descriptor);
-                        }
-                    } else {
-                        current_token.push(file_descriptor);
+                file_descriptor
+                    if file_descriptor == STDOUT_STDERR_FILE_DESCRIPTOR
+                        && characters.peek() == Some(&CHAR_GREATER_THAN)
+                        && !escape_next_char
+                        && !in_single_quotes
+                        && !in_double_quotes =>
+                {
+                    finish_word(
+                        &mut current_token,
+                        &mut words,
+                        &mut redirections,
+                        &mut here_document_lines,
+                        expander,
+                    );
+                    let start = offset_of(command_text, &characters) - character.len_utf8();
+                    let pending = redirections.is_pending();
+                    characters.next();
+                    redirections.in_output = vec![STDOUT_DESCRIPTOR, STDERR_DESCRIPTOR];
+                    redirections.append_to = characters.next_if_eq(&CHAR_GREATER_THAN).is_some();
+                    if pending {
+                        return Err(ParseError::unexpected_token(input, span_to(start, &characters)));
                     }
                 }
 
-                file_descriptor if file_descriptor == STDOUT_STDERR_FILE_DESCRIPTOR && current_token.is_empty() => {
-                    if let Some(next_character) = characters.peek() {
-                        if *next_character == CHAR_GREATER_THAN {
-                            in_stdout_redirection = true;
-                            in_stderr_redirection = true;
-                            characters.next();
-                        } else {
-                            current_token.push(file_descriptor);
+                CHAR_PAREN_OPEN
+                    if current_token.is_empty()
+                        && words.arguments.is_empty()
+                        && words.assignments.is_empty()
+                        && subshell.is_none()
+                        && !escape_next_char
+                        && !in_single_quotes
+                        && !in_double_quotes =>
+                {
+                    match take_substitution(&mut characters) {
+                        Ok(list) => subshell = Some(list),
+                        Err(unterminated) => {
+                            current_token.push(character);
+                            current_token.push_str(&unterminated);
                         }
-                    } else {
-                        current_token.push(file_descriptor);
                     }
                 }
 
-                redirect_operator
-                    if redirect_operator == CHAR_GREATER_THAN
-                        && !in_stdout_redirection
-                        && !in_stderr_redirection
+                CHAR_COMMENT
+                    if current_token.is_empty() && !escape_next_char && !in_single_quotes && !in_double_quotes =>
+                {
+                    skip_comment(&mut characters);
+                }
+
+                // A list separated by `&` is split by `split_command_list`, so `&` ends the command.
+                CHAR_AMPERSAND
+                    if redirections.in_output.is_empty()
                         && !escape_next_char
                         && !in_single_quotes
                         && !in_double_quotes =>
                 {
-                    in_stdout_redirection = true;
+                    background = true;
+                    break;
+                }
+
+                CHAR_LESS_THAN
+                    if characters.peek() == Some(&CHAR_LESS_THAN)
+                        && !escape_next_char
+                        && !in_single_quotes
+                        && !in_double_quotes =>
+                {
+                    finish_word(
+                        &mut current_token,
+                        &mut words,
+                        &mut redirections,
+                        &mut here_document_lines,
+                        expander,
+                    );
+                    let start = offset_of(command_text, &characters) - character.len_utf8();
+                    let pending = redirections.is_pending();
+                    characters.next();
+                    redirections.in_here_document = Some((STDIN_DESCRIPTOR, characters.next_if_eq(&'-').is_some()));
+                    if pending {
+                        return Err(ParseError::unexpected_token(input, span_to(start, &characters)));
+                    }
+                }
+
+                CHAR_LESS_THAN if !escape_next_char && !in_single_quotes && !in_double_quotes => {
+                    finish_word(
+                        &mut current_token,
+                        &mut words,
+                        &mut redirections,
+                        &mut here_document_lines,
+                        expander,
+                    );
+                    if redirections.is_pending() {
+                        let start = offset_of(command_text, &characters) - character.len_utf8();
+                        return Err(ParseError::unexpected_token(input, span_to(start, &characters)));
+                    }
+                    redirections.in_input = Some(STDIN_DESCRIPTOR);
+                    redirections.duplicate = characters.next_if_eq(&CHAR_AMPERSAND).is_some();
                 }
 
-                redirect_operator if redirect_operator == CHAR_GREATER_THAN => {
-                    stdout.append_to = in_stdout_redirection;
-                    stderr.append_to = in_stderr_redirection;
+                CHAR_GREATER_THAN if !escape_next_char && !in_single_quotes && !in_double_quotes => {
+                    finish_word(
+                        &mut current_token,
+                        &mut words,
+                        &mut redirections,
+                        &mut here_document_lines,
+                        expander,
+                    );
+                    let start = offset_of(command_text, &characters) - character.len_utf8();
+                    let pending = redirections.is_pending();
+                    redirections.in_output = vec![STDOUT_DESCRIPTOR];
+                    redirections.append_to = characters.next_if_eq(&CHAR_GREATER_THAN).is_some();
+                    redirections.duplicate =
+                        !redirections.append_to && characters.next_if_eq(&CHAR_AMPERSAND).is_some();
+                    if pending {
+                        return Err(ParseError::unexpected_token(input, span_to(start, &characters)));
+                    }
+                }
+
+                CHAR_DOLLAR_SIGN
+                    if !escape_next_char && !in_single_quotes && characters.peek() == Some(&CHAR_PAREN_OPEN) =>
+                {
+                    characters.next();
+                    let command = take_substitution(&mut characters);
+                    let output = command
+                        .as_deref()
+                        .ok()
+                        .and_then(|command| substitute(command, expander));
+                    match (output, command) {
+                        (Some(output), _) => push_expansion(
+                            &output,
+                            in_double_quotes,
+                            &mut current_token,
+                            &mut words,
+                            &mut redirections,
+                            &mut here_document_lines,
+                            expander,
+                        ),
+                        (None, Ok(command)) => {
+                            current_token.push(CHAR_DOLLAR_SIGN);
+                            current_token.push(CHAR_PAREN_OPEN);
+                            current_token.push_str(&command);
+                            current_token.push(CHAR_PAREN_CLOSE);
+                        }
+                        (None, Err(unterminated)) => {
+                            current_token.push(CHAR_DOLLAR_SIGN);
+                            current_token.push(CHAR_PAREN_OPEN);
+                            current_token.push_str(&unterminated);
+                        }
+                    }
+                }
+
+                CHAR_DOLLAR_SIGN
+                    if !escape_next_char && !in_single_quotes && parameter_length(&characters).is_some() =>
+                {
+                    let written = take_parameter(&mut characters);
+                    let name = parameter_name(&written);
+                    let arguments = if name == "@" && in_double_quotes {
+                        expander.arguments()
+                    } else {
+                        None
+                    };
+                    if let Some(arguments) = arguments {
+                        if arguments.is_empty() && current_token.text.is_empty() {
+                            // `"$@"` without arguments is no word, unless the rest of the word adds to it.
+                            current_token.quoted = false;
+                        }
+                        for (index, argument) in arguments.iter().enumerate() {
+                            if index > 0 {
+                                finish_word(
+                                    &mut current_token,
+                                    &mut words,
+                                    &mut redirections,
+                                    &mut here_document_lines,
+                                    expander,
+                                );
+                                current_token.quoted = true;
+                            }
+                            current_token.push_str(argument);
+                        }
+                        continue;
+                    }
+                    match expand_parameter_expression(expander, &written) {
+                        Some(value) => push_expansion(
+                            &value,
+                            in_double_quotes,
+                            &mut current_token,
+                            &mut words,
+                            &mut redirections,
+                            &mut here_document_lines,
+                            expander,
+                        ),
+                        None => {
+                            current_token.push(CHAR_DOLLAR_SIGN);
+                            current_token.push_str(&written);
+                        }
+                    }
+                }
+
+                CHAR_TILDE
+                    if current_token.is_empty() && !escape_next_char && !in_single_quotes && !in_double_quotes =>
+                {
+                    let mut prefix = String::new();
+                    while let Some(&c) = characters.peek() {
+                        if !is_tilde_prefix_char(c) {
+                            break;
+                        }
+                        prefix.push(c);
+                        characters.next();
+                    }
+                    let at_word_end = characters
+                        .peek()
+                        .map_or(true, |&c| c == '/' || c == CHAR_PIPE || c.is_whitespace());
+                    match expand_tilde(&prefix).filter(|_| at_word_end) {
+                        Some(directory) => current_token.push_str(&directory.to_string_lossy()),
+                        None => {
+                            current_token.push(CHAR_TILDE);
+                            current_token.push_str(&prefix);
+                        }
+                    }
                 }
 
                 character if character.is_whitespace() && !escape_next_char => {
                     if in_single_quotes || in_double_quotes {
                         current_token.push(character);
-                    } else if !current_token.is_empty() {
-                        if in_stdout_redirection {
-                            stdout.file_name = Some(current_token);
-                            in_stdout_redirection = false;
-                        } else if in_stderr_redirection {
-                            stderr.file_name = Some(current_token);
-                            in_stderr_redirection = false;
-                        } else {
-                            tokens.push(current_token);
-                        }
-                        current_token = String::new();
+                    } else {
+                        finish_word(
+                            &mut current_token,
+                            &mut words,
+                            &mut redirections,
+                            &mut here_document_lines,
+                            expander,
+                        );
                     }
                 }
 
                 _ => {
-                    current_token.push(character);
+                    if escape_next_char || in_single_quotes || in_double_quotes {
+                        current_token.push(character);
+                    } else {
+                        current_token.push_unquoted(character);
+                    }
                     escape_next_char = false;
                 }
             }
         }
 
-        if !current_token.is_empty() {
-            if in_stdout_redirection {
-                stdout.file_name = Some(current_token);
-            } else if in_stderr_redirection {
-                stderr.file_name = Some(current_token);
-            } else {
-                tokens.push(current_token);
-            }
-        }
-
-        let background = tokens.last().is_some_and(|t| t == "&");
-        if background {
-            tokens.pop();
+        finish_word(
+            &mut current_token,
+            &mut words,
+            &mut redirections,
+            &mut here_document_lines,
+            expander,
+        );
+        let is_empty = words.arguments.is_empty()
+            && words.assignments.is_empty()
+            && redirections.table.is_empty()
+            && subshell.is_none();
+        if redirections.is_pending() || (is_empty && !pipeline.is_empty()) {
+            return Err(ParseError::unexpected_end(input.trim_end(), None));
         }
 
         pipeline.push(ParsedCommand {
-            tokens: if tokens.is_empty() { None } else { Some(tokens) },
-            stdout,
-            stderr,
+            tokens: expand_words(words.arguments, glob_options),
+            assignments: words.assignments,
+            redirections: redirections.table,
+            subshell,
             background,
         });
 
         break;
     }
 
-    if pipeline.is_empty() {
+    Ok(pipeline)
+}
+
+/// The byte offset in `text` of the next character of `characters`, which iterates over `text`.
+fn offset_of(text: &str, characters: &Peekable<Chars>) -> usize {
+    text.len() - characters.clone().map(char::len_utf8).sum::<usize>()
+}
+
+/// Split a function definition, `name() { body }` or the first line of one, into the name and
+/// the text after `{`. The `{` may follow the `()` on the same line only.
+#[must_use]
+pub fn parse_function_header(command_line: &str) -> Option<(&str, &str)> {
+    let (name, rest) = command_line.trim_start().split_once('(')?;
+    let name = name.trim_end();
+    let valid_name = !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid_name {
+        return None;
+    }
+    let body = rest
+        .trim_start()
+        .strip_prefix(')')?
+        .trim_start()
+        .strip_prefix(CHAR_BRACE_OPEN)?;
+    if body.starts_with(|c: char| !c.is_whitespace()) {
+        return None;
+    }
+    Some((name, body))
+}
+
+/// The body of a function whose header line leaves `rest` after its `{`, up to the `}` closing
+/// it, with the rest of that line after the `}` and a `;` following it, as in `f() { cmd; }; f`.
+/// When `rest` does not close the body, the following lines of `command_lines` continue it, and
+/// the input ends too early without a `}`. A `;` before the `}` is dropped.
+pub fn take_function_body(
+    rest: &str,
+    command_lines: &mut impl Iterator<Item = String>,
+) -> Result<(String, String), ParseError> {
+    let mut text = rest.to_string();
+    let close = loop {
+        if let Some(close) = closing_brace(&text) {
+            break close;
+        }
+        let Some(line) = command_lines.next() else {
+            return Err(ParseError::unexpected_end(text.trim(), Some(CHAR_BRACE_CLOSE)));
+        };
+        text.push(CHAR_NEWLINE);
+        text.push_str(&line);
+    };
+    // A `;` before the closing brace ends the last command, as in `f() { cmd; }`.
+    let body = text[..close].trim().trim_end_matches(CHAR_SEMICOLON).trim_end();
+    let after = text[close + 1..].trim_start();
+    let after = after.strip_prefix(CHAR_SEMICOLON).unwrap_or(after).trim();
+    Ok((body.to_string(), after.to_string()))
+}
+
+/// Whether `input` ends inside the body of a function: after a header whose `{` no `}` closes yet.
+#[must_use]
+pub fn ends_in_function_body(input: &str) -> bool {
+    let mut command_lines = split_command_lines(input).into_iter();
+    while let Some(mut command_line) = command_lines.next() {
+        while let Some((_, rest)) = parse_function_header(&command_line) {
+            match take_function_body(rest, &mut command_lines) {
+                Ok((_, after)) => command_line = after,
+                Err(_) => return true,
+            }
+        }
+    }
+    false
+}
+
+/// The index of the `}` closing a function body that starts with `text`: an unquoted `}` word in
+/// command position, after as many as the `{` words opened before it.
+fn closing_brace(text: &str) -> Option<usize> {
+    let lexed = lex(text);
+    let mut depth = 0_usize;
+    for index in lexed.command_word_indices(text) {
+        let token = &lexed.tokens[index];
+        if !token.quoted.is_empty() {
+            continue;
+        }
+        let word = &text[token.span.clone()];
+        match word.chars().next().filter(|_| word.len() == 1) {
+            Some(CHAR_BRACE_OPEN) => depth += 1,
+            Some(CHAR_BRACE_CLOSE) if depth == 0 => return Some(token.span.start),
+            Some(CHAR_BRACE_CLOSE) => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether `name` can name a variable: a letter or `_`, then letters, digits, and `_`.
+#[must_use]
+pub fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// `word` quoted so that the shell reads it back as one word with the same text: as it is when it
+/// holds only characters without a special meaning, or else with [`single_quote`].
+#[must_use]
+pub fn quote_word(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "%+,-./:=@_".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        return word.to_string();
+    }
+    single_quote(word)
+}
+
+/// `text` in single quotes, with each `'` written as `'\''`.
+#[must_use]
+pub fn single_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// Whether `c` can appear in the login name of a `~name` prefix, or is the `+` or `-` of `~+`
+/// and `~-`.
+fn is_tilde_prefix_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '+')
+}
+
+/// How a pipeline in a command list depends on the one before it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connector {
+    /// The first pipeline, or one after `;`, which always runs.
+    Always,
+    /// `&&`: runs when the previous pipeline succeeded.
+    And,
+    /// `||`: runs when the previous pipeline failed.
+    Or,
+}
+
+/// Split `line` at its unquoted `&&`, `||`, `;`, and `&` into the pipelines of a command list, each
+/// with the connector before it, so each pipeline is parsed and expanded only when it runs. A
+/// pipeline ended by `&` keeps it, to be parsed as a background pipeline. The bodies of
+/// here-documents go with the pipeline whose `<<` they belong to, and an `if ... fi` block is one
+/// pipeline, whatever operators it holds.
+///
+/// A connector without a pipeline on both sides is a [`ParseError`]; only a `;` may end the list.
+pub fn split_command_list(line: &str) -> Result<Vec<(Connector, String)>, ParseError> {
+    let text_offset = line.len() - line.trim_start().len();
+    let input = line.trim();
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+    let (command_text, _) = split_here_documents(input);
+    let compounds = lex(command_text).compound_spans(command_text);
+    let mut bodies = if command_text.len() < input.len() {
+        lex(input).here_documents.into_iter()
+    } else {
+        Vec::new().into_iter()
+    };
+    let mut list = Vec::new();
+    // Adds the pipeline in `text`, or returns `false` when there is none.
+    let mut push = |connector, text: &str, here_documents| {
+        let mut pipeline = text.trim().to_string();
+        if pipeline.is_empty() {
+            return false;
+        }
+        let pipeline_bodies: Vec<Range<usize>> = bodies.by_ref().take(here_documents).collect();
+        if let (Some(first), Some(last)) = (pipeline_bodies.first(), pipeline_bodies.last()) {
+            pipeline.push(CHAR_NEWLINE);
+            pipeline.push_str(&input[first.start..last.end]);
+        }
+        list.push((connector, pipeline));
+        true
+    };
+
+    let mut connector = Connector::Always;
+    let mut start = 0;
+    let mut offset = 0;
+    let mut here_documents = 0;
+    let mut in_single_quotes = false;
+    let mut in_double_quotes = false;
+    let mut escaped = false;
+    let mut characters = command_text.chars().peekable();
+    while let Some(c) = characters.next() {
+        let operator_start = offset;
+        offset += c.len_utf8();
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        if let Some(compound) = compounds.iter().find(|span| span.start == operator_start) {
+            let skipped = &command_text[offset..compound.end];
+            characters.by_ref().take(skipped.chars().count()).for_each(drop);
+            offset = compound.end;
+            continue;
+        }
+        match c {
+            CHAR_BACKSLASH if !in_single_quotes => escaped = true,
+            CHAR_SINGLE_QUOTE if !in_double_quotes => in_single_quotes = !in_single_quotes,
+            CHAR_DOUBLE_QUOTE if !in_single_quotes => in_double_quotes = !in_double_quotes,
+            _ if in_single_quotes => {}
+            CHAR_DOLLAR_SIGN if characters.next_if_eq(&CHAR_PAREN_OPEN).is_some() => {
+                offset += match take_substitution(&mut characters) {
+                    Ok(command) => command.len() + 2,
+                    Err(unterminated) => unterminated.len() + 1,
+                };
+            }
+            _ if in_double_quotes => {}
+            CHAR_COMMENT if starts_word(&command_text[..operator_start]) => offset += skip_comment(&mut characters),
+            // A `( ... )` group is one command, whatever operators it holds.
+            CHAR_PAREN_OPEN => {
+                offset += match take_substitution(&mut characters) {
+                    Ok(list) => list.len() + 1,
+                    Err(unterminated) => unterminated.len(),
+                };
+            }
+            CHAR_LESS_THAN if characters.next_if_eq(&CHAR_LESS_THAN).is_some() => {
+                offset += 1;
+                here_documents += 1;
+            }
+            CHAR_AMPERSAND | CHAR_PIPE if characters.next_if_eq(&c).is_some() => {
+                offset += 1;
+                if !push(connector, &command_text[start..operator_start], here_documents) {
+                    return Err(ParseError::unexpected_token(
+                        line,
+                        text_offset + operator_start..text_offset + offset,
+                    ));
+                }
+                connector = if c == CHAR_AMPERSAND {
+                    Connector::And
+                } else {
+                    Connector::Or
+                };
+                start = offset;
+                here_documents = 0;
+            }
+            // `&>`, `>&`, and `<&` are redirections.
+            CHAR_AMPERSAND
+                if characters.peek() != Some(&CHAR_GREATER_THAN)
+                    && !command_text[..operator_start].ends_with([CHAR_GREATER_THAN, CHAR_LESS_THAN]) =>
+            {
+                if command_text[start..operator_start].trim().is_empty() {
+                    return Err(ParseError::unexpected_token(
+                        line,
+                        text_offset + operator_start..text_offset + offset,
+                    ));
+                }
+                push(connector, &command_text[start..offset], here_documents);
+                connector = Connector::Always;
+                start = offset;
+                here_documents = 0;
+            }
+            CHAR_SEMICOLON => {
+                if !push(connector, &command_text[start..operator_start], here_documents) {
+                    return Err(ParseError::unexpected_token(
+                        line,
+                        text_offset + operator_start..text_offset + offset,
+                    ));
+                }
+                connector = Connector::Always;
+                start = offset;
+                here_documents = 0;
+            }
+            _ => {}
+        }
+    }
+    if !push(connector, &command_text[start..], here_documents) && connector != Connector::Always {
+        return Err(ParseError::unexpected_end(line.trim_end(), None));
+    }
+    Ok(list)
+}
+
+/// The pipeline `pipeline` negates when it starts with the reserved word `!`, as in
+/// `! grep -q x file`, whose status is `0` when the pipeline fails and `1` when it succeeds.
+#[must_use]
+pub fn strip_negation(pipeline: &str) -> Option<&str> {
+    let lexed = lex(pipeline);
+    let first = lexed.tokens.first()?;
+    (reserved_word(pipeline, first) == Some(RESERVED_WORD_NOT)).then(|| &pipeline[first.span.end..])
+}
+
+/// Whether a word starts after `text`: at the start of the input, or after whitespace or an operator.
+fn starts_word(text: &str) -> bool {
+    text.chars().next_back().map_or(true, |c| {
+        c.is_whitespace()
+            || matches!(
+                c,
+                CHAR_SEMICOLON
+                    | CHAR_AMPERSAND
+                    | CHAR_PIPE
+                    | CHAR_PAREN_OPEN
+                    | CHAR_PAREN_CLOSE
+                    | CHAR_LESS_THAN
+                    | CHAR_GREATER_THAN
+            )
+    })
+}
+
+/// Consume a comment up to the end of its line, after the `#` that opened it, and return its length
+/// in bytes. The newline is left in place.
+fn skip_comment(characters: &mut Peekable<Chars>) -> usize {
+    let mut length = 0;
+    while let Some(c) = characters.next_if(|&c| c != CHAR_NEWLINE) {
+        length += c.len_utf8();
+    }
+    length
+}
+
+/// The length in characters of the parameter after a `$`, braces included, or `None` when none
+/// follows: a variable name (`$HOME`), one digit or special character (`$1`, `$?`), or an
+/// expression in braces (`${10}`, `${HOME:-/}`), which can hold nested braces and quotes.
+fn parameter_length(characters: &Peekable<Chars>) -> Option<usize> {
+    let mut characters = characters.clone();
+    match characters.next()? {
+        CHAR_BRACE_OPEN => {
+            let mut depth = 1;
+            let mut quote = None;
+            let mut escaped = false;
+            for (index, c) in characters.enumerate() {
+                match c {
+                    _ if escaped => escaped = false,
+                    CHAR_BACKSLASH if quote != Some(CHAR_SINGLE_QUOTE) => escaped = true,
+                    _ if quote == Some(c) => quote = None,
+                    _ if quote.is_some() => {}
+                    CHAR_SINGLE_QUOTE | CHAR_DOUBLE_QUOTE => quote = Some(c),
+                    CHAR_BRACE_OPEN => depth += 1,
+                    CHAR_BRACE_CLOSE => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(index + 2);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            None
+        }
+        c if c.is_ascii_alphabetic() || c == '_' => Some(
+            1 + characters
+                .take_while(|&c| c.is_ascii_alphanumeric() || c == '_')
+                .count(),
+        ),
+        c if c.is_ascii_digit() || SPECIAL_PARAMETERS.contains(&c) => Some(1),
+        _ => None,
+    }
+}
+
+/// Consume the parameter after a `$` as written, which [`parameter_length`] has found.
+fn take_parameter(characters: &mut Peekable<Chars>) -> String {
+    let length = parameter_length(characters).unwrap_or_default();
+    characters.take(length).collect()
+}
+
+/// The name of a parameter as written after its `$`, or the expression in its braces: `1` for `1`
+/// or `{1}`.
+fn parameter_name(written: &str) -> &str {
+    written
+        .strip_prefix(CHAR_BRACE_OPEN)
+        .and_then(|name| name.strip_suffix(CHAR_BRACE_CLOSE))
+        .unwrap_or(written)
+}
+
+/// The value of parameter `name` from `expander`, with `$@` and `$*` as the positional parameters
+/// joined by the first field separator, or by nothing when there is none.
+fn expand_parameter(expander: &mut dyn Expander, name: &str) -> Option<String> {
+    match name {
+        "@" | "*" => {
+            let arguments = expander.arguments()?;
+            let separator: String = expander.field_separators().chars().take(1).collect();
+            Some(arguments.join(&separator))
+        }
+        name => expander.parameter(name),
+    }
+}
+
+/// What a `${name<operator>word}` expansion does with the value of `name`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ParameterOperator {
+    /// `:-`: the word when the value is unset or empty.
+    Default,
+    /// `:=`: the word when the value is unset or empty, also assigned to `name`.
+    Assign,
+    /// `:?`: an error, with the word as its message, when the value is unset or empty.
+    Error,
+    /// `:+`: the word when the value is not empty.
+    Alternative,
+    /// `#` and `##`: the value without the shortest or longest prefix the word matches.
+    RemovePrefix { longest: bool },
+    /// `%` and `%%`: the value without the shortest or longest suffix the word matches.
+    RemoveSuffix { longest: bool },
+    /// `/` and `//`: the value with the first or every match of the word's pattern replaced.
+    Replace { all: bool },
+}
+
+impl ParameterOperator {
+    /// The operator at the start of `text` and the word after it.
+    fn split(text: &str) -> Option<(Self, &str)> {
+        let mut characters = text.chars();
+        let operator = match characters.next()? {
+            PARAMETER_OPERATOR_PREFIX => match characters.next()? {
+                PARAMETER_OPERATOR_DEFAULT => Self::Default,
+                PARAMETER_OPERATOR_ASSIGN => Self::Assign,
+                PARAMETER_OPERATOR_ERROR => Self::Error,
+                PARAMETER_OPERATOR_ALTERNATIVE => Self::Alternative,
+                _ => return None,
+            },
+            c @ (PARAMETER_OPERATOR_REMOVE_PREFIX | PARAMETER_OPERATOR_REMOVE_SUFFIX | PARAMETER_OPERATOR_REPLACE) => {
+                let doubled = characters.clone().next() == Some(c);
+                if doubled {
+                    characters.next();
+                }
+                match c {
+                    PARAMETER_OPERATOR_REMOVE_PREFIX => Self::RemovePrefix { longest: doubled },
+                    PARAMETER_OPERATOR_REMOVE_SUFFIX => Self::RemoveSuffix { longest: doubled },
+                    _ => Self::Replace { all: doubled },
+                }
+            }
+            _ => return None,
+        };
+        Some((operator, characters.as_str()))
+    }
+}
+
+/// The value of the parameter expansion written as `written` after its `$`, or `None` to leave it
+/// as written.
+///
+/// In braces, `${#name}` is the length of the value in characters, and an operator can follow the
+/// name. `${name:-word}` stands in `word` for an unset or empty value, `${name:=word}` also assigns
+/// it to `name`, and `${name:?message}` reports `message` through `expander`, which keeps the
+/// command from running. `${name:+word}` expands to `word` only when the value is not empty.
+/// `${name#pattern}` and `${name%pattern}` remove the shortest prefix or suffix matching a shell
+/// pattern (`##` and `%%` the longest), and `${name/pattern/string}` replaces its first longest
+/// match (`//` every match). The word is expanded only when it is used.
+fn expand_parameter_expression(expander: &mut dyn Expander, written: &str) -> Option<String> {
+    let expression = parameter_name(written);
+    if let Some(name) = expression
+        .strip_prefix(PARAMETER_LENGTH)
+        .filter(|name| is_parameter_name(name))
+    {
+        return match name {
+            "@" | "*" => expander.arguments().map(|arguments| arguments.len().to_string()),
+            name => {
+                expander.check_set(name);
+                expander.parameter(name).map(|value| value.chars().count().to_string())
+            }
+        };
+    }
+    let name_length = parameter_name_length(expression);
+    let (name, rest) = expression.split_at(name_length);
+    if name.is_empty() || rest.is_empty() {
+        if !matches!(expression, "@" | "*") {
+            expander.check_set(expression);
+        }
+        return expand_parameter(expander, expression);
+    }
+    let (operator, word) = ParameterOperator::split(rest)?;
+    let tests_set = matches!(
+        operator,
+        ParameterOperator::Default
+            | ParameterOperator::Assign
+            | ParameterOperator::Error
+            | ParameterOperator::Alternative
+    );
+    if !tests_set && !matches!(name, "@" | "*") {
+        expander.check_set(name);
+    }
+    let value = expand_parameter(expander, name)?;
+    match operator {
+        ParameterOperator::Alternative if value.is_empty() => Some(value),
+        ParameterOperator::Alternative => Some(expand_operand(word, false, expander)),
+        ParameterOperator::RemovePrefix { longest } => {
+            let pattern = expand_operand(word, true, expander);
+            Some(remove_matching_prefix(&pattern, &value, longest).to_string())
+        }
+        ParameterOperator::RemoveSuffix { longest } => {
+            let pattern = expand_operand(word, true, expander);
+            Some(remove_matching_suffix(&pattern, &value, longest).to_string())
+        }
+        ParameterOperator::Replace { all } => {
+            let (pattern, replacement) = split_replacement(word);
+            let pattern = expand_operand(pattern, true, expander);
+            if pattern.is_empty() {
+                return Some(value);
+            }
+            let replacement = expand_operand(replacement, false, expander);
+            Some(replace_matches(&pattern, &value, &replacement, all))
+        }
+        _ if !value.is_empty() => Some(value),
+        ParameterOperator::Assign => {
+            let value = expand_operand(word, false, expander);
+            if is_variable_name(name) {
+                expander.assign(name, &value);
+            } else {
+                expander.parameter_error(name, "cannot assign in this way");
+            }
+            Some(value)
+        }
+        ParameterOperator::Error => {
+            let message = expand_operand(word, false, expander);
+            let message = if message.is_empty() {
+                PARAMETER_NULL_OR_UNSET
+            } else {
+                &message
+            };
+            expander.parameter_error(name, message);
+            Some(value)
+        }
+        ParameterOperator::Default => Some(expand_operand(word, false, expander)),
+    }
+}
+
+/// The length of the parameter name `expression` starts with: a variable name, a number, or one
+/// special character, or 0 when it starts with none.
+fn parameter_name_length(expression: &str) -> usize {
+    match expression.chars().next() {
+        Some(c) if c.is_ascii_digit() => expression.chars().take_while(char::is_ascii_digit).count(),
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => expression
+            .chars()
+            .take_while(|&c| c.is_ascii_alphanumeric() || c == '_')
+            .count(),
+        Some(c) if SPECIAL_PARAMETERS.contains(&c) => 1,
+        _ => 0,
+    }
+}
+
+/// Whether all of `name` names a parameter.
+fn is_parameter_name(name: &str) -> bool {
+    !name.is_empty() && parameter_name_length(name) == name.len()
+}
+
+/// Split the word of a `${name/pattern/string}` expansion at its first unquoted, unescaped `/`. The
+/// replacement is empty without one.
+fn split_replacement(word: &str) -> (&str, &str) {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in word.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            CHAR_BACKSLASH if quote != Some(CHAR_SINGLE_QUOTE) => escaped = true,
+            _ if quote == Some(c) => quote = None,
+            _ if quote.is_some() => {}
+            CHAR_SINGLE_QUOTE | CHAR_DOUBLE_QUOTE => quote = Some(c),
+            PARAMETER_OPERATOR_REPLACE => return (&word[..index], &word[index + 1..]),
+            _ => {}
+        }
+    }
+    (word, "")
+}
+
+/// Expand the word of a `${name:-word}` expression, or the word or a pattern of a `case`: its
+/// parameters and command substitutions are replaced and its quotes removed, but it is not split
+/// into fields or globbed. As a pattern (`as_pattern`), the quoted and escaped characters of the
+/// word are escaped so they match only themselves.
+pub fn expand_operand(word: &str, as_pattern: bool, expander: &mut dyn Expander) -> String {
+    let mut expanded = String::with_capacity(word.len());
+    let push_quoted = |expanded: &mut String, c: char| {
+        if as_pattern {
+            push_literal(expanded, c);
+        } else {
+            expanded.push(c);
+        }
+    };
+    let mut characters = word.chars().peekable();
+    let mut quote = None;
+    while let Some(c) = characters.next() {
+        match c {
+            _ if quote == Some(c) => quote = None,
+            _ if quote == Some(CHAR_SINGLE_QUOTE) => push_quoted(&mut expanded, c),
+            CHAR_SINGLE_QUOTE | CHAR_DOUBLE_QUOTE if quote.is_none() => quote = Some(c),
+            CHAR_BACKSLASH
+                if quote.is_none()
+                    || matches!(
+                        characters.peek(),
+                        Some(&(CHAR_DOLLAR_SIGN | CHAR_BACKTICK | CHAR_BACKSLASH | CHAR_DOUBLE_QUOTE))
+                    ) =>
+            {
+                if let Some(c) = characters.next() {
+                    push_quoted(&mut expanded, c);
+                }
+            }
+            CHAR_DOLLAR_SIGN if starts_dollar_expansion(&characters) => {
+                let value = expand_dollar(&mut characters, expander);
+                if quote.is_some() {
+                    value.chars().for_each(|c| push_quoted(&mut expanded, c));
+                } else {
+                    expanded.push_str(&value);
+                }
+            }
+            c if quote.is_some() => push_quoted(&mut expanded, c),
+            c => expanded.push(c),
+        }
+    }
+    expanded
+}
+
+/// Whether the text after a `$` is a command substitution or a parameter.
+fn starts_dollar_expansion(characters: &Peekable<Chars>) -> bool {
+    characters.clone().next() == Some(CHAR_PAREN_OPEN) || parameter_length(characters).is_some()
+}
+
+/// Consume the command substitution or parameter after a `$`, which [`starts_dollar_expansion`]
+/// has found, and return its value without splitting it, or the text as written when `expander`
+/// leaves it so. Trailing newlines are stripped from the output of a command.
+fn expand_dollar(characters: &mut Peekable<Chars>, expander: &mut dyn Expander) -> String {
+    let mut expanded = String::new();
+    if characters.next_if_eq(&CHAR_PAREN_OPEN).is_some() {
+        let command = take_substitution(characters);
+        match command
+            .as_deref()
+            .ok()
+            .and_then(|command| substitute(command, expander))
+        {
+            Some(output) => expanded.push_str(&output),
+            None => {
+                expanded.push(CHAR_DOLLAR_SIGN);
+                expanded.push(CHAR_PAREN_OPEN);
+                match command {
+                    Ok(command) => {
+                        expanded.push_str(&command);
+                        expanded.push(CHAR_PAREN_CLOSE);
+                    }
+                    Err(unterminated) => expanded.push_str(&unterminated),
+                }
+            }
+        }
+        return expanded;
+    }
+    let written = take_parameter(characters);
+    match expand_parameter_expression(expander, &written) {
+        Some(value) => expanded.push_str(&value),
+        None => {
+            expanded.push(CHAR_DOLLAR_SIGN);
+            expanded.push_str(&written);
+        }
+    }
+    expanded
+}
+
+/// The text of the `$(...)` expansion of `command`: the output of the command, without trailing
+/// newlines, or the value of the arithmetic expression when `command` is `(expression)`, as in
+/// `$((1 + 2))`. An expression that cannot be evaluated is reported as a parameter error and
+/// expands to nothing. `None` leaves the expansion as written.
+fn substitute(command: &str, expander: &mut dyn Expander) -> Option<String> {
+    let Some(expression) = command
+        .strip_prefix(CHAR_PAREN_OPEN)
+        .and_then(|command| command.strip_suffix(CHAR_PAREN_CLOSE))
+    else {
+        let output = expander.substitute_command(command)?;
+        return Some(output.trim_end_matches(CHAR_NEWLINE).to_string());
+    };
+    match expander.arithmetic(expression)? {
+        Ok(value) => Some(value.to_string()),
+        Err(error) => {
+            expander.parameter_error(expression.trim(), &error.to_string());
+            Some(String::new())
+        }
+    }
+}
+
+/// Append the text of an expansion to `word`: inside double quotes or the value of an assignment
+/// it is part of the word; otherwise it is split into fields and globbed, but not brace-expanded.
+///
+/// Field splitting follows POSIX: the field separators (`$IFS`) that are blanks are trimmed and
+/// a run of them ends a field, while each other separator ends one, possibly empty, together with
+/// any blanks around it. A trailing separator does not start a new field, and with no separators
+/// nothing is split. In the target of a redirection, a second field makes the redirection
+/// ambiguous instead of starting another word.
+fn push_expansion(
+    text: &str,
+    in_double_quotes: bool,
+    word: &mut WordBuffer,
+    words: &mut Words,
+    redirections: &mut Redirections,
+    here_document_lines: &mut Lines<'_>,
+    expander: &mut dyn Expander,
+) {
+    if in_double_quotes || (word.name_length.is_some() && words.arguments.is_empty()) {
+        word.push_str(text);
+        return;
+    }
+    let target = redirections.is_target_pending();
+    word.expanded |= target;
+    let separators = expander.field_separators();
+    // A field was just ended by separator blanks, which a following separator belongs with.
+    let mut ended_by_blanks = false;
+    for c in text.chars() {
+        if !separators.contains(c) {
+            ended_by_blanks = false;
+            if BRACE_SPECIAL_CHARS.contains(&c) {
+                word.push(c);
+            } else {
+                word.push_unquoted(c);
+            }
+        } else if c.is_ascii_whitespace() {
+            if !word.is_empty() {
+                if target {
+                    word.field_ended = true;
+                } else {
+                    finish_word(word, words, redirections, here_document_lines, expander);
+                }
+                ended_by_blanks = true;
+            }
+        } else if ended_by_blanks {
+            ended_by_blanks = false;
+        } else {
+            // Quoted, so an empty field is kept.
+            word.quoted = true;
+            if target {
+                word.field_ended = true;
+            } else {
+                finish_word(word, words, redirections, here_document_lines, expander);
+            }
+        }
+    }
+}
+
+/// Consume a command substitution's text up to its matching `)`, after the `$(` that opened it.
+///
+/// Parentheses nest, and quotes and backslashes inside hide them. Returns the text read so far as
+/// the error when the input ends first.
+fn take_substitution(characters: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut command = String::new();
+    let mut depth = 0_usize;
+    let mut in_single_quotes = false;
+    let mut in_double_quotes = false;
+    let mut escaped = false;
+
+    for c in characters.by_ref() {
+        if escaped {
+            escaped = false;
+        } else {
+            match c {
+                CHAR_BACKSLASH if !in_single_quotes => escaped = true,
+                CHAR_SINGLE_QUOTE if !in_double_quotes => in_single_quotes = !in_single_quotes,
+                CHAR_DOUBLE_QUOTE if !in_single_quotes => in_double_quotes = !in_double_quotes,
+                CHAR_PAREN_OPEN if !in_single_quotes && !in_double_quotes => depth += 1,
+                CHAR_PAREN_CLOSE if !in_single_quotes && !in_double_quotes => {
+                    if depth == 0 {
+                        return Ok(command);
+                    }
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+        command.push(c);
+    }
+
+    Err(command)
+}
+
+/// A word being read, in pattern form: characters that were quoted or escaped, and so must not
+/// act as wildcards or braces, are escaped with `\`.
+#[derive(Default)]
+struct WordBuffer {
+    text: String,
+    /// Part of the word was quoted or escaped.
+    quoted: bool,
+    /// The length of `NAME` when the word starts with an unquoted `NAME=`, so it can be an assignment.
+    name_length: Option<usize>,
+    /// An unquoted expansion was read into a redirection target, which is then a word even when empty.
+    expanded: bool,
+    /// Field splitting ended a field of a redirection target, so any further text starts another.
+    field_ended: bool,
+    /// The redirection target was split into more than one field.
+    ambiguous: bool,
+}
+
+impl WordBuffer {
+    /// Append `c` as quoted text.
+    fn push(&mut self, c: char) {
+        self.start_field();
+        if BRACE_SPECIAL_CHARS.contains(&c) {
+            self.text.push(PATTERN_ESCAPE);
+            self.text.push(c);
+        } else {
+            push_literal(&mut self.text, c);
+        }
+    }
+
+    /// Append `s` as quoted text.
+    fn push_str(&mut self, s: &str) {
+        for c in s.chars() {
+            self.push(c);
+        }
+    }
+
+    /// Append `c` as unquoted text, where it keeps any pattern meaning.
+    fn push_unquoted(&mut self, c: char) {
+        self.start_field();
+        if c == CHAR_EQUALS_SIGN && self.name_length.is_none() && !self.quoted && is_variable_name(&self.text) {
+            self.name_length = Some(self.text.len());
+        }
+        if c == PATTERN_ESCAPE {
+            push_literal(&mut self.text, c);
+        } else {
+            self.text.push(c);
+        }
+    }
+
+    /// No character has been read into the word: an empty quoted string (`""`, `''`) is still a word.
+    fn is_empty(&self) -> bool {
+        self.text.is_empty() && !self.quoted
+    }
+
+    /// Start the next word, keeping the capacity of the text.
+    fn clear(&mut self) {
+        self.text.clear();
+        self.quoted = false;
+        self.name_length = None;
+        self.expanded = false;
+        self.field_ended = false;
+        self.ambiguous = false;
+    }
+
+    /// Note a second field of a redirection target before its first character, keeping the fields
+    /// apart in the text for the error message.
+    fn start_field(&mut self) {
+        if std::mem::take(&mut self.field_ended) {
+            self.ambiguous = true;
+            self.text.push(' ');
+        }
+    }
+}
+
+/// The file name redirection target `word` stands for after brace and pathname expansion, which
+/// may only leave one word. Otherwise, or when it was split into fields or expanded to nothing, the
+/// redirection is reported as ambiguous through `expander` and the command must not run.
+fn redirection_target(word: &WordBuffer, expander: &mut dyn Expander) -> Option<String> {
+    let fields = if word.ambiguous || word.is_empty() {
         None
     } else {
-        Some(pipeline)
+        expand_words(vec![word.text.clone()], expander.glob_options())
+    };
+    match fields.as_deref() {
+        Some([file_name]) => Some(file_name.to_string_lossy().into_owned()),
+        _ => {
+            expander.parameter_error(&unescape(&word.text), "ambiguous redirect");
+            None
+        }
+    }
+}
+
+/// The expansion pass from words in pattern form to the command's arguments: each word is
+/// brace-expanded, then a word with an unquoted wildcard becomes the paths it matches, or stays
+/// as written when none does.
+fn expand_words(words: Vec<String>, glob_options: GlobOptions) -> Option<Vec<OsString>> {
+    let mut tokens = Vec::with_capacity(words.len());
+    for word in words.iter().flat_map(|word| expand_braces(word)) {
+        let wildcards = has_wildcards(&word);
+        let matches = if wildcards {
+            expand_pathname(&word, glob_options)
+        } else {
+            Vec::new()
+        };
+        if matches.is_empty() && !(wildcards && glob_options.nullglob) {
+            tokens.push(OsString::from(unescape(&word)));
+        } else {
+            tokens.extend(matches.into_iter().map(OsString::from));
+        }
+    }
+    (!tokens.is_empty()).then_some(tokens)
+}
+
+/// The redirections `N>&target` or `N<&target` makes of `descriptor`: `-` closes it, and `M`
+/// duplicates descriptor `M` onto it, or with `M-` moves it there, closing `M`. `None` when
+/// `target` is neither.
+fn duplicate_redirections(descriptor: RawFd, target: &str) -> Option<Vec<(RawFd, Redirection)>> {
+    if target == "-" {
+        return Some(vec![(descriptor, Redirection::Close)]);
+    }
+    let (source, moved) = target
+        .strip_suffix('-')
+        .map_or((target, false), |source| (source, true));
+    if source.is_empty() || !source.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let source: RawFd = source.parse().ok()?;
+    let mut redirections = vec![(descriptor, Redirection::Duplicate(source))];
+    if moved && source != descriptor {
+        redirections.push((source, Redirection::Close));
+    }
+    Some(redirections)
+}
+
+/// Finish `word` as the target of a pending redirection (both outputs for `&>`), as the delimiter of a
+/// here-document, whose body is read from `here_document_lines`, as an assignment before the
+/// command name, or as an argument, still in pattern form.
+///
+/// After `>&` or `<&`, a descriptor number or `-` duplicates or closes the descriptor, and any
+/// other word is a file name, which `>&` opens for both outputs, as `&>` does.
+///
+/// `word` is the caller's scratch buffer: it is copied out at its final size and cleared, so its
+/// capacity is reused by the next word.
+fn finish_word(
+    word: &mut WordBuffer,
+    words: &mut Words,
+    redirections: &mut Redirections,
+    here_document_lines: &mut Lines<'_>,
+    expander: &mut dyn Expander,
+) {
+    if word.is_empty() && !(word.expanded && redirections.is_target_pending()) {
+        return;
+    }
+    let duplicate = std::mem::take(&mut redirections.duplicate);
+    if let Some((descriptor, strip_tabs)) = redirections.in_here_document.take() {
+        let body = read_here_document(here_document_lines, &unescape(&word.text), strip_tabs);
+        let body = if word.quoted {
+            body
+        } else {
+            expand_here_document(&body, expander)
+        };
+        redirections
+            .table
+            .push((descriptor, Redirection::Input(InputRedirection::HereDocument(body))));
+    } else if let Some(descriptor) = redirections.in_input.take() {
+        let Some(file_name) = redirection_target(word, expander) else {
+            return word.clear();
+        };
+        match duplicate
+            .then(|| duplicate_redirections(descriptor, &file_name))
+            .flatten()
+        {
+            Some(duplicates) => redirections.table.extend(duplicates),
+            None => redirections
+                .table
+                .push((descriptor, Redirection::Input(InputRedirection::File(file_name)))),
+        }
+    } else if !redirections.in_output.is_empty() {
+        let Some(file_name) = redirection_target(word, expander) else {
+            redirections.in_output.clear();
+            return word.clear();
+        };
+        let descriptor = redirections.in_output[0];
+        if let Some(duplicates) = duplicate
+            .then(|| duplicate_redirections(descriptor, &file_name))
+            .flatten()
+        {
+            redirections.in_output.clear();
+            redirections.table.extend(duplicates);
+        } else {
+            if duplicate && descriptor == STDOUT_DESCRIPTOR {
+                redirections.in_output.push(STDERR_DESCRIPTOR);
+            }
+            let output = OutputRedirection {
+                file_name,
+                append_to: redirections.append_to,
+            };
+            for descriptor in redirections.in_output.drain(..) {
+                redirections
+                    .table
+                    .push((descriptor, Redirection::Output(output.clone())));
+            }
+        }
+    } else if let Some(name_length) = word.name_length.filter(|_| words.arguments.is_empty()) {
+        let value = unescape(&word.text[name_length + 1..]);
+        words.assignments.push((word.text[..name_length].to_string(), value));
+    } else {
+        words.arguments.push(word.text.clone());
+    }
+    word.clear();
+}
+
+/// Split `input` into the command line and the here-document bodies following it, which start on
+/// the line after the first `<<` operator.
+fn split_here_documents(input: &str) -> (&str, &str) {
+    if !input.contains(HERE_DOCUMENT) {
+        return (input, "");
+    }
+    match lex(input).here_documents.first() {
+        Some(body) => (&input[..body.start], &input[body.start..]),
+        None => (input, ""),
+    }
+}
+
+/// Read a here-document body from `lines` up to the line holding just `delimiter`, with leading
+/// tabs removed from every line when `strip_tabs` (`<<-`). Each line keeps its newline.
+fn read_here_document(lines: &mut Lines<'_>, delimiter: &str, strip_tabs: bool) -> String {
+    let mut body = String::new();
+    for line in lines {
+        let line = if strip_tabs {
+            line.trim_start_matches(CHAR_TAB)
+        } else {
+            line
+        };
+        if line == delimiter {
+            break;
+        }
+        body.push_str(line);
+        body.push(CHAR_NEWLINE);
+    }
+    body
+}
+
+/// Expand an unquoted here-document's `body`: `$(command)` substitutions, parameters, and backslash escapes
+/// of `$`, `` ` ``, `\`, and newlines. Quotes are ordinary characters.
+fn expand_here_document(body: &str, expander: &mut dyn Expander) -> String {
+    let mut expanded = String::with_capacity(body.len());
+    let mut characters = body.chars().peekable();
+    while let Some(c) = characters.next() {
+        match c {
+            CHAR_BACKSLASH => match characters.peek() {
+                Some(&(CHAR_DOLLAR_SIGN | CHAR_BACKTICK | CHAR_BACKSLASH)) => expanded.extend(characters.next()),
+                Some(&CHAR_NEWLINE) => {
+                    characters.next();
+                }
+                _ => expanded.push(c),
+            },
+            CHAR_DOLLAR_SIGN if starts_dollar_expansion(&characters) => {
+                expanded.push_str(&expand_dollar(&mut characters, expander));
+            }
+            c => expanded.push(c),
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::arithmetic::evaluate_arithmetic;
+
+    /// Substitutes each command in angle brackets, with spaces as `_` to keep one word, except
+    /// `words`, which outputs two words, and `unknown`, which is left as written. Shared with the
+    /// tests of the other modules that expand words.
+    #[derive(Default)]
+    pub(crate) struct TestExpander {
+        glob_options: GlobOptions,
+        /// `$IFS`, when set.
+        field_separators: Option<&'static str>,
+        /// The expansion errors reported, as `name: message`.
+        errors: Vec<String>,
+        /// Report unset parameters, as `set -u` does.
+        nounset: bool,
+    }
+
+    impl Expander for TestExpander {
+        fn substitute_command(&mut self, command: &str) -> Option<String> {
+            match command {
+                "words" => Some("one two\n\n".to_string()),
+                "unknown" => None,
+                command => Some(format!("<{}>\n", command.replace(' ', "_"))),
+            }
+        }
+
+        fn arithmetic(&mut self, expression: &str) -> Option<Result<i64, ArithmeticError>> {
+            Some(evaluate_arithmetic(expression, self))
+        }
+
+        fn parameter(&mut self, name: &str) -> Option<String> {
+            match name {
+                "?" => Some("42".to_string()),
+                "$" => Some("100".to_string()),
+                "1" => Some("a b".to_string()),
+                "10" => Some("ten".to_string()),
+                "#" => Some("2".to_string()),
+                "USER" => Some("me".to_string()),
+                "_empty" => Some(String::new()),
+                "fields" => Some(" a : b  :c::".to_string()),
+                _ => None,
+            }
+        }
+
+        fn arguments(&mut self) -> Option<Vec<String>> {
+            Some(vec!["a b".to_string(), String::new()])
+        }
+
+        fn check_set(&mut self, name: &str) {
+            if self.nounset && self.parameter(name).is_none() {
+                self.parameter_error(name, "unbound variable");
+            }
+        }
+
+        fn parameter_error(&mut self, name: &str, message: &str) {
+            self.errors.push(format!("{name}: {message}"));
+        }
+
+        fn field_separators(&mut self) -> String {
+            self.field_separators.unwrap_or(DEFAULT_FIELD_SEPARATORS).to_string()
+        }
+
+        fn glob_options(&self) -> GlobOptions {
+            self.glob_options
+        }
+    }
+
+    fn assert_cases(cases: &[(&str, &[&str])]) {
+        for (input, expected) in cases {
+            let pipeline = parse_input(input).unwrap_or_default();
+            let rendered: Vec<String> = pipeline.iter().map(ToString::to_string).collect();
+            assert_eq!(&rendered, expected, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn splits_words_on_unquoted_whitespace() {
+        assert_cases(&[
+            ("echo hello world", &[r#"["echo", "hello", "world"]"#]),
+            ("  echo   spaced \t out  ", &[r#"["echo", "spaced", "out"]"#]),
+            ("pwd", &[r#"["pwd"]"#]),
+        ]);
+    }
+
+    #[test]
+    fn single_quotes_are_literal() {
+        assert_cases(&[
+            ("echo 'hello   world'", &[r#"["echo", "hello   world"]"#]),
+            ("echo 'it''s'", &[r#"["echo", "its"]"#]),
+            (r#"echo 'a "b" c'"#, &[r#"["echo", "a \"b\" c"]"#]),
+            (r"echo 'a\nb\\'", &[r#"["echo", "a\\nb\\\\"]"#]),
+            ("echo 'a|b' 'c > d'", &[r#"["echo", "a|b", "c > d"]"#]),
+        ]);
+    }
+
+    #[test]
+    fn echo_escape_sequences() {
+        let cases: &[(&str, &[u8], bool)] = &[
+            (r#"a\tb\n\\ \" \'"#, b"a\tb\n\\ \" '", false),
+            (r"\a\b\e\E\f\v\r", b"\x07\x08\x1b\x1b\x0c\x0b\r", false),
+            (r"\0101\0\08\0400", b"A\0\08\0", false),
+            (r"\x41\x4a2\xg \x", b"AJ2\\xg \\x", false),
+            (r"\u20ac\u41z \uZ", "\u{20ac}Az \\uZ".as_bytes(), false),
+            (r"\xe2\x82\xac", "\u{20ac}".as_bytes(), false),
+            (r"a\cb\n", b"a", true),
+            (r"\q\", b"\\q\\", false),
+        ];
+        for (input, expected, stopped) in cases {
+            assert_eq!(
+                expand_escape_sequences(input),
+                (expected.to_vec(), *stopped),
+                "input: {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn double_quotes_group_words_and_keep_single_quotes() {
+        assert_cases(&[
+            (r#"echo "hello   world""#, &[r#"["echo", "hello   world"]"#]),
+            (r#"echo "a 'b' c""#, &[r#"["echo", "a 'b' c"]"#]),
+            (r#"echo "hello"world'!'"#, &[r#"["echo", "helloworld!"]"#]),
+            (r#"echo "a|b" "c>d""#, &[r#"["echo", "a|b", "c>d"]"#]),
+            (r#"echo "1>x" "2>y""#, &[r#"["echo", "1>x", "2>y"]"#]),
+        ]);
+    }
+
+    #[test]
+    fn assignment_prefixes() {
+        assert_cases(&[
+            (
+                "FOO=bar LC_ALL=C cmd args",
+                &[r#"FOO="bar" LC_ALL="C" ["cmd", "args"]"#],
+            ),
+            (r#"X="a b" _y1='*' Z= cmd"#, &[r#"X="a b" _y1="*" Z="" ["cmd"]"#]),
+            ("cmd A=1", &[r#"["cmd", "A=1"]"#]),
+            ("A=1 > out B=2 cmd", &[r#"A="1" B="2" ["cmd"] 1>out"#]),
+            (
+                r#""A"=1 \B=2 1A=3 A-B=4 =5 cmd"#,
+                &[r#"["A=1", "B=2", "1A=3", "A-B=4", "=5", "cmd"]"#],
+            ),
+            ("A=x=y", &[r#"A="x=y" []"#]),
+            ("A=1 cmd | B=2 cmd", &[r#"A="1" ["cmd"]"#, r#"B="2" ["cmd"]"#]),
+        ]);
+        let pipeline = parse_input_with("A=$(words) cmd $(words)", &mut TestExpander::default()).unwrap_or_default();
+        let rendered: Vec<String> = pipeline.iter().map(ToString::to_string).collect();
+        assert_eq!(rendered, [r#"A="one two" ["cmd", "one", "two"]"#]);
+    }
+
+    #[test]
+    fn adjacent_segments_form_one_word() {
+        assert_cases(&[
+            (r#"echo "foo"'bar'baz"#, &[r#"["echo", "foobarbaz"]"#]),
+            (r#"echo a"b c"d'e f'g\ h"#, &[r#"["echo", "ab cde fg h"]"#]),
+            (r#"echo a "" b ''"#, &[r#"["echo", "a", "", "b", ""]"#]),
+            (r#"echo ""x '' \""#, &[r#"["echo", "x", "", "\""]"#]),
+            (r##"echo ""2>f ''~ ""#x"##, &[r##"["echo", "2", "~", "#x"] 1>f"##]),
+        ]);
+    }
+
+    #[test]
+    fn backslash_escapes() {
+        assert_cases(&[
+            (r"echo a\ b", &[r#"["echo", "a b"]"#]),
+            (r"echo \'x\'", &[r#"["echo", "'x'"]"#]),
+            (r"echo \\", &[r#"["echo", "\\"]"#]),
+            (r"echo a\|b", &[r#"["echo", "a|b"]"#]),
+            (r"echo a\>b", &[r#"["echo", "a>b"]"#]),
+            (r#"echo "a\"b""#, &[r#"["echo", "a\"b"]"#]),
+            (r#"echo "a\\b""#, &[r#"["echo", "a\\b"]"#]),
+            (r#"echo "\$HOME""#, &[r#"["echo", "$HOME"]"#]),
+            (r#"echo "a\nb""#, &[r#"["echo", "a\\nb"]"#]),
+            ("echo a\\\nb", &[r#"["echo", "ab"]"#]),
+        ]);
+    }
+
+    #[test]
+    fn stdout_redirections() {
+        assert_cases(&[
+            ("echo hi > out", &[r#"["echo", "hi"] 1>out"#]),
+            ("echo hi >out", &[r#"["echo", "hi"] 1>out"#]),
+            ("echo hi 1> out", &[r#"["echo", "hi"] 1>out"#]),
+            ("echo hi 1>out", &[r#"["echo", "hi"] 1>out"#]),
+            ("echo hi >> out", &[r#"["echo", "hi"] 1>>out"#]),
+            ("echo hi 1>> out", &[r#"["echo", "hi"] 1>>out"#]),
+            ("echo hi 1>>out", &[r#"["echo", "hi"] 1>>out"#]),
+            ("echo hi > 'my file'", &[r#"["echo", "hi"] 1>my file"#]),
+        ]);
+    }
+
+    #[test]
+    fn stderr_and_combined_redirections() {
+        assert_cases(&[
+            ("ls nope 2> err", &[r#"["ls", "nope"] 2>err"#]),
+            ("ls nope 2>err", &[r#"["ls", "nope"] 2>err"#]),
+            ("ls nope 2>> err", &[r#"["ls", "nope"] 2>>err"#]),
+            ("ls nope 2>>err", &[r#"["ls", "nope"] 2>>err"#]),
+            ("ls nope &> all", &[r#"["ls", "nope"] 1>all 2>all"#]),
+            ("ls nope &>> all", &[r#"["ls", "nope"] 1>>all 2>>all"#]),
+            ("ls . nope > out 2> err", &[r#"["ls", ".", "nope"] 1>out 2>err"#]),
+            ("ls . nope 2>> err 1>> out", &[r#"["ls", ".", "nope"] 2>>err 1>>out"#]),
+        ]);
+    }
+
+    #[test]
+    fn digits_are_only_descriptors_before_redirections() {
+        assert_cases(&[
+            ("echo 1 2", &[r#"["echo", "1", "2"]"#]),
+            ("echo a1 b2 12", &[r#"["echo", "a1", "b2", "12"]"#]),
+            ("echo x1> out", &[r#"["echo", "x1"] 1>out"#]),
+            (r"echo \1>out", &[r#"["echo", "1"] 1>out"#]),
+            ("echo 12>f 3 '3'<g", &[r#"["echo", "12", "3", "3"] 1>f <g"#]),
+        ]);
+    }
+
+    #[test]
+    fn pipelines() {
+        assert_cases(&[
+            ("ls | wc -l", &[r#"["ls"]"#, r#"["wc", "-l"]"#]),
+            ("ls|wc", &[r#"["ls"]"#, r#"["wc"]"#]),
+            (
+                "cat f | grep x | wc",
+                &[r#"["cat", "f"]"#, r#"["grep", "x"]"#, r#"["wc"]"#],
+            ),
+            ("ls -l | grep x > out", &[r#"["ls", "-l"]"#, r#"["grep", "x"] 1>out"#]),
+            ("ls 2> err | wc", &[r#"["ls"] 2>err"#, r#"["wc"]"#]),
+            (r#"echo "a | b" | cat"#, &[r#"["echo", "a | b"]"#, r#"["cat"]"#]),
+        ]);
+    }
+
+    #[test]
+    fn background_jobs() {
+        assert_cases(&[
+            ("sleep 1 &", &[r#"["sleep", "1"] &"#]),
+            ("sleep 1 > out &", &[r#"["sleep", "1"] 1>out &"#]),
+            ("sleep 1&", &[r#"["sleep", "1"] &"#]),
+            ("a | b &", &[r#"["a"]"#, r#"["b"] &"#]),
+            (r#"echo "&" '&' \&"#, &[r#"["echo", "&", "&", "&"]"#]),
+            ("cmd&>log", &[r#"["cmd"] 1>log 2>log"#]),
+        ]);
+    }
+
+    #[test]
+    fn comments() {
+        assert_cases(&[
+            ("echo hi # a comment", &[r#"["echo", "hi"]"#]),
+            ("echo hi #| wc > out", &[r#"["echo", "hi"]"#]),
+            (
+                r##"echo a#b '#' "#" \# x#"##,
+                &[r##"["echo", "a#b", "#", "#", "#", "x#"]"##],
+            ),
+            ("ls | # first\nwc", &[r#"["ls"]"#, r#"["wc"]"#]),
+            ("cat <<EOF # not the body\n# body\nEOF", &[r##"["cat"] <<"# body\n""##]),
+        ]);
+    }
+
+    #[test]
+    fn subshells() {
+        assert_cases(&[
+            ("(cd /tmp && pwd)", &["(cd /tmp && pwd)"]),
+            ("( echo (a) ')' ) > out 2>err", &["( echo (a) ')' ) 1>out 2>err"]),
+            ("(a; b) | wc -l", &["(a; b)", r#"["wc", "-l"]"#]),
+            ("(sleep 1) &", &["(sleep 1) &"]),
+            ("echo (a) '(b)'", &[r#"["echo", "(a)", "(b)"]"#]),
+            ("(unterminated", &[r#"["(unterminated"]"#]),
+        ]);
+    }
+
+    #[test]
+    fn command_substitutions() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo $(date)", &[r#"["echo", "<date>"]"#]),
+            ("echo a$(words)b", &[r#"["echo", "aone", "twob"]"#]),
+            (r#"echo "$(words)""#, &[r#"["echo", "one two"]"#]),
+            ("echo $(echo $(date))", &[r#"["echo", "<echo_$(date)>"]"#]),
+            (r"echo $(echo ')' \)) x", &[r#"["echo", "<echo_')'_\\)>", "x"]"#]),
+            ("echo $(date | cat) | wc", &[r#"["echo", "<date_|_cat>"]"#, r#"["wc"]"#]),
+            (r"echo '$(date)' \$(date)", &[r#"["echo", "$(date)", "$(date)"]"#]),
+            ("echo $(unknown)", &[r#"["echo", "$(unknown)"]"#]),
+            ("echo $(date{1,2})", &[r#"["echo", "<date{1,2}>"]"#]),
+            ("echo $(date", &[r#"["echo", "$(date"]"#]),
+        ];
+        for (input, expected) in cases {
+            let pipeline = parse_input_with(input, &mut TestExpander::default()).unwrap_or_default();
+            let rendered: Vec<String> = pipeline.iter().map(ToString::to_string).collect();
+            assert_eq!(&rendered, expected, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn expanded_redirection_targets() {
+        let cases: &[(&str, &[&str], &[&str])] = &[
+            (
+                r#"echo > "my file" 2>'it''s' <"$1""#,
+                &[r#"["echo"] 1>my file 2>its <a b"#],
+                &[],
+            ),
+            (
+                "echo > $USER.log 2>> ~root/$(date) x",
+                &[r#"["echo", "x"] 1>me.log 2>>/root/<date>"#],
+                &[],
+            ),
+            ("echo > x$1 y", &[r#"["echo", "y"]"#], &["xa b: ambiguous redirect"]),
+            (
+                "echo > $_empty y < {a,b}",
+                &[r#"["echo", "y"]"#],
+                &[": ambiguous redirect", "{a,b}: ambiguous redirect"],
+            ),
+            ("echo >$fields", &[r#"["echo"]"#], &["a : b :c::: ambiguous redirect"]),
+            (
+                "echo > $1: 2>&$?",
+                &[r#"["echo"] 2>&42"#],
+                &["a b:: ambiguous redirect"],
+            ),
+            ("echo > ' a ' >$USER' '", &[r#"["echo"] 1> a  1>me "#], &[]),
+        ];
+        for (input, expected, errors) in cases {
+            let mut expander = TestExpander::default();
+            let pipeline = parse_input_with(input, &mut expander).unwrap_or_default();
+            let rendered: Vec<String> = pipeline.iter().map(ToString::to_string).collect();
+            assert_eq!(&rendered, expected, "input: {input:?}");
+            assert_eq!(&expander.errors, errors, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn field_splitting() {
+        let cases: &[(Option<&'static str>, &str, &[&str])] = &[
+            (None, "echo $fields", &[r#"["echo", "a", ":", "b", ":c::"]"#]),
+            (
+                None,
+                r#"echo x$fields"y" "$fields""#,
+                &[r#"["echo", "x", "a", ":", "b", ":c::y", " a : b  :c::"]"#],
+            ),
+            (Some(" :"), "echo $fields", &[r#"["echo", "a", "b", "c", ""]"#]),
+            (Some(":"), "echo $fields", &[r#"["echo", " a ", " b  ", "c", ""]"#]),
+            (Some(""), "echo $fields", &[r#"["echo", " a : b  :c::"]"#]),
+            (Some(":"), "echo $* $(words)", &[r#"["echo", "a b", "one two"]"#]),
+            (
+                Some(":"),
+                r#"A=$fields cmd "$*""#,
+                &[r#"A=" a : b  :c::" ["cmd", "a b:"]"#],
+            ),
+        ];
+        for (field_separators, input, expected) in cases {
+            let mut expander = TestExpander {
+                field_separators: *field_separators,
+                ..TestExpander::default()
+            };
+            let pipeline = parse_input_with(input, &mut expander).unwrap_or_default();
+            let rendered: Vec<String> = pipeline.iter().map(ToString::to_string).collect();
+            assert_eq!(&rendered, expected, "input: {input:?} with IFS {field_separators:?}");
+        }
+    }
+
+    #[test]
+    fn parameters() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo $?", &[r#"["echo", "42"]"#]),
+            (r#"echo "status $?" x$?y"#, &[r#"["echo", "status 42", "x42y"]"#]),
+            (r"echo '$?' \$? $", &[r#"["echo", "$?", "$?", "$"]"#]),
+            ("echo $$ $$$ $!", &[r#"["echo", "100", "100$", "$!"]"#]),
+            (r#"echo "$$" \$$"#, &[r#"["echo", "100", "$$"]"#]),
+            (
+                "echo $1 $10 ${10} $2 $#",
+                &[r#"["echo", "a", "b", "a", "b0", "ten", "$2", "2"]"#],
+            ),
+            (
+                r#"echo "$1" "${1}x" ${x} ${1"#,
+                &[r#"["echo", "a b", "a bx", "${x}", "${1"]"#],
+            ),
+            (r#"echo $@ $* "$*""#, &[r#"["echo", "a", "b", "a", "b", "a b "]"#]),
+            (r#"echo "$@" "<$@>""#, &[r#"["echo", "a b", "", "<a b", ">"]"#]),
+            ("cat <<E\n$# $@\nE", &[r#"["cat"] <<"2 a b \n""#]),
+            (
+                r#"echo $USER "$USER" ${USER}s $USERs $USER.x"#,
+                &[r#"["echo", "me", "me", "mes", "$USERs", "me.x"]"#],
+            ),
+            (r#"echo $_empty "$_empty" a$_empty"#, &[r#"["echo", "", "a"]"#]),
+            ("cat <<E\n$USER\nE", &[r#"["cat"] <<"me\n""#]),
+            ("cat <<E\n$? \\$?\nE", &[r#"["cat"] <<"42 $?\n""#]),
+            ("cat <<'E'\n$?\nE", &[r#"["cat"] <<"$?\n""#]),
+        ];
+        for (input, expected) in cases {
+            let pipeline = parse_input_with(input, &mut TestExpander::default()).unwrap_or_default();
+            let rendered: Vec<String> = pipeline.iter().map(ToString::to_string).collect();
+            assert_eq!(&rendered, expected, "input: {input:?}");
+        }
+        assert_cases(&[("ls $?", &[r#"["ls", "$?"]"#])]);
+    }
+
+    #[test]
+    fn parameter_operators() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo ${_empty:-a  b} ${USER:-a}", &[r#"["echo", "a", "b", "me"]"#]),
+            (
+                r#"echo "${_empty:-a  b}" ${_empty:-"x  y"}z"#,
+                &[r#"["echo", "a  b", "x", "yz"]"#],
+            ),
+            (
+                "echo ${_empty:-${USER}s} ${_empty:-'}'} ${_empty:-$(true)}",
+                &[r#"["echo", "mes", "}", "<true>"]"#],
+            ),
+            (
+                "echo ${USER:+alt} ${_empty:+alt} ${1:+$#}",
+                &[r#"["echo", "alt", "2"]"#],
+            ),
+            (
+                "echo ${_empty:=set} ${_empty:?} ${USER:?unset}",
+                &[r#"["echo", "set", "me"]"#],
+            ),
+            (
+                "echo ${unknown:-x} ${USER-x} ${USER:x}",
+                &[r#"["echo", "${unknown:-x}", "${USER-x}", "${USER:x}"]"#],
+            ),
+            ("cat <<E\n${_empty:-$?} ${USER:+\"q\"}\nE", &[r#"["cat"] <<"42 q\n""#]),
+            (
+                "echo ${#USER} ${#_empty} ${#1} ${#@} ${#} ${#x}",
+                &[r#"["echo", "2", "0", "3", "2", "2", "${#x}"]"#],
+            ),
+            (
+                "echo ${fields#*:} ${fields##*:} ${fields%:*} ${fields%%:*}",
+                &[r#"["echo", "b", ":c::", "a", ":", "b", ":c:", "a"]"#],
+            ),
+            (
+                r#"echo "${fields#*:}" "${fields%%:*}" ${1#x} ${1%"b"}"#,
+                &[r#"["echo", " b  :c::", " a ", "a", "b", "a"]"#],
+            ),
+            (
+                r#"echo ${USER/e/E} "${fields//:/-}" "${1/ /"*"}" ${1//}"#,
+                &[r#"["echo", "mE", " a - b  -c--", "a*b", "a", "b"]"#],
+            ),
+            (
+                r#"echo "${1/?/${USER}}" "${1//[a-b]/\/}" ${USER/m} ${USER%'?'}"#,
+                &[r#"["echo", "me b", "/ /", "e", "me"]"#],
+            ),
+        ];
+        for (input, expected) in cases {
+            let pipeline = parse_input_with(input, &mut TestExpander::default()).unwrap_or_default();
+            let rendered: Vec<String> = pipeline.iter().map(ToString::to_string).collect();
+            assert_eq!(&rendered, expected, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn unset_parameters_with_nounset() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo $USER ${1} $# $@ $* ${#@}", &[]),
+            ("echo $x ${2}", &["x: unbound variable", "2: unbound variable"]),
+            ("echo ${x:-a} ${x:=a} ${x:?} ${x:+a}", &[]),
+            (
+                r#"echo ${#x} "${x#a}" ${x/a/b} $((x + 1))"#,
+                &["x: unbound variable"; 4],
+            ),
+        ];
+        for (input, errors) in cases {
+            let mut expander = TestExpander {
+                nounset: true,
+                ..TestExpander::default()
+            };
+            let _ = parse_input_with(input, &mut expander);
+            assert_eq!(&expander.errors, errors, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn quoted_words() {
+        let cases = [
+            ("abc", "abc"),
+            ("a/b-c.d=e", "a/b-c.d=e"),
+            ("", "''"),
+            ("a b", "'a b'"),
+            ("it's", r"'it'\''s'"),
+        ];
+        for (word, expected) in cases {
+            assert_eq!(quote_word(word), expected, "word: {word:?}");
+        }
+    }
+
+    #[test]
+    fn tilde_prefixes() {
+        let root = expand_tilde("root").map_or("~root".to_string(), |home| home.display().to_string());
+        assert_cases(&[
+            ("ls ~root/tmp", &[&format!(r#"["ls", "{root}/tmp"]"#)]),
+            ("ls ~no-such-user/x", &[r#"["ls", "~no-such-user/x"]"#]),
+            (
+                r#"echo '~' "~" \~ a~ ~root'x'"#,
+                &[r#"["echo", "~", "~", "~", "a~", "~rootx"]"#],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn pathname_expansion() {
+        let directory = std::env::temp_dir().join(format!("ccsh-glob-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("sub")).unwrap();
+        for name in ["a.rs", "b.rs", "c.txt", ".hidden.rs", "sub/d.rs"] {
+            std::fs::write(directory.join(name), "").unwrap();
+        }
+        let d = directory.display();
+        assert_cases(&[
+            (
+                &format!("ls {d}/*.rs"),
+                &[&format!(r#"["ls", "{d}/a.rs", "{d}/b.rs"]"#)],
+            ),
+            (
+                &format!("ls {d}/?.txt {d}/*/*.rs"),
+                &[&format!(r#"["ls", "{d}/c.txt", "{d}/sub/d.rs"]"#)],
+            ),
+            (
+                &format!("ls {d}/.*.rs {d}/*/"),
+                &[&format!(r#"["ls", "{d}/.hidden.rs", "{d}/sub/"]"#)],
+            ),
+            (
+                &format!(r#"ls "{d}/*.rs" {d}/\*.rs '{d}'/*.txt"#),
+                &[&format!(r#"["ls", "{d}/*.rs", "{d}/*.rs", "{d}/c.txt"]"#)],
+            ),
+            (
+                &format!("ls {d}/*.md > {d}/*.out"),
+                &[&format!(r#"["ls", "{d}/*.md"] 1>{d}/*.out"#)],
+            ),
+            (
+                &format!("ls > {d}/c.* 2> {d}/*.rs"),
+                &[&format!(r#"["ls"] 1>{d}/c.txt"#)],
+            ),
+            (
+                &format!("ls {d}/[ab].rs {d}/[!a-b].*"),
+                &[&format!(r#"["ls", "{d}/a.rs", "{d}/b.rs", "{d}/c.txt"]"#)],
+            ),
+            (
+                &format!(r#"ls {d}/[^a]?rs {d}/"[a]".rs {d}/\[a].rs {d}/[a"#),
+                &[&format!(r#"["ls", "{d}/b.rs", "{d}/[a].rs", "{d}/[a].rs", "{d}/[a"]"#)],
+            ),
+        ]);
+
+        let globstar_cases = [
+            (
+                format!("ls {d}/**/*.rs"),
+                format!(r#"["ls", "{d}/a.rs", "{d}/b.rs", "{d}/sub/d.rs"]"#),
+            ),
+            (
+                format!("ls {d}/**"),
+                format!(r#"["ls", "{d}/a.rs", "{d}/b.rs", "{d}/c.txt", "{d}/sub", "{d}/sub/d.rs"]"#),
+            ),
+        ];
+        for (input, expected) in globstar_cases {
+            let glob_options = GlobOptions {
+                globstar: true,
+                ..GlobOptions::default()
+            };
+            let pipeline = parse_input_with(
+                &input,
+                &mut TestExpander {
+                    glob_options,
+                    ..TestExpander::default()
+                },
+            )
+            .unwrap_or_default();
+            let rendered: Vec<String> = pipeline.iter().map(ToString::to_string).collect();
+            assert_eq!(rendered, [expected], "input: {input:?}");
+        }
+        assert_cases(&[(&format!("ls {d}/**/*.rs"), &[&format!(r#"["ls", "{d}/sub/d.rs"]"#)])]);
+
+        let option_cases = [
+            (
+                GlobOptions {
+                    nullglob: true,
+                    ..GlobOptions::default()
+                },
+                format!("ls {d}/*.md {d}/c.*"),
+                format!(r#"["ls", "{d}/c.txt"]"#),
+            ),
+            (
+                GlobOptions {
+                    dotglob: true,
+                    ..GlobOptions::default()
+                },
+                format!("ls {d}/*.rs"),
+                format!(r#"["ls", "{d}/.hidden.rs", "{d}/a.rs", "{d}/b.rs"]"#),
+            ),
+            (
+                GlobOptions {
+                    nocaseglob: true,
+                    ..GlobOptions::default()
+                },
+                format!("ls {d}/[A]*.RS {d}/C.*"),
+                format!(r#"["ls", "{d}/a.rs", "{d}/c.txt"]"#),
+            ),
+        ];
+        for (glob_options, input, expected) in option_cases {
+            let pipeline = parse_input_with(
+                &input,
+                &mut TestExpander {
+                    glob_options,
+                    ..TestExpander::default()
+                },
+            )
+            .unwrap_or_default();
+            let rendered: Vec<String> = pipeline.iter().map(ToString::to_string).collect();
+            assert_eq!(rendered, [expected], "input: {input:?}");
+        }
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn quoted_braces() {
+        assert_cases(&[(
+            r#"echo "{a,b}" \{a,b} '{a,b}' a{"b,c",d} {a,b}"#,
+            &[r#"["echo", "{a,b}", "{a,b}", "{a,b}", "ab,c", "ad", "a", "b"]"#],
+        )]);
+    }
+
+    #[test]
+    fn input_redirections() {
+        assert_cases(&[
+            ("sort < data.txt", &[r#"["sort"] <data.txt"#]),
+            ("sort<data.txt -r", &[r#"["sort", "-r"] <data.txt"#]),
+            ("sort < a | uniq > b", &[r#"["sort"] <a"#, r#"["uniq"] 1>b"#]),
+            (r#"cat < "my file" \< '<'"#, &[r#"["cat", "<", "<"] <my file"#]),
+        ]);
+    }
+
+    #[test]
+    fn descriptor_redirections() {
+        assert_cases(&[
+            ("cmd 3> trace.log", &[r#"["cmd"] 3>trace.log"#]),
+            ("cmd 9>>a 0<in 4<data", &[r#"["cmd"] 9>>a <in 4<data"#]),
+            ("cmd > a 2> b < c >> d", &[r#"["cmd"] 1>a 2>b <c 1>>d"#]),
+            ("cmd > a &> b 2> c", &[r#"["cmd"] 1>a 1>b 2>b 2>c"#]),
+            ("cmd > out 1> last", &[r#"["cmd"] 1>out 1>last"#]),
+            ("cmd &> both 2> err", &[r#"["cmd"] 1>both 2>both 2>err"#]),
+        ]);
+    }
+
+    #[test]
+    fn duplicate_and_close_redirections() {
+        assert_cases(&[
+            ("ls nope 2>&1 | wc", &[r#"["ls", "nope"] 2>&1"#, r#"["wc"]"#]),
+            ("cmd >&2 2>&- 0<&3", &[r#"["cmd"] 1>&2 2>&- 0>&3"#]),
+            ("cmd 3>&1- <&-", &[r#"["cmd"] 3>&1 1>&- 0>&-"#]),
+            ("cmd >& all 2>&log <&in", &[r#"["cmd"] 1>all 2>all 2>log <in"#]),
+            ("cmd 2>& 1 1>&'2' &", &[r#"["cmd"] 2>&1 1>&2 &"#]),
+        ]);
+    }
+
+    #[test]
+    fn here_documents() {
+        let cases: &[(&str, &[&str])] = &[
+            ("cat <<EOF\nhello\n  world\nEOF", &[r#"["cat"] <<"hello\n  world\n""#]),
+            (
+                "cat <<EOF | wc -l\none\nEOF",
+                &[r#"["cat"] <<"one\n""#, r#"["wc", "-l"]"#],
+            ),
+            ("cat <<-END\n\t\tindented\n\tEND", &[r#"["cat"] <<"indented\n""#]),
+            ("cat <<EOF\n$(date) \\$x 'q'\nEOF", &[r#"["cat"] <<"<date> $x 'q'\n""#]),
+            ("cat <<'EOF'\n$(date) \\$x\nEOF", &[r#"["cat"] <<"$(date) \\$x\n""#]),
+            ("cat <<\"E F\" arg\nbody\nE F", &[r#"["cat", "arg"] <<"body\n""#]),
+            ("cat <<A <<B\na\nA\nb\nB", &[r#"["cat"] <<"a\n" <<"b\n""#]),
+            ("cat <<EOF\nunterminated", &[r#"["cat"] <<"unterminated\n""#]),
+            ("echo '<<EOF'", &[r#"["echo", "<<EOF"]"#]),
+        ];
+        for (input, expected) in cases {
+            let pipeline = parse_input_with(input, &mut TestExpander::default()).unwrap_or_default();
+            let rendered: Vec<String> = pipeline.iter().map(ToString::to_string).collect();
+            assert_eq!(&rendered, expected, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn command_lists() {
+        use Connector::Always;
+        use Connector::And;
+        use Connector::Or;
+        let cases: &[(&str, &[(Connector, &str)])] = &[
+            (
+                "true && echo yes || echo no",
+                &[(Always, "true"), (And, "echo yes"), (Or, "echo no")],
+            ),
+            ("a|b&&c", &[(Always, "a|b"), (And, "c")]),
+            (r#"echo '&&' "||" \&\& x"#, &[(Always, r#"echo '&&' "||" \&\& x"#)]),
+            ("echo $(a && b) || c", &[(Always, "echo $(a && b)"), (Or, "c")]),
+            ("a &&\nb", &[(Always, "a"), (And, "b")]),
+            (
+                "cat <<A && cat <<B\none\nA\ntwo\nB",
+                &[(Always, "cat <<A\none\nA"), (And, "cat <<B\ntwo\nB")],
+            ),
+            ("cat <<A && echo\n&&\nA", &[(Always, "cat <<A\n&&\nA"), (And, "echo")]),
+            (
+                "cd /tmp; pwd;echo done",
+                &[(Always, "cd /tmp"), (Always, "pwd"), (Always, "echo done")],
+            ),
+            (
+                "false && a; b || c;",
+                &[(Always, "false"), (And, "a"), (Always, "b"), (Or, "c")],
+            ),
+            (r"echo ';' \; x", &[(Always, r"echo ';' \; x")]),
+            ("sleep 1 & echo hi&", &[(Always, "sleep 1 &"), (Always, "echo hi&")]),
+            (
+                "if a; then b && c; fi && d",
+                &[(Always, "if a; then b && c; fi"), (And, "d")],
+            ),
+            (
+                "x; if a; then if b; then c; fi; fi; echo fi",
+                &[
+                    (Always, "x"),
+                    (Always, "if a; then if b; then c; fi; fi"),
+                    (Always, "echo fi"),
+                ],
+            ),
+            (
+                "echo if; 'if' a; b",
+                &[(Always, "echo if"), (Always, "'if' a"), (Always, "b")],
+            ),
+            ("a &> log & b", &[(Always, "a &> log &"), (Always, "b")]),
+            ("a 2>&1 <&3 & b", &[(Always, "a 2>&1 <&3 &"), (Always, "b")]),
+            (
+                "(cd /tmp && pwd; ls) || echo failed",
+                &[(Always, "(cd /tmp && pwd; ls)"), (Or, "echo failed")],
+            ),
+            ("a # b; c && d", &[(Always, "a # b; c && d")]),
+            ("a;# b; c\nd", &[(Always, "a"), (Always, "# b; c\nd")]),
+            ("echo x#y; z", &[(Always, "echo x#y"), (Always, "z")]),
+            ("echo '&' \\& &", &[(Always, "echo '&' \\& &")]),
+            (
+                "cat <<A; cat <<B\none\nA\ntwo\nB",
+                &[(Always, "cat <<A\none\nA"), (Always, "cat <<B\ntwo\nB")],
+            ),
+            ("[[ a && b || c ]] || d", &[(Always, "[[ a && b || c ]]"), (Or, "d")]),
+            (
+                "case a in a) b;; esac && c",
+                &[(Always, "case a in a) b;; esac"), (And, "c")],
+            ),
+            ("", &[]),
+        ];
+        for (input, expected) in cases {
+            let expected: Vec<(Connector, String)> = expected
+                .iter()
+                .map(|(connector, text)| (*connector, (*text).to_string()))
+                .collect();
+            assert_eq!(split_command_list(input), Ok(expected), "input: {input:?}");
+        }
+
+        let errors = [
+            ("&& a", "syntax error near unexpected token `&&` at column 1"),
+            ("a || || b", "syntax error near unexpected token `||` at column 6"),
+            ("a &&", "syntax error: unexpected end of input"),
+            ("; a", "syntax error near unexpected token `;` at column 1"),
+            ("  a;; b", "syntax error near unexpected token `;` at column 5"),
+            ("a &&; b", "syntax error near unexpected token `;` at column 5"),
+            ("a & & b", "syntax error near unexpected token `&` at column 5"),
+        ];
+        for (input, expected) in errors {
+            let error = split_command_list(input).expect_err(input);
+            assert_eq!(error.to_string(), expected, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let cases = [
+            ("| ls", "`|`", 0..1),
+            ("echo a | | cat", "`|`", 9..10),
+            ("echo é | | cat", "`|`", 10..11),
+            ("echo a > | cat", "`|`", 9..10),
+            ("ls > >> out", "`>>`", 5..7),
+            ("ls 2> < in", "`<`", 6..7),
+            ("cat <<- &> out", "`&>`", 8..10),
+            ("cat 1< 2<< E", "`2<<`", 7..10),
+            ("echo a |", "end", 8..8),
+            ("ls >", "end", 4..4),
+            ("  cat <  ", "end", 7..7),
+        ];
+        for (input, token, span) in cases {
+            let error = parse_input(input).expect_err(input);
+            let expected = if token == "end" {
+                ParseErrorKind::UnexpectedEnd(None)
+            } else {
+                ParseErrorKind::UnexpectedToken(token.trim_matches('`').to_string())
+            };
+            assert_eq!((error.kind, error.span), (expected, span), "input: {input:?}");
+        }
+        let error = parse_input("echo x\ny | | z").expect_err("second line");
+        assert_eq!(error.to_string(), "syntax error near unexpected token `|` at column 5");
+        assert_eq!(
+            ParseError::unexpected_end("f() {", Some(CHAR_BRACE_CLOSE)).to_string(),
+            "syntax error: unexpected end of input, expected `}`"
+        );
+        assert_cases(&[("ls >> out 2>>err &>>both", &[r#"["ls"] 1>>out 2>>err 1>>both 2>>both"#])]);
+    }
+
+    #[test]
+    fn arithmetic_expansion() {
+        let cases: &[(&str, &[&str])] = &[
+            (
+                r#"echo $((1 + 2 * 3)) "$(( (1+2)*3 ))" $((2**10)) $((7/2)) $((-7%3)) $((0x1f)) $((010)) $((2#101))"#,
+                &[r#"["echo", "7", "9", "1024", "3", "-1", "31", "8", "5"]"#],
+            ),
+            (
+                "echo $(($# * 3)) $((10 > 3 && _empty == 0)) $((_empty ? 1 : 2)) $(( (1, ~4) )) x$((1 / 0))",
+                &[r#"["echo", "6", "1", "2", "-5", "x"]"#],
+            ),
+            (
+                "echo $(( ${#USER} + 1 )) ${_empty:-$((-2))}",
+                &[r#"["echo", "3", "-2"]"#],
+            ),
+            (
+                "echo $((1<<4)) $((256 >> 2)) $(( 1 << 2 <= 4 ))",
+                &[r#"["echo", "16", "64", "1"]"#],
+            ),
+        ];
+        for (input, expected) in cases {
+            let pipeline = parse_input_with(input, &mut TestExpander::default()).unwrap_or_default();
+            let rendered: Vec<String> = pipeline.iter().map(ToString::to_string).collect();
+            assert_eq!(&rendered, expected, "input: {input:?}");
+        }
+        assert_cases(&[("echo $((1+2))", &[r#"["echo", "$((1+2))"]"#])]);
+
+        assert_eq!(
+            split_here_documents("x=$((1<<2))\necho after"),
+            ("x=$((1<<2))\necho after", "")
+        );
+    }
+
+    #[test]
+    fn pipeline_negation() {
+        let cases = [
+            ("! grep -q x file", Some(" grep -q x file")),
+            ("!  ! true", Some("  ! true")),
+            ("! [[ a ]]", Some(" [[ a ]]")),
+            ("echo !", None),
+            ("'!' x", None),
+            ("!x", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(strip_negation(input), expected, "input: {input:?}");
+        }
+        assert_eq!(
+            split_command_list("! if a; then b; fi && ! c"),
+            Ok(vec![
+                (Connector::Always, "! if a; then b; fi".to_string()),
+                (Connector::And, "! c".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn function_headers() {
+        let cases: &[(&str, Option<(&str, &str)>)] = &[
+            ("greet() { echo hi; }", Some(("greet", " echo hi; }"))),
+            ("  precmd ( ) {", Some(("precmd", ""))),
+            ("my-func_2() {\n", Some(("my-func_2", "\n"))),
+            ("greet() {echo hi; }", None),
+            ("echo (a) { b }", None),
+            ("2f() { x; }", None),
+            ("greet()", None),
+            ("echo hi", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse_function_header(input), *expected, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn function_bodies() {
+        let cases: &[(&str, Option<(&str, &str)>)] = &[
+            ("f() { return 3; }; f", Some(("return 3", "f"))),
+            ("f() {\n  echo a\n}", Some(("echo a", ""))),
+            ("f() { echo '}' \\}; }", Some(("echo '}' \\}", ""))),
+            ("f() {\n  echo a; }\ng", Some(("echo a", ""))),
+            ("f() { echo }", None),
+            ("f() {", None),
+        ];
+        for (input, expected) in cases {
+            let mut lines = input.lines().map(ToString::to_string);
+            let header = lines.next().unwrap_or_default();
+            let (_, rest) = parse_function_header(&header).unwrap_or_default();
+            let body = take_function_body(rest, &mut lines).ok();
+            let expected = expected.map(|(body, after)| (body.to_string(), after.to_string()));
+            assert_eq!(body, expected, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn empty_input_has_no_command() {
+        assert_cases(&[("", &[r"[]"]), ("   ", &[r"[]"])]);
     }
 }
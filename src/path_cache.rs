@@ -0,0 +1,104 @@
+use crate::commands::is_executable;
+use crate::commands::search_executable;
+use crate::locale::collate;
+use crate::parser::ENVIRONMENT_VARIABLE_EXECIGNORE;
+use crate::parser::ENVIRONMENT_VARIABLE_PATH;
+use std::collections::BTreeMap;
+use std::env::var_os;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+/// A remembered executable path and how often it was used.
+#[derive(Clone, Debug)]
+struct CachedPath {
+    path: PathBuf,
+    hits: usize,
+}
+
+/// Remembered `$PATH` lookups (bash's hash table), so repeated commands skip the directory scans.
+///
+/// The cache empties itself when `$PATH` changes, and a remembered path that is no longer
+/// executable is looked up again.
+#[derive(Debug, Default)]
+pub struct PathCache {
+    path_var: Option<OsString>,
+    execignore_var: Option<OsString>,
+    entries: BTreeMap<OsString, CachedPath>,
+}
+
+impl PathCache {
+    /// Resolve `command` through the cache, searching `$PATH` on a miss.
+    ///
+    /// Names containing `/` are never looked up in `$PATH`, so they are not remembered.
+    pub fn lookup(&mut self, command: impl AsRef<OsStr>) -> Option<PathBuf> {
+        let command = command.as_ref();
+        if command.as_bytes().contains(&b'/') {
+            return search_executable(command);
+        }
+        self.invalidate_on_path_change();
+
+        if let Some(cached) = self.entries.get_mut(command) {
+            if is_executable(&cached.path).unwrap_or(false) {
+                cached.hits += 1;
+                return Some(cached.path.clone());
+            }
+        }
+
+        let Some(path) = search_executable(command) else {
+            self.entries.remove(command);
+            return None;
+        };
+        self.entries.insert(
+            command.to_os_string(),
+            CachedPath {
+                path: path.clone(),
+                hits: 1,
+            },
+        );
+        Some(path)
+    }
+
+    /// Look `command` up and remember it without counting a use, as `hash name` does.
+    pub fn remember(&mut self, command: impl AsRef<OsStr>) -> Option<PathBuf> {
+        let command = command.as_ref();
+        let path = self.lookup(command)?;
+        if let Some(cached) = self.entries.get_mut(command) {
+            cached.hits -= 1;
+        }
+        Some(path)
+    }
+
+    /// Forget every remembered path (`hash -r`).
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Print the remembered paths in `hash` format, sorted by command name in the collation locale.
+    pub fn list(&self, out: &mut dyn Write) {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by(|(a, _), (b, _)| collate(&a.to_string_lossy(), &b.to_string_lossy()));
+        let _ = writeln!(out, "hits\tcommand");
+        for (_, cached) in entries {
+            let _ = writeln!(out, "{:>4}\t{}", cached.hits, cached.path.display());
+        }
+    }
+
+    /// Forget every path once `$PATH` or `$EXECIGNORE` no longer matches what they were found with.
+    fn invalidate_on_path_change(&mut self) {
+        let path_var = var_os(ENVIRONMENT_VARIABLE_PATH);
+        let execignore_var = var_os(ENVIRONMENT_VARIABLE_EXECIGNORE);
+        if path_var != self.path_var || execignore_var != self.execignore_var {
+            self.entries.clear();
+            self.path_var = path_var;
+            self.execignore_var = execignore_var;
+        }
+    }
+}
@@ -0,0 +1,364 @@
+const PATTERN_ANY_CHAR: char = '?';
+const PATTERN_ANY_RUN: char = '*';
+const PATTERN_BRACKET_CLOSE: char = ']';
+const PATTERN_BRACKET_NEGATE: &[char] = &['!', '^'];
+const PATTERN_BRACKET_OPEN: char = '[';
+const PATTERN_BRACKET_RANGE: char = '-';
+pub const PATTERN_ESCAPE: char = '\\';
+/// Characters with a meaning in patterns, escaped when they stand for themselves.
+const PATTERN_SPECIAL_CHARS: &[char] = &[
+    PATTERN_ANY_CHAR,
+    PATTERN_ANY_RUN,
+    PATTERN_BRACKET_CLOSE,
+    PATTERN_BRACKET_OPEN,
+    PATTERN_ESCAPE,
+];
+
+/// Append `c` to `pattern` as a literal character, escaping it when it has a pattern meaning.
+pub fn push_literal(pattern: &mut String, c: char) {
+    if PATTERN_SPECIAL_CHARS.contains(&c) {
+        pattern.push(PATTERN_ESCAPE);
+    }
+    pattern.push(c);
+}
+
+/// Whether `pattern` has an unescaped wildcard or bracket expression, and so can match more than
+/// its literal text.
+#[must_use]
+pub fn has_wildcards(pattern: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let mut p = 0;
+    while p < pattern.len() {
+        match pattern[p] {
+            PATTERN_ESCAPE => p += 1,
+            PATTERN_ANY_CHAR | PATTERN_ANY_RUN => return true,
+            PATTERN_BRACKET_OPEN if bracket_end(&pattern, p).is_some() => return true,
+            _ => {}
+        }
+        p += 1;
+    }
+    false
+}
+
+/// The literal text `pattern` stands for, with its escapes removed.
+#[must_use]
+pub fn unescape(pattern: &str) -> String {
+    let mut text = String::with_capacity(pattern.len());
+    let mut characters = pattern.chars();
+    while let Some(c) = characters.next() {
+        match c {
+            PATTERN_ESCAPE => text.extend(characters.next()),
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+/// Whether all of `text` matches the shell pattern `pattern`: `*` matches any run of characters,
+/// `/` included, `?` matches any one character, `[...]` matches one character from a set of
+/// characters and `a-z` ranges (any character not in it after `!` or `^`), and `\` makes the next
+/// character literal. A `[` without a closing `]` is literal.
+#[must_use]
+pub fn pattern_matches(pattern: &str, text: &str) -> bool {
+    pattern_matches_with_case(pattern, text, false)
+}
+
+/// Whether all of `text` matches `pattern`, as [`pattern_matches`] does, but with letters of
+/// either case matching each other when `ignore_case` is set, in bracket expressions too.
+#[must_use]
+pub fn pattern_matches_with_case(pattern: &str, text: &str, ignore_case: bool) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*`: the pattern index after it and the text index it covers up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(&PATTERN_ANY_RUN) => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+                continue;
+            }
+            Some(&PATTERN_ANY_CHAR) => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some(&PATTERN_BRACKET_OPEN) if bracket_end(&pattern, p).is_some() => {
+                let end = bracket_end(&pattern, p).unwrap_or(p);
+                if bracket_matches(&pattern[p + 1..end], text[t], ignore_case) {
+                    p = end + 1;
+                    t += 1;
+                    continue;
+                }
+            }
+            Some(&PATTERN_ESCAPE) if pattern.get(p + 1).is_some_and(|&c| same_char(c, text[t], ignore_case)) => {
+                p += 2;
+                t += 1;
+                continue;
+            }
+            Some(&literal) if literal != PATTERN_ESCAPE && same_char(literal, text[t], ignore_case) => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+        // Mismatch: let the last `*` swallow one more character, or fail without one.
+        let Some((resume, covered)) = backtrack else {
+            return false;
+        };
+        backtrack = Some((resume, covered + 1));
+        p = resume;
+        t = covered + 1;
+    }
+    pattern[p..].iter().all(|&c| c == PATTERN_ANY_RUN)
+}
+
+/// `text` without its shortest prefix matching `pattern`, or its longest with `longest`. `text` is
+/// unchanged when no prefix matches.
+#[must_use]
+pub fn remove_matching_prefix<'a>(pattern: &str, text: &'a str, longest: bool) -> &'a str {
+    let mut ends = char_boundaries(text);
+    if longest {
+        ends.reverse();
+    }
+    ends.into_iter()
+        .find(|&end| pattern_matches(pattern, &text[..end]))
+        .map_or(text, |end| &text[end..])
+}
+
+/// `text` without its shortest suffix matching `pattern`, or its longest with `longest`. `text` is
+/// unchanged when no suffix matches.
+#[must_use]
+pub fn remove_matching_suffix<'a>(pattern: &str, text: &'a str, longest: bool) -> &'a str {
+    let mut starts = char_boundaries(text);
+    if !longest {
+        starts.reverse();
+    }
+    starts
+        .into_iter()
+        .find(|&start| pattern_matches(pattern, &text[start..]))
+        .map_or(text, |start| &text[..start])
+}
+
+/// `text` with the first longest non-empty match of `pattern` replaced by `replacement`, or every
+/// match with `all`, scanning left to right.
+#[must_use]
+pub fn replace_matches(pattern: &str, text: &str, replacement: &str, all: bool) -> String {
+    let boundaries = char_boundaries(text);
+    let mut replaced = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (index, &start) in boundaries.iter().enumerate() {
+        if start < copied {
+            continue;
+        }
+        let Some(&end) = boundaries[index + 1..]
+            .iter()
+            .rev()
+            .find(|&&end| pattern_matches(pattern, &text[start..end]))
+        else {
+            continue;
+        };
+        replaced.push_str(&text[copied..start]);
+        replaced.push_str(replacement);
+        copied = end;
+        if !all {
+            break;
+        }
+    }
+    replaced.push_str(&text[copied..]);
+    replaced
+}
+
+/// The byte offsets in `text` where a character starts, and its length.
+fn char_boundaries(text: &str) -> Vec<usize> {
+    text.char_indices()
+        .map(|(index, _)| index)
+        .chain([text.len()])
+        .collect()
+}
+
+/// The index of the `]` closing the bracket expression opened at `pattern[open]`. A `]` right after
+/// the `[` (or after its `!` or `^`) is a member rather than the end.
+fn bracket_end(pattern: &[char], open: usize) -> Option<usize> {
+    let mut p = open + 1;
+    if pattern.get(p).is_some_and(|c| PATTERN_BRACKET_NEGATE.contains(c)) {
+        p += 1;
+    }
+    if pattern.get(p) == Some(&PATTERN_BRACKET_CLOSE) {
+        p += 1;
+    }
+    while let Some(&c) = pattern.get(p) {
+        match c {
+            PATTERN_BRACKET_CLOSE => return Some(p),
+            PATTERN_ESCAPE => p += 2,
+            _ => p += 1,
+        }
+    }
+    None
+}
+
+/// Whether `c` is in the set described by `members`, the text between a bracket expression's
+/// `[` and `]`.
+fn bracket_matches(members: &[char], c: char, ignore_case: bool) -> bool {
+    let (negated, members) = match members.split_first() {
+        Some((first, rest)) if PATTERN_BRACKET_NEGATE.contains(first) => (true, rest),
+        _ => (false, members),
+    };
+    let found = if ignore_case {
+        [c, lowercase(c), uppercase(c)]
+            .into_iter()
+            .any(|c| bracket_contains(members, c))
+    } else {
+        bracket_contains(members, c)
+    };
+    found != negated
+}
+
+/// Whether `c` is one of `members`, the set of a bracket expression without its negation.
+fn bracket_contains(members: &[char], c: char) -> bool {
+    let mut found = false;
+    let mut m = 0;
+    while m < members.len() {
+        let mut low = members[m];
+        if low == PATTERN_ESCAPE && m + 1 < members.len() {
+            m += 1;
+            low = members[m];
+        }
+        m += 1;
+        // A `-` first, last, or after a range is a member itself.
+        if members.get(m) == Some(&PATTERN_BRACKET_RANGE) && m + 1 < members.len() {
+            let mut high = members[m + 1];
+            m += 2;
+            if high == PATTERN_ESCAPE && m < members.len() {
+                high = members[m];
+                m += 1;
+            }
+            found |= low <= c && c <= high;
+        } else {
+            found |= low == c;
+        }
+    }
+    found
+}
+
+/// Whether `a` and `b` are the same character, or the same letter in either case with `ignore_case`.
+fn same_char(a: char, b: char, ignore_case: bool) -> bool {
+    a == b || ignore_case && lowercase(a) == lowercase(b)
+}
+
+/// The lowercase form of `c`, or `c` itself when that is not a single character.
+fn lowercase(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => c,
+    }
+}
+
+/// The uppercase form of `c`, or `c` itself when that is not a single character.
+fn uppercase(c: char) -> char {
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(upper), None) => upper,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching() {
+        let cases = [
+            ("", "", true),
+            ("", "a", false),
+            ("abc", "abc", true),
+            ("abc", "abd", false),
+            ("*", "", true),
+            ("*", "anything/at all", true),
+            ("*.rs", "main.rs", true),
+            ("*.rs", "main.rs.bak", false),
+            ("a*b*c", "a-b-b-c", true),
+            ("a*b*c", "a-c-b", false),
+            ("**", "x", true),
+            ("?", "é", true),
+            ("?", "", false),
+            ("??", "a", false),
+            ("a?c", "abc", true),
+            ("[abc]", "b", true),
+            ("[abc]", "d", false),
+            ("[a-c]x", "bx", true),
+            ("[a-c]x", "dx", false),
+            ("[!a-c]", "d", true),
+            ("[!a-c]", "a", false),
+            ("[^a-c]", "d", true),
+            ("[]a]", "]", true),
+            ("[!]a]", "]", false),
+            ("[a-]", "-", true),
+            ("[-a]", "-", true),
+            ("[a\\]]", "]", true),
+            ("[\\!a]", "!", true),
+            ("[0-9][0-9]", "42", true),
+            ("[a", "[a", true),
+            ("[a", "a", false),
+            ("\\*", "*", true),
+            ("\\*", "x", false),
+            ("\\?\\[x]", "?[x]", true),
+            ("a\\\\b", "a\\b", true),
+            // Unlike pathname expansion, a plain match has no leading-dot rule.
+            ("*", ".hidden", true),
+            ("?hidden", ".hidden", true),
+            ("[.]hidden", ".hidden", true),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(pattern_matches(pattern, text), expected, "{pattern:?} ~ {text:?}");
+        }
+    }
+
+    #[test]
+    fn case_insensitive_matching() {
+        assert!(pattern_matches_with_case("*.RS", "main.rs", true));
+        assert!(pattern_matches_with_case("[A-C]x", "bX", true));
+        assert!(pattern_matches_with_case("[!a]", "A", false));
+        assert!(!pattern_matches_with_case("[!a]", "A", true));
+        assert!(!pattern_matches_with_case("*.RS", "main.rs", false));
+    }
+
+    #[test]
+    fn literals_and_wildcards() {
+        let mut pattern = String::new();
+        for c in "a*?[]\\b".chars() {
+            push_literal(&mut pattern, c);
+        }
+        assert_eq!(pattern, "a\\*\\?\\[\\]\\\\b");
+        assert!(!has_wildcards(&pattern));
+        assert!(pattern_matches(&pattern, "a*?[]\\b"));
+        assert_eq!(unescape(&pattern), "a*?[]\\b");
+
+        for (pattern, expected) in [
+            ("*", true),
+            ("a?", true),
+            ("[ab]", true),
+            ("[ab", false),
+            ("plain", false),
+        ] {
+            assert_eq!(has_wildcards(pattern), expected, "{pattern:?}");
+        }
+    }
+
+    #[test]
+    fn prefixes_suffixes_and_replacements() {
+        assert_eq!(remove_matching_prefix("*/", "a/b/c", false), "b/c");
+        assert_eq!(remove_matching_prefix("*/", "a/b/c", true), "c");
+        assert_eq!(remove_matching_prefix("x", "abc", true), "abc");
+        assert_eq!(remove_matching_suffix(".*", "a.tar.gz", false), "a.tar");
+        assert_eq!(remove_matching_suffix(".*", "a.tar.gz", true), "a");
+        assert_eq!(replace_matches("o*", "foo boo", "0", false), "f0");
+        assert_eq!(replace_matches("o", "foo boo", "0", true), "f00 b00");
+        assert_eq!(replace_matches("[aeiou]", "héllo", "", true), "héll");
+    }
+}
@@ -0,0 +1,79 @@
+use crate::config::ShellConfig;
+use crate::error::ReadError;
+use crate::error::ShellError;
+use crate::frecency::SharedDirectoryDatabase;
+use crate::history::CommandHistory;
+use crate::jobs::JobSummary;
+use crate::options::ShellOptions;
+use std::io;
+use std::io::BufRead;
+use std::io::IsTerminal;
+use std::io::Write;
+
+/// Line reader for builds without the `readline` feature: one line at a time from stdin, the
+/// prompt shown only on a terminal, history kept in memory, and no completion or key bindings.
+pub struct PlainReader {
+    history: Vec<String>,
+    interactive: bool,
+}
+
+impl PlainReader {
+    /// A reader on stdin. Takes the same arguments as the line editor it replaces.
+    pub fn new(_config: &ShellConfig, _directories: SharedDirectoryDatabase) -> Result<Self, ShellError> {
+        Ok(Self {
+            history: Vec::new(),
+            interactive: io::stdin().is_terminal(),
+        })
+    }
+
+    #[must_use]
+    pub fn history(&self) -> &dyn CommandHistory {
+        &self.history
+    }
+
+    pub fn history_mut(&mut self) -> &mut dyn CommandHistory {
+        &mut self.history
+    }
+
+    /// Nothing to complete without a line editor.
+    pub fn add_commands(&mut self, _names: impl IntoIterator<Item = String>) {}
+
+    /// Nor aliases.
+    pub fn set_aliases(&mut self, _names: impl IntoIterator<Item = String>) {}
+
+    /// Nor variables.
+    pub fn set_variables(&mut self, _names: impl IntoIterator<Item = String>) {}
+
+    /// Append the non-empty lines saved in `path` to the history.
+    pub fn load_history(&mut self, path: &str) {
+        if let Ok(content) = std::fs::read_to_string(path) {
+            for line in content.lines().filter(|line| !line.is_empty()) {
+                self.history.add(line);
+            }
+        }
+    }
+
+    /// Show `prompt` on a terminal and read one line; the caller adds it to the history. Invalid
+    /// UTF-8 is replaced rather than failing the line, so a script with a stray byte keeps running.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        _options: &ShellOptions,
+        _jobs: Vec<JobSummary>,
+    ) -> Result<String, ReadError> {
+        if self.interactive {
+            let mut stdout = io::stdout();
+            write!(stdout, "{prompt}").unwrap_or_default();
+            stdout.flush().unwrap_or_default();
+        }
+
+        let mut bytes = Vec::new();
+        if io::stdin().lock().read_until(b'\n', &mut bytes)? == 0 {
+            return Err(ReadError::Eof);
+        }
+        if bytes.last() == Some(&b'\n') {
+            bytes.pop();
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::io::Write;
+
+/// An extra builtin supplied by the embedding program instead of `commands.rs`.
+///
+/// Plugins see only their arguments and streams, so the shell's internals can change without
+/// breaking them. A plugin named like a core builtin is shadowed by it.
+pub trait BuiltinPlugin {
+    /// The command name that runs this builtin.
+    fn name(&self) -> &str;
+
+    /// Run with `arguments` (not including the name) and return the exit status.
+    fn run(
+        &mut self,
+        arguments: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32;
+}
+
+/// The plugins registered on a shell, by name.
+#[derive(Default)]
+pub struct PluginRegistry {
+    plugins: BTreeMap<String, Box<dyn BuiltinPlugin>>,
+}
+
+impl PluginRegistry {
+    /// Add `plugin`, returning the plugin it replaces under the same name, if any.
+    pub fn register(&mut self, plugin: Box<dyn BuiltinPlugin>) -> Option<Box<dyn BuiltinPlugin>> {
+        self.plugins.insert(plugin.name().to_string(), plugin)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut (dyn BuiltinPlugin + 'static)> {
+        self.plugins.get_mut(name).map(AsMut::as_mut)
+    }
+
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.plugins.contains_key(name)
+    }
+
+    /// Registered names, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.plugins.keys().map(String::as_str)
+    }
+}
@@ -0,0 +1,509 @@
+use crate::parser::expand_escape_sequences;
+
+const DIRECTIVE_START: char = '%';
+/// The width or precision is taken from the next argument.
+const DIRECTIVE_FROM_ARGUMENT: char = '*';
+const DIRECTIVE_PRECISION: char = '.';
+/// The default precision of floating-point conversions.
+const DEFAULT_PRECISION: usize = 6;
+
+/// One conversion of a format, such as `%-8.3s`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Directive {
+    /// `-`: pad on the right.
+    left: bool,
+    /// `+`: sign positive numbers.
+    plus: bool,
+    /// ` `: put a space before positive numbers.
+    space: bool,
+    /// `0`: pad numbers with zeros.
+    zero: bool,
+    /// `#`: the alternate form, `0x` before hexadecimal and trailing zeros kept by `%g`.
+    alternate: bool,
+    /// `None` for `*`, taken from an argument.
+    width: Option<Option<usize>>,
+    /// `None` for `.*`, taken from an argument.
+    precision: Option<Option<usize>>,
+    conversion: char,
+}
+
+/// A piece of a format: text printed as it is, or a conversion of the next argument.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Piece {
+    Text(Vec<u8>),
+    Directive(Directive),
+    /// `\c`: nothing more is printed.
+    Stop,
+}
+
+/// Format `arguments` as `printf format arguments...` does, returning the output and the errors
+/// to report, such as an argument that is not a number.
+///
+/// The format's backslash escapes are those of `echo -e`. `%s` prints an argument, `%b` with its
+/// escapes expanded (where `\c` ends all output), `%q` quoted so the shell reads it back, and `%c`
+/// its first character. `%d` and `%i` print an integer, `%u`, `%o`, `%x`, and `%X` an unsigned one,
+/// and `%f`, `%e`, `%g` (and their capitals) a floating-point number; an integer argument can be
+/// hexadecimal (`0x1f`), octal (`017`), or a quote followed by a character, which stands for its
+/// code. Flags (`-+ 0#`), a width, and a precision can come between the `%` and the conversion, a
+/// `*` taking them from an argument. `%%` is a `%`. The format is used again while arguments are
+/// left, and missing ones are empty or zero.
+#[must_use]
+pub fn format_printf(format: &str, arguments: &[String]) -> (Vec<u8>, Vec<String>) {
+    let mut output = Vec::new();
+    let mut errors = Vec::new();
+    let pieces = parse_format(format, &mut errors);
+    // A format cut short by an invalid conversion is not used again.
+    let complete = errors.is_empty();
+    let mut arguments = arguments.iter().map(String::as_str);
+    loop {
+        let left = arguments.len();
+        for piece in &pieces {
+            match piece {
+                Piece::Text(text) => output.extend_from_slice(text),
+                Piece::Directive(directive) => {
+                    if !convert(directive, &mut arguments, &mut output, &mut errors) {
+                        return (output, errors);
+                    }
+                }
+                Piece::Stop => return (output, errors),
+            }
+        }
+        if !complete || arguments.len() == 0 || arguments.len() == left {
+            break;
+        }
+    }
+    (output, errors)
+}
+
+/// Split `format` into its pieces. An invalid conversion is reported in `errors` and ends the
+/// format there.
+fn parse_format(format: &str, errors: &mut Vec<String>) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut characters = format.char_indices().peekable();
+    let mut text_start = 0;
+    let push_text = |pieces: &mut Vec<Piece>, text: &str| {
+        if text.is_empty() {
+            return true;
+        }
+        let (bytes, stopped) = expand_escape_sequences(text);
+        pieces.push(Piece::Text(bytes));
+        if stopped {
+            pieces.push(Piece::Stop);
+        }
+        !stopped
+    };
+    while let Some((index, c)) = characters.next() {
+        if c != DIRECTIVE_START {
+            continue;
+        }
+        if !push_text(&mut pieces, &format[text_start..index]) {
+            return pieces;
+        }
+        if characters.next_if(|(_, c)| *c == DIRECTIVE_START).is_some() {
+            pieces.push(Piece::Text(vec![b'%']));
+            text_start = index + 2;
+            continue;
+        }
+        let mut directive = Directive::default();
+        while let Some((_, flag)) = characters.next_if(|(_, c)| "-+ 0#".contains(*c)) {
+            match flag {
+                '-' => directive.left = true,
+                '+' => directive.plus = true,
+                ' ' => directive.space = true,
+                '0' => directive.zero = true,
+                _ => directive.alternate = true,
+            }
+        }
+        directive.width = take_count(&mut characters);
+        if characters.next_if(|(_, c)| *c == DIRECTIVE_PRECISION).is_some() {
+            directive.precision = Some(take_count(&mut characters).unwrap_or(Some(0)));
+        }
+        match characters.next() {
+            Some((end, conversion)) if "bcdeEfFgGioqsuxX".contains(conversion) => {
+                directive.conversion = conversion;
+                pieces.push(Piece::Directive(directive));
+                text_start = end + conversion.len_utf8();
+            }
+            Some((_, conversion)) => {
+                errors.push(format!("`{conversion}': invalid format character"));
+                return pieces;
+            }
+            None => {
+                errors.push(format!("`{}': missing format character", &format[index..]));
+                return pieces;
+            }
+        }
+    }
+    push_text(&mut pieces, &format[text_start..]);
+    pieces
+}
+
+/// A width or precision: digits, or `*` (`Some(None)`) to take it from an argument.
+fn take_count(characters: &mut std::iter::Peekable<std::str::CharIndices>) -> Option<Option<usize>> {
+    if characters.next_if(|(_, c)| *c == DIRECTIVE_FROM_ARGUMENT).is_some() {
+        return Some(None);
+    }
+    let mut count = None;
+    while let Some((_, digit)) = characters.next_if(|(_, c)| c.is_ascii_digit()) {
+        let digit = digit.to_digit(10).unwrap_or_default() as usize;
+        count = Some(count.unwrap_or(0_usize).saturating_mul(10).saturating_add(digit));
+    }
+    count.map(Some)
+}
+
+/// Append `directive` applied to the next arguments to `output`. Returns `false` when a `\c` in a
+/// `%b` argument ended all output.
+fn convert<'a>(
+    directive: &Directive,
+    arguments: &mut impl Iterator<Item = &'a str>,
+    output: &mut Vec<u8>,
+    errors: &mut Vec<String>,
+) -> bool {
+    let mut directive = *directive;
+    let mut width = 0;
+    match directive.width {
+        Some(Some(count)) => width = count,
+        Some(None) => {
+            let count = parse_integer(arguments.next().unwrap_or_default(), errors);
+            directive.left |= count < 0;
+            width = usize::try_from(count.unsigned_abs()).unwrap_or(usize::MAX);
+        }
+        None => {}
+    }
+    let precision = match directive.precision {
+        Some(Some(count)) => Some(count),
+        // A negative precision is as if there were none.
+        Some(None) => usize::try_from(parse_integer(arguments.next().unwrap_or_default(), errors)).ok(),
+        None => None,
+    };
+    let argument = arguments.next().unwrap_or_default();
+
+    let (prefix, body, zero_padded): (String, Vec<u8>, bool) = match directive.conversion {
+        's' | 'q' => {
+            let text = if directive.conversion == 'q' {
+                quote_for_input(argument)
+            } else {
+                argument.to_string()
+            };
+            let text = match precision {
+                Some(precision) => text.chars().take(precision).collect(),
+                None => text,
+            };
+            (String::new(), text.into_bytes(), false)
+        }
+        'b' => {
+            let (mut bytes, stopped) = expand_escape_sequences(argument);
+            if let Some(precision) = precision {
+                bytes.truncate(precision);
+            }
+            if stopped {
+                output.extend_from_slice(&pad(&directive, width, "", &bytes, false));
+                return false;
+            }
+            (String::new(), bytes, false)
+        }
+        'c' => (
+            String::new(),
+            argument.chars().take(1).collect::<String>().into_bytes(),
+            false,
+        ),
+        'd' | 'i' => {
+            let value = parse_integer(argument, errors);
+            let digits = with_precision(value.unsigned_abs().to_string(), precision, value == 0);
+            (sign(&directive, value < 0), digits.into_bytes(), precision.is_none())
+        }
+        conversion @ ('u' | 'o' | 'x' | 'X') => {
+            // Negative numbers wrap around, as in C.
+            let value = parse_integer(argument, errors) as u64;
+            let digits = match conversion {
+                'u' => value.to_string(),
+                'o' => format!("{value:o}"),
+                'x' => format!("{value:x}"),
+                _ => format!("{value:X}"),
+            };
+            let mut digits = with_precision(digits, precision, value == 0);
+            let mut prefix = String::new();
+            if directive.alternate && conversion == 'o' && !digits.starts_with('0') {
+                digits.insert(0, '0');
+            } else if directive.alternate && value != 0 && conversion != 'o' && conversion != 'u' {
+                prefix = if conversion == 'x' { "0x" } else { "0X" }.to_string();
+            }
+            (prefix, digits.into_bytes(), precision.is_none())
+        }
+        conversion => {
+            let value = parse_float(argument, errors);
+            let precision = precision.unwrap_or(DEFAULT_PRECISION);
+            let body = format_float(value.abs(), conversion, precision, directive.alternate);
+            (
+                sign(&directive, value.is_sign_negative() && !value.is_nan()),
+                body.into_bytes(),
+                value.is_finite(),
+            )
+        }
+    };
+    output.extend_from_slice(&pad(&directive, width, &prefix, &body, zero_padded));
+    true
+}
+
+/// `text` as `%q` writes it, the way bash does: with a backslash before each character the shell
+/// would treat specially, or in `$'...'` with escapes when it holds control characters, which a
+/// backslash cannot protect. An empty argument is `''`.
+fn quote_for_input(text: &str) -> String {
+    if text.is_empty() {
+        return "''".to_string();
+    }
+    if text.chars().any(char::is_control) {
+        let mut quoted = String::from("$'");
+        for c in text.chars() {
+            match c {
+                '\\' | '\'' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                '\n' => quoted.push_str("\\n"),
+                '\t' => quoted.push_str("\\t"),
+                '\r' => quoted.push_str("\\r"),
+                '\x07' => quoted.push_str("\\a"),
+                '\x08' => quoted.push_str("\\b"),
+                '\x1b' => quoted.push_str("\\E"),
+                '\x0c' => quoted.push_str("\\f"),
+                '\x0b' => quoted.push_str("\\v"),
+                c if c.is_ascii_control() => quoted.push_str(&format!("\\{:03o}", u32::from(c))),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('\'');
+        return quoted;
+    }
+    let mut quoted = String::new();
+    for (index, c) in text.char_indices() {
+        // `~` and `#` are special only at the start of a word.
+        let special = " \"'\\|&;()<>!{}*[?]^$`,".contains(c) || (index == 0 && matches!(c, '~' | '#'));
+        if special {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted
+}
+
+/// The sign a number is printed with: `-` when it is negative, else as the `+` and ` ` flags ask.
+fn sign(directive: &Directive, negative: bool) -> String {
+    if negative {
+        "-".to_string()
+    } else if directive.plus {
+        "+".to_string()
+    } else if directive.space {
+        " ".to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// `digits` with zeros before them up to `precision` digits; a precision of 0 prints no digits for 0.
+fn with_precision(digits: String, precision: Option<usize>, zero: bool) -> String {
+    match precision {
+        Some(0) if zero => String::new(),
+        Some(precision) => format!("{digits:0>precision$}"),
+        None => digits,
+    }
+}
+
+/// `prefix` and `body` padded to `width` bytes: with spaces on the left, or on the right for the
+/// `-` flag, or with zeros after the prefix for the `0` flag when `zero_padded` allows it.
+fn pad(directive: &Directive, width: usize, prefix: &str, body: &[u8], zero_padded: bool) -> Vec<u8> {
+    let fill = width.saturating_sub(prefix.len() + body.len());
+    let mut padded = Vec::with_capacity(prefix.len() + body.len() + fill);
+    if directive.left {
+        padded.extend_from_slice(prefix.as_bytes());
+        padded.extend_from_slice(body);
+        padded.resize(padded.len() + fill, b' ');
+    } else if directive.zero && zero_padded {
+        padded.extend_from_slice(prefix.as_bytes());
+        padded.resize(padded.len() + fill, b'0');
+        padded.extend_from_slice(body);
+    } else {
+        padded.resize(fill, b' ');
+        padded.extend_from_slice(prefix.as_bytes());
+        padded.extend_from_slice(body);
+    }
+    padded
+}
+
+/// The integer `argument` stands for: decimal, `0x` hexadecimal, `0` octal, or `'c` for the code
+/// of `c`. Empty is 0; anything else is reported in `errors`, and the number it starts with used.
+fn parse_integer(argument: &str, errors: &mut Vec<String>) -> i64 {
+    if let Some(quoted) = argument.strip_prefix(['\'', '"']) {
+        return quoted.chars().next().map_or(0, |c| i64::from(u32::from(c)));
+    }
+    let text = argument.trim_start();
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (radix, digits) = if let Some(hex) = unsigned.strip_prefix("0x").or_else(|| unsigned.strip_prefix("0X")) {
+        (16, hex)
+    } else if unsigned.len() > 1 && unsigned.starts_with('0') {
+        (8, &unsigned[1..])
+    } else {
+        (10, unsigned)
+    };
+    let length = digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len());
+    if !text.is_empty() && (length == 0 || length < digits.len()) {
+        errors.push(format!("{argument}: invalid number"));
+    }
+    if length == 0 {
+        return 0;
+    }
+    let magnitude = u64::from_str_radix(&digits[..length], radix)
+        .ok()
+        .and_then(|magnitude| i64::try_from(magnitude).ok());
+    match magnitude {
+        Some(magnitude) if negative => -magnitude,
+        Some(magnitude) => magnitude,
+        None => {
+            errors.push(format!("{argument}: Numerical result out of range"));
+            if negative {
+                i64::MIN
+            } else {
+                i64::MAX
+            }
+        }
+    }
+}
+
+/// The floating-point number `argument` stands for, or `'c` for the code of `c`. Empty is 0;
+/// anything else is reported in `errors` and taken as 0.
+fn parse_float(argument: &str, errors: &mut Vec<String>) -> f64 {
+    if argument.starts_with(['\'', '"']) {
+        return parse_integer(argument, errors) as f64;
+    }
+    let text = argument.trim();
+    if text.is_empty() {
+        return 0.0;
+    }
+    text.parse().unwrap_or_else(|_| {
+        errors.push(format!("{argument}: invalid number"));
+        0.0
+    })
+}
+
+/// The non-negative `value` in the form of `conversion`: fixed (`f`), exponent (`e`), or the
+/// shorter of the two (`g`), with `precision` digits after the point (significant digits for `g`).
+fn format_float(value: f64, conversion: char, precision: usize, alternate: bool) -> String {
+    let formatted = if value.is_infinite() {
+        "inf".to_string()
+    } else if value.is_nan() {
+        "nan".to_string()
+    } else {
+        match conversion.to_ascii_lowercase() {
+            'f' => format!("{value:.precision$}"),
+            'e' => exponent_form(value, precision),
+            _ => general_form(value, precision.max(1), alternate),
+        }
+    };
+    if conversion.is_ascii_uppercase() {
+        formatted.to_ascii_uppercase()
+    } else {
+        formatted
+    }
+}
+
+/// `value` as `d.ddde+XX`, with `precision` digits after the point and at least two in the
+/// exponent, as C prints it.
+fn exponent_form(value: f64, precision: usize) -> String {
+    let formatted = format!("{value:.precision$e}");
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or_default();
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{mantissa}e{sign}{:02}", exponent.unsigned_abs())
+}
+
+/// `value` with `precision` significant digits, in exponent form when its exponent is below -4 or
+/// not below the precision, and without trailing zeros unless `alternate`.
+fn general_form(value: f64, precision: usize, alternate: bool) -> String {
+    let rounded = format!("{value:.*e}", precision - 1);
+    let exponent: i64 = rounded
+        .split_once('e')
+        .map_or(0, |(_, exponent)| exponent.parse().unwrap_or_default());
+    let significant = i64::try_from(precision).unwrap_or(i64::MAX);
+    let formatted = if exponent < -4 || exponent >= significant {
+        exponent_form(value, precision - 1)
+    } else {
+        let decimals = usize::try_from(significant - 1 - exponent).unwrap_or_default();
+        format!("{value:.decimals$}")
+    };
+    if alternate {
+        return formatted;
+    }
+    let (mantissa, exponent) = match formatted.find('e') {
+        Some(index) => formatted.split_at(index),
+        None => (formatted.as_str(), ""),
+    };
+    let mantissa = if mantissa.contains('.') {
+        mantissa.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        mantissa
+    };
+    format!("{mantissa}{exponent}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let cases: &[(&str, &[&str], &str, &[&str])] = &[
+            ("%s-%s\\n", &["a", "b", "c"], "a-b\nc-\n", &[]),
+            ("[%5s][%-5s][%.2s]", &["ab", "cd", "efgh"], "[   ab][cd   ][ef]", &[]),
+            (
+                "%d %+d % d %05d %-4d| %.3d",
+                &["42", "3", "4", "-42", "9", "5"],
+                "42 +3  4 -0042 9   | 005",
+                &[],
+            ),
+            (
+                "%x %X %#x %o %#o %u",
+                &["255", "255", "255", "8", "8", "-1"],
+                "ff FF 0xff 10 010 18446744073709551615",
+                &[],
+            ),
+            (
+                "%f %.2f %e %g %g %G",
+                &["3.14159", "2.5", "12345.678", "100000", "1000000", "1e-5"],
+                "3.141590 2.50 1.234568e+04 100000 1e+06 1E-05",
+                &[],
+            ),
+            (
+                "%*d|%-*d|%.*f",
+                &["4", "1", "3", "2", "1", "3.14159"],
+                "   1|2  |3.1",
+                &[],
+            ),
+            ("%c %q %q %%", &["hello", "a b", "it's"], "h a\\ b it\\'s %", &[]),
+            (
+                "%q|%q|%q|%q|%q",
+                &["", "~/a#b~", "$x*[y]{a,b}", "a\nb'c", "\x01\x1b\\"],
+                "''|\\~/a#b~|\\$x\\*\\[y\\]\\{a\\,b\\}|$'a\\nb\\'c'|$'\\001\\E\\\\'",
+                &[],
+            ),
+            ("%b|%s|\\x41", &["x\\ty", "x\\ty"], "x\ty|x\\ty|A", &[]),
+            ("%b stop\\c never", &["a\\cb"], "a", &[]),
+            (
+                "%d,",
+                &["'A", "0x1f", "017", "12x", "abc", ""],
+                "65,31,15,12,0,0,",
+                &["12x: invalid number", "abc: invalid number"],
+            ),
+            ("%s %d|", &[], " 0|", &[]),
+            ("a%zb", &["1", "2"], "a", &["`z': invalid format character"]),
+        ];
+        for (format, arguments, expected, errors) in cases {
+            let arguments: Vec<String> = arguments.iter().map(ToString::to_string).collect();
+            let (output, reported) = format_printf(format, &arguments);
+            assert_eq!(String::from_utf8_lossy(&output), *expected, "format: {format:?}");
+            assert_eq!(&reported, errors, "format: {format:?}");
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use crate::color::color_enabled;
+use crate::color::strip_colors;
+use crate::config::ShellConfig;
+use crate::home::home_directory;
+use crate::parser::CHAR_BACKSLASH;
+use crate::parser::ENVIRONMENT_VARIABLE_PROMPT_DIRTRIM;
+use crate::parser::HOME_DIRECTORY;
+use std::env::current_dir;
+use std::env::var;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::time::Duration;
+use std::time::Instant;
+
+const ANSI_ESCAPE: char = '\x1b';
+const ANSI_STATUS_FAILURE: &str = "\x1b[31m";
+const ANSI_STATUS_SUCCESS: &str = "\x1b[32m";
+const DIRTRIM_ELLIPSIS: &str = "...";
+const GIT_DIRTY_MARKER: char = '*';
+const GIT_HEAD_REF_PREFIX: &str = "ref: refs/heads/";
+const GIT_POLL_INTERVAL: Duration = Duration::from_millis(5);
+const GIT_SHORT_HASH_LENGTH: usize = 7;
+
+/// Expand the `PS1`-style escapes in `template`.
+///
+/// Supported escapes:
+/// - `\?` last exit status
+/// - `\S` status color: green after success, red after failure (pair with `\e[0m` to reset)
+/// - `\e` escape character, for raw ANSI sequences
+/// - `\w` current working directory with `$HOME` shown as `~`, trimmed to `$PROMPT_DIRTRIM` trailing components
+/// - `\W` last component of the working directory (`~` at home)
+/// - `\g` ` (branch*)` inside a git work tree, when `prompt_git` is enabled in the config
+/// - `\u` user name, `\h` host name up to the first `.`
+/// - `\$` `#` for root, `$` otherwise
+/// - `\n` newline, `\\` backslash; `\[` and `\]` are accepted and dropped
+///
+/// Color sequences, from `\S` or raw `\e[...m`, are dropped when [`color_enabled`] says no.
+#[must_use]
+pub fn render_prompt(template: &str, last_status: i32, config: &ShellConfig) -> String {
+    let mut prompt = String::with_capacity(template.len());
+    let mut characters = template.chars();
+
+    while let Some(character) = characters.next() {
+        if character != CHAR_BACKSLASH {
+            prompt.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('?') => prompt.push_str(&last_status.to_string()),
+            Some('S') => prompt.push_str(if last_status == 0 {
+                ANSI_STATUS_SUCCESS
+            } else {
+                ANSI_STATUS_FAILURE
+            }),
+            Some('e') => prompt.push(ANSI_ESCAPE),
+            Some('w') => {
+                let dirtrim = var(ENVIRONMENT_VARIABLE_PROMPT_DIRTRIM)
+                    .ok()
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(0);
+                prompt.push_str(&abbreviate_directory(&working_directory(), &home_prefix(), dirtrim));
+            }
+            Some('W') => {
+                let directory = working_directory();
+                if !directory.is_empty() && directory == home_prefix() {
+                    prompt.push_str(HOME_DIRECTORY);
+                } else {
+                    let base = directory.rsplit('/').find(|part| !part.is_empty()).unwrap_or("/");
+                    prompt.push_str(base);
+                }
+            }
+            Some('g') if config.prompt_git => {
+                if let Some(segment) = git_segment(Duration::from_millis(config.prompt_git_timeout_ms)) {
+                    prompt.push_str(&segment);
+                }
+            }
+            Some('g') => {}
+            Some('u') => prompt.push_str(&var("USER").unwrap_or_default()),
+            Some('h') => prompt.push_str(host_name().split('.').next().unwrap_or_default()),
+            Some('$') => prompt.push(if is_root() { '#' } else { '$' }),
+            Some('n') => prompt.push('\n'),
+            Some('[' | ']') => {}
+            Some(CHAR_BACKSLASH) => prompt.push(CHAR_BACKSLASH),
+            Some(other) => {
+                prompt.push(CHAR_BACKSLASH);
+                prompt.push(other);
+            }
+            None => prompt.push(CHAR_BACKSLASH),
+        }
+    }
+
+    if color_enabled(&std::io::stdout()) {
+        prompt
+    } else {
+        strip_colors(&prompt)
+    }
+}
+
+fn working_directory() -> String {
+    current_dir()
+        .map(|directory| directory.display().to_string())
+        .unwrap_or_default()
+}
+
+fn home_prefix() -> String {
+    home_directory()
+        .map(|home| home.display().to_string().trim_end_matches('/').to_string())
+        .unwrap_or_default()
+}
+
+/// Replace a leading `home` with `~` and keep only the last `dirtrim` components (0 keeps all).
+fn abbreviate_directory(directory: &str, home: &str, dirtrim: usize) -> String {
+    let (prefix, rest) = match directory.strip_prefix(home) {
+        Some(rest) if !home.is_empty() && (rest.is_empty() || rest.starts_with('/')) => (HOME_DIRECTORY, rest),
+        _ => ("", directory),
+    };
+
+    let components: Vec<&str> = rest.split('/').filter(|part| !part.is_empty()).collect();
+    if dirtrim == 0 || components.len() <= dirtrim {
+        return if prefix.is_empty() {
+            directory.to_string()
+        } else {
+            format!("{prefix}{rest}")
+        };
+    }
+
+    let kept = components[components.len() - dirtrim..].join("/");
+    if prefix.is_empty() {
+        format!("{DIRTRIM_ELLIPSIS}/{kept}")
+    } else {
+        format!("{prefix}/{DIRTRIM_ELLIPSIS}/{kept}")
+    }
+}
+
+fn host_name() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn is_root() -> bool {
+    // SAFETY: `geteuid` takes no arguments and cannot fail.
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Render ` (branch)` or ` (branch*)` for the work tree containing the current directory.
+fn git_segment(timeout: Duration) -> Option<String> {
+    let cwd = current_dir().ok()?;
+    let git_dir = find_git_dir(&cwd)?;
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let branch = match head.strip_prefix(GIT_HEAD_REF_PREFIX) {
+        Some(branch) => branch.to_string(),
+        None => head.chars().take(GIT_SHORT_HASH_LENGTH).collect(),
+    };
+    let dirty = if is_git_dirty(&cwd, timeout) {
+        GIT_DIRTY_MARKER.to_string()
+    } else {
+        String::new()
+    };
+    Some(format!(" ({branch}{dirty})"))
+}
+
+fn find_git_dir(start: &Path) -> Option<PathBuf> {
+    for directory in start.ancestors() {
+        let candidate = directory.join(".git");
+        if candidate.is_dir() {
+            return Some(candidate);
+        }
+        // Worktrees and submodules use a `.git` file pointing at the real directory.
+        if let Ok(content) = std::fs::read_to_string(&candidate) {
+            let target = content.trim().strip_prefix("gitdir:")?.trim();
+            return Some(directory.join(target));
+        }
+    }
+    None
+}
+
+/// Ask git whether tracked files differ from HEAD, giving up (not dirty) after `timeout`.
+fn is_git_dirty(cwd: &Path, timeout: Duration) -> bool {
+    let Ok(mut child) = Command::new("git")
+        .args(["--no-optional-locks", "diff-index", "--quiet", "HEAD", "--"])
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    else {
+        return false;
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return status.code() == Some(1),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(GIT_POLL_INTERVAL),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abbreviated_directories() {
+        let home = "/home/user";
+        let cases = [
+            ("/home/user", 0, "~"),
+            ("/home/user", 3, "~"),
+            ("/home/user/src/ccsh", 0, "~/src/ccsh"),
+            ("/home/user/src/ccsh", 2, "~/src/ccsh"),
+            ("/home/user/src/ccsh", 9, "~/src/ccsh"),
+            ("/home/user/a/b/c/d", 2, "~/.../c/d"),
+            ("/home/user/a/b/c/d", 1, "~/.../d"),
+            ("/home/username", 0, "/home/username"),
+            ("/home/username/a", 1, ".../a"),
+            ("/usr/local/share/man", 2, ".../share/man"),
+            ("/usr/local", 2, "/usr/local"),
+            ("/usr/local", 5, "/usr/local"),
+            ("/", 0, "/"),
+            ("/", 1, "/"),
+        ];
+        for (directory, dirtrim, expected) in cases {
+            assert_eq!(
+                abbreviate_directory(directory, home, dirtrim),
+                expected,
+                "{directory:?} with dirtrim {dirtrim}"
+            );
+        }
+        assert_eq!(abbreviate_directory("/home/user/a", "", 0), "/home/user/a");
+    }
+}
@@ -0,0 +1,239 @@
+use crate::state::ShellState;
+use crate::xdg::shell_file;
+use std::collections::BTreeMap;
+use std::env::current_dir;
+use std::env::set_current_dir;
+use std::ffi::OsString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::ffi::OsStringExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+pub const SESSION_FILE_NAME: &str = "session";
+/// Where the default session lived before it moved to the state directory.
+pub const LEGACY_SESSION_FILE_NAME: &str = ".ccsh_session";
+const SESSION_HEADER: &str = "# ccsh session";
+const FIELD_SEPARATOR: u8 = b'\t';
+const KEY_ALIAS: &[u8] = b"alias";
+const KEY_CWD: &[u8] = b"cwd";
+const KEY_DIRECTORY: &[u8] = b"dir";
+const KEY_HISTORY: &[u8] = b"history";
+const KEY_VARIABLE: &[u8] = b"var";
+
+/// The parts of a shell session that `session save` persists and `session restore` brings back.
+///
+/// Saved as a `# ccsh session` header followed by one `key<TAB>field...` line per item, where
+/// backslash, tab, and newline in a field are escaped as `\\`, `\t`, and `\n`. Paths are kept as
+/// raw bytes, so directories with non-UTF-8 names survive the round trip.
+#[derive(Debug, Default, PartialEq)]
+pub struct Session {
+    pub cwd: Option<PathBuf>,
+    /// The `pushd`/`popd` stack, most recent last.
+    pub directory_stack: Vec<PathBuf>,
+    pub variables: BTreeMap<String, String>,
+    pub aliases: BTreeMap<String, String>,
+    /// History entries before this index are already in the `history -a` file.
+    pub history_position: usize,
+}
+
+impl Session {
+    /// `session` in the shell's state directory, used when `session` is given no file. The
+    /// directory is created if needed, and a `~/.ccsh_session` left from older versions is moved
+    /// there first.
+    #[must_use]
+    pub fn default_path(state: &ShellState) -> Option<PathBuf> {
+        let directory = state.state_directory.as_deref()?;
+        let _ = std::fs::create_dir_all(directory);
+        Some(shell_file(directory, SESSION_FILE_NAME, Some(LEGACY_SESSION_FILE_NAME)))
+    }
+
+    /// Snapshot the working directory and the persistable parts of `state`.
+    #[must_use]
+    pub fn capture(state: &ShellState) -> Self {
+        Self {
+            cwd: current_dir().ok(),
+            directory_stack: state.directory_stack.clone(),
+            variables: state.variables.clone(),
+            aliases: state.aliases.clone(),
+            history_position: state.last_appended_index,
+        }
+    }
+
+    /// Replace the session parts of `state` with this snapshot and change to its directory.
+    ///
+    /// Everything else is restored even when the directory no longer exists; that error is returned.
+    pub fn apply(self, state: &mut ShellState) -> io::Result<()> {
+        state.directory_stack = self.directory_stack;
+        state.variables = self.variables;
+        state.aliases = self.aliases;
+        state.last_appended_index = self.history_position;
+        match self.cwd {
+            Some(cwd) => set_current_dir(cwd),
+            None => Ok(()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let content = std::fs::read(path)?;
+        Self::parse(&content).map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(SESSION_HEADER.as_bytes());
+        out.push(b'\n');
+        if let Some(cwd) = &self.cwd {
+            write_line(&mut out, KEY_CWD, &[cwd.as_os_str().as_bytes()]);
+        }
+        for directory in &self.directory_stack {
+            write_line(&mut out, KEY_DIRECTORY, &[directory.as_os_str().as_bytes()]);
+        }
+        for (name, value) in &self.variables {
+            write_line(&mut out, KEY_VARIABLE, &[name.as_bytes(), value.as_bytes()]);
+        }
+        for (name, value) in &self.aliases {
+            write_line(&mut out, KEY_ALIAS, &[name.as_bytes(), value.as_bytes()]);
+        }
+        write_line(&mut out, KEY_HISTORY, &[self.history_position.to_string().as_bytes()]);
+        out
+    }
+
+    fn parse(content: &[u8]) -> Result<Self, String> {
+        let mut lines = content.split(|&byte| byte == b'\n');
+        if lines.next() != Some(SESSION_HEADER.as_bytes()) {
+            return Err("not a session file".to_string());
+        }
+
+        let mut session = Self::default();
+        for (index, line) in lines.enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<Vec<u8>> = line.split(|&byte| byte == FIELD_SEPARATOR).map(unescape).collect();
+            let line_number = index + 2;
+            match fields.as_slice() {
+                [key, cwd] if key == KEY_CWD => session.cwd = Some(path_from(cwd)),
+                [key, directory] if key == KEY_DIRECTORY => session.directory_stack.push(path_from(directory)),
+                [key, name, value] if key == KEY_VARIABLE => {
+                    session.variables.insert(text_from(name), text_from(value));
+                }
+                [key, name, value] if key == KEY_ALIAS => {
+                    session.aliases.insert(text_from(name), text_from(value));
+                }
+                [key, position] if key == KEY_HISTORY => {
+                    session.history_position = text_from(position)
+                        .parse()
+                        .map_err(|_| format!("line {line_number}: invalid history position"))?;
+                }
+                _ => return Err(format!("line {line_number}: unrecognized entry")),
+            }
+        }
+        Ok(session)
+    }
+}
+
+fn write_line(out: &mut Vec<u8>, key: &[u8], fields: &[&[u8]]) {
+    out.extend_from_slice(key);
+    for field in fields {
+        out.push(FIELD_SEPARATOR);
+        for &byte in *field {
+            match byte {
+                b'\\' => out.extend_from_slice(b"\\\\"),
+                b'\t' => out.extend_from_slice(b"\\t"),
+                b'\n' => out.extend_from_slice(b"\\n"),
+                byte => out.push(byte),
+            }
+        }
+    }
+    out.push(b'\n');
+}
+
+fn unescape(field: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(field.len());
+    let mut bytes = field.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'\\' {
+            out.push(byte);
+            continue;
+        }
+        match bytes.next() {
+            Some(b't') => out.push(b'\t'),
+            Some(b'n') => out.push(b'\n'),
+            Some(&other) => out.push(other),
+            None => out.push(b'\\'),
+        }
+    }
+    out
+}
+
+fn path_from(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(OsString::from_vec(bytes.to_vec()))
+}
+
+fn text_from(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let session = Session {
+            cwd: Some(path_from(b"/tmp/caf\xe9")),
+            directory_stack: vec![PathBuf::from("/"), PathBuf::from("/a\tb\\c\nd")],
+            variables: BTreeMap::from([
+                ("EMPTY".to_string(), String::new()),
+                ("TEXT".to_string(), "tab\there\nnewline \\t literal\\".to_string()),
+            ]),
+            aliases: BTreeMap::from([("ll".to_string(), "ls -l".to_string())]),
+            history_position: 42,
+        };
+        let bytes = session.to_bytes();
+        assert_eq!(bytes.iter().filter(|&&byte| byte == b'\n').count(), 8);
+        assert_eq!(Session::parse(&bytes), Ok(session));
+        assert_eq!(Session::parse(&Session::default().to_bytes()), Ok(Session::default()));
+    }
+
+    #[test]
+    fn escaping() {
+        let mut out = Vec::new();
+        write_line(&mut out, KEY_VARIABLE, &[b"X", b"a\\b\tc\nd"]);
+        assert_eq!(out, b"var\tX\ta\\\\b\\tc\\nd\n");
+
+        let cases: [(&[u8], &[u8]); 5] = [
+            (b"plain", b"plain"),
+            (b"\\\\\\t\\n", b"\\\t\n"),
+            (b"\\x", b"x"),
+            (b"trailing\\", b"trailing\\"),
+            (b"", b""),
+        ];
+        for (field, expected) in cases {
+            assert_eq!(unescape(field), expected, "{:?}", String::from_utf8_lossy(field));
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        let cases = [
+            ("", "not a session file"),
+            ("cwd\t/\n", "not a session file"),
+            ("# ccsh session\nbogus\tx\n", "line 2: unrecognized entry"),
+            ("# ccsh session\n\nvar\tX\n", "line 3: unrecognized entry"),
+            ("# ccsh session\nhistory\tmany\n", "line 2: invalid history position"),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(
+                Session::parse(content.as_bytes()),
+                Err(expected.to_string()),
+                "{content:?}"
+            );
+        }
+    }
+}
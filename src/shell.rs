@@ -0,0 +1,824 @@
+use crate::alias::expand_aliases;
+use crate::arithmetic::evaluate_arithmetic;
+use crate::arithmetic::ArithmeticError;
+use crate::commands::BuiltinAction;
+use crate::compound::parse_compound_command;
+use crate::compound::CompoundCommand;
+use crate::config::ShellConfig;
+#[cfg(feature = "readline")]
+use crate::editor::LineEditor;
+use crate::error::report_error;
+use crate::error::ReadError;
+use crate::error::ShellError;
+use crate::executor::execute_pipeline;
+use crate::executor::ExecutionContext;
+use crate::frecency::DirectoryDatabase;
+use crate::frecency::SharedDirectoryDatabase;
+use crate::glob::GlobOptions;
+use crate::history_expansion::expand_history;
+use crate::lexer::is_incomplete;
+use crate::lexer::split_command_lines;
+use crate::notify::notify_finished;
+use crate::options::ShellOptions;
+use crate::parse_dump::dump_parse;
+use crate::parser::expand_operand;
+use crate::parser::is_variable_name;
+use crate::parser::parse_function_header;
+use crate::parser::parse_input_with;
+use crate::parser::quote_word;
+use crate::parser::split_command_list;
+use crate::parser::strip_negation;
+use crate::parser::take_function_body;
+use crate::parser::Connector;
+use crate::parser::Expander;
+use crate::parser::ParsedCommand;
+use crate::parser::COMMAND_RETURN;
+use crate::parser::DEFAULT_FIELD_SEPARATORS;
+use crate::parser::ENVIRONMENT_VARIABLE_IFS;
+use crate::parser::ENVIRONMENT_VARIABLE_PS1;
+use crate::parser::ENVIRONMENT_VARIABLE_PS2;
+use crate::parser::ENVIRONMENT_VARIABLE_PS4;
+use crate::parser::RESERVED_WORD_CONDITIONAL_START;
+use crate::parser::SHELL_CONTINUATION_PROMPT;
+use crate::parser::SHELL_PROMPT;
+use crate::parser::XTRACE_PREFIX;
+use crate::pattern::pattern_matches;
+#[cfg(not(feature = "readline"))]
+use crate::plain_reader::PlainReader as LineEditor;
+use crate::plugins::BuiltinPlugin;
+use crate::prompt::render_prompt;
+use crate::signals;
+use crate::state::ShellState;
+use crate::subshell::capture_output;
+use crate::subshell::exit_subshell;
+use crate::terminal::TerminalState;
+use crate::trace::trace_tokens;
+use std::io::IsTerminal;
+use std::io::Write;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Function called before each prompt is drawn.
+const HOOK_PRECMD: &str = "precmd";
+/// Function called with each command line read, before it runs.
+const HOOK_PREEXEC: &str = "preexec";
+/// Deepest chain of nested function calls, so runaway recursion reports an error instead of
+/// overflowing the stack.
+const MAX_FUNCTION_DEPTH: usize = 100;
+
+/// Status of the last command run by [`Shell::eval`]: its exit code, or `128 + N` when it was
+/// killed by signal `N`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExitStatus(i32);
+
+impl ExitStatus {
+    #[must_use]
+    pub fn code(self) -> i32 {
+        self.0
+    }
+
+    #[must_use]
+    pub fn success(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// The shell engine: line reader, history, jobs, options, and the state commands share.
+///
+/// `eval` runs command lines without a terminal; `read_line` adds the interactive prompt.
+pub struct Shell {
+    editor: LineEditor,
+    config: ShellConfig,
+    state: ShellState,
+    /// Terminal attributes at startup, restored after a command panics or reading a line fails.
+    terminal: Option<TerminalState>,
+    exit_code: Option<i32>,
+    /// Function calls currently running, innermost last.
+    function_depth: usize,
+    /// An expansion of the pipeline being parsed failed, as `${name:?message}` does, so it must not run.
+    expansion_failed: bool,
+    /// A failed expansion abandoned the command line being run, whose remaining commands must not run.
+    aborting: bool,
+    /// `if` conditions currently running, whose failure `errexit` ignores.
+    condition_depth: usize,
+    /// `return` ran in the innermost function call, whose remaining commands must not run.
+    returning: bool,
+}
+
+impl Shell {
+    /// A shell with the default configuration.
+    pub fn new() -> Result<Self, ShellError> {
+        Self::with_config(ShellConfig::default())
+    }
+
+    /// A shell with `config`, its key bindings installed on the line editor.
+    pub fn with_config(config: ShellConfig) -> Result<Self, ShellError> {
+        let state_directory = config.state_directory();
+        let directories: SharedDirectoryDatabase =
+            Arc::new(Mutex::new(DirectoryDatabase::load(state_directory.as_deref())));
+        let mut state = ShellState::new(ShellOptions::from(&config), directories.clone());
+        state.state_directory = state_directory;
+        Ok(Self {
+            editor: LineEditor::new(&config, directories)?,
+            state,
+            config,
+            terminal: TerminalState::save(),
+            exit_code: None,
+            function_depth: 0,
+            expansion_failed: false,
+            aborting: false,
+            condition_depth: 0,
+            returning: false,
+        })
+    }
+
+    /// Run `input`, one command line at a time, and return the status of the last command.
+    ///
+    /// Stops at `exit`, which is reported by [`Shell::exit_requested`] instead of ending the process.
+    /// With `errexit` on, a failed pipeline exits the same way, unless it is negated with `!`, part
+    /// of an `if` condition, or followed by `&&` or `||`. A failed expansion, such as
+    /// `${name:?message}` or an unset variable under `nounset`, abandons the rest of `input`, and
+    /// also exits a shell that is not interactive.
+    pub fn eval(&mut self, input: &str) -> ExitStatus {
+        let status = self.run_lines(input);
+        self.aborting = false;
+        status
+    }
+
+    /// Run the command lines of `input` as [`Shell::eval`] does, as part of a command line that is
+    /// already running: a failed expansion abandons the enclosing command line as well.
+    fn run_lines(&mut self, input: &str) -> ExitStatus {
+        let mut command_lines = split_command_lines(input).into_iter();
+        while let Some(command_line) = command_lines.next() {
+            let mut command_line = command_line.trim().to_string();
+            while let Some((name, rest)) = parse_function_header(&command_line) {
+                command_line = self.define_function(name, rest, &mut command_lines).unwrap_or_default();
+            }
+            if command_line.is_empty() {
+                continue;
+            }
+
+            let command_line = expand_aliases(&command_line, &self.state.aliases);
+            trace_tokens(&self.state.options, &command_line);
+            let list = match split_command_list(&command_line) {
+                Ok(list) => list,
+                Err(error) => {
+                    self.state.last_status = ShellError::ParseError(error).report(&mut std::io::stderr());
+                    continue;
+                }
+            };
+            // A pipeline skipped by `&&` or `||` leaves the status for the next connector to test.
+            let mut list = list.into_iter().peekable();
+            while let Some((connector, pipeline)) = list.next() {
+                let runs = match connector {
+                    Connector::Always => true,
+                    Connector::And => self.state.last_status == 0,
+                    Connector::Or => self.state.last_status != 0,
+                };
+                if !runs {
+                    continue;
+                }
+                if !self.run_pipeline(&pipeline) {
+                    return ExitStatus(self.state.last_status);
+                }
+                let tested = self.condition_depth > 0
+                    || strip_negation(&pipeline).is_some()
+                    || list.peek().is_some_and(|(next, _)| *next != Connector::Always);
+                if self.state.options.errexit && self.state.last_status != 0 && !tested {
+                    self.exit_code = Some(self.state.last_status);
+                    return ExitStatus(self.state.last_status);
+                }
+            }
+        }
+        ExitStatus(self.state.last_status)
+    }
+
+    /// Print how `input` parses instead of running it, for `--parse-only`. The status is `2` when
+    /// it is not valid syntax.
+    pub fn dump_parse(&mut self, input: &str) -> ExitStatus {
+        let mut stderr = std::io::stderr();
+        let dumped = dump_parse(input, &self.state.aliases, &mut std::io::stdout(), &mut stderr);
+        self.state.last_status = dumped.unwrap_or_else(|error| ShellError::from(error).report(&mut stderr));
+        ExitStatus(self.state.last_status)
+    }
+
+    /// Parse and run one pipeline of a command list. Returns `false` when the rest of the input must
+    /// not run: after `exit`, when an expansion failed, or when Ctrl-C interrupted the pipeline.
+    fn run_pipeline(&mut self, pipeline_text: &str) -> bool {
+        if let Some(negated) = strip_negation(pipeline_text) {
+            if !self.run_pipeline(negated) {
+                return false;
+            }
+            self.state.last_status = i32::from(self.state.last_status == 0);
+            return true;
+        }
+        match parse_compound_command(pipeline_text) {
+            Some(Ok(compound)) => return self.run_compound(&compound),
+            Some(Err(error)) => {
+                self.state.last_status = ShellError::ParseError(error).report(&mut std::io::stderr());
+                return true;
+            }
+            None => {}
+        }
+        self.state.substitution_status = None;
+        let parsed = parse_input_with(pipeline_text, self);
+        if std::mem::take(&mut self.expansion_failed) {
+            return self.abort();
+        }
+        let pipeline = match parsed {
+            Ok(pipeline) => pipeline,
+            Err(error) => {
+                self.state.last_status = ShellError::ParseError(error).report(&mut std::io::stderr());
+                return true;
+            }
+        };
+        if self.state.options.xtrace {
+            self.trace_commands(&pipeline);
+        }
+        if let Some((name, arguments)) = self.function_call(&pipeline) {
+            self.call_function(&name, arguments);
+            return self.keeps_running();
+        }
+        let mut ctx = ExecutionContext {
+            history: self.editor.history_mut(),
+            state: &mut self.state,
+        };
+        // A bug in one command must not take the session down with it.
+        let result =
+            panic::catch_unwind(AssertUnwindSafe(|| execute_pipeline(&pipeline, &mut ctx))).unwrap_or_else(|payload| {
+                if let Some(terminal) = &self.terminal {
+                    terminal.restore();
+                }
+                Err(ShellError::from_panic(pipeline_text, payload.as_ref()))
+            });
+        // Ctrl-C abandons the rest of the input, as it would a pasted block in bash.
+        let interrupted = signals::take(libc::SIGINT);
+        match result {
+            Ok(BuiltinAction::Exit(code)) => {
+                self.state.last_status = code;
+                self.exit_code = Some(code);
+                return false;
+            }
+            Ok(BuiltinAction::Return(code)) if self.function_depth > 0 => {
+                self.state.last_status = code;
+                self.returning = true;
+                return false;
+            }
+            Ok(BuiltinAction::Return(_)) => {
+                report_error(
+                    &mut std::io::stderr(),
+                    COMMAND_RETURN,
+                    "can only `return' from a function",
+                );
+                self.state.last_status = 1;
+            }
+            Ok(BuiltinAction::Continue(_)) => {}
+            // This is the forked child of a `( ... )` group, which ends with the group.
+            Ok(BuiltinAction::Subshell(list)) => {
+                let status = self.eval(&list);
+                exit_subshell(self.exit_code.unwrap_or(status.code()));
+            }
+            Err(error) => self.state.last_status = error.report(&mut std::io::stderr()),
+        }
+        !interrupted
+    }
+
+    /// Print the simple commands of `pipeline` on stderr for `xtrace`, after expansion, each with its
+    /// assignments and words quoted as the shell would read them, and prefixed by `$PS4`.
+    fn trace_commands(&self, pipeline: &[ParsedCommand]) {
+        let prefix = self
+            .state
+            .variable(ENVIRONMENT_VARIABLE_PS4)
+            .unwrap_or_else(|| XTRACE_PREFIX.to_string());
+        let mut stderr = std::io::stderr().lock();
+        for command in pipeline.iter().filter(|command| command.subshell.is_none()) {
+            let assignments = command
+                .assignments
+                .iter()
+                .map(|(name, value)| format!("{name}={}", quote_word(value)));
+            let words = command
+                .tokens
+                .iter()
+                .flatten()
+                .map(|word| quote_word(&word.to_string_lossy()));
+            let words: Vec<String> = assignments.chain(words).collect();
+            let _ = writeln!(stderr, "{prefix}{}", words.join(" "));
+        }
+    }
+
+    /// Run the command lists of `compound` in this shell. Returns `false` when the rest of the input
+    /// must not run, after `exit` or a failed expansion.
+    ///
+    /// An `if` runs each condition in turn until one succeeds, then its `then` list, or else the
+    /// `else` list. Its status is that of the list it ran last, or `0` when no condition succeeded
+    /// and there is no `else`.
+    fn run_compound(&mut self, compound: &CompoundCommand) -> bool {
+        match compound {
+            CompoundCommand::If { branches, otherwise } => {
+                for (condition, list) in branches {
+                    self.condition_depth += 1;
+                    self.run_lines(condition);
+                    self.condition_depth -= 1;
+                    if !self.keeps_running() {
+                        return false;
+                    }
+                    if self.state.last_status == 0 {
+                        self.run_lines(list);
+                        return self.keeps_running();
+                    }
+                }
+                match otherwise {
+                    Some(list) => {
+                        self.run_lines(list);
+                    }
+                    None => self.state.last_status = 0,
+                }
+                self.keeps_running()
+            }
+            CompoundCommand::Case { subject, arms } => {
+                let subject = expand_operand(subject, false, self);
+                if std::mem::take(&mut self.expansion_failed) {
+                    return self.abort();
+                }
+                for (patterns, list) in arms {
+                    let matched = patterns
+                        .iter()
+                        .any(|pattern| pattern_matches(&expand_operand(pattern, true, self), &subject));
+                    if std::mem::take(&mut self.expansion_failed) {
+                        return self.abort();
+                    }
+                    if matched {
+                        // An empty list succeeds.
+                        self.state.last_status = 0;
+                        self.run_lines(list);
+                        return self.keeps_running();
+                    }
+                }
+                self.state.last_status = 0;
+                true
+            }
+            CompoundCommand::Conditional(expression) => {
+                let result = expression.evaluate(self);
+                if std::mem::take(&mut self.expansion_failed) {
+                    return self.abort();
+                }
+                self.state.last_status = match result {
+                    Ok(holds) => i32::from(!holds),
+                    Err(error) => {
+                        report_error(&mut std::io::stderr(), RESERVED_WORD_CONDITIONAL_START, error);
+                        2
+                    }
+                };
+                true
+            }
+            CompoundCommand::Arithmetic(expression) => {
+                let result = evaluate_arithmetic(expression, self);
+                if std::mem::take(&mut self.expansion_failed) {
+                    return self.abort();
+                }
+                self.state.last_status = match result {
+                    Ok(value) => i32::from(value == 0),
+                    Err(error) => {
+                        report_error(&mut std::io::stderr(), "((", format!("{}: {error}", expression.trim()));
+                        1
+                    }
+                };
+                true
+            }
+        }
+    }
+
+    /// Whether the commands after the one that just ran still run: not after `exit`, a failed
+    /// expansion, or `return` in a function.
+    fn keeps_running(&self) -> bool {
+        self.exit_code.is_none() && !self.returning && !self.aborting
+    }
+
+    /// Abandon the command line after a failed expansion, with status `1`. A shell that is not
+    /// interactive exits, as bash does. Returns `false`, for [`Shell::run_pipeline`] to return.
+    fn abort(&mut self) -> bool {
+        self.state.last_status = 1;
+        self.aborting = true;
+        if !self.state.interactive {
+            self.exit_code = Some(1);
+        }
+        false
+    }
+
+    /// Store the function `name`, whose body is `rest` up to its closing `}`. When `rest` does not
+    /// close the body, the following command lines continue it. Returns the rest of the line after
+    /// the `}`, to run next, or `None` when the body is not closed.
+    fn define_function(
+        &mut self,
+        name: &str,
+        rest: &str,
+        command_lines: &mut impl Iterator<Item = String>,
+    ) -> Option<String> {
+        let (body, after) = match take_function_body(rest, command_lines) {
+            Ok(taken) => taken,
+            Err(error) => {
+                self.state.last_status = ShellError::ParseError(error).report(&mut std::io::stderr());
+                return None;
+            }
+        };
+        self.state.functions.insert(name.to_string(), body);
+        self.editor.add_commands([name.to_string()]);
+        self.state.last_status = 0;
+        Some(after)
+    }
+
+    /// The function name and arguments when `pipeline` is a plain call of a defined function:
+    /// one command, run in the foreground, without redirections. Other calls run in a forked copy
+    /// of the shell, as a pipeline stage of [`execute_pipeline`].
+    fn function_call(&self, pipeline: &[ParsedCommand]) -> Option<(String, Vec<String>)> {
+        let [command] = pipeline else {
+            return None;
+        };
+        if command.background || !command.redirections.is_empty() {
+            return None;
+        }
+        let (name, arguments) = command.tokens.as_deref()?.split_first()?;
+        let name = name.to_str()?;
+        if !self.state.functions.contains_key(name) {
+            return None;
+        }
+        let arguments = arguments
+            .iter()
+            .map(|argument| argument.to_string_lossy().into_owned())
+            .collect();
+        Some((name.to_string(), arguments))
+    }
+
+    /// Run the body of function `name` with `arguments` as its positional parameters.
+    fn call_function(&mut self, name: &str, arguments: Vec<String>) {
+        let Some(body) = self.state.functions.get(name).cloned() else {
+            return;
+        };
+        if self.function_depth >= MAX_FUNCTION_DEPTH {
+            report_error(&mut std::io::stderr(), name, "maximum function nesting level exceeded");
+            self.state.last_status = 1;
+            return;
+        }
+        let caller_arguments = std::mem::replace(&mut self.state.positional, arguments);
+        self.function_depth += 1;
+        self.run_lines(&body);
+        self.function_depth -= 1;
+        self.returning = false;
+        self.state.positional = caller_arguments;
+    }
+
+    /// Call the hook function `name`, if defined, leaving `$?` as the last command set it.
+    fn run_hook(&mut self, name: &str, arguments: Vec<String>) {
+        if !self.state.functions.contains_key(name) {
+            return;
+        }
+        let last_status = self.state.last_status;
+        self.call_function(name, arguments);
+        self.aborting = false;
+        self.state.last_status = last_status;
+    }
+
+    /// Add an extra builtin, also offered by command completion. Core builtins take precedence
+    /// over a plugin with the same name.
+    pub fn register_builtin(&mut self, plugin: Box<dyn BuiltinPlugin>) {
+        self.editor.add_commands([plugin.name().to_string()]);
+        self.state.plugins.register(plugin);
+    }
+
+    /// Record the name the shell was invoked as (`argv[0]`), which `$0` reports. A leading `-`,
+    /// as `login` and `sshd` pass it, makes this a login shell.
+    pub fn set_arg0(&mut self, arg0: &str) {
+        self.state.options.login_shell = arg0.starts_with('-');
+        self.state.arg0 = arg0.to_string();
+    }
+
+    /// Set the positional parameters (`$1`, `$2`, ...) of the commands the shell reads, from its
+    /// command-line arguments.
+    pub fn set_positional(&mut self, arguments: Vec<String>) {
+        self.state.positional = arguments;
+    }
+
+    /// Status of the last command run, which the shell exits with at end of input.
+    #[must_use]
+    pub fn last_status(&self) -> ExitStatus {
+        ExitStatus(self.state.last_status)
+    }
+
+    /// Record that Ctrl-C discarded the line at the prompt: `$?` becomes `130`, as in bash.
+    pub fn interrupt_line(&mut self) {
+        self.state.last_status = 128 + libc::SIGINT;
+    }
+
+    /// The code passed to `exit`, once a command asked the shell to terminate.
+    #[must_use]
+    pub fn exit_requested(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    #[must_use]
+    pub fn options(&self) -> &ShellOptions {
+        &self.state.options
+    }
+
+    /// Report finished background jobs, then prompt for a line, expand its history references
+    /// when `histexpand` is on, and add it to history.
+    ///
+    /// An expanded line is echoed on stderr, as bash does; a failed expansion is reported and an
+    /// empty line returned instead. The `precmd` function, if defined, runs before the prompt is
+    /// drawn, and `preexec` runs with the line (as `$1`, `$2`, and `$3`, like zsh) before it is
+    /// returned.
+    pub fn read_line(&mut self) -> Result<String, ReadError> {
+        // Signals caught while the last command ran have been acted on by now.
+        signals::take_pending();
+        self.state.job_mgr.reap(self.state.interactive);
+        self.run_hook(HOOK_PRECMD, Vec::new());
+        // `alias`, `unalias`, and `session restore` change the aliases completion offers, and
+        // assignments the variables.
+        self.editor.set_aliases(self.state.aliases.keys().cloned());
+        self.editor.set_variables(self.state.variables.keys().cloned());
+        let prompt_template = std::env::var(ENVIRONMENT_VARIABLE_PS1).unwrap_or_else(|_| SHELL_PROMPT.to_string());
+        let prompt = render_prompt(&prompt_template, self.state.last_status, &self.config);
+        let mut line = self
+            .editor
+            .read_line(&prompt, &self.state.options, self.state.job_mgr.summaries())?;
+        self.read_continuation_lines(&mut line)?;
+        if self.state.options.histexpand {
+            match expand_history(&line, self.editor.history()) {
+                Ok(Some(expanded)) => {
+                    let _ = writeln!(std::io::stderr(), "{expanded}");
+                    line = expanded;
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    report_error(&mut std::io::stderr(), &error.expansion, error.message);
+                    return Ok(String::new());
+                }
+            }
+        }
+        if !line.trim().is_empty() {
+            self.editor.history_mut().add(&line);
+            self.run_hook(HOOK_PREEXEC, vec![line.clone(); 3]);
+        }
+        Ok(line)
+    }
+
+    /// Append lines to `line`, prompting with `PS2`, while it is incomplete: inside a quote, a
+    /// here-document, or parentheses, or after a trailing backslash, `|`, `||`, or `&&`. The line editor already does this on
+    /// a terminal; this covers input read without it. End of input leaves `line` as it is.
+    fn read_continuation_lines(&mut self, line: &mut String) -> Result<(), ReadError> {
+        while is_incomplete(line) {
+            let prompt =
+                std::env::var(ENVIRONMENT_VARIABLE_PS2).unwrap_or_else(|_| SHELL_CONTINUATION_PROMPT.to_string());
+            match self.editor.read_line(&prompt, &self.state.options, Vec::new()) {
+                Ok(next) => {
+                    line.push('\n');
+                    line.push_str(&next);
+                }
+                Err(ReadError::Eof) => break,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    /// Notify that `command_line` finished with `status` when it ran for at least the config's
+    /// `notify_after_ms` and stdout is a terminal.
+    pub fn notify_if_slow(&self, command_line: &str, status: ExitStatus, elapsed: Duration) {
+        let threshold = self.config.notify_after_ms;
+        let mut stdout = std::io::stdout();
+        if threshold == 0 || elapsed < Duration::from_millis(threshold) || !stdout.is_terminal() {
+            return;
+        }
+        notify_finished(
+            &mut stdout,
+            self.config.notify_style,
+            self.config.notify_when,
+            command_line,
+            status.code(),
+            elapsed,
+        );
+    }
+
+    /// Put the terminal back in the mode it had at startup.
+    pub fn reset_terminal(&self) {
+        if let Some(terminal) = &self.terminal {
+            terminal.restore();
+        }
+    }
+
+    /// Block until every background job has finished.
+    pub fn wait_for_jobs(&mut self) {
+        self.state.job_mgr.wait_all();
+    }
+
+    /// Orderly teardown before the process exits: save history to `history_path`, put the
+    /// terminal back, and flush anything still buffered on stdout and stderr.
+    pub fn shutdown(&mut self, history_path: Option<&str>) {
+        if let Some(path) = history_path {
+            self.save_history(path);
+        }
+        self.reset_terminal();
+        std::io::stdout().flush().unwrap_or_default();
+        std::io::stderr().flush().unwrap_or_default();
+    }
+
+    /// Load history from `path`; entries already there are not appended again by `history -a`.
+    pub fn load_history(&mut self, path: &str) {
+        self.editor.load_history(path);
+        self.state.last_appended_index = self.editor.history().len();
+    }
+
+    /// Write the history to `path` as plain lines, creating its directory if needed.
+    pub fn save_history(&self, path: &str) {
+        if let Some(parent) = Path::new(path).parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(mut file) = std::fs::File::create(path) {
+            for entry in self.editor.history().entries() {
+                let _ = writeln!(file, "{entry}");
+            }
+        }
+    }
+}
+
+impl Expander for Shell {
+    /// Run `command` in a subshell and return its output. Its stderr is the shell's; a failure to
+    /// start it is reported and substitutes nothing.
+    fn substitute_command(&mut self, command: &str) -> Option<String> {
+        let captured = capture_output(|| {
+            let status = self.eval(command);
+            self.exit_code.unwrap_or(status.code())
+        });
+        match captured {
+            Ok((output, status)) => {
+                self.state.last_status = status;
+                self.state.substitution_status = Some(status);
+                Some(String::from_utf8_lossy(&output).into_owned())
+            }
+            Err(error) => {
+                self.state.last_status = ShellError::Io(error).report(&mut std::io::stderr());
+                self.state.substitution_status = Some(self.state.last_status);
+                Some(String::new())
+            }
+        }
+    }
+
+    fn arithmetic(&mut self, expression: &str) -> Option<Result<i64, ArithmeticError>> {
+        Some(evaluate_arithmetic(expression, self))
+    }
+
+    /// `$?` is the status of the last pipeline, substitution, or builtin, `$$` the shell's PID, and
+    /// `$!` the PID of the last background job, or nothing before the first. `$0` is the shell's
+    /// name, `$1`, `$2`, ... the positional parameters (nothing past the last), and `$#` their count.
+    /// A variable is a shell variable or else an environment variable, and empty when unset.
+    fn parameter(&mut self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(self.state.last_status.to_string()),
+            "$" => Some(self.state.shell_pid.to_string()),
+            "!" => Some(
+                self.state
+                    .last_background_pid
+                    .map(|pid| pid.to_string())
+                    .unwrap_or_default(),
+            ),
+            "#" => Some(self.state.positional.len().to_string()),
+            "0" => Some(self.state.arg0.clone()),
+            name if is_variable_name(name) => Some(self.state.variable(name).unwrap_or_default()),
+            name => {
+                let index = name.parse::<usize>().ok()?.checked_sub(1)?;
+                Some(self.state.positional.get(index).cloned().unwrap_or_default())
+            }
+        }
+    }
+
+    fn arguments(&mut self) -> Option<Vec<String>> {
+        Some(self.state.positional.clone())
+    }
+
+    /// With `nounset` on, an unset variable, a positional parameter past the last, and `$!` before
+    /// the first background job are reported as `ccsh: name: unbound variable`.
+    fn check_set(&mut self, name: &str) {
+        if !self.state.options.nounset {
+            return;
+        }
+        let set = match name {
+            "!" => self.state.last_background_pid.is_some(),
+            name if is_variable_name(name) => self.state.variable(name).is_some(),
+            name => name
+                .parse::<usize>()
+                .map_or(true, |index| index <= self.state.positional.len()),
+        };
+        if !set {
+            self.parameter_error(name, "unbound variable");
+        }
+    }
+
+    fn assign(&mut self, name: &str, value: &str) {
+        self.state.set_variable(name, value);
+    }
+
+    /// Reported as `ccsh: name: message`; the pipeline fails with status 1 instead of running, and
+    /// the rest of the command line is abandoned.
+    fn parameter_error(&mut self, name: &str, message: &str) {
+        report_error(&mut std::io::stderr(), name, message);
+        self.expansion_failed = true;
+    }
+
+    /// `$IFS`, or space, tab, and newline when it is unset.
+    fn field_separators(&mut self) -> String {
+        self.state
+            .variable(ENVIRONMENT_VARIABLE_IFS)
+            .unwrap_or_else(|| DEFAULT_FIELD_SEPARATORS.to_string())
+    }
+
+    fn glob_options(&self) -> GlobOptions {
+        GlobOptions::from(&self.state.options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The output and exit status of `input` run by a fresh shell, interactive or not.
+    fn run(input: &str, interactive: bool) -> (String, i32) {
+        let mut shell = Shell::new().unwrap();
+        shell.state.interactive = interactive;
+        let (output, status) = capture_output(|| {
+            let status = shell.eval(input);
+            shell.exit_requested().unwrap_or(status.code())
+        })
+        .unwrap();
+        (String::from_utf8_lossy(&output).into_owned(), status)
+    }
+
+    #[test]
+    fn failed_expansions_abort() {
+        let cases = [
+            ("echo ${x:?oops}; echo after\necho later", ""),
+            ("echo before && echo ${x:?} || echo or\necho later", "before\n"),
+            ("if true; then echo ${x:?}; echo then; fi; echo after", ""),
+            ("f() { echo ${x:?}; echo body; }\nf; echo after", ""),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(run(input, false), (expected.to_string(), 1), "{input:?}");
+        }
+        assert_eq!(
+            run("echo $(echo ${x:?}; echo inner) outer", false),
+            ("outer\n".to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn interactive_shells_survive_failed_expansions() {
+        let mut shell = Shell::new().unwrap();
+        shell.state.interactive = true;
+        assert_eq!(shell.eval("echo ${x:?}; echo after").code(), 1);
+        assert_eq!(shell.exit_requested(), None);
+        assert_eq!(shell.eval("if true; then x=1; fi; echo $x >/dev/null").code(), 0);
+        assert_eq!(shell.state.variable("x").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn colon_expands_its_arguments() {
+        assert_eq!(
+            run(": ${x:=vi} $(exit 3); echo $x $?", false),
+            ("vi 0\n".to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn unbound_variables_abort() {
+        assert_eq!(
+            run("set -u; echo $nope; echo after\necho later", false),
+            (String::new(), 1)
+        );
+        assert_eq!(
+            run("set -u; echo ${nope:-unset}; echo $1", false),
+            ("unset\n".to_string(), 1)
+        );
+
+        let mut shell = Shell::new().unwrap();
+        shell.state.interactive = true;
+        assert_eq!(shell.eval("set -u; echo $nope; x=1").code(), 1);
+        assert_eq!(shell.exit_requested(), None);
+        assert_eq!(shell.state.variable("x"), None);
+    }
+
+    #[test]
+    fn writes_to_closed_descriptors_fail() {
+        assert_eq!(
+            run("echo x >&-; echo $?; printf x >&-; echo $?; echo ok 2>&-", false),
+            ("1\n1\nok\n".to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn scripts_do_not_announce_background_jobs() {
+        assert_eq!(run("sleep 0 &\necho $?", false), ("0\n".to_string(), 0));
+    }
+}
@@ -1,26 +1,62 @@
+use crate::command_cache::CommandCache;
+use crate::commands::is_executable;
+use crate::completions::CompletionSpec;
+use crate::completions::CompletionSpecs;
+use crate::frecency::SharedDirectoryDatabase;
+use crate::fuzzy::rank;
+use crate::jobs::JobSummary;
+use crate::lexer::is_incomplete;
+use crate::lexer::lex;
+use crate::lexer::reserved_word;
+use crate::lexer::starts_arithmetic_command;
+use crate::lexer::TokenKind;
+use crate::locale::collate;
+use crate::options::ShellOptions;
+use crate::parser::parse_input;
+use crate::parser::BUILTIN_COMMANDS;
+use crate::parser::CHAR_BACKSLASH;
+use crate::parser::CHAR_BACKTICK;
+use crate::parser::CHAR_DOLLAR_SIGN;
+use crate::parser::CHAR_DOUBLE_QUOTE;
+use crate::parser::CHAR_SINGLE_QUOTE;
 use crate::parser::COMMAND_CD;
-use crate::parser::COMMAND_ECHO;
-use crate::parser::COMMAND_EXIT;
-use crate::parser::COMMAND_HISTORY;
-use crate::parser::COMMAND_JOBS;
-use crate::parser::COMMAND_PWD;
-use crate::parser::COMMAND_TYPE;
-use crate::parser::ENVIRONMENT_VARIABLE_PATH;
-use crate::parser::ENVIRONMENT_VARIABLE_PATH_DELIMITER;
+use crate::parser::COMMAND_JUMP;
+use crate::parser::COMMAND_KILL;
+use crate::parser::COMMAND_Z;
+use crate::parser::ENVIRONMENT_VARIABLE_FUZZY_COMPLETION;
 use crate::parser::SHELL_PROMPT;
+use crate::signals::SIGNALS;
 use rustyline::completion::Completer;
 use rustyline::completion::Pair;
 use rustyline::error::ReadlineError;
+use rustyline::highlight::CmdKind;
 use rustyline::highlight::Highlighter;
+use rustyline::hint::HistoryHinter;
+use rustyline::validate::ValidationContext;
+use rustyline::validate::ValidationResult;
+use rustyline::validate::Validator;
+use rustyline::Cmd;
 use rustyline::Completer;
+use rustyline::ConditionalEventHandler;
 use rustyline::Context;
+use rustyline::Event;
+use rustyline::EventContext;
 use rustyline::Helper;
 use rustyline::Hinter;
-use rustyline::Validator;
+use rustyline::RepeatCount;
+use std::borrow::Cow;
 use std::env::var;
 use std::io::Write;
-use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 use std::sync::Mutex;
+use std::sync::PoisonError;
+
+const ANSI_DIM: &str = "\x1b[2m";
+const ANSI_GREEN: &str = "\x1b[32m";
+const ANSI_MAGENTA: &str = "\x1b[35m";
+const ANSI_RED: &str = "\x1b[31m";
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_YELLOW: &str = "\x1b[33m";
 
 static LAST_PREFIX: Mutex<Option<String>> = Mutex::new(None);
 
@@ -54,50 +90,357 @@ fn compute_lcp(prefix: &str, matches: &[(String, bool)]) -> String {
     lcp_chars.into_iter().collect()
 }
 
-#[derive(Helper, Completer, Hinter, Validator)]
+#[derive(Helper, Completer, Hinter)]
 pub struct ShellHelper {
     #[rustyline(Completer)]
     pub completer: ShellCompleter,
+    #[rustyline(Hinter)]
+    pub hinter: HistoryHinter,
+    /// Color command words, quotes, and operators; refreshed from the color policy before each prompt.
+    pub color: bool,
+    /// Keep reading lines while the input is incomplete. Off when stdin is not a terminal, where the
+    /// shell joins continuation lines itself and reports input ending inside a command.
+    pub validate: bool,
 }
 
-impl Highlighter for ShellHelper {}
+impl ShellHelper {
+    fn resolves(&self, word: &str) -> bool {
+        let Some(command) = parse_input(word)
+            .ok()
+            .and_then(|pipeline| pipeline.into_iter().next())
+            .and_then(|command| command.tokens)
+            .and_then(|tokens| tokens.into_iter().next())
+            .and_then(|command| command.into_string().ok())
+        else {
+            return false;
+        };
+        if command.contains('/') {
+            return is_executable(Path::new(&command)).unwrap_or(false);
+        }
+        self.completer.is_command(&command)
+    }
+}
 
-pub struct ShellCompleter {
-    pub commands: Vec<String>,
+impl Highlighter for ShellHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let lexed = lex(line);
+        if !self.color || lexed.tokens.is_empty() {
+            return Cow::Borrowed(line);
+        }
+
+        let command_words = lexed.command_word_indices(line);
+        let mut highlighted = String::with_capacity(line.len() * 2);
+        let mut copied = 0;
+
+        for (index, token) in lexed.tokens.iter().enumerate() {
+            highlighted.push_str(&line[copied..token.span.start]);
+            let text = &line[token.span.clone()];
+            match token.kind {
+                TokenKind::Word if command_words.contains(&index) => {
+                    let known = reserved_word(line, token).is_some()
+                        || starts_arithmetic_command(line, token)
+                        || self.resolves(text);
+                    let color = if known { ANSI_GREEN } else { ANSI_RED };
+                    highlighted.push_str(&format!("{color}{text}{ANSI_RESET}"));
+                }
+                TokenKind::Word => {
+                    let mut word_copied = token.span.start;
+                    for quoted in &token.quoted {
+                        highlighted.push_str(&line[word_copied..quoted.start]);
+                        highlighted.push_str(&format!("{ANSI_YELLOW}{}{ANSI_RESET}", &line[quoted.clone()]));
+                        word_copied = quoted.end;
+                    }
+                    highlighted.push_str(&line[word_copied..token.span.end]);
+                }
+                TokenKind::Redirection | TokenKind::Operator => {
+                    highlighted.push_str(&format!("{ANSI_MAGENTA}{text}{ANSI_RESET}"));
+                }
+                TokenKind::Comment => highlighted.push_str(&format!("{ANSI_DIM}{text}{ANSI_RESET}")),
+            }
+            copied = token.span.end;
+        }
+        highlighted.push_str(&line[copied..]);
+
+        Cow::Owned(highlighted)
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        true
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("{ANSI_DIM}{hint}{ANSI_RESET}"))
+    }
 }
 
-impl ShellCompleter {
-    pub fn new() -> Self {
-        let mut commands = vec![
-            COMMAND_CD.to_string(),
-            COMMAND_ECHO.to_string(),
-            COMMAND_EXIT.to_string(),
-            COMMAND_PWD.to_string(),
-            COMMAND_TYPE.to_string(),
-            COMMAND_HISTORY.to_string(),
-            COMMAND_JOBS.to_string(),
-        ];
-
-        if let Ok(path_var) = var(ENVIRONMENT_VARIABLE_PATH) {
-            for path_dir in path_var.split(ENVIRONMENT_VARIABLE_PATH_DELIMITER) {
-                if let Ok(dir_entries) = std::fs::read_dir(path_dir) {
-                    for dir_entry in dir_entries.flatten() {
-                        if let Ok(entry_metadata) = dir_entry.metadata() {
-                            if entry_metadata.is_file() && (entry_metadata.permissions().mode() & 0o111 != 0) {
-                                if let Ok(file_name) = dir_entry.file_name().into_string() {
-                                    commands.push(file_name);
-                                }
-                            }
-                        }
+impl Validator for ShellHelper {
+    /// Keeps reading lines while the input ends inside a quote, a here-document, or parentheses, after a
+    /// backslash, or after `|`, `||`, `&&`, as [`is_incomplete`] decides.
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if self.validate && is_incomplete(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+/// Accepts the displayed history hint when the cursor is at the end of the line.
+///
+/// Bound to End; rustyline already does the same for Right arrow.
+pub struct AcceptHintHandler;
+
+impl ConditionalEventHandler for AcceptHintHandler {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        if ctx.has_hint() && ctx.pos() == ctx.line().len() {
+            Some(Cmd::CompleteHint)
+        } else {
+            None
+        }
+    }
+}
+
+/// Accepts only the next word of the displayed history hint when the cursor is at the end of the
+/// line, including the whitespace before it.
+///
+/// Bound to Alt-Right and Ctrl-Right; without a hint they keep moving forward by word.
+pub struct AcceptHintWordHandler;
+
+impl ConditionalEventHandler for AcceptHintWordHandler {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        if ctx.pos() != ctx.line().len() {
+            return None;
+        }
+        let hint = ctx.hint_text().filter(|hint| !hint.is_empty())?;
+        Some(Cmd::Insert(1, next_hint_word(hint).to_string()))
+    }
+}
+
+/// The leading whitespace of `hint` followed by its first word.
+fn next_hint_word(hint: &str) -> &str {
+    let word_start = hint.len() - hint.trim_start().len();
+    let word_end = hint[word_start..]
+        .find(char::is_whitespace)
+        .map_or(hint.len(), |offset| word_start + offset);
+    &hint[..word_end]
+}
+
+/// Join `entries` two spaces apart, wrapping into aligned rows of at most `max_columns` entries
+/// when it is non-zero.
+fn layout_columns(entries: &[String], max_columns: usize) -> String {
+    if max_columns == 0 {
+        return entries.join("  ");
+    }
+    let width = entries
+        .iter()
+        .map(|entry| entry.chars().count())
+        .max()
+        .unwrap_or_default();
+    entries
+        .chunks(max_columns)
+        .map(|row| {
+            let cells: Vec<String> = row.iter().map(|entry| format!("{entry:<width$}")).collect();
+            cells.join("  ").trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The word being completed, located with the lexer so quotes and escapes are respected.
+struct CompletionWord {
+    /// Byte offset where the word starts, including any opening quote.
+    start: usize,
+    /// The word with quotes and escapes removed.
+    text: String,
+    /// The quote left open at the cursor, if any.
+    quote: Option<char>,
+    /// Whether the word is the command name of a pipeline stage.
+    command_position: bool,
+    /// The command name of the pipeline stage the word belongs to.
+    command: Option<String>,
+}
+
+impl CompletionWord {
+    fn at(line: &str, pos: usize) -> Self {
+        let lexed = lex(&line[..pos]);
+        let command_words = lexed.command_word_indices(&line[..pos]);
+        let current = lexed
+            .tokens
+            .iter()
+            .enumerate()
+            .next_back()
+            .filter(|(_, token)| token.kind == TokenKind::Word && token.span.end == pos);
+
+        let mut word = match current {
+            Some((index, token)) => Self {
+                start: token.span.start,
+                text: unquote_partial(&line[token.span.clone()]),
+                quote: lexed.open_quote,
+                command_position: command_words.contains(&index),
+                command: None,
+            },
+            None => Self {
+                start: pos,
+                text: String::new(),
+                quote: None,
+                command_position: !lexed
+                    .tokens
+                    .last()
+                    .is_some_and(|token| token.kind != TokenKind::Operator),
+                command: None,
+            },
+        };
+
+        if !word.command_position {
+            let stage_start = lexed
+                .tokens
+                .iter()
+                .rposition(|token| token.kind == TokenKind::Operator)
+                .map_or(0, |index| index + 1);
+            word.command = command_words
+                .iter()
+                .find(|index| **index >= stage_start)
+                .map(|index| unquote_partial(&line[lexed.tokens[*index].span.clone()]));
+        }
+
+        word
+    }
+
+    /// Quote `text` the way the word was typed; `finished` closes the quote and adds a space.
+    fn requote(&self, text: &str, finished: bool) -> String {
+        match self.quote {
+            Some(quote) if finished => format!("{quote}{text}{quote} "),
+            Some(quote) => format!("{quote}{text}"),
+            None => {
+                let mut escaped = String::with_capacity(text.len());
+                for character in text.chars() {
+                    if character.is_whitespace() || "\\'\"$`&|;<>()*?[]#~!{}".contains(character) {
+                        escaped.push(CHAR_BACKSLASH);
                     }
+                    escaped.push(character);
                 }
+                if finished {
+                    escaped.push(' ');
+                }
+                escaped
             }
         }
+    }
+}
 
-        commands.sort_unstable();
-        commands.dedup();
+/// Strip quotes and backslash escapes from a possibly unterminated word.
+fn unquote_partial(raw: &str) -> String {
+    let mut text = String::with_capacity(raw.len());
+    let mut quote: Option<char> = None;
+    let mut characters = raw.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        match (quote, character) {
+            (None, CHAR_SINGLE_QUOTE | CHAR_DOUBLE_QUOTE) => quote = Some(character),
+            (Some(open), _) if character == open => quote = None,
+            (None, CHAR_BACKSLASH) => text.extend(characters.next()),
+            (Some(CHAR_DOUBLE_QUOTE), CHAR_BACKSLASH)
+                if characters.peek().is_some_and(|next| {
+                    matches!(
+                        *next,
+                        CHAR_BACKSLASH | CHAR_DOUBLE_QUOTE | CHAR_DOLLAR_SIGN | CHAR_BACKTICK
+                    )
+                }) =>
+            {
+                text.extend(characters.next());
+            }
+            _ => text.push(character),
+        }
+    }
 
-        Self { commands }
+    text
+}
+
+pub struct ShellCompleter {
+    pub commands: Vec<String>,
+    /// Alias names, sorted, refreshed by the REPL before each prompt.
+    pub aliases: Vec<String>,
+    /// Shell variable names, which are not in the environment, refreshed by the REPL before each
+    /// prompt.
+    pub variables: Vec<String>,
+    /// Fall back to ranked fuzzy matching when no command starts with the typed word.
+    pub fuzzy: bool,
+    /// Frecent directories offered for `cd` and `j` arguments.
+    pub directories: SharedDirectoryDatabase,
+    /// Return every filename match for the editor to cycle through instead of listing them.
+    pub circular: bool,
+    /// Ring the bell before listing ambiguous filename matches.
+    pub bell: bool,
+    /// Most filename matches per listed row; 0 puts them all on one row.
+    pub max_columns: usize,
+    /// Background jobs offered for `kill` arguments, refreshed by the REPL before each prompt.
+    pub jobs: Vec<JobSummary>,
+    /// Argument completions declared in the completion files.
+    pub specs: CompletionSpecs,
+}
+
+impl ShellCompleter {
+    pub fn new(directories: SharedDirectoryDatabase, mut command_cache: CommandCache) -> Self {
+        let mut commands: Vec<String> = BUILTIN_COMMANDS.iter().map(ToString::to_string).collect();
+        commands.extend(command_cache.executables());
+
+        let fuzzy = var(ENVIRONMENT_VARIABLE_FUZZY_COMPLETION).is_ok_and(|value| value == "1");
+
+        let mut completer = Self {
+            commands: Vec::new(),
+            aliases: Vec::new(),
+            variables: Vec::new(),
+            fuzzy,
+            directories,
+            circular: false,
+            bell: true,
+            max_columns: 0,
+            jobs: Vec::new(),
+            specs: CompletionSpecs::new(),
+        };
+        completer.merge_commands(commands);
+        completer
+    }
+
+    /// Follow the completion presentation chosen through the config file or `shopt`.
+    pub fn apply_options(&mut self, options: &ShellOptions) {
+        self.circular = options.menu_complete;
+        self.bell = options.completion_bell;
+        self.max_columns = options.completion_max_columns;
+    }
+
+    /// Add command names (aliases, functions, ...) to the first-word candidates,
+    /// keeping the list sorted and free of duplicates.
+    pub fn merge_commands<I: IntoIterator<Item = String>>(&mut self, names: I) {
+        self.commands.extend(names);
+        self.commands.sort_unstable();
+        self.commands.dedup();
+    }
+
+    /// Offer `names` as the alias names, replacing the previous ones.
+    pub fn set_aliases<I: IntoIterator<Item = String>>(&mut self, names: I) {
+        self.aliases = names.into_iter().collect();
+        self.aliases.sort_unstable();
+    }
+
+    /// Offer `names` as the shell variable names, replacing the previous ones.
+    pub fn set_variables<I: IntoIterator<Item = String>>(&mut self, names: I) {
+        self.variables = names.into_iter().collect();
+    }
+
+    /// Whether `name` is a known command name or alias.
+    fn is_command(&self, name: &str) -> bool {
+        let known = |names: &[String]| names.binary_search_by(|known| known.as_str().cmp(name)).is_ok();
+        known(&self.commands) || known(&self.aliases)
+    }
+
+    /// The command names and the alias names that are not also command names.
+    fn command_names(&self) -> impl Iterator<Item = &str> {
+        let aliases = self
+            .aliases
+            .iter()
+            .filter(|alias| self.commands.binary_search(alias).is_err());
+        self.commands.iter().chain(aliases).map(String::as_str)
     }
 
     fn find_matching_entries(prefix: &str) -> Vec<(String, bool)> {
@@ -125,6 +468,157 @@ impl ShellCompleter {
             })
             .collect()
     }
+
+    /// Complete a command typed as a path: directories get a trailing `/` so completion can
+    /// continue into them, executables get a trailing space.
+    fn complete_command_path(word: &str) -> Vec<Pair> {
+        let dir_prefix = word.rfind('/').map_or("", |slash_pos| &word[..=slash_pos]);
+        let mut candidates: Vec<Pair> = Self::find_matching_entries(word)
+            .into_iter()
+            .filter_map(|(name, is_dir)| {
+                let full_path = format!("{dir_prefix}{name}");
+                let trailing = if is_dir {
+                    "/"
+                } else if is_executable(Path::new(&full_path)).unwrap_or(false) {
+                    " "
+                } else {
+                    return None;
+                };
+                Some(Pair {
+                    replacement: format!("{full_path}{trailing}"),
+                    display: format!("{name}{}", trailing.trim()),
+                })
+            })
+            .collect();
+        candidates.sort_by(|a, b| collate(&a.display, &b.display));
+        candidates
+    }
+
+    /// Offer visited directories matching the typed fragment, best frecency first.
+    fn complete_frecent_directory(&self, word: &CompletionWord) -> Vec<Pair> {
+        let Ok(directories) = self.directories.lock() else {
+            return Vec::new();
+        };
+        directories
+            .matches(&word.text)
+            .into_iter()
+            .map(|directory| {
+                let directory = directory.display().to_string();
+                Pair {
+                    replacement: word.requote(&format!("{directory}/"), false),
+                    display: directory,
+                }
+            })
+            .collect()
+    }
+
+    /// Offer the words of a declared completion spec, plus directories or files when it asks for them.
+    fn complete_from_spec(spec: &CompletionSpec, word: &CompletionWord) -> Vec<Pair> {
+        let mut candidates: Vec<Pair> = spec
+            .words
+            .iter()
+            .filter(|candidate| candidate.starts_with(word.text.as_str()))
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: word.requote(candidate, true),
+            })
+            .collect();
+
+        if spec.directories || spec.files {
+            let dir_prefix = word.text.rfind('/').map_or("", |slash_pos| &word.text[..=slash_pos]);
+            for (name, is_dir) in Self::find_matching_entries(&word.text) {
+                let full_path = format!("{dir_prefix}{name}");
+                if is_dir {
+                    candidates.push(Pair {
+                        replacement: word.requote(&format!("{full_path}/"), false),
+                        display: format!("{name}/"),
+                    });
+                } else if spec.files {
+                    candidates.push(Pair {
+                        replacement: word.requote(&full_path, true),
+                        display: name,
+                    });
+                }
+            }
+        }
+
+        candidates.sort_by(|a, b| collate(&a.display, &b.display));
+        candidates.dedup_by(|a, b| a.display == b.display);
+        candidates
+    }
+
+    /// Offer `-SIGNAL` names and numbers for a word starting with `-`, and job specs and pids of
+    /// background jobs otherwise. Other words fall back to filename completion.
+    fn complete_kill(&self, word: &CompletionWord) -> Option<Vec<Pair>> {
+        let text = word.text.as_str();
+        let candidates: Vec<(String, String)> = if let Some(signal) = text.strip_prefix('-') {
+            let signal = signal.to_ascii_uppercase();
+            SIGNALS
+                .iter()
+                .flat_map(|(name, number)| [format!("-{name}"), format!("-{number}")])
+                .filter(|candidate| candidate[1..].starts_with(signal.as_str()))
+                .map(|candidate| (candidate.clone(), candidate))
+                .collect()
+        } else if text.is_empty() || text.starts_with('%') || text.chars().all(|c| c.is_ascii_digit()) {
+            self.jobs
+                .iter()
+                .flat_map(|job| {
+                    [
+                        (format!("%{}", job.id), format!("%{}  {}", job.id, job.command)),
+                        (job.pid.to_string(), format!("{}  {}", job.pid, job.command)),
+                    ]
+                })
+                .filter(|(candidate, _)| candidate.starts_with(text))
+                .collect()
+        } else {
+            return None;
+        };
+
+        Some(
+            candidates
+                .into_iter()
+                .map(|(candidate, display)| Pair {
+                    display,
+                    replacement: format!("{candidate} "),
+                })
+                .collect(),
+        )
+    }
+
+    /// Complete `$NAME` or `${NAME` at the cursor against shell and environment variable names.
+    fn complete_variable(&self, line: &str, pos: usize) -> Option<(usize, Vec<Pair>)> {
+        let word_start = line[..pos].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let dollar = word_start + line[word_start..pos].rfind(CHAR_DOLLAR_SIGN)?;
+        let after_dollar = &line[dollar + 1..pos];
+        let (braced, fragment) = match after_dollar.strip_prefix('{') {
+            Some(fragment) => (true, fragment),
+            None => (false, after_dollar),
+        };
+        if !fragment.chars().all(|c| c == '_' || c.is_ascii_alphanumeric()) {
+            return None;
+        }
+
+        let mut names: Vec<String> = std::env::vars_os()
+            .filter_map(|(name, _)| name.into_string().ok())
+            .chain(self.variables.iter().cloned())
+            .filter(|name| name.starts_with(fragment))
+            .collect();
+        names.sort_unstable_by(|a, b| collate(a, b));
+        names.dedup();
+
+        let candidates = names
+            .into_iter()
+            .map(|name| Pair {
+                replacement: if braced {
+                    format!("${{{name}}}")
+                } else {
+                    format!("${name}")
+                },
+                display: name,
+            })
+            .collect();
+        Some((dollar, candidates))
+    }
 }
 
 impl Completer for ShellCompleter {
@@ -136,11 +630,39 @@ impl Completer for ShellCompleter {
         pos: usize,
         _ctx: &Context<'_>,
     ) -> Result<(usize, Vec<Self::Candidate>), ReadlineError> {
-        if pos > 0 && line[..pos].contains(' ') {
-            let prefix_start = line[..pos].rfind(' ').map_or(0, |i| i + 1);
-            let prefix = &line[prefix_start..pos];
+        if let Some(completion) = self.complete_variable(line, pos) {
+            return Ok(completion);
+        }
+
+        let word = CompletionWord::at(line, pos);
+
+        if !word.command_position {
+            let prefix_start = word.start;
+            let prefix = word.text.as_str();
 
-            let matches = Self::find_matching_entries(prefix);
+            let command = word.command.as_deref();
+            if let Some(spec) = command.and_then(|command| self.specs.get(command)) {
+                return Ok((prefix_start, Self::complete_from_spec(spec, &word)));
+            }
+
+            if command == Some(COMMAND_KILL) {
+                if let Some(candidates) = self.complete_kill(&word) {
+                    return Ok((prefix_start, candidates));
+                }
+            }
+
+            let matches = if matches!(command, Some(COMMAND_JUMP | COMMAND_Z)) {
+                Vec::new()
+            } else {
+                Self::find_matching_entries(prefix)
+            };
+
+            if matches.is_empty()
+                && matches!(command, Some(COMMAND_CD | COMMAND_JUMP | COMMAND_Z))
+                && !prefix.is_empty()
+            {
+                return Ok((prefix_start, self.complete_frecent_directory(&word)));
+            }
 
             if matches.len() == 1 {
                 let (filename, is_dir) = &matches[0];
@@ -150,16 +672,41 @@ impl Completer for ShellCompleter {
                     ""
                 };
                 let full_path = format!("{dir_prefix}{filename}");
-                let trailing = if *is_dir { "/" } else { " " };
+                let replacement = if *is_dir {
+                    word.requote(&format!("{full_path}/"), false)
+                } else {
+                    word.requote(&full_path, true)
+                };
                 return Ok((
                     prefix_start,
                     vec![Pair {
-                        display: full_path.clone(),
-                        replacement: format!("{full_path}{trailing}"),
+                        display: full_path,
+                        replacement,
                     }],
                 ));
             }
 
+            if matches.len() > 1 && self.circular {
+                let dir_prefix = prefix.rfind('/').map_or("", |slash_pos| &prefix[..=slash_pos]);
+                let mut candidates: Vec<Pair> = matches
+                    .iter()
+                    .map(|(filename, is_dir)| {
+                        let full_path = format!("{dir_prefix}{filename}");
+                        let replacement = if *is_dir {
+                            word.requote(&format!("{full_path}/"), false)
+                        } else {
+                            word.requote(&full_path, true)
+                        };
+                        Pair {
+                            display: full_path,
+                            replacement,
+                        }
+                    })
+                    .collect();
+                candidates.sort_by(|a, b| collate(&a.display.to_lowercase(), &b.display.to_lowercase()));
+                return Ok((prefix_start, candidates));
+            }
+
             if matches.len() > 1 {
                 let lcp = compute_lcp(prefix, &matches);
 
@@ -173,13 +720,14 @@ impl Completer for ShellCompleter {
                     return Ok((
                         prefix_start,
                         vec![Pair {
-                            display: full_path.clone(),
-                            replacement: full_path,
+                            replacement: word.requote(&full_path, false),
+                            display: full_path,
                         }],
                     ));
                 }
 
-                let mut last_prefix = LAST_PREFIX.lock().unwrap();
+                // A panic mid-completion must not disable completion for the rest of the session.
+                let mut last_prefix = LAST_PREFIX.lock().unwrap_or_else(PoisonError::into_inner);
                 let first_tab = match &*last_prefix {
                     Some(p) if p == prefix => false,
                     _ => {
@@ -189,7 +737,9 @@ impl Completer for ShellCompleter {
                 };
 
                 if first_tab {
-                    eprint!("\x07");
+                    if self.bell {
+                        let _ = write!(std::io::stderr(), "\x07");
+                    }
                     return Ok((0, Vec::new()));
                 }
 
@@ -204,9 +754,15 @@ impl Completer for ShellCompleter {
                     })
                     .collect();
 
-                matches_sorted.sort_by_key(|a| a.to_lowercase());
+                matches_sorted.sort_by(|a, b| collate(&a.to_lowercase(), &b.to_lowercase()));
 
-                print!("\n{}\n{}{}", matches_sorted.join("  "), SHELL_PROMPT, line);
+                let _ = write!(
+                    std::io::stdout(),
+                    "\n{}\n{}{}",
+                    layout_columns(&matches_sorted, self.max_columns),
+                    SHELL_PROMPT,
+                    line
+                );
                 std::io::stdout().flush().ok();
 
                 return Ok((0, Vec::new()));
@@ -215,17 +771,34 @@ impl Completer for ShellCompleter {
             return Ok((0, Vec::new()));
         }
 
-        let (start, word) = rustyline::completion::extract_word(line, pos, None, char::is_whitespace);
+        let start = word.start;
+        let word = word.text.as_str();
 
+        if word.contains('/') {
+            return Ok((start, Self::complete_command_path(word)));
+        }
+
+        // `commands` stays in byte order for `resolves`; only the listing follows the locale.
         let mut candidates = Vec::new();
-        for command in &self.commands {
+        for command in self.command_names() {
             if command.starts_with(word) {
                 candidates.push(Pair {
-                    display: command.clone(),
+                    display: command.to_string(),
+                    replacement: format!("{command} "),
+                });
+            }
+        }
+        candidates.sort_by(|a, b| collate(&a.display, &b.display));
+
+        if candidates.is_empty() && self.fuzzy && !word.is_empty() {
+            for command in rank(self.command_names(), word) {
+                candidates.push(Pair {
+                    display: command.to_string(),
                     replacement: format!("{command} "),
                 });
             }
         }
+
         Ok((start, candidates))
     }
 }
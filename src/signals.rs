@@ -0,0 +1,181 @@
+use std::io;
+use std::os::fd::RawFd;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+/// Signals known by name, in numeric order, without the `SIG` prefix.
+pub const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("SYS", libc::SIGSYS),
+];
+
+/// Resolve a signal given as a number or a name, with or without the `SIG` prefix, in any case.
+#[must_use]
+pub fn parse_signal(spec: &str) -> Option<i32> {
+    if let Ok(number) = spec.parse::<i32>() {
+        return SIGNALS.iter().any(|(_, signal)| *signal == number).then_some(number);
+    }
+    let name = spec.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    SIGNALS
+        .iter()
+        .find(|(signal_name, _)| *signal_name == name)
+        .map(|(_, signal)| *signal)
+}
+
+/// The name of `signal` without the `SIG` prefix.
+#[must_use]
+pub fn signal_name(signal: i32) -> Option<&'static str> {
+    SIGNALS
+        .iter()
+        .find(|(_, number)| *number == signal)
+        .map(|(name, _)| *name)
+}
+
+/// Signals the interactive shell catches; each one is recorded in [`take_pending`]'s set and
+/// written to the self-pipe instead of running its default action.
+pub const CAUGHT_SIGNALS: &[i32] = &[libc::SIGINT, libc::SIGCHLD];
+
+/// Signals the interactive shell ignores, as bash does, so they only reach the foreground job.
+pub const IGNORED_SIGNALS: &[i32] = &[libc::SIGQUIT, libc::SIGTERM];
+
+static PENDING: AtomicU64 = AtomicU64::new(0);
+static SELF_PIPE_READ: AtomicI32 = AtomicI32::new(-1);
+static SELF_PIPE_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn record_signal(signal: libc::c_int) {
+    let Ok(bit) = u32::try_from(signal) else {
+        return;
+    };
+    PENDING.fetch_or(1 << bit, Ordering::SeqCst);
+    let fd = SELF_PIPE_WRITE.load(Ordering::SeqCst);
+    if fd >= 0 {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let byte = signal as u8;
+        // A full pipe already wakes the reader; the bit above is what records the signal.
+        // SAFETY: `write` is async-signal-safe, and the only call the handler makes besides atomic
+        // operations; `byte` outlives it and `fd` is the non-blocking write end of the self-pipe.
+        unsafe { libc::write(fd, std::ptr::addr_of!(byte).cast(), 1) };
+    }
+}
+
+/// Install the interactive shell's dispositions: catch [`CAUGHT_SIGNALS`] and ignore
+/// [`IGNORED_SIGNALS`]. Children get the defaults back through [`reset_child_signals`].
+pub fn install_handlers() -> io::Result<()> {
+    if SELF_PIPE_READ.load(Ordering::SeqCst) < 0 {
+        let mut fds = [0; 2];
+        // SAFETY: `fds` has room for the two descriptors `pipe` writes.
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        for fd in fds {
+            // SAFETY: `fd` was just opened by `pipe`; `fcntl` only changes its flags.
+            unsafe {
+                libc::fcntl(fd, libc::F_SETFL, libc::fcntl(fd, libc::F_GETFL) | libc::O_NONBLOCK);
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+        SELF_PIPE_READ.store(fds[0], Ordering::SeqCst);
+        SELF_PIPE_WRITE.store(fds[1], Ordering::SeqCst);
+    }
+
+    for &signal in CAUGHT_SIGNALS {
+        set_disposition(
+            signal,
+            record_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        )?;
+    }
+    for &signal in IGNORED_SIGNALS {
+        set_disposition(signal, libc::SIG_IGN)?;
+    }
+    Ok(())
+}
+
+fn set_disposition(signal: i32, handler: libc::sighandler_t) -> io::Result<()> {
+    // SAFETY: an all-zero `sigaction` is valid, and `handler` is `SIG_IGN` or `record_signal`,
+    // which is async-signal-safe.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler;
+        action.sa_flags = libc::SA_RESTART;
+        if signal == libc::SIGCHLD {
+            action.sa_flags |= libc::SA_NOCLDSTOP;
+        }
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Restore the default disposition of every signal the shell handles in `command`'s child, just
+/// before it executes.
+pub fn reset_child_signals(command: &mut Command) -> &mut Command {
+    // SAFETY: `signal` is async-signal-safe, which is all `pre_exec` requires.
+    unsafe {
+        command.pre_exec(|| {
+            for &signal in CAUGHT_SIGNALS.iter().chain(IGNORED_SIGNALS) {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Read end of the self-pipe: it becomes readable when a caught signal arrives, for callers that
+/// wait with `poll`. `None` until [`install_handlers`] has run.
+#[must_use]
+pub fn signal_fd() -> Option<RawFd> {
+    let fd = SELF_PIPE_READ.load(Ordering::SeqCst);
+    (fd >= 0).then_some(fd)
+}
+
+/// Clear `signal` if it arrived since it was last taken, reporting whether it did.
+pub fn take(signal: i32) -> bool {
+    let Ok(bit) = u32::try_from(signal) else {
+        return false;
+    };
+    PENDING.fetch_and(!(1 << bit), Ordering::SeqCst) & (1 << bit) != 0
+}
+
+/// Every caught signal that arrived since the last call, in numeric order, emptying the self-pipe.
+pub fn take_pending() -> Vec<i32> {
+    if let Some(fd) = signal_fd() {
+        let mut buffer = [0_u8; 64];
+        // SAFETY: `buffer` is valid for `buffer.len()` bytes, and `fd` is the self-pipe's read end.
+        while unsafe { libc::read(fd, buffer.as_mut_ptr().cast(), buffer.len()) } > 0 {}
+    }
+    let pending = PENDING.swap(0, Ordering::SeqCst);
+    (1..64).filter(|bit| pending & (1 << bit) != 0).collect()
+}
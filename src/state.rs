@@ -0,0 +1,94 @@
+use crate::error::SHELL_NAME;
+use crate::frecency::SharedDirectoryDatabase;
+use crate::jobs::JobManager;
+use crate::options::ShellOptions;
+use crate::path_cache::PathCache;
+use crate::plugins::PluginRegistry;
+use std::collections::BTreeMap;
+use std::env;
+use std::io;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+/// Everything a command can read or change about the running shell, passed by `&mut` to the
+/// executor and to every builtin.
+pub struct ShellState {
+    /// Shell variables that are not exported to the environment.
+    pub variables: BTreeMap<String, String>,
+    /// Alias name to replacement text.
+    pub aliases: BTreeMap<String, String>,
+    /// Function name to body.
+    pub functions: BTreeMap<String, String>,
+    /// Arguments of the running function call (`$1`, `$2`, ...).
+    pub positional: Vec<String>,
+    /// The `pushd`/`popd` directory stack, most recent last.
+    pub directory_stack: Vec<PathBuf>,
+    /// Frecent directories, shared with the completer.
+    pub directories: SharedDirectoryDatabase,
+    pub options: ShellOptions,
+    /// Status of the last command, as `$?` reports it.
+    pub last_status: i32,
+    /// Status of the last command substitution run while expanding the pipeline about to run, if
+    /// any: the status of a command made only of assignments.
+    pub substitution_status: Option<i32>,
+    /// The shell's process ID, as `$$` reports it; a subshell keeps its parent's.
+    pub shell_pid: u32,
+    /// The process ID of the last background job, as `$!` reports it.
+    pub last_background_pid: Option<u32>,
+    pub job_mgr: JobManager,
+    pub path_cache: PathCache,
+    pub plugins: PluginRegistry,
+    /// History entries before this index are already in the file written by `history -a`.
+    pub last_appended_index: usize,
+    /// The name the shell was invoked as, reported as `$0`; a leading `-` marks a login shell.
+    pub arg0: String,
+    /// Where history, visited directories, and sessions are kept by default.
+    pub state_directory: Option<PathBuf>,
+    /// Commands are read from a terminal, so errors that would end a script only abandon the line.
+    pub interactive: bool,
+}
+
+impl ShellState {
+    #[must_use]
+    pub fn new(options: ShellOptions, directories: SharedDirectoryDatabase) -> Self {
+        Self {
+            variables: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            functions: BTreeMap::new(),
+            positional: Vec::new(),
+            directory_stack: Vec::new(),
+            directories,
+            options,
+            last_status: 0,
+            substitution_status: None,
+            shell_pid: std::process::id(),
+            last_background_pid: None,
+            job_mgr: JobManager::new(),
+            path_cache: PathCache::default(),
+            plugins: PluginRegistry::default(),
+            last_appended_index: 0,
+            arg0: SHELL_NAME.to_string(),
+            state_directory: None,
+            interactive: io::stdin().is_terminal(),
+        }
+    }
+
+    /// The value of variable `name`: a shell variable, or else an environment variable.
+    #[must_use]
+    pub fn variable(&self, name: &str) -> Option<String> {
+        self.variables
+            .get(name)
+            .cloned()
+            .or_else(|| env::var_os(name).map(|value| value.to_string_lossy().into_owned()))
+    }
+
+    /// Set variable `name` to `value`: in the environment when it is already exported there (as
+    /// `PATH` is), or else as a shell variable, which commands do not see.
+    pub fn set_variable(&mut self, name: &str, value: &str) {
+        if env::var_os(name).is_some() {
+            env::set_var(name, value);
+        } else {
+            self.variables.insert(name.to_string(), value.to_string());
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::os::fd::OwnedFd;
+use std::os::fd::RawFd;
+use std::os::unix::process::ExitStatusExt;
+use std::process::Child;
+use std::process::ExitStatus;
+
+/// A forked copy of the shell running a `( ... )` group, as [`fork_subshell`] returns it to the parent.
+#[derive(Debug)]
+pub struct Subshell {
+    pid: libc::pid_t,
+    /// Set once the subshell has been waited for, since its pid may then be reused.
+    status: Option<ExitStatus>,
+}
+
+impl Subshell {
+    #[must_use]
+    pub fn id(&self) -> u32 {
+        self.pid.unsigned_abs()
+    }
+
+    /// Wait for the subshell to exit.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        if let Some(status) = self.status {
+            return Ok(status);
+        }
+        let status = ExitStatus::from_raw(wait_raw(self.pid, 0)?.unwrap_or_default());
+        self.status = Some(status);
+        Ok(status)
+    }
+
+    /// The subshell's status if it has exited, without blocking.
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        if self.status.is_none() {
+            self.status = wait_raw(self.pid, libc::WNOHANG)?.map(ExitStatus::from_raw);
+        }
+        Ok(self.status)
+    }
+}
+
+/// A process of a pipeline: an external command or a forked subshell.
+#[derive(Debug)]
+pub enum Process {
+    Command(Child),
+    Subshell(Subshell),
+}
+
+impl Process {
+    #[must_use]
+    pub fn id(&self) -> u32 {
+        match self {
+            Self::Command(child) => child.id(),
+            Self::Subshell(subshell) => subshell.id(),
+        }
+    }
+
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        match self {
+            Self::Command(child) => child.wait(),
+            Self::Subshell(subshell) => subshell.wait(),
+        }
+    }
+
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        match self {
+            Self::Command(child) => child.try_wait(),
+            Self::Subshell(subshell) => subshell.try_wait(),
+        }
+    }
+
+    /// Whether the process is stopped by a signal, as `SIGSTOP` or `SIGTSTP` stop it. The stop is
+    /// left to be reported again, so the process can still be waited for as usual.
+    #[must_use]
+    pub fn is_stopped(&self) -> bool {
+        let pid: libc::id_t = self.id();
+        // SAFETY: an all-zero `siginfo_t` is valid, and `waitid` only writes to it.
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let options = libc::WSTOPPED | libc::WNOHANG | libc::WNOWAIT;
+        // SAFETY: `info` is a valid place for `waitid` to write to.
+        let waited = unsafe { libc::waitid(libc::P_PID, pid, &mut info, options) };
+        waited == 0 && info.si_code == libc::CLD_STOPPED
+    }
+}
+
+/// The side of [`fork_subshell`] the caller returned on.
+#[derive(Debug)]
+pub enum Fork {
+    Parent(Subshell),
+    /// The caller is the subshell: it runs the group, then calls [`exit_subshell`].
+    Child,
+}
+
+/// Fork a copy of the shell for a `( ... )` group, with each file in `descriptors` open as its
+/// descriptor number in the child, or that descriptor closed for `None`. Changes the child makes to
+/// the shell's state stay in it.
+pub fn fork_subshell(descriptors: Vec<(RawFd, Option<OwnedFd>)>) -> io::Result<Fork> {
+    // Anything still buffered would otherwise be written twice, once by each process.
+    io::stdout().flush()?;
+    io::stderr().flush()?;
+
+    // SAFETY: the child only goes on to run the group on this thread's copy of the shell.
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => {
+            for (descriptor, file) in &descriptors {
+                match file {
+                    // SAFETY: both descriptors are valid; a failure leaves the shell's own stream in place.
+                    Some(file) => unsafe { libc::dup2(file.as_raw_fd(), *descriptor) },
+                    // SAFETY: closing a descriptor that is not open only fails.
+                    None => unsafe { libc::close(*descriptor) },
+                };
+            }
+            Ok(Fork::Child)
+        }
+        pid => Ok(Fork::Parent(Subshell { pid, status: None })),
+    }
+}
+
+/// End a forked child with `status`, after flushing its output but without running destructors or
+/// unwinding into the parent's frames.
+pub fn exit_subshell(status: i32) -> ! {
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    // SAFETY: `_exit` ends the process immediately, which is all a forked child has left to do.
+    unsafe { libc::_exit(status) }
+}
+
+/// Run `body` in a forked copy of the shell with its stdout going to a pipe, and return what it
+/// wrote there with the status `body` returned, or `128 + N` when it was killed by signal `N`.
+///
+/// The child exits right after `body`, without running destructors, so changes it makes to the
+/// shell's state (the working directory, variables, history) stay in the child.
+pub fn capture_output(body: impl FnOnce() -> i32) -> io::Result<(Vec<u8>, i32)> {
+    let (mut reader, writer) = os_pipe::pipe()?;
+    // Anything still buffered would otherwise be written twice, once by each process.
+    io::stdout().flush()?;
+    io::stderr().flush()?;
+
+    // SAFETY: the child only runs `body` on this thread's copy of the shell and then exits.
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => {
+            drop(reader);
+            // SAFETY: both descriptors are open; stdout now refers to the pipe.
+            unsafe { libc::dup2(writer.as_raw_fd(), libc::STDOUT_FILENO) };
+            drop(writer);
+            exit_subshell(body())
+        }
+        pid => {
+            drop(writer);
+            let mut output = Vec::new();
+            let read = reader.read_to_end(&mut output);
+            let status = wait_for(pid)?;
+            read?;
+            Ok((output, status))
+        }
+    }
+}
+
+/// Wait for the child `pid` to exit and decode its status as the shell reports it.
+fn wait_for(pid: libc::pid_t) -> io::Result<i32> {
+    let status = wait_raw(pid, 0)?.unwrap_or_default();
+    if libc::WIFSIGNALED(status) {
+        Ok(128 + libc::WTERMSIG(status))
+    } else {
+        Ok(libc::WEXITSTATUS(status))
+    }
+}
+
+/// The raw wait status of the child `pid`, or `None` when `options` has `WNOHANG` and it is still
+/// running.
+fn wait_raw(pid: libc::pid_t, options: libc::c_int) -> io::Result<Option<i32>> {
+    let mut status = 0;
+    loop {
+        // SAFETY: `status` is a valid place for `waitpid` to write to.
+        match unsafe { libc::waitpid(pid, &mut status, options) } {
+            -1 => {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    return Err(error);
+                }
+            }
+            0 => return Ok(None),
+            _ => return Ok(Some(status)),
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::io::IsTerminal;
+use std::os::fd::AsRawFd;
+
+/// The terminal attributes of stdin, saved so they can be put back after something left the
+/// terminal in a broken mode (raw input, no echo).
+pub struct TerminalState {
+    attributes: libc::termios,
+}
+
+impl TerminalState {
+    /// The current attributes, or `None` when stdin is not a terminal.
+    #[must_use]
+    pub fn save() -> Option<Self> {
+        let stdin = std::io::stdin();
+        if !stdin.is_terminal() {
+            return None;
+        }
+        let mut attributes: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(stdin.as_raw_fd(), &mut attributes) } != 0 {
+            return None;
+        }
+        Some(Self { attributes })
+    }
+
+    /// Put the saved attributes back.
+    pub fn restore(&self) {
+        unsafe { libc::tcsetattr(std::io::stdin().as_raw_fd(), libc::TCSANOW, &self.attributes) };
+    }
+}
@@ -0,0 +1,56 @@
+use crate::lexer::lex;
+use crate::lexer::TokenKind;
+use crate::options::ShellOptions;
+use std::io::Write;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// Print `message()` on stderr as a timestamped trace line when the `trace` option is on.
+///
+/// The message is only built when tracing, so call sites can format freely.
+pub fn trace(options: &ShellOptions, message: impl FnOnce() -> String) {
+    if !options.trace {
+        return;
+    }
+    let mut stderr = std::io::stderr().lock();
+    let _ = writeln!(stderr, "+ [{}] {}", timestamp(), message());
+    let _ = stderr.flush();
+}
+
+/// Trace the lexer tokens of `command_line` as `kind(text)` items.
+pub fn trace_tokens(options: &ShellOptions, command_line: &str) {
+    trace(options, || {
+        let tokens: Vec<String> = lex(command_line)
+            .tokens
+            .iter()
+            .map(|token| {
+                let kind = match token.kind {
+                    TokenKind::Word => "word",
+                    TokenKind::Redirection => "redirection",
+                    TokenKind::Operator => "operator",
+                    TokenKind::Comment => "comment",
+                };
+                format!("{kind}({})", &command_line[token.span.clone()])
+            })
+            .collect();
+        format!("lex: {}", tokens.join(" "))
+    });
+}
+
+/// The local wall-clock time as `HH:MM:SS.mmm`.
+fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    // `time_t` is left to inference: naming it is deprecated on musl, whose width is changing.
+    let seconds = now.as_secs().try_into().unwrap_or_default();
+    let mut local: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&seconds, &mut local) }.is_null() {
+        return format!("{}.{:03}", now.as_secs(), now.subsec_millis());
+    }
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        local.tm_hour,
+        local.tm_min,
+        local.tm_sec,
+        now.subsec_millis()
+    )
+}
@@ -0,0 +1,67 @@
+use crate::home::home_directory;
+use crate::parser::ENVIRONMENT_VARIABLE_XDG_CACHE_HOME;
+use crate::parser::ENVIRONMENT_VARIABLE_XDG_CONFIG_HOME;
+use crate::parser::ENVIRONMENT_VARIABLE_XDG_STATE_HOME;
+use std::env::var_os;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Directory under each base directory that holds this shell's files.
+const SHELL_DIRECTORY_NAME: &str = "ccsh";
+
+/// The XDG base directories this shell keeps files in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BaseDirectory {
+    /// Settings and completion files (`$XDG_CONFIG_HOME`, default `~/.config`).
+    Config,
+    /// History, visited directories, and sessions (`$XDG_STATE_HOME`, default `~/.local/state`).
+    State,
+    /// Data that can be rebuilt, such as the command cache (`$XDG_CACHE_HOME`, default `~/.cache`).
+    Cache,
+}
+
+impl BaseDirectory {
+    fn variable(self) -> &'static str {
+        match self {
+            Self::Config => ENVIRONMENT_VARIABLE_XDG_CONFIG_HOME,
+            Self::State => ENVIRONMENT_VARIABLE_XDG_STATE_HOME,
+            Self::Cache => ENVIRONMENT_VARIABLE_XDG_CACHE_HOME,
+        }
+    }
+
+    fn default_under_home(self) -> &'static str {
+        match self {
+            Self::Config => ".config",
+            Self::State => ".local/state",
+            Self::Cache => ".cache",
+        }
+    }
+
+    /// This shell's directory in the base directory, such as `~/.local/state/ccsh`. Empty and
+    /// relative values of the variable are ignored, as the specification requires.
+    #[must_use]
+    pub fn shell_directory(self) -> Option<PathBuf> {
+        var_os(self.variable())
+            .map(PathBuf::from)
+            .filter(|base| base.is_absolute())
+            .or_else(|| home_directory().map(|home| home.join(self.default_under_home())))
+            .map(|base| base.join(SHELL_DIRECTORY_NAME))
+    }
+}
+
+/// `directory/name`, first moving the pre-XDG dotfile `~/legacy` there when only the old file
+/// exists. If the move fails the old path is returned, so the existing data is still used.
+#[must_use]
+pub fn shell_file(directory: &Path, name: &str, legacy: Option<&str>) -> PathBuf {
+    let path = directory.join(name);
+    let Some(legacy) = legacy.and_then(|legacy| Some(home_directory()?.join(legacy))) else {
+        return path;
+    };
+    if path.symlink_metadata().is_ok() || legacy.symlink_metadata().is_err() {
+        return path;
+    }
+    match std::fs::create_dir_all(directory).and_then(|()| std::fs::rename(&legacy, &path)) {
+        Ok(()) => path,
+        Err(_) => legacy,
+    }
+}